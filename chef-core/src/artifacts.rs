@@ -0,0 +1,127 @@
+//! The files `cook` adds to the target directories and to `CARGO_HOME`, listed with their
+//! SHA-256 so that image post-processing tools (layer squashers, SBOM attachers, cache
+//! deduplicators) can operate on exactly what was cooked.
+//!
+//! A snapshot of both is taken before cooking, recording the size and modification time of
+//! every file: only files that are new or changed afterwards are hashed and listed.
+use anyhow::Context;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The state of the output directories of `cook`, before it runs.
+#[derive(Debug, Clone)]
+pub struct OutputSnapshot {
+    directories: Vec<PathBuf>,
+    files: BTreeMap<PathBuf, FileState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileState {
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Everything `cook` added or modified in its output directories.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactsManifest {
+    /// The directories that were scanned, absolute.
+    pub directories: Vec<PathBuf>,
+    /// Sorted by path.
+    pub files: Vec<Artifact>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Absolute, i.e. where the file lands in the image.
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl OutputSnapshot {
+    /// Record the regular files found in `directories`, which do not need to exist yet.
+    /// Symlinks are not followed.
+    pub fn capture(directories: Vec<PathBuf>) -> Result<Self, anyhow::Error> {
+        let mut unique_directories = vec![];
+        for directory in directories {
+            if !unique_directories.contains(&directory) {
+                unique_directories.push(directory);
+            }
+        }
+        let files = scan(&unique_directories)?;
+        Ok(OutputSnapshot {
+            directories: unique_directories,
+            files,
+        })
+    }
+
+    /// The files that were added to the directories, or modified, since the snapshot.
+    /// Removed files are not reported.
+    pub fn artifacts(&self) -> Result<ArtifactsManifest, anyhow::Error> {
+        let mut files = vec![];
+        for (path, state) in scan(&self.directories)? {
+            if self.files.get(&path) == Some(&state) {
+                continue;
+            }
+            files.push(Artifact {
+                sha256: sha256(&path)?,
+                size: state.size,
+                path,
+            });
+        }
+        Ok(ArtifactsManifest {
+            directories: self.directories.clone(),
+            files,
+        })
+    }
+}
+
+/// Where `cargo` keeps registries, git checkouts and installed binaries: `CARGO_HOME`, or
+/// `.cargo` in the home directory.
+pub fn cargo_home() -> Option<PathBuf> {
+    match std::env::var_os("CARGO_HOME") {
+        Some(cargo_home) => std::env::current_dir()
+            .ok()
+            .map(|current_directory| current_directory.join(cargo_home)),
+        None => std::env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")),
+    }
+}
+
+pub(crate) fn scan(directories: &[PathBuf]) -> Result<BTreeMap<PathBuf, FileState>, anyhow::Error> {
+    let mut files = BTreeMap::new();
+    let mut queue: Vec<PathBuf> = directories
+        .iter()
+        .filter(|directory| directory.is_dir())
+        .cloned()
+        .collect();
+    while let Some(directory) = queue.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let metadata = fs::symlink_metadata(entry.path())?;
+            if metadata.is_dir() {
+                queue.push(entry.path());
+            } else if metadata.is_file() {
+                files.insert(
+                    entry.path(),
+                    FileState {
+                        size: metadata.len(),
+                        modified: metadata.modified().ok(),
+                    },
+                );
+            }
+        }
+    }
+    Ok(files)
+}
+
+pub(crate) fn sha256(path: &Path) -> Result<String, anyhow::Error> {
+    let mut hasher = Sha256::new();
+    let mut file = fs::File::open(path)?;
+    std::io::copy(&mut file, &mut hasher).with_context(|| format!("Failed to hash {:?}", path))?;
+    let digest = hasher.finalize();
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
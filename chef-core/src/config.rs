@@ -0,0 +1,90 @@
+//! `chef.toml`, the configuration `prepare` reads from the root of the project: rules for
+//! known build pitfalls (see [`crate::Rule`]) and named profiles, to maintain several flavours
+//! of the recipe (e.g. for local development, CI and release images) out of the same project:
+//!
+//! ```toml
+//! [profiles.release]
+//! dev-dependencies = false
+//! metadata = false
+//! exclude = ["xtask", "fuzz"]
+//! ```
+use crate::Rule;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// The file, at the root of the project, `prepare` reads its configuration from.
+pub const CHEF_CONFIG_FILE_NAME: &str = "chef.toml";
+
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct ChefConfig {
+    #[serde(default)]
+    pub(crate) rules: Vec<Rule>,
+    #[serde(default)]
+    pub(crate) profiles: BTreeMap<String, ChefProfile>,
+}
+
+impl ChefConfig {
+    /// The configuration of the project at `base_path`, empty if there is no `chef.toml`.
+    pub(crate) fn read(base_path: &Path) -> Result<Self, anyhow::Error> {
+        let path = base_path.join(CHEF_CONFIG_FILE_NAME);
+        if !path.is_file() {
+            return Ok(ChefConfig::default());
+        }
+        let contents = fs_err::read_to_string(&path)?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+    }
+}
+
+/// A named set of `prepare` settings, declared as `[profiles.<name>]` in `chef.toml` and
+/// selected with `prepare --chef-profile <name>`.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ChefProfile {
+    /// Keep dev-dependencies in the recipe. Without them, the dependencies of tests, benches
+    /// and examples are not cooked.
+    #[serde(default = "default_true")]
+    pub dev_dependencies: bool,
+    /// Keep `package.metadata` and `workspace.metadata`, which only tools other than `cargo`
+    /// read but invalidate the recipe whenever they change. `metadata.chef` is always kept.
+    #[serde(default = "default_true")]
+    pub metadata: bool,
+    /// Workspace members to leave out of the recipe, as with `prepare --exclude`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Default for ChefProfile {
+    fn default() -> Self {
+        ChefProfile {
+            dev_dependencies: true,
+            metadata: true,
+            exclude: vec![],
+        }
+    }
+}
+
+impl ChefProfile {
+    /// The profile called `name` in the `chef.toml` of the project at `base_path`.
+    pub fn load(base_path: &Path, name: &str) -> Result<Self, anyhow::Error> {
+        let mut config = ChefConfig::read(base_path)?;
+        config.profiles.remove(name).ok_or_else(|| {
+            let available: Vec<_> = config.profiles.keys().map(String::as_str).collect();
+            anyhow::anyhow!(
+                "There is no profile named `{}` in {} (available: {}).",
+                name,
+                CHEF_CONFIG_FILE_NAME,
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            )
+        })
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
@@ -0,0 +1,297 @@
+//! Sample workspaces written by `cargo chef demo`, each with a Dockerfile demonstrating a way of
+//! caching its dependencies. They double as fixtures for the test suite, which runs the
+//! `cargo chef` commands of every Dockerfile against its workspace.
+use anyhow::Context;
+use fs_err as fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DemoStrategy {
+    /// A planner, a builder and a runtime stage.
+    Basic,
+    /// One cook for several target triples, one runtime image per target.
+    MultiTarget,
+    /// A recipe with dev-dependencies for the CI image, one without for production.
+    SplitRecipes,
+    /// Cache mounts for the registry and the target directory.
+    CacheMounts,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemoFile {
+    /// Relative to the root of the demo.
+    pub relative_path: PathBuf,
+    pub contents: String,
+}
+
+impl DemoStrategy {
+    pub const ALL: [DemoStrategy; 4] = [
+        DemoStrategy::Basic,
+        DemoStrategy::MultiTarget,
+        DemoStrategy::SplitRecipes,
+        DemoStrategy::CacheMounts,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DemoStrategy::Basic => "basic",
+            DemoStrategy::MultiTarget => "multi-target",
+            DemoStrategy::SplitRecipes => "split-recipes",
+            DemoStrategy::CacheMounts => "cache-mounts",
+        }
+    }
+
+    /// The files of the demo: a workspace with a binary and a library, a Dockerfile, a
+    /// `.dockerignore` and a README.
+    pub fn files(self) -> Vec<DemoFile> {
+        let dev_dependencies = if self == DemoStrategy::SplitRecipes {
+            "\n[dev-dependencies]\npretty_assertions = \"1\"\n"
+        } else {
+            ""
+        };
+        let mut files = vec![
+            file("Cargo.toml", WORKSPACE_MANIFEST.to_string()),
+            file("app/Cargo.toml", APP_MANIFEST.to_string()),
+            file("app/src/main.rs", APP_MAIN.to_string()),
+            file(
+                "shared/Cargo.toml",
+                format!("{}{}", SHARED_MANIFEST, dev_dependencies),
+            ),
+            file("shared/src/lib.rs", SHARED_LIB.to_string()),
+            file(".dockerignore", "target\n".to_string()),
+            file("README.md", self.readme()),
+            file("Dockerfile", self.dockerfile()),
+        ];
+        if self == DemoStrategy::SplitRecipes {
+            files.push(file(
+                crate::CHEF_CONFIG_FILE_NAME,
+                SPLIT_CHEF_CONFIG.to_string(),
+            ));
+        }
+        files
+    }
+
+    /// Write the files of the demo in `directory`, which must not contain any of them yet.
+    pub fn write(self, directory: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let files = self.files();
+        if let Some(existing) = files
+            .iter()
+            .map(|file| directory.join(&file.relative_path))
+            .find(|path| path.exists())
+        {
+            return Err(anyhow::anyhow!(
+                "{:?} already exists: the demo must be written to a new directory.",
+                existing
+            ));
+        }
+        let mut paths = vec![];
+        for file in files {
+            let path = directory.join(&file.relative_path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, &file.contents)
+                .with_context(|| format!("Failed to write {:?}", path))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    fn readme(self) -> String {
+        let (description, build) = match self {
+            DemoStrategy::Basic => (
+                "The recipe is computed in a `planner` stage, the dependencies are cooked in \
+                 their own layer of the `builder` stage, and the binary is copied to a slim \
+                 `runtime` image.",
+                "docker build -t chef-demo .\ndocker run --rm chef-demo",
+            ),
+            DemoStrategy::MultiTarget => (
+                "A single `cook` builds the dependencies for both `x86_64-unknown-linux-gnu` \
+                 and `x86_64-unknown-linux-musl`, then each binary ends up in its own runtime \
+                 image.",
+                "docker build --target runtime-debian -t chef-demo:debian .\n\
+                 docker build --target runtime-alpine -t chef-demo:alpine .\n\
+                 docker run --rm chef-demo:alpine",
+            ),
+            DemoStrategy::SplitRecipes => (
+                "The `ci` stage cooks a recipe with dev-dependencies, to run the tests. The \
+                 production image cooks one without them, computed with the `release` profile \
+                 of `chef.toml`: changing a dev-dependency does not invalidate its layers.",
+                "docker build --target ci .\n\
+                 docker build -t chef-demo .\n\
+                 docker run --rm chef-demo",
+            ),
+            DemoStrategy::CacheMounts => (
+                "The registry and the target directory are cache mounts, which outlive the \
+                 layers: even when the recipe changes, `cook` only builds the dependencies \
+                 that are not in the cache yet.",
+                "docker build -t chef-demo .\ndocker run --rm chef-demo",
+            ),
+        };
+        format!(
+            "# `cargo-chef` demo: {}\n\n{}\n\n```bash\n{}\n```\n",
+            self.name(),
+            description,
+            build
+        )
+    }
+
+    fn dockerfile(self) -> String {
+        let dockerfile = match self {
+            DemoStrategy::Basic => BASIC_DOCKERFILE,
+            DemoStrategy::MultiTarget => MULTI_TARGET_DOCKERFILE,
+            DemoStrategy::SplitRecipes => SPLIT_RECIPES_DOCKERFILE,
+            DemoStrategy::CacheMounts => CACHE_MOUNTS_DOCKERFILE,
+        };
+        dockerfile.to_string()
+    }
+}
+
+fn file(relative_path: &str, contents: String) -> DemoFile {
+    DemoFile {
+        relative_path: relative_path.into(),
+        contents,
+    }
+}
+
+const WORKSPACE_MANIFEST: &str = r#"[workspace]
+members = ["app", "shared"]
+resolver = "2"
+"#;
+
+const APP_MANIFEST: &str = r#"[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+anyhow = "1"
+shared = { path = "../shared" }
+"#;
+
+const APP_MAIN: &str = r#"fn main() -> anyhow::Result<()> {
+    println!("{}", shared::greeting());
+    Ok(())
+}
+"#;
+
+const SHARED_MANIFEST: &str = r#"[package]
+name = "shared"
+version = "0.1.0"
+edition = "2021"
+"#;
+
+const SHARED_LIB: &str = r#"pub fn greeting() -> &'static str {
+    "Hello from a cooked image!"
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn greets() {
+        assert!(super::greeting().starts_with("Hello"));
+    }
+}
+"#;
+
+const SPLIT_CHEF_CONFIG: &str = r#"[profiles.release]
+# Dev-dependencies are only needed by the CI image.
+dev-dependencies = false
+"#;
+
+const BASIC_DOCKERFILE: &str = r#"FROM lukemathwalker/cargo-chef:latest-rust-1 AS chef
+WORKDIR /app
+
+FROM chef AS planner
+COPY . .
+RUN cargo chef prepare --recipe-path recipe.json
+
+FROM chef AS builder
+COPY --from=planner /app/recipe.json recipe.json
+# Build dependencies - this is the caching Docker layer!
+RUN cargo chef cook --release --recipe-path recipe.json
+# Build application
+COPY . .
+RUN cargo build --release --bin app
+
+# We do not need the Rust toolchain to run the binary!
+FROM debian:bookworm-slim AS runtime
+COPY --from=builder /app/target/release/app /usr/local/bin/
+ENTRYPOINT ["/usr/local/bin/app"]
+"#;
+
+const MULTI_TARGET_DOCKERFILE: &str = r#"FROM lukemathwalker/cargo-chef:latest-rust-1 AS chef
+RUN apt-get update && apt-get install -y musl-tools && rm -rf /var/lib/apt/lists/*
+RUN rustup target add x86_64-unknown-linux-musl
+WORKDIR /app
+
+FROM chef AS planner
+COPY . .
+RUN cargo chef prepare --recipe-path recipe.json
+
+FROM chef AS builder
+COPY --from=planner /app/recipe.json recipe.json
+# The dependencies of both targets are cooked in the same layer.
+RUN cargo chef cook --release --target x86_64-unknown-linux-gnu --target x86_64-unknown-linux-musl --recipe-path recipe.json
+COPY . .
+RUN cargo build --release --target x86_64-unknown-linux-gnu --target x86_64-unknown-linux-musl --bin app
+
+FROM debian:bookworm-slim AS runtime-debian
+COPY --from=builder /app/target/x86_64-unknown-linux-gnu/release/app /usr/local/bin/
+ENTRYPOINT ["/usr/local/bin/app"]
+
+FROM alpine AS runtime-alpine
+COPY --from=builder /app/target/x86_64-unknown-linux-musl/release/app /usr/local/bin/
+ENTRYPOINT ["/usr/local/bin/app"]
+"#;
+
+const SPLIT_RECIPES_DOCKERFILE: &str = r#"FROM lukemathwalker/cargo-chef:latest-rust-1 AS chef
+WORKDIR /app
+
+FROM chef AS planner
+COPY . .
+# The recipe of the CI image, with dev-dependencies...
+RUN cargo chef prepare --recipe-path recipe.json
+# ...and the one of the production image, without them (see chef.toml).
+RUN cargo chef prepare --chef-profile release --recipe-path recipe.release.json
+
+FROM chef AS ci
+COPY --from=planner /app/recipe.json recipe.json
+RUN cargo chef cook --all-targets --recipe-path recipe.json
+COPY . .
+RUN cargo test --workspace
+
+FROM chef AS builder
+COPY --from=planner /app/recipe.release.json recipe.json
+RUN cargo chef cook --release --recipe-path recipe.json
+COPY . .
+RUN cargo build --release --bin app
+
+FROM debian:bookworm-slim AS runtime
+COPY --from=builder /app/target/release/app /usr/local/bin/
+ENTRYPOINT ["/usr/local/bin/app"]
+"#;
+
+const CACHE_MOUNTS_DOCKERFILE: &str = r#"# syntax=docker/dockerfile:1
+FROM lukemathwalker/cargo-chef:latest-rust-1 AS chef
+WORKDIR /app
+
+FROM chef AS planner
+COPY . .
+RUN cargo chef prepare --recipe-path recipe.json
+
+FROM chef AS builder
+COPY --from=planner /app/recipe.json recipe.json
+RUN --mount=type=cache,target=/usr/local/cargo/registry \
+    --mount=type=cache,target=/app/target \
+    cargo chef cook --release --recipe-path recipe.json
+COPY . .
+# The target directory is not part of the image: copy the binary out of it in the same step.
+RUN --mount=type=cache,target=/usr/local/cargo/registry \
+    --mount=type=cache,target=/app/target \
+    cargo build --release --bin app && cp target/release/app /usr/local/bin/app
+
+FROM debian:bookworm-slim AS runtime
+COPY --from=builder /usr/local/bin/app /usr/local/bin/
+ENTRYPOINT ["/usr/local/bin/app"]
+"#;
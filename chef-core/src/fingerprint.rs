@@ -0,0 +1,219 @@
+//! The environment `cook` ran in, persisted to `.chef-env` so that the stage building the
+//! actual project can check that it lines up - any difference causes dependencies to be
+//! rebuilt, defeating the purpose of the cooked layer.
+use crate::{AllFeatures, CookSummary, DefaultFeatures, OptimisationProfile};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::process::Command;
+
+/// The file name `cook` writes the fingerprint of its environment to.
+pub const FINGERPRINT_FILE_NAME: &str = ".chef-env";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvFingerprint {
+    /// The output of `rustc -vV`, minus the lines that do not affect compilation.
+    pub toolchain: BTreeMap<String, String>,
+    pub profile: String,
+    pub targets: BTreeSet<String>,
+    pub default_features: bool,
+    pub all_features: bool,
+    pub features: BTreeSet<String>,
+    /// `cook --each-feature` built every feature of the members, one at a time: the features
+    /// of the final build are not compared.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub each_feature: bool,
+    pub environment: BTreeMap<String, String>,
+}
+
+/// A difference between the fingerprint recorded by `cook` and the live environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub setting: String,
+    pub cooked: String,
+    pub current: String,
+    /// What happens to the cooked dependencies because of the mismatch.
+    pub impact: &'static str,
+}
+
+impl EnvFingerprint {
+    /// Capture the toolchain and environment variables of the current process, together with
+    /// the build settings relevant for caching.
+    pub fn capture(
+        profile: &OptimisationProfile,
+        targets: Option<&[String]>,
+        default_features: DefaultFeatures,
+        all_features: AllFeatures,
+        features: Option<&HashSet<String>>,
+    ) -> Result<Self, anyhow::Error> {
+        let profile = match profile {
+            OptimisationProfile::Release => "release",
+            OptimisationProfile::Debug => "dev",
+            OptimisationProfile::Other(custom_profile) => custom_profile,
+        };
+        Ok(EnvFingerprint {
+            toolchain: toolchain()?,
+            profile: profile.to_string(),
+            targets: targets.into_iter().flatten().cloned().collect(),
+            default_features: default_features == DefaultFeatures::Enabled,
+            all_features: all_features == AllFeatures::Enabled,
+            features: features.into_iter().flatten().cloned().collect(),
+            each_feature: false,
+            environment: CookSummary::CACHE_RELEVANT_ENVIRONMENT
+                .iter()
+                .filter_map(|key| {
+                    std::env::var(key)
+                        .ok()
+                        .map(|value| (key.to_string(), value))
+                })
+                .collect(),
+        })
+    }
+
+    /// List every setting that differs between `self` (what `cook` ran with) and `current`.
+    pub fn mismatches(&self, current: &EnvFingerprint) -> Vec<Mismatch> {
+        let mut mismatches = vec![];
+        let mut compare = |setting: &str, cooked: String, live: String, impact| {
+            if cooked != live {
+                mismatches.push(Mismatch {
+                    setting: setting.to_string(),
+                    cooked,
+                    current: live,
+                    impact,
+                });
+            }
+        };
+
+        for key in union(self.toolchain.keys(), current.toolchain.keys()) {
+            compare(
+                &format!("rustc {}", key),
+                describe(self.toolchain.get(key)),
+                describe(current.toolchain.get(key)),
+                "all dependencies will be rebuilt",
+            );
+        }
+        compare(
+            "profile",
+            self.profile.clone(),
+            current.profile.clone(),
+            "none of the cooked artifacts will be used",
+        );
+        compare(
+            "targets",
+            describe_set(&self.targets),
+            describe_set(&current.targets),
+            "artifacts of targets that were not cooked will be built from scratch",
+        );
+        // Dependencies were cooked with every feature selection `--each-feature` goes through.
+        if !self.each_feature {
+            compare(
+                "default features",
+                self.default_features.to_string(),
+                current.default_features.to_string(),
+                "dependencies whose features change will be rebuilt",
+            );
+            compare(
+                "all features",
+                self.all_features.to_string(),
+                current.all_features.to_string(),
+                "dependencies whose features change will be rebuilt",
+            );
+            compare(
+                "features",
+                describe_set(&self.features),
+                describe_set(&current.features),
+                "dependencies whose features change will be rebuilt",
+            );
+        }
+        for key in union(self.environment.keys(), current.environment.keys()) {
+            let (mut cooked, mut live) = (self.environment.get(key), current.environment.get(key));
+            // `cook` disables incremental compilation on its own: the final build follows the
+            // default of its profile if it does not set `CARGO_INCREMENTAL` at all.
+            let (cooked_default, live_default) = (
+                default_incremental(&self.profile),
+                default_incremental(&current.profile),
+            );
+            if key == "CARGO_INCREMENTAL" {
+                cooked = cooked.or(cooked_default.as_ref());
+                live = live.or(live_default.as_ref());
+            }
+            compare(
+                key,
+                describe(cooked),
+                describe(live),
+                environment_impact(key),
+            );
+        }
+        mismatches
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: cooked with {}, currently {} ({})",
+            self.setting, self.cooked, self.current, self.impact
+        )
+    }
+}
+
+pub(crate) fn toolchain() -> Result<BTreeMap<String, String>, anyhow::Error> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(&rustc)
+        .arg("-vV")
+        .output()
+        .with_context(|| format!("Failed to run `{} -vV`", rustc))?;
+    let output = String::from_utf8(output.stdout)?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .filter(|(key, _)| ["release", "commit-hash", "host", "LLVM version"].contains(key))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+fn environment_impact(key: &str) -> &'static str {
+    match key {
+        "CARGO_HOME" => "dependencies will be fetched again and rebuilt",
+        "CARGO_TARGET_DIR" => "the cooked artifacts will not be found",
+        "CARGO_BUILD_TARGET" => {
+            "artifacts of targets that were not cooked will be built from scratch"
+        }
+        "CARGO_INCREMENTAL" => "workspace members will be rebuilt",
+        _ => "all dependencies will be rebuilt",
+    }
+}
+
+/// The value of `CARGO_INCREMENTAL` equivalent to the default of the built-in `profile`.
+fn default_incremental(profile: &str) -> Option<String> {
+    match profile {
+        "dev" | "test" => Some("1".to_string()),
+        "release" | "bench" => Some("0".to_string()),
+        _ => None,
+    }
+}
+
+fn union<'a>(
+    a: impl Iterator<Item = &'a String>,
+    b: impl Iterator<Item = &'a String>,
+) -> BTreeSet<&'a String> {
+    a.chain(b).collect()
+}
+
+fn describe(value: Option<&String>) -> String {
+    match value {
+        Some(value) => format!("`{}`", value),
+        None => "nothing".to_string(),
+    }
+}
+
+fn describe_set(values: &BTreeSet<String>) -> String {
+    if values.is_empty() {
+        "none".to_string()
+    } else {
+        let values: Vec<_> = values.iter().map(String::as_str).collect();
+        values.join(",")
+    }
+}
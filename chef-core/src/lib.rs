@@ -0,0 +1,40 @@
+//! The library behind `cargo-chef`: derive a [`Recipe`] (the skeleton of a project, i.e. its
+//! manifests and lock file) and cook it, i.e. build the dependencies of the project alone.
+//! It does not depend on the command-line interface, which lives in the `cargo-chef` binary.
+mod artifacts;
+mod config;
+mod demo;
+mod fingerprint;
+mod metrics;
+mod pitfalls;
+mod postprocess;
+mod recipe;
+mod report;
+mod signature;
+mod skeleton;
+mod snapshot;
+mod store;
+mod timings;
+mod toolchain;
+mod trace;
+
+pub use artifacts::{cargo_home, Artifact, ArtifactsManifest, OutputSnapshot};
+pub use config::{ChefProfile, CHEF_CONFIG_FILE_NAME};
+pub use demo::{DemoFile, DemoStrategy};
+pub use fingerprint::{EnvFingerprint, Mismatch, FINGERPRINT_FILE_NAME};
+pub use metrics::{CookMetrics, OtlpExporter};
+pub use pitfalls::{Diagnostic, Rule, Severity};
+pub use postprocess::postprocess_recipe;
+pub use recipe::{
+    cook_environment, coverage_rustflags, fast_dependencies_overrides, AllFeatures, BestEffort,
+    CommandArg, CookArgs, CookPhase, CookSummary, DefaultFeatures, Linker, OptimisationProfile,
+    ProfileSetting, Recipe, RecipeFormat, RecipeIntegrity, TargetArgs, WorkspaceRecipe,
+};
+pub use report::{DependencyChange, Estimate, RecipeReport, ReportFormat};
+pub use signature::{sign_recipe, verify_recipe_signature};
+pub use skeleton::*;
+pub use snapshot::overlay_registry_snapshot;
+pub use store::{ArtifactStore, StoreStats};
+pub use timings::{CriticalPath, TimedUnit};
+pub use toolchain::{ignored_toolchain_file, PinnedToolchain};
+pub use trace::{TraceFormat, TraceRecorder};
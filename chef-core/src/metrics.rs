@@ -0,0 +1,251 @@
+//! Metrics of a cook (its duration, the compilation units it built or found up to date, the hash
+//! of the recipe), exported to an OpenTelemetry collector so that the health of Docker builds can
+//! be tracked across repositories.
+//!
+//! The exporter is configured with the standard OTLP environment variables and speaks OTLP/HTTP
+//! with JSON payloads: `OTEL_EXPORTER_OTLP_METRICS_ENDPOINT` (or `OTEL_EXPORTER_OTLP_ENDPOINT`,
+//! to which `/v1/metrics` is appended), `OTEL_EXPORTER_OTLP_METRICS_HEADERS` (or
+//! `OTEL_EXPORTER_OTLP_HEADERS`) and `OTEL_RESOURCE_ATTRIBUTES`.
+use anyhow::Context;
+use fs_err as fs;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a cook did, as reported to the collector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookMetrics {
+    pub duration: Duration,
+    /// The compilation units `cargo` built.
+    pub units_compiled: u64,
+    /// The compilation units of the target directories that were already up to date.
+    pub units_fresh: u64,
+    /// The SHA-256 of the serialized recipe.
+    pub recipe_hash: String,
+    pub succeeded: bool,
+    /// Attributes of every data point (profile, command, ...), on top of the recipe hash.
+    pub attributes: Vec<(String, String)>,
+}
+
+/// Where the metrics go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtlpExporter {
+    pub endpoint: String,
+    pub headers: Vec<(String, String)>,
+    pub resource_attributes: Vec<(String, String)>,
+}
+
+impl CookMetrics {
+    /// The metrics of a cook of `serialized_recipe` into `target_directories`, which started
+    /// at `start`: units whose fingerprint was written since then were compiled.
+    pub fn capture(
+        serialized_recipe: &str,
+        target_directories: &[PathBuf],
+        start: SystemTime,
+        succeeded: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let mut metrics = CookMetrics {
+            duration: start.elapsed().unwrap_or_default(),
+            units_compiled: 0,
+            units_fresh: 0,
+            recipe_hash: hex(&Sha256::digest(serialized_recipe.as_bytes())),
+            succeeded,
+            attributes: vec![],
+        };
+        for fingerprints in target_directories
+            .iter()
+            .flat_map(|directory| fingerprint_directories(directory))
+        {
+            for entry in fs::read_dir(&fingerprints)? {
+                let entry = entry?;
+                if !entry.file_type()?.is_dir() {
+                    continue;
+                }
+                if modified_since(&entry.path(), start)? {
+                    metrics.units_compiled += 1;
+                } else {
+                    metrics.units_fresh += 1;
+                }
+            }
+        }
+        Ok(metrics)
+    }
+
+    /// The share of the units that did not need to be compiled, `None` if there were none.
+    pub fn cache_reuse_ratio(&self) -> Option<f64> {
+        let total = self.units_compiled + self.units_fresh;
+        (total > 0).then(|| self.units_fresh as f64 / total as f64)
+    }
+
+    /// An OTLP/JSON `ExportMetricsServiceRequest`, one gauge per metric.
+    pub fn to_otlp(&self, resource_attributes: &[(String, String)]) -> serde_json::Value {
+        let time = nanos(SystemTime::now()).to_string();
+        let mut attributes = vec![
+            ("recipe.hash".to_string(), self.recipe_hash.clone()),
+            (
+                "outcome".to_string(),
+                if self.succeeded { "success" } else { "failure" }.to_string(),
+            ),
+        ];
+        attributes.extend(self.attributes.iter().cloned());
+        let attributes = key_values(&attributes);
+        let gauge = |name: &str, unit: &str, description: &str, value: serde_json::Value| {
+            let mut point = json!({ "attributes": attributes, "timeUnixNano": time });
+            let kind = if value.is_f64() { "asDouble" } else { "asInt" };
+            point[kind] = value;
+            json!({
+                "name": name,
+                "unit": unit,
+                "description": description,
+                "gauge": { "dataPoints": [point] },
+            })
+        };
+        let mut metrics = vec![
+            gauge(
+                "cargo_chef.cook.duration",
+                "s",
+                "How long the cook took.",
+                json!(self.duration.as_secs_f64()),
+            ),
+            // `asInt` is a string in OTLP/JSON, as is every 64-bit integer.
+            gauge(
+                "cargo_chef.cook.units.compiled",
+                "{unit}",
+                "The compilation units that were built.",
+                json!(self.units_compiled.to_string()),
+            ),
+            gauge(
+                "cargo_chef.cook.units.fresh",
+                "{unit}",
+                "The compilation units that were already up to date.",
+                json!(self.units_fresh.to_string()),
+            ),
+        ];
+        if let Some(ratio) = self.cache_reuse_ratio() {
+            metrics.push(gauge(
+                "cargo_chef.cook.cache_reuse_ratio",
+                "1",
+                "The share of the compilation units that did not need to be built.",
+                json!(ratio),
+            ));
+        }
+        let mut resource = vec![
+            ("service.name".to_string(), "cargo-chef".to_string()),
+            (
+                "service.version".to_string(),
+                env!("CARGO_PKG_VERSION").to_string(),
+            ),
+        ];
+        for (key, value) in resource_attributes {
+            match resource.iter_mut().find(|(existing, _)| existing == key) {
+                Some(existing) => existing.1 = value.clone(),
+                None => resource.push((key.clone(), value.clone())),
+            }
+        }
+        json!({
+            "resourceMetrics": [{
+                "resource": { "attributes": key_values(&resource) },
+                "scopeMetrics": [{ "scope": { "name": "cargo-chef" }, "metrics": metrics }],
+            }],
+        })
+    }
+}
+
+impl OtlpExporter {
+    /// The exporter configured in the environment, `None` if no endpoint is.
+    pub fn from_env() -> Option<Self> {
+        let var = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+        let endpoint = var("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT").or_else(|| {
+            var("OTEL_EXPORTER_OTLP_ENDPOINT")
+                .map(|endpoint| format!("{}/v1/metrics", endpoint.trim_end_matches('/')))
+        })?;
+        let headers = var("OTEL_EXPORTER_OTLP_METRICS_HEADERS")
+            .or_else(|| var("OTEL_EXPORTER_OTLP_HEADERS"))
+            .map(|headers| parse_key_values(&headers))
+            .unwrap_or_default();
+        let resource_attributes = var("OTEL_RESOURCE_ATTRIBUTES")
+            .map(|attributes| parse_key_values(&attributes))
+            .unwrap_or_default();
+        Some(OtlpExporter {
+            endpoint,
+            headers,
+            resource_attributes,
+        })
+    }
+
+    /// Send `metrics` to the collector.
+    pub fn export(&self, metrics: &CookMetrics) -> Result<(), anyhow::Error> {
+        let body = serde_json::to_string(&metrics.to_otlp(&self.resource_attributes))?;
+        let mut request = ureq::post(&self.endpoint)
+            .timeout(Duration::from_secs(10))
+            .set("Content-Type", "application/json");
+        for (key, value) in &self.headers {
+            request = request.set(key, value);
+        }
+        request
+            .send_string(&body)
+            .context("Failed to export the metrics")?;
+        Ok(())
+    }
+}
+
+/// The `.fingerprint` directories of a target directory: one per profile, and per profile of
+/// each target triple.
+fn fingerprint_directories(target_directory: &Path) -> Vec<PathBuf> {
+    let mut directories = vec![];
+    let mut queue = vec![(target_directory.to_path_buf(), 0)];
+    while let Some((directory, depth)) = queue.pop() {
+        let Ok(entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                continue;
+            }
+            if entry.file_name() == ".fingerprint" {
+                directories.push(entry.path());
+            } else if depth < 2 {
+                queue.push((entry.path(), depth + 1));
+            }
+        }
+    }
+    directories
+}
+
+fn modified_since(unit: &Path, start: SystemTime) -> Result<bool, anyhow::Error> {
+    for entry in fs::read_dir(unit)? {
+        if entry?.metadata()?.modified()? >= start {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// `key1=value1,key2=value2`, as in the OTLP environment variables.
+fn parse_key_values(list: &str) -> Vec<(String, String)> {
+    list.split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+fn key_values(pairs: &[(String, String)]) -> Vec<serde_json::Value> {
+    pairs
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+fn nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,327 @@
+//! Dependencies known to fail to build in some configurations, with an error that only shows
+//! up late in the build (often from a build script) and rarely points at the actual fix.
+//! `cook` checks for them before building anything.
+//!
+//! Each pitfall is described by a [`Rule`]: predicates on the recipe (the packages in
+//! `Cargo.lock`, the features enabled by the manifests) and on the build (the target, the
+//! environment), which must all hold for its diagnostic to be emitted. `cargo-chef` ships with
+//! rules for the usual suspects, projects can add their own to `chef.toml`:
+//!
+//! ```toml
+//! [[rules]]
+//! package = "foo-sys"
+//! targets = "*-windows-*"
+//! unless-env = ["FOO_DIR"]
+//! message = "Install libfoo and point `FOO_DIR` to it."
+//! ```
+use crate::config::{ChefConfig, CHEF_CONFIG_FILE_NAME};
+use crate::skeleton::without_glibc_version;
+use crate::toolchain::is_in_path;
+use crate::Skeleton;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Rule {
+    /// The package, in `Cargo.lock`, that fails to build.
+    pub package: String,
+    /// A glob the target triple must match, e.g. `*-musl*`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<String>,
+    /// Only applies when cross-compiling (`true`) or when building for the host (`false`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cross_compiling: Option<bool>,
+    /// At least one of these features, as `crate/feature`, must be enabled by a manifest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// None of these features, as `crate/feature`, must be enabled by a manifest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub without_features: Vec<String>,
+    /// None of these packages must be in `Cargo.lock`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub without_packages: Vec<String>,
+    /// Environment variables, any of which being set means the pitfall has been taken care of.
+    /// `{target}` stands for the target triple with underscores (as in `CC_{target}`), and
+    /// `{TARGET}` for the same in uppercase.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unless_env: Vec<String>,
+    /// Executables, all of which being in `PATH` means the pitfall has been taken care of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unless_commands: Vec<String>,
+    /// Libraries, all of which being found by `pkg-config` for the target means the pitfall has
+    /// been taken care of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unless_pkg_config: Vec<String>,
+    #[serde(default)]
+    pub severity: Severity,
+    /// What goes wrong, and how to fix it.
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// `cook` fails before building anything.
+    #[default]
+    Error,
+    /// `cook` warns, and goes on.
+    Warning,
+}
+
+/// A rule that applies to a build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub package: String,
+    pub target: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = match self.severity {
+            Severity::Error => "cannot be built",
+            Severity::Warning => "might fail to build",
+        };
+        write!(
+            f,
+            "`{}` {} for {}: {}",
+            self.package, outcome, self.target, self.message
+        )
+    }
+}
+
+/// The rules `cargo-chef` ships with.
+fn built_in_rules() -> Vec<Rule> {
+    let rule = |package: &str, message: &str| Rule {
+        package: package.to_string(),
+        targets: None,
+        cross_compiling: None,
+        features: vec![],
+        without_features: vec![],
+        without_packages: vec![],
+        unless_env: vec![],
+        unless_commands: vec![],
+        unless_pkg_config: vec![],
+        severity: Severity::Error,
+        message: message.to_string(),
+    };
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+    vec![
+        Rule {
+            targets: Some("*-musl*".to_string()),
+            // The `vendored` feature builds OpenSSL from source, through `openssl-src`.
+            without_packages: strings(&["openssl-src"]),
+            unless_env: strings(&[
+                "OPENSSL_DIR",
+                "OPENSSL_LIB_DIR",
+                "{TARGET}_OPENSSL_DIR",
+                "{TARGET}_OPENSSL_LIB_DIR",
+            ]),
+            unless_pkg_config: strings(&["openssl"]),
+            ..rule(
+                "openssl-sys",
+                "OpenSSL is linked dynamically against the system library, which is not \
+                available for musl targets. Enable the `vendored` feature of the `openssl` \
+                crate, to build it from source, or install a musl build of OpenSSL and point \
+                `OPENSSL_DIR` to it.",
+            )
+        },
+        Rule {
+            cross_compiling: Some(true),
+            unless_env: strings(&["TARGET_CC", "CC_{target}"]),
+            severity: Severity::Warning,
+            ..rule(
+                "ring",
+                "its C and assembly sources are compiled with the C compiler for the target, \
+                which is looked up as `<target>-gcc` unless `CC_<target>` (with underscores) \
+                points to it. Install a cross-compiler, or use `cargo zigbuild`.",
+            )
+        },
+        Rule {
+            features: strings(&["rdkafka/cmake-build", "rdkafka-sys/cmake-build"]),
+            unless_commands: strings(&["cmake"]),
+            ..rule(
+                "rdkafka-sys",
+                "the `cmake-build` feature builds librdkafka with CMake, which is not \
+                installed. Install `cmake` (and a C++ compiler) before cooking.",
+            )
+        },
+        Rule {
+            without_features: strings(&[
+                "libsqlite3-sys/bundled",
+                "libsqlite3-sys/bundled-sqlcipher",
+                "rusqlite/bundled",
+                "rusqlite/bundled-full",
+                "sqlx/sqlite",
+            ]),
+            unless_env: strings(&["SQLITE3_LIB_DIR"]),
+            unless_pkg_config: strings(&["sqlite3"]),
+            severity: Severity::Warning,
+            ..rule(
+                "libsqlite3-sys",
+                "SQLite is linked against the system library, which could not be found. \
+                Install its development package (e.g. `libsqlite3-dev`), or enable the \
+                `bundled` feature of `rusqlite` to build it from source.",
+            )
+        },
+        Rule {
+            without_packages: strings(&["protobuf-src", "protoc-bin-vendored"]),
+            unless_env: strings(&["PROTOC"]),
+            unless_commands: strings(&["protoc"]),
+            severity: Severity::Warning,
+            ..rule(
+                "prost-build",
+                "code generation relies on `protoc`, which is not installed. Install it (e.g. \
+                `protobuf-compiler`), point `PROTOC` to it, or depend on `protoc-bin-vendored`.",
+            )
+        },
+    ]
+}
+
+/// The rules configured in the `chef.toml` at the root of `base_path`, if any.
+pub(crate) fn user_rules(base_path: &Path) -> Result<Vec<Rule>, anyhow::Error> {
+    let config = ChefConfig::read(base_path)?;
+    for rule in &config.rules {
+        target_matcher(rule)
+            .with_context(|| format!("Invalid rule in {}", CHEF_CONFIG_FILE_NAME))?;
+    }
+    Ok(config.rules)
+}
+
+/// Evaluate the built-in rules and `user_rules` against `skeleton`, for each of `targets` (the
+/// host, if none).
+pub(crate) fn diagnostics(
+    skeleton: &Skeleton,
+    user_rules: &[Rule],
+    targets: Option<&[String]>,
+) -> Result<Vec<Diagnostic>, anyhow::Error> {
+    let Some(lock_file) = &skeleton.lock_file else {
+        return Ok(vec![]);
+    };
+    let lock_file: toml::Value = toml::from_str(lock_file)?;
+    let packages: BTreeSet<&str> = lock_file
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|package| package.get("name")?.as_str())
+        .collect();
+    let rules: Vec<Rule> = built_in_rules()
+        .into_iter()
+        .chain(user_rules.iter().cloned())
+        .filter(|rule| packages.contains(rule.package.as_str()))
+        .collect();
+    if rules.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let features = skeleton.declared_features()?;
+    let host = crate::fingerprint::toolchain()?
+        .remove("host")
+        .ok_or_else(|| anyhow::anyhow!("Failed to determine the host target from `rustc -vV`"))?;
+    let targets = match targets {
+        Some(targets) => targets
+            .iter()
+            .map(|target| without_glibc_version(target).to_string())
+            .collect(),
+        None => vec![std::env::var("CARGO_BUILD_TARGET").unwrap_or_else(|_| host.clone())],
+    };
+    let mut diagnostics = vec![];
+    for target in &targets {
+        let build = Build {
+            packages: &packages,
+            features: &features,
+            target,
+            host: &host,
+        };
+        for rule in &rules {
+            if build.applies(rule)? {
+                diagnostics.push(Diagnostic {
+                    severity: rule.severity,
+                    package: rule.package.clone(),
+                    target: target.clone(),
+                    message: rule.message.clone(),
+                });
+            }
+        }
+    }
+    Ok(diagnostics)
+}
+
+/// What is being built: the packages in the lock file and the features enabled by the
+/// manifests, for a single target.
+struct Build<'a> {
+    packages: &'a BTreeSet<&'a str>,
+    features: &'a BTreeSet<String>,
+    target: &'a str,
+    host: &'a str,
+}
+
+impl Build<'_> {
+    /// Whether all the predicates of `rule` hold. The environment is only looked at if
+    /// everything else matches.
+    fn applies(&self, rule: &Rule) -> Result<bool, anyhow::Error> {
+        let cross_compiling = self.target != self.host;
+        let enabled = |feature: &String| self.features.contains(feature);
+        let applies = target_matcher(rule)?.is_none_or(|matcher| matcher.is_match(self.target))
+            && rule
+                .cross_compiling
+                .is_none_or(|cross| cross == cross_compiling)
+            && (rule.features.is_empty() || rule.features.iter().any(enabled))
+            && !rule.without_features.iter().any(enabled)
+            && !rule
+                .without_packages
+                .iter()
+                .any(|package| self.packages.contains(package.as_str()));
+        Ok(applies && !self.is_taken_care_of(rule, cross_compiling))
+    }
+
+    fn is_taken_care_of(&self, rule: &Rule, cross_compiling: bool) -> bool {
+        let target = self.target.replace('-', "_");
+        let configured = rule.unless_env.iter().any(|key| {
+            let key = key
+                .replace("{target}", &target)
+                .replace("{TARGET}", &target.to_uppercase());
+            std::env::var_os(key).is_some()
+        });
+        let installed = !rule.unless_commands.is_empty()
+            && rule
+                .unless_commands
+                .iter()
+                .all(|command| is_in_path(command));
+        // `pkg-config` only looks for the host's libraries, unless told otherwise.
+        let found = !rule.unless_pkg_config.is_empty()
+            && (!cross_compiling || std::env::var_os("PKG_CONFIG_ALLOW_CROSS").is_some())
+            && rule
+                .unless_pkg_config
+                .iter()
+                .all(|library| pkg_config_finds(library));
+        configured || installed || found
+    }
+}
+
+fn target_matcher(rule: &Rule) -> Result<Option<globset::GlobMatcher>, anyhow::Error> {
+    rule.targets
+        .as_deref()
+        .map(|pattern| {
+            globset::Glob::new(pattern)
+                .map(|glob| glob.compile_matcher())
+                .with_context(|| format!("Invalid target pattern for `{}`", rule.package))
+        })
+        .transpose()
+}
+
+fn pkg_config_finds(library: &str) -> bool {
+    Command::new(std::env::var("PKG_CONFIG").unwrap_or_else(|_| "pkg-config".to_string()))
+        .args(["--exists", library])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
@@ -0,0 +1,100 @@
+//! Pipe a recipe through an external command, to apply organization-specific normalization
+//! or policies without forking `cargo-chef`.
+//!
+//! The protocol is as simple as it gets: the command receives the recipe, serialized as JSON,
+//! on stdin and must print the (possibly modified) recipe, in the same format, on stdout.
+//! Anything written to stderr is forwarded to the user, and a non-zero exit status aborts.
+use crate::{Recipe, Skeleton};
+use anyhow::{anyhow, Context};
+use std::io::Write;
+use std::path::{Component, Path};
+use std::process::{Command, Stdio};
+
+/// Run `command` through the shell, feeding it `recipe`, and return the recipe it emits.
+pub fn postprocess_recipe(recipe: &Recipe, command: &str) -> Result<Recipe, anyhow::Error> {
+    let input = serde_json::to_vec(recipe)?;
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to run the postprocessing command `{}`", command))?;
+
+    // Write from a separate thread: the command might start emitting its output before
+    // having consumed all of its input.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    if let Err(e) = writer.join().expect("the writer thread panicked") {
+        // The command is allowed to ignore its input, as long as it succeeds.
+        log::debug!(
+            "The postprocessing command did not read the whole recipe: {}",
+            e
+        );
+    }
+    if !output.status.success() {
+        return Err(anyhow!(
+            "The postprocessing command `{}` failed ({})",
+            command,
+            output.status
+        ));
+    }
+
+    let recipe: Recipe = serde_json::from_slice(&output.stdout)
+        .context("The postprocessing command did not emit a valid recipe")?;
+    validate(&recipe.skeleton)?;
+    for workspace in &recipe.workspaces {
+        check_relative(&workspace.relative_path)?;
+        validate(&workspace.skeleton)?;
+    }
+    Ok(recipe)
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// Beyond having the right structure, the recipe must be safe and possible to cook.
+fn validate(skeleton: &Skeleton) -> Result<(), anyhow::Error> {
+    for manifest in &skeleton.manifests {
+        check_relative(&manifest.relative_path)?;
+        cargo_manifest::Manifest::from_slice(manifest.contents.as_bytes()).with_context(|| {
+            format!(
+                "The postprocessing command emitted an invalid manifest for {:?}",
+                manifest.relative_path
+            )
+        })?;
+    }
+    if let Some(lock_file) = &skeleton.lock_file {
+        toml::from_str::<toml::Value>(lock_file)
+            .context("The postprocessing command emitted an invalid lockfile")?;
+    }
+    if let Some(config_file) = &skeleton.config_file {
+        toml::from_str::<toml::Value>(config_file)
+            .context("The postprocessing command emitted an invalid cargo configuration")?;
+    }
+    Ok(())
+}
+
+fn check_relative(path: &Path) -> Result<(), anyhow::Error> {
+    if path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(anyhow!(
+            "The postprocessing command emitted a path outside of the project: {:?}",
+            path
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,1761 @@
+use crate::pitfalls::{self, Diagnostic, Rule};
+use crate::skeleton::delta::{delta, Delta};
+use crate::skeleton::{
+    artifact_directories, host as host_dependencies, without_glibc_version, UpstreamSkeleton,
+};
+use crate::toolchain::{install_targets, is_in_path, rustc_version, rustup_is_installed};
+use crate::{ChefProfile, PackageStub, PinnedToolchain, Skeleton};
+use anyhow::Context;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+    pub skeleton: Skeleton,
+    /// Independent workspaces living in sub-directories of the project, see `prepare --path`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub workspaces: Vec<WorkspaceRecipe>,
+    /// Known build pitfalls configured in `chef.toml`, checked by `cook` on top of the built-in
+    /// ones, see [`Recipe::preflight`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceRecipe {
+    /// Relative path of the workspace root with respect to the project root.
+    #[serde(with = "crate::skeleton::portable_path")]
+    pub relative_path: PathBuf,
+    pub skeleton: Skeleton,
+}
+
+/// The key of the checksum a serialized recipe carries, next to its contents.
+const CHECKSUM_KEY: &str = "checksum";
+
+/// A recipe as it is serialized, see [`Recipe::serialize`].
+#[derive(Serialize)]
+struct SealedRecipe<'a> {
+    #[serde(flatten)]
+    recipe: &'a Recipe,
+    checksum: String,
+}
+
+/// Whether a serialized recipe was left as it was serialized, see [`Recipe::integrity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipeIntegrity {
+    /// The recipe matches its checksum.
+    Intact,
+    /// The recipe no longer matches its checksum, e.g. it was edited by hand.
+    Modified,
+    /// The recipe carries no checksum: it was serialized by an older version of `cargo-chef`,
+    /// or by upstream `cargo-chef`.
+    Unsealed,
+}
+
+/// A recipe produced by upstream `cargo-chef`, see [`UpstreamSkeleton`].
+#[derive(Deserialize)]
+struct UpstreamRecipe {
+    skeleton: UpstreamSkeleton,
+}
+
+#[derive(Clone)]
+pub struct TargetArgs {
+    pub benches: bool,
+    pub tests: bool,
+    pub examples: bool,
+    pub all_targets: bool,
+}
+
+#[derive(Clone)]
+pub enum CommandArg {
+    Build,
+    Check,
+    Clippy,
+    Zigbuild,
+    Doc,
+    /// `cargo test --no-run`, as `cargo nextest` invokes it to build the tests.
+    Nextest,
+}
+
+#[derive(Clone)]
+pub struct CookArgs {
+    pub profile: OptimisationProfile,
+    pub command: CommandArg,
+    pub default_features: DefaultFeatures,
+    pub all_features: AllFeatures,
+    pub features: Option<HashSet<String>>,
+    pub unstable_features: Option<HashSet<String>>,
+    pub target: Option<Vec<String>>,
+    pub target_dir: Option<PathBuf>,
+    pub target_args: TargetArgs,
+    pub manifest_path: Option<PathBuf>,
+    pub package: Option<Vec<String>>,
+    pub workspace: bool,
+    /// Packages to leave out, together with `workspace`.
+    pub exclude: Option<Vec<String>>,
+    pub offline: bool,
+    pub timings: bool,
+    pub no_std: bool,
+    pub bin: Option<String>,
+    pub phase: CookPhase,
+    /// Replaces the `cargo` invocation used to build dependencies, as a list of arguments:
+    /// `{cargo}` stands for the path to `cargo` (followed by `+<toolchain>` when building with
+    /// a pinned toolchain) and `{args}` for the arguments `cook` would pass to it (appended at
+    /// the end if missing).
+    pub build_command: Option<Vec<String>>,
+    /// Replaces `cargo build` (the program and its subcommand), e.g. `cargo auditable build`:
+    /// the arguments `cook` would pass to `cargo build` are appended. A leading `cargo` stands
+    /// for the path to `cargo`, as `{cargo}` does in [`CookArgs::build_command`].
+    pub cargo_command: Option<Vec<String>>,
+    /// Passed on to `cargo` after the arguments of `cook`.
+    pub extra_arguments: Vec<String>,
+    /// Lint flags passed on to clippy (after `--`) with [`CommandArg::Clippy`], e.g.
+    /// `-D warnings`, as in the final `cargo clippy`.
+    pub clippy_arguments: Vec<String>,
+    /// Build the dependencies that are not part of the workspace with the settings of
+    /// [`fast_dependencies_overrides`], injected as profile overrides in the skeleton.
+    pub fast_dependencies: bool,
+    /// The `debug` setting of the dependencies that are not part of the workspace (e.g. `0` or
+    /// `line-tables-only`), injected as a profile override in the skeleton. Workspace members
+    /// keep the settings of the profile.
+    pub dependencies_debuginfo: Option<String>,
+    /// Leave incremental compilation alone, instead of disabling it (see [`cook_environment`]).
+    pub incremental: bool,
+    /// The recipe the target directory was last cooked from, with the same arguments: if only
+    /// `Cargo.lock` changed since, only the external crates whose locked version changed and
+    /// the ones depending on them are rebuilt.
+    pub previous_recipe: Option<Recipe>,
+    /// Build each package once per feature, as `cargo hack --each-feature` does: without
+    /// default features, then with each of its features alone, then with all of them.
+    pub each_feature: bool,
+    /// Settings patched into the `[profile.*]` tables of the skeleton, after the overrides of
+    /// [`CookArgs::dependency_overrides`].
+    pub profile_settings: Vec<ProfileSetting>,
+    /// Local crates to replace with stubs, by package name, e.g. FFI crates that cannot be
+    /// compiled where `cook` runs: their own dependencies are not cooked, the others are.
+    pub replace_local: Vec<(String, PackageStub)>,
+    /// The toolchain to build with, as `cargo +<toolchain>`. Unless set, `cook` uses the one the
+    /// toolchain file of the recipe pins, if any and if rustup is installed.
+    pub toolchain: Option<String>,
+    /// Install the toolchain pinned by the toolchain file of the recipe with rustup, unless it
+    /// already is, instead of failing to build when it is missing.
+    pub install_toolchain: bool,
+    /// Add the targets of `target` (or `CARGO_BUILD_TARGET`) that are not installed with
+    /// `rustup target add`, instead of failing to build when their standard library is missing.
+    pub auto_install_target: bool,
+    /// Build even if the toolchain is older than the `rust-version` of the workspace, passing
+    /// `--ignore-rust-version` to `cargo`.
+    pub ignore_rust_version: bool,
+    /// Link with this linker, by appending its flags to the `rustflags` of the skeleton
+    /// configuration.
+    pub linker: Option<Linker>,
+    /// Build with `cross` rather than `cargo`, in the container of the target.
+    pub cross: bool,
+    /// Groups of dependencies built last, with `--keep-going`: their compilation failures are
+    /// logged as warnings instead of failing the cook, and whatever did compile is cached.
+    pub best_effort: Vec<BestEffort>,
+    /// Instrument dependencies for coverage as `cargo llvm-cov` does: with the rustflags of
+    /// [`coverage_rustflags`], in the `llvm-cov-target` directory of the target directory.
+    pub coverage: bool,
+}
+
+/// The flags `cargo llvm-cov` appends to the `rustflags` on a stable toolchain.
+pub fn coverage_rustflags() -> Vec<String> {
+    [
+        "-C",
+        "instrument-coverage",
+        "--cfg=coverage",
+        "--cfg=trybuild_no_target",
+    ]
+    .iter()
+    .map(|flag| flag.to_string())
+    .collect()
+}
+
+/// The environment variables `cook` sets for `cargo`, on top of its own environment.
+///
+/// Incremental compilation is disabled, unless `incremental` is set or `CARGO_INCREMENTAL`
+/// already is: dependencies are never compiled incrementally, while the incremental artifacts
+/// of the dummy workspace members are of no use to the final build and bloat the cooked layer.
+pub fn cook_environment(incremental: bool) -> Vec<(&'static str, String)> {
+    if incremental || std::env::var_os("CARGO_INCREMENTAL").is_some() {
+        vec![]
+    } else {
+        vec![("CARGO_INCREMENTAL", "0".to_string())]
+    }
+}
+
+/// The profile settings `cook --fast-deps` overrides for external dependencies: they compile
+/// faster, with more parallelism, at the expense of the performance of the generated code.
+pub fn fast_dependencies_overrides() -> Vec<(&'static str, toml::Value)> {
+    vec![
+        ("codegen-units", toml::Value::Integer(256)),
+        ("incremental", toml::Value::Boolean(false)),
+    ]
+}
+
+/// A setting of the `[profile]` table of the root manifest, written as `<profile>.<key>=<value>`
+/// (e.g. `release.lto=thin` or `release.package."*".opt-level=3`). Values that are not valid
+/// TOML are taken as strings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileSetting {
+    /// The keys below `[profile]`, the name of the profile first.
+    pub path: Vec<String>,
+    pub value: toml::Value,
+}
+
+impl std::str::FromStr for ProfileSetting {
+    type Err = anyhow::Error;
+
+    fn from_str(setting: &str) -> Result<Self, Self::Err> {
+        let (key, value) = setting
+            .split_once('=')
+            .with_context(|| format!("`{}` is not of the form `<key>=<value>`.", setting))?;
+        let mut table: toml::Table = toml::from_str(&format!("{} = 0", key.trim()))
+            .with_context(|| format!("`{}` is not a valid TOML key.", key))?;
+        let mut path = vec![];
+        while let Some((key, value)) = table.into_iter().next() {
+            path.push(key);
+            match value {
+                toml::Value::Table(nested) => table = nested,
+                _ => break,
+            }
+        }
+        if path.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "`{}` does not name a setting of a profile, as in `release.lto`.",
+                key
+            ));
+        }
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", value.trim()))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.trim().to_string()));
+        Ok(ProfileSetting { path, value })
+    }
+}
+
+impl ProfileSetting {
+    /// The setting as a `--config` value for `cargo`.
+    pub fn to_config(&self) -> String {
+        let keys: Vec<String> = self
+            .path
+            .iter()
+            .map(|key| {
+                let bare = !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if bare {
+                    key.clone()
+                } else {
+                    toml::Value::String(key.clone()).to_string()
+                }
+            })
+            .collect();
+        format!("profile.{}={}", keys.join("."), self.value)
+    }
+}
+
+/// A faster linker than the default one of the platform, which dependencies are linked with
+/// (build scripts, proc macros and, with test targets, a lot of test binaries).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Linker {
+    Mold,
+    Lld,
+}
+
+impl std::str::FromStr for Linker {
+    type Err = anyhow::Error;
+
+    fn from_str(linker: &str) -> Result<Self, Self::Err> {
+        match linker {
+            "mold" => Ok(Linker::Mold),
+            "lld" => Ok(Linker::Lld),
+            _ => Err(anyhow::anyhow!(
+                "Unknown linker `{}`: expected `mold` or `lld`.",
+                linker
+            )),
+        }
+    }
+}
+
+impl Linker {
+    /// The executable the C compiler driving the link looks for.
+    pub fn binary(&self) -> &'static str {
+        match self {
+            Linker::Mold => "mold",
+            Linker::Lld => "ld.lld",
+        }
+    }
+
+    /// The flags appended to the `rustflags` of the skeleton configuration.
+    pub fn rustflags(&self) -> Vec<String> {
+        let name = match self {
+            Linker::Mold => "mold",
+            Linker::Lld => "lld",
+        };
+        vec!["-C".to_string(), format!("link-arg=-fuse-ld={}", name)]
+    }
+}
+
+/// A group of dependencies whose compilation failures `cook` downgrades to warnings, see
+/// [`CookArgs::best_effort`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BestEffort {
+    /// The dev-dependencies, i.e. what tests, benches and examples depend on on top of the
+    /// libraries and binaries.
+    DevDependencies,
+    /// The dependencies built for one of the `--target` triples.
+    Target(String),
+}
+
+impl std::str::FromStr for BestEffort {
+    type Err = anyhow::Error;
+
+    fn from_str(group: &str) -> Result<Self, Self::Err> {
+        match group {
+            "dev" => Ok(BestEffort::DevDependencies),
+            "" => Err(anyhow::anyhow!(
+                "Expected `dev` or one of the `--target` triples."
+            )),
+            target => Ok(BestEffort::Target(target.to_string())),
+        }
+    }
+}
+
+impl CookArgs {
+    /// The profile settings overridden for the dependencies that are not part of the
+    /// workspace, as `[profile.<profile>.package."*"]` in the skeleton.
+    pub fn dependency_overrides(&self) -> Vec<(&'static str, toml::Value)> {
+        let mut overrides = vec![];
+        if self.fast_dependencies {
+            overrides.extend(fast_dependencies_overrides());
+        }
+        if let Some(level) = &self.dependencies_debuginfo {
+            let level = match level.as_str() {
+                "true" => toml::Value::Boolean(true),
+                "false" => toml::Value::Boolean(false),
+                level => match level.parse() {
+                    Ok(level) => toml::Value::Integer(level),
+                    Err(_) => toml::Value::String(level.to_string()),
+                },
+            };
+            overrides.push(("debug", level));
+        }
+        overrides
+    }
+}
+
+/// Which dependencies to cook, to cache them in separate layers.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CookPhase {
+    /// Whatever the selected targets depend on.
+    All,
+    /// Normal and build dependencies only, i.e. what the libraries and binaries depend on.
+    Dependencies,
+    /// Dev-dependencies too, i.e. what tests, benches and examples depend on. Nothing is built
+    /// if the recipe does not declare any.
+    DevDependencies,
+    /// Build-dependencies and proc-macros only (along with their own dependencies), which are
+    /// expensive to build but rarely change. Only crates from crates.io or git are cooked.
+    BuildDependencies,
+}
+
+/// The serialization format of a recipe file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RecipeFormat {
+    Json,
+    Toml,
+}
+
+impl RecipeFormat {
+    /// Guess the format of a serialized recipe from its contents.
+    ///
+    /// A JSON recipe is always an object, therefore it must start with `{`, while a TOML
+    /// recipe never does.
+    pub fn detect(serialized: &str) -> Self {
+        if serialized.trim_start().starts_with('{') {
+            RecipeFormat::Json
+        } else {
+            RecipeFormat::Toml
+        }
+    }
+}
+
+impl Recipe {
+    /// Compute the recipe of the project at `base_path`.
+    ///
+    /// Workspace members matching `allow_missing_members` that do not exist are replaced by
+    /// placeholders, see `Skeleton::derive_allowing_missing_members`.
+    pub fn prepare(
+        base_path: PathBuf,
+        member: Option<String>,
+        allow_missing_members: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        let rules = pitfalls::user_rules(&base_path)?;
+        let skeleton = derive_skeleton(base_path, member, allow_missing_members)?;
+        Ok(Recipe {
+            skeleton,
+            workspaces: vec![],
+            rules,
+        })
+    }
+
+    /// Like `prepare`, but without invoking `cargo`, see `Skeleton::derive_standalone`.
+    pub fn prepare_standalone(
+        base_path: PathBuf,
+        member: Option<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let rules = pitfalls::user_rules(&base_path)?;
+        let _span = tracing::info_span!("prepare", directory = %base_path.display()).entered();
+        let skeleton = Skeleton::derive_standalone(base_path, member)?;
+        Ok(Recipe {
+            skeleton,
+            workspaces: vec![],
+            rules,
+        })
+    }
+
+    /// Combine several independent workspaces, found at `workspace_paths` (relative to
+    /// `base_path`), into a single recipe.
+    pub fn prepare_workspaces(
+        base_path: PathBuf,
+        workspace_paths: &[PathBuf],
+        allow_missing_members: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        let mut recipe = Recipe {
+            skeleton: Skeleton {
+                manifests: vec![],
+                config_file: None,
+                external_config: None,
+                lock_file: None,
+                toolchain_file: None,
+                vendored_sources: vec![],
+                files: vec![],
+            },
+            workspaces: vec![],
+            rules: pitfalls::user_rules(&base_path)?,
+        };
+        for workspace_path in workspace_paths {
+            let relative_path = workspace_path
+                .strip_prefix(&base_path)
+                .unwrap_or(workspace_path)
+                .to_path_buf();
+            if relative_path.is_absolute()
+                || relative_path
+                    .components()
+                    .any(|c| c == std::path::Component::ParentDir)
+            {
+                return Err(anyhow::anyhow!(
+                    "The workspace at {:?} is not inside the project root.",
+                    workspace_path
+                ));
+            }
+            let workspace_root = base_path.join(&relative_path);
+            let skeleton = derive_skeleton(workspace_root, None, allow_missing_members)
+                .with_context(|| format!("Failed to compute recipe for {:?}", workspace_path))?;
+            if relative_path
+                .components()
+                .all(|c| c == std::path::Component::CurDir)
+            {
+                recipe.skeleton = skeleton;
+            } else {
+                recipe.workspaces.push(WorkspaceRecipe {
+                    relative_path,
+                    skeleton,
+                });
+            }
+        }
+        recipe
+            .workspaces
+            .sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        Ok(recipe)
+    }
+
+    /// Combine independent recipes into a single one, each under its own root (relative to
+    /// the directory they are cooked from), e.g. for an image hosting several services.
+    pub fn merge(recipes: Vec<(PathBuf, Recipe)>) -> Result<Self, anyhow::Error> {
+        let mut merged = Recipe {
+            skeleton: Skeleton {
+                manifests: vec![],
+                config_file: None,
+                external_config: None,
+                lock_file: None,
+                toolchain_file: None,
+                vendored_sources: vec![],
+                files: vec![],
+            },
+            workspaces: vec![],
+            rules: vec![],
+        };
+        let is_root = |path: &Path| path.components().all(|c| c == std::path::Component::CurDir);
+        for (root, recipe) in recipes {
+            let skeletons = std::iter::once((root.clone(), recipe.skeleton)).chain(
+                recipe
+                    .workspaces
+                    .into_iter()
+                    .map(|workspace| (root.join(workspace.relative_path), workspace.skeleton)),
+            );
+            for (relative_path, skeleton) in skeletons {
+                if skeleton.manifests.is_empty() {
+                    continue;
+                }
+                let taken = if is_root(&relative_path) {
+                    !merged.skeleton.manifests.is_empty()
+                } else {
+                    merged
+                        .workspaces
+                        .iter()
+                        .any(|workspace| workspace.relative_path == relative_path)
+                };
+                if taken {
+                    return Err(anyhow::anyhow!(
+                        "Two recipes would be cooked in {:?}: they must live in distinct \
+                        directories.",
+                        relative_path
+                    ));
+                }
+                if is_root(&relative_path) {
+                    merged.skeleton = skeleton;
+                } else {
+                    merged.workspaces.push(WorkspaceRecipe {
+                        relative_path,
+                        skeleton,
+                    });
+                }
+            }
+            for rule in recipe.rules {
+                if !merged.rules.contains(&rule) {
+                    merged.rules.push(rule);
+                }
+            }
+        }
+        merged
+            .workspaces
+            .sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        Ok(merged)
+    }
+
+    /// Serialize the recipe, along with the checksum of its contents, see
+    /// [`Recipe::integrity`].
+    pub fn serialize(&self, format: RecipeFormat) -> Result<String, anyhow::Error> {
+        let sealed = SealedRecipe {
+            recipe: self,
+            checksum: self.checksum()?,
+        };
+        let serialized = match format {
+            RecipeFormat::Json => serde_json::to_string(&sealed)?,
+            RecipeFormat::Toml => toml::to_string(&sealed)?,
+        };
+        Ok(serialized)
+    }
+
+    /// SHA-256 of the contents of the recipe, which does not depend on the format it is
+    /// serialized in (nor on its whitespace).
+    fn checksum(&self) -> Result<String, anyhow::Error> {
+        let digest = Sha256::digest(serde_json::to_string(self)?);
+        Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Whether a serialized recipe still matches the checksum it was serialized with: a recipe
+    /// edited by hand no longer does, and cooking it is likely to fail in puzzling ways, e.g.
+    /// with dummy targets that do not match their manifests.
+    pub fn integrity(serialized: &str) -> Result<RecipeIntegrity, anyhow::Error> {
+        let checksum = match RecipeFormat::detect(serialized) {
+            RecipeFormat::Json => serde_json::from_str::<serde_json::Value>(serialized)?
+                .get(CHECKSUM_KEY)
+                .and_then(|checksum| checksum.as_str())
+                .map(str::to_string),
+            RecipeFormat::Toml => toml::from_str::<toml::Value>(serialized)?
+                .get(CHECKSUM_KEY)
+                .and_then(|checksum| checksum.as_str())
+                .map(str::to_string),
+        };
+        let Some(checksum) = checksum else {
+            return Ok(RecipeIntegrity::Unsealed);
+        };
+        if checksum == Recipe::deserialize(serialized)?.checksum()? {
+            Ok(RecipeIntegrity::Intact)
+        } else {
+            Ok(RecipeIntegrity::Modified)
+        }
+    }
+
+    /// Deserialize a recipe, auto-detecting whether it was serialized as JSON or TOML.
+    ///
+    /// JSON recipes produced by upstream `cargo-chef` are accepted as well.
+    pub fn deserialize(serialized: &str) -> Result<Self, anyhow::Error> {
+        let recipe = match RecipeFormat::detect(serialized) {
+            RecipeFormat::Json => match serde_json::from_str(serialized) {
+                Ok(recipe) => recipe,
+                Err(e) => {
+                    let Ok(upstream) = serde_json::from_str::<UpstreamRecipe>(serialized) else {
+                        return Err(e.into());
+                    };
+                    log::info!("Converting a recipe produced by upstream cargo-chef.");
+                    Recipe {
+                        skeleton: upstream.skeleton.try_into()?,
+                        workspaces: vec![],
+                        rules: vec![],
+                    }
+                }
+            },
+            RecipeFormat::Toml => toml::from_str(serialized)?,
+        };
+        Ok(recipe)
+    }
+
+    /// A recipe serialized by an older version of `cargo-chef` (or by upstream `cargo-chef`),
+    /// serialized again in the current format, in the same JSON or TOML flavour. What older
+    /// formats do not record is re-derived from the manifests. `None` if the recipe is already
+    /// up to date: it is kept byte for byte, and so is its hash.
+    pub fn upgrade(serialized: &str) -> Result<Option<String>, anyhow::Error> {
+        if Recipe::integrity(serialized)? == RecipeIntegrity::Modified {
+            return Err(anyhow::anyhow!(
+                "The recipe was modified since it was prepared: prepare it again instead."
+            ));
+        }
+        let format = RecipeFormat::detect(serialized);
+        let upgraded = Recipe::deserialize(serialized)?.serialize(format)?;
+        if upgraded == serialized.trim_end() {
+            return Ok(None);
+        }
+        // A recipe that is up to date but was formatted differently (e.g. pretty-printed) is
+        // left alone as well.
+        let unchanged = match format {
+            RecipeFormat::Json => {
+                serde_json::from_str::<serde_json::Value>(serialized)?
+                    == serde_json::from_str::<serde_json::Value>(&upgraded)?
+            }
+            RecipeFormat::Toml => {
+                toml::from_str::<toml::Value>(serialized)?
+                    == toml::from_str::<toml::Value>(&upgraded)?
+            }
+        };
+        Ok((!unchanged).then_some(upgraded))
+    }
+
+    /// Apply a `chef.toml` profile: dev-dependencies and metadata are stripped from every
+    /// skeleton if it says so, then its excluded members are left out of the root skeleton, as
+    /// with `prepare --exclude`.
+    pub fn with_profile(self, profile: &ChefProfile) -> Result<Recipe, anyhow::Error> {
+        let apply = |skeleton: Skeleton| -> Result<Skeleton, anyhow::Error> {
+            let skeleton = if profile.dev_dependencies {
+                skeleton
+            } else {
+                skeleton.without_dev_dependencies()?
+            };
+            if profile.metadata {
+                Ok(skeleton)
+            } else {
+                skeleton.without_metadata()
+            }
+        };
+        // Members that are only dev-dependencies of others can be excluded once they are gone.
+        let skeleton = apply(self.skeleton)?;
+        let skeleton = if profile.exclude.is_empty() || skeleton.manifests.is_empty() {
+            skeleton
+        } else {
+            skeleton
+                .exclude_members(&profile.exclude)
+                .context("Failed to exclude workspace members")?
+        };
+        Ok(Recipe {
+            skeleton,
+            workspaces: self
+                .workspaces
+                .into_iter()
+                .map(|workspace| {
+                    Ok(WorkspaceRecipe {
+                        relative_path: workspace.relative_path,
+                        skeleton: apply(workspace.skeleton)?,
+                    })
+                })
+                .collect::<Result<_, anyhow::Error>>()?,
+            rules: self.rules,
+        })
+    }
+
+    /// Check the dependencies of every skeleton in the recipe against the known build pitfalls,
+    /// for each of `targets` (the host, if none), before cooking.
+    pub fn preflight(&self, targets: Option<&[String]>) -> Result<Vec<Diagnostic>, anyhow::Error> {
+        let mut diagnostics = vec![];
+        for skeleton in
+            std::iter::once(&self.skeleton).chain(self.workspaces.iter().map(|w| &w.skeleton))
+        {
+            for diagnostic in pitfalls::diagnostics(skeleton, &self.rules, targets)? {
+                if !diagnostics.contains(&diagnostic) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    /// The directories `cook` builds into: `target_dir` (relative to the current directory),
+    /// or the `target` directory of each skeleton.
+    pub fn target_directories(
+        &self,
+        target_dir: Option<&Path>,
+    ) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let current_directory = std::env::current_dir()?;
+        if let Some(target_dir) = target_dir {
+            return Ok(vec![current_directory.join(target_dir)]);
+        }
+        let mut directories = vec![];
+        if !self.skeleton.manifests.is_empty() {
+            directories.push(current_directory.join("target"));
+        }
+        for workspace in &self.workspaces {
+            directories.push(
+                current_directory
+                    .join(&workspace.relative_path)
+                    .join("target"),
+            );
+        }
+        Ok(directories)
+    }
+
+    /// Cook every skeleton in the recipe, each from its own directory.
+    pub fn cook(&self, mut args: CookArgs) -> Result<Vec<CookSummary>, anyhow::Error> {
+        let current_directory = std::env::current_dir()?;
+        // Workspaces are built from their own directory, but a relative target directory
+        // is meant with respect to the one `cook` was invoked from.
+        args.target_dir = args
+            .target_dir
+            .map(|target_dir| current_directory.join(target_dir));
+
+        let previous_recipe = args.previous_recipe.take();
+        let mut summaries = vec![];
+        if !self.skeleton.manifests.is_empty() {
+            let previous = previous_recipe.as_ref().map(|recipe| &recipe.skeleton);
+            summaries.extend(cook_skeleton(
+                &self.skeleton,
+                &current_directory,
+                &args,
+                previous,
+            )?);
+        }
+        for workspace in &self.workspaces {
+            let directory = current_directory.join(&workspace.relative_path);
+            let previous = previous_recipe.as_ref().and_then(|recipe| {
+                recipe
+                    .workspaces
+                    .iter()
+                    .find(|previous| previous.relative_path == workspace.relative_path)
+                    .map(|previous| &previous.skeleton)
+            });
+            summaries.extend(cook_skeleton(
+                &workspace.skeleton,
+                &directory,
+                &args,
+                previous,
+            )?);
+        }
+        Ok(summaries)
+    }
+}
+
+/// Build-dependencies and proc-macros are cooked through a throwaway package, which declares
+/// all of them as its own build-dependencies, see `host_dependencies`.
+fn cook_host_dependencies(
+    skeleton: &Skeleton,
+    directory: &Path,
+    args: CookArgs,
+) -> Result<Option<CookSummary>, anyhow::Error> {
+    let host_skeleton = host_dependencies::skeleton(directory, skeleton, &args)
+        .context("Failed to determine the build-dependencies of the recipe.")?;
+    let Some(host_skeleton) = host_skeleton else {
+        log::info!(
+            "Skipping {}: there are no build-dependencies or proc-macros to cook.",
+            directory.display()
+        );
+        return Ok(None);
+    };
+    let target_dir = args
+        .target_dir
+        .clone()
+        .unwrap_or_else(|| directory.join("target"));
+    let host_directory = target_dir.join(host_dependencies::PACKAGE_NAME);
+    host_skeleton.build_minimum_project(&host_directory, false)?;
+
+    // The final build is expected to use the flags `cook` was invoked with.
+    let summary = CookSummary::new(&args, directory);
+    // Feature and package selection flags were meant for the actual workspace.
+    let args = CookArgs {
+        default_features: DefaultFeatures::Enabled,
+        all_features: AllFeatures::Disabled,
+        features: None,
+        target_args: TargetArgs {
+            benches: false,
+            tests: false,
+            examples: false,
+            all_targets: false,
+        },
+        manifest_path: Some(host_directory.join("Cargo.toml")),
+        target_dir: Some(target_dir),
+        package: None,
+        workspace: false,
+        bin: None,
+        ..args
+    };
+    build_dependencies(&args, directory)?;
+    host_skeleton
+        .remove_compiled_dummies(&host_directory, args.profile, args.target, args.target_dir)
+        .context("Failed to clean up dummy compilation artifacts.")?;
+    fs::remove_dir_all(&host_directory)?;
+    Ok(Some(summary))
+}
+
+fn derive_skeleton(
+    base_path: PathBuf,
+    member: Option<String>,
+    allow_missing_members: Option<&str>,
+) -> Result<Skeleton, anyhow::Error> {
+    let _span = tracing::info_span!("prepare", directory = %base_path.display()).entered();
+    match allow_missing_members {
+        Some(pattern) => Skeleton::derive_allowing_missing_members(base_path, member, pattern),
+        None => Skeleton::derive(base_path, member),
+    }
+}
+
+fn cook_skeleton(
+    skeleton: &Skeleton,
+    directory: &Path,
+    args: &CookArgs,
+    previous: Option<&Skeleton>,
+) -> Result<Option<CookSummary>, anyhow::Error> {
+    let recipe_skeleton = skeleton;
+    let _span = tracing::info_span!("cook", directory = %directory.display()).entered();
+    let mut args = args.clone();
+    match args.phase {
+        CookPhase::All => {}
+        CookPhase::Dependencies => {
+            args.target_args = TargetArgs {
+                benches: false,
+                tests: false,
+                examples: false,
+                all_targets: false,
+            };
+        }
+        CookPhase::DevDependencies => {
+            if !skeleton.has_dev_dependencies()? {
+                log::info!(
+                    "Skipping {}: there are no dev-dependencies to cook.",
+                    directory.display()
+                );
+                return Ok(None);
+            }
+            args.target_args.all_targets = true;
+        }
+        // Host dependencies can only be determined once the skeleton is on disk, see below.
+        CookPhase::BuildDependencies => {}
+    }
+    if args.coverage {
+        // `cargo llvm-cov` builds in a directory of its own, in the target directory.
+        let target_dir = match (&args.target_dir, std::env::var_os("CARGO_TARGET_DIR")) {
+            (Some(target_dir), _) => target_dir.clone(),
+            (None, Some(target_dir)) => PathBuf::from(target_dir),
+            (None, None) => directory.join("target"),
+        };
+        args.target_dir = Some(target_dir.join("llvm-cov-target"));
+    }
+    if args.toolchain.is_none() {
+        args.toolchain = pinned_channel(skeleton, args.install_toolchain)?;
+    }
+    if !matches!(args.command, CommandArg::Zigbuild) {
+        let suffixed = args
+            .target
+            .iter()
+            .flatten()
+            .find(|target| without_glibc_version(target) != target.as_str());
+        if let Some(target) = suffixed {
+            return Err(anyhow::anyhow!(
+                "`{}` pins the version of glibc, which only `cargo zigbuild` supports: pass `--zigbuild`, or `--target {}`.",
+                target,
+                without_glibc_version(target)
+            ));
+        }
+    }
+    if args.cross && args.target.is_none() {
+        args.target = Some(cross_targets(skeleton)?);
+    }
+    for group in &args.best_effort {
+        if let BestEffort::Target(target) = group {
+            if !args.target.iter().flatten().any(|t| t == target) {
+                return Err(anyhow::anyhow!(
+                    "`--best-effort {}` is neither `dev` nor one of the `--target` triples.",
+                    target
+                ));
+            }
+        }
+    }
+    if args.auto_install_target {
+        let targets: Vec<String> = match &args.target {
+            Some(targets) => targets.clone(),
+            None => std::env::var("CARGO_BUILD_TARGET").into_iter().collect(),
+        }
+        .iter()
+        .map(|target| without_glibc_version(target).to_string())
+        .collect();
+        install_targets(args.toolchain.as_deref(), &targets)?;
+    }
+    if skeleton.uses_artifact_dependencies()? {
+        args.unstable_features
+            .get_or_insert_with(HashSet::new)
+            .insert("bindeps".to_string());
+    }
+    // `cargo build --bin` fails if the binary requires features that are not enabled: like
+    // `cargo build` on its package, leave the binary out and cook the rest.
+    if let Some(bin) = &args.bin {
+        if let Some((package, missing)) = skeleton.missing_required_features(bin, &args)? {
+            log::warn!(
+                "The binary `{}` requires the features `{}`, which are not enabled: cooking the other targets of `{}` instead.",
+                bin,
+                missing.join("`, `"),
+                package
+            );
+            args.bin = None;
+            args.package = Some(vec![package]);
+        }
+    }
+    // Members that are not going to be built are left out of the skeleton altogether,
+    // unless other members depend on them.
+    let selected;
+    let skeleton = if let Some(package) = &args.package {
+        // Package specifications can carry a version, e.g. `foo@1.0.0`.
+        let names: Vec<_> = package
+            .iter()
+            .map(|spec| spec.split('@').next().unwrap_or(spec))
+            .collect();
+        selected = skeleton.keep_packages(|name| names.contains(&name))?;
+        &selected
+    } else if let Some(exclude) = args.exclude.as_ref().filter(|_| args.workspace) {
+        selected = skeleton.keep_packages(|name| !exclude.iter().any(|e| e == name))?;
+        &selected
+    } else if !args.workspace && args.manifest_path.is_none() {
+        // Like `cargo build`, only build the `default-members` of the workspace, if any.
+        selected = skeleton.keep_default_members()?;
+        &selected
+    } else {
+        skeleton
+    };
+    let stubbed;
+    let skeleton = if args.replace_local.is_empty() {
+        skeleton
+    } else {
+        stubbed = skeleton.with_stubbed_packages(&args.replace_local)?;
+        &stubbed
+    };
+    let overridden;
+    let dependency_overrides = args.dependency_overrides();
+    let skeleton = if dependency_overrides.is_empty() {
+        skeleton
+    } else {
+        overridden = skeleton
+            .with_dependency_profile_overrides(args.profile.name(), &dependency_overrides)?;
+        &overridden
+    };
+    let patched;
+    let skeleton = if args.profile_settings.is_empty() {
+        skeleton
+    } else {
+        patched = skeleton.with_profile_settings(&args.profile_settings)?;
+        &patched
+    };
+    let linked;
+    let skeleton = match args.linker {
+        None => skeleton,
+        Some(linker) => {
+            if !is_in_path(linker.binary()) {
+                return Err(anyhow::anyhow!(
+                    "`{}` was not found in PATH: install it to link with it.",
+                    linker.binary()
+                ));
+            }
+            if let Some(key) = ["CARGO_ENCODED_RUSTFLAGS", "RUSTFLAGS"]
+                .iter()
+                .find(|key| std::env::var_os(key).is_some())
+            {
+                log::warn!(
+                    "`{}` takes precedence over the `rustflags` of the configuration: add `{}` to it to link with {}.",
+                    key,
+                    linker.rustflags().join(" "),
+                    linker.binary()
+                );
+            }
+            linked = skeleton.with_rustflags(&linker.rustflags())?;
+            &linked
+        }
+    };
+    let instrumented;
+    let skeleton = if args.coverage {
+        instrumented = skeleton.with_rustflags(&coverage_rustflags())?;
+        &instrumented
+    } else {
+        skeleton
+    };
+    check_rust_version(skeleton, &args)?;
+    skeleton.check_vendored_sources(directory)?;
+    {
+        let _span = tracing::info_span!("write_skeleton").entered();
+        skeleton.build_minimum_project(directory, args.no_std)?;
+    }
+    if args.phase == CookPhase::BuildDependencies {
+        return cook_host_dependencies(skeleton, directory, args);
+    }
+    let mut summary = CookSummary::new(&args, directory);
+    let delta = match previous {
+        Some(previous) => delta(directory, previous, recipe_skeleton, &args)
+            .context("Failed to compare the recipe with the previous one.")?,
+        None => Delta::Everything("there is no previous recipe".to_string()),
+    };
+    match delta {
+        Delta::Everything(reason) => {
+            if previous.is_some() {
+                log::warn!(
+                    "Cooking every dependency of {}: {}.",
+                    directory.display(),
+                    reason
+                );
+            }
+            if args.each_feature {
+                build_each_feature(skeleton, &args, directory)?;
+            } else {
+                build_dependencies_best_effort(&args, directory)?;
+            }
+        }
+        Delta::Packages(packages) => {
+            if !packages.is_empty() {
+                build_dependencies_best_effort(
+                    &CookArgs {
+                        package: Some(packages.clone()),
+                        workspace: false,
+                        exclude: None,
+                        bin: None,
+                        ..args.clone()
+                    },
+                    directory,
+                )?;
+            }
+            summary.rebuilt = Some(packages);
+        }
+    }
+    let _span = tracing::info_span!("cleanup").entered();
+    skeleton
+        .remove_compiled_dummies(directory, args.profile, args.target, args.target_dir)
+        .context("Failed to clean up dummy compilation artifacts.")?;
+    Ok(Some(summary))
+}
+
+/// What `cook` did, phrased as instructions for the stage that builds the actual project.
+///
+/// Most caching issues boil down to the final build not lining up with `cook`: different
+/// working directory, different flags or a differently configured environment.
+#[derive(Debug, Clone)]
+pub struct CookSummary {
+    pub working_directory: PathBuf,
+    /// `cargo`, `cross` with `cook --cross`, or the [`CookArgs::cargo_command`] along with its
+    /// subcommand, which `cargo_arguments` then leave out.
+    pub program: String,
+    pub cargo_arguments: Vec<OsString>,
+    pub artifact_directories: Vec<PathBuf>,
+    pub environment: Vec<(&'static str, String)>,
+    /// The profile overrides injected in the skeleton, as `--config` values.
+    pub profile_overrides: Vec<String>,
+    /// The flags appended to the `rustflags` of the skeleton configuration.
+    pub rustflags: Vec<String>,
+    /// The crates rebuilt on top of what was cooked from the previous recipe, `None` if every
+    /// dependency was cooked.
+    pub rebuilt: Option<Vec<String>>,
+    /// Each package was cooked once per feature, see [`CookArgs::each_feature`].
+    pub each_feature: bool,
+    /// Dependencies were instrumented for coverage, see [`CookArgs::coverage`].
+    pub coverage: bool,
+}
+
+impl CookSummary {
+    fn new(args: &CookArgs, directory: &Path) -> Self {
+        CookSummary {
+            program: match &args.cargo_command {
+                Some(cargo_command) => cargo_command.join(" "),
+                None if args.cross => "cross".to_string(),
+                None => "cargo".to_string(),
+            },
+            cargo_arguments: cargo_arguments(args)
+                .into_iter()
+                .skip(usize::from(args.cargo_command.is_some()))
+                .collect(),
+            artifact_directories: artifact_directories(
+                directory,
+                &args.profile,
+                args.target.as_deref(),
+                args.target_dir.as_deref(),
+            ),
+            environment: Self::CACHE_RELEVANT_ENVIRONMENT
+                .iter()
+                .filter_map(|key| std::env::var(key).ok().map(|value| (*key, value)))
+                .chain(cook_environment(args.incremental))
+                .collect(),
+            working_directory: directory.to_path_buf(),
+            profile_overrides: args
+                .dependency_overrides()
+                .into_iter()
+                .map(|(key, value)| {
+                    format!(
+                        "profile.{}.package.\"*\".{}={}",
+                        args.profile.name(),
+                        key,
+                        value
+                    )
+                })
+                .chain(args.profile_settings.iter().map(ProfileSetting::to_config))
+                .collect(),
+            rustflags: args
+                .linker
+                .map(|linker| linker.rustflags())
+                .unwrap_or_default(),
+            rebuilt: None,
+            each_feature: args.each_feature,
+            coverage: args.coverage,
+        }
+    }
+
+    /// Environment variables that change how dependencies are compiled.
+    pub(crate) const CACHE_RELEVANT_ENVIRONMENT: &'static [&'static str] = &[
+        "CARGO_HOME",
+        "CARGO_TARGET_DIR",
+        "CARGO_BUILD_TARGET",
+        "CARGO_ENCODED_RUSTFLAGS",
+        "RUSTFLAGS",
+        "RUSTC_WRAPPER",
+        "CARGO_INCREMENTAL",
+    ];
+}
+
+impl fmt::Display for CookSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arguments: Vec<_> = self
+            .cargo_arguments
+            .iter()
+            .map(|argument| argument.to_string_lossy())
+            .collect();
+        match &self.rebuilt {
+            Some(rebuilt) if rebuilt.is_empty() => writeln!(
+                f,
+                "Cargo.lock did not change since the previous recipe: nothing was rebuilt."
+            )?,
+            Some(rebuilt) => writeln!(
+                f,
+                "Only rebuilt what changed since the previous recipe: {}",
+                rebuilt.join(", ")
+            )?,
+            None => {}
+        }
+        writeln!(f, "Dependencies have been cooked. To reuse them:")?;
+        writeln!(
+            f,
+            "- build from the same directory: {}",
+            self.working_directory.display()
+        )?;
+        writeln!(
+            f,
+            "- with the same flags: {} {}",
+            self.program,
+            arguments.join(" ")
+        )?;
+        if self.each_feature {
+            writeln!(
+                f,
+                "    or with the feature selections of `cargo hack --each-feature`, package by package"
+            )?;
+        }
+        if !self.profile_overrides.is_empty() {
+            writeln!(
+                f,
+                "- with the same profile overrides, in the manifest or with `--config`:"
+            )?;
+            for value in &self.profile_overrides {
+                writeln!(f, "    --config '{}'", value)?;
+            }
+        }
+        if !self.rustflags.is_empty() {
+            writeln!(
+                f,
+                "- with the same rustflags, in `.cargo/config.toml`: {}",
+                self.rustflags.join(" ")
+            )?;
+        }
+        if self.coverage {
+            writeln!(
+                f,
+                "- through `cargo llvm-cov`, which adds the same coverage rustflags: {}",
+                coverage_rustflags().join(" ")
+            )?;
+        }
+        if !self.environment.is_empty() {
+            writeln!(f, "- with the same environment:")?;
+            for (key, value) in &self.environment {
+                writeln!(f, "    {}={}", key, value)?;
+            }
+        }
+        write!(f, "- artifacts will land in:")?;
+        for directory in &self.artifact_directories {
+            write!(f, "\n    {}", directory.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OptimisationProfile {
+    Release,
+    Debug,
+    Other(String),
+}
+
+impl OptimisationProfile {
+    /// The name of the profile, as used in `[profile.<name>]`.
+    pub fn name(&self) -> &str {
+        match self {
+            OptimisationProfile::Release => "release",
+            OptimisationProfile::Debug => "dev",
+            OptimisationProfile::Other(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DefaultFeatures {
+    Enabled,
+    Disabled,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AllFeatures {
+    Enabled,
+    Disabled,
+}
+
+/// The arguments `cook` passes to `cargo`, subcommand included.
+fn cargo_arguments(args: &CookArgs) -> Vec<OsString> {
+    let CookArgs {
+        profile,
+        command: command_arg,
+        default_features,
+        all_features,
+        features,
+        unstable_features,
+        target,
+        target_dir,
+        target_args,
+        manifest_path,
+        package,
+        workspace,
+        exclude,
+        offline,
+        timings,
+        bin,
+        no_std: _no_std,
+        phase: _phase,
+        build_command: _build_command,
+        cargo_command: _cargo_command,
+        extra_arguments,
+        clippy_arguments,
+        fast_dependencies: _fast_dependencies,
+        incremental: _incremental,
+        dependencies_debuginfo: _dependencies_debuginfo,
+        previous_recipe: _previous_recipe,
+        each_feature: _each_feature,
+        profile_settings: _profile_settings,
+        replace_local: _replace_local,
+        // The final build picks the toolchain from the toolchain file.
+        toolchain: _toolchain,
+        install_toolchain: _install_toolchain,
+        auto_install_target: _auto_install_target,
+        ignore_rust_version,
+        // The final build picks the linker flags from the configuration.
+        linker: _linker,
+        cross: _cross,
+        best_effort: _best_effort,
+        // Passed on through the rustflags and the target directory.
+        coverage: _coverage,
+    } = args;
+    let mut arguments: Vec<OsString> = vec![match command_arg {
+        CommandArg::Build => "build".into(),
+        CommandArg::Check => "check".into(),
+        CommandArg::Clippy => "clippy".into(),
+        CommandArg::Zigbuild => "zigbuild".into(),
+        CommandArg::Doc => "doc".into(),
+        CommandArg::Nextest => "test".into(),
+    }];
+    if matches!(command_arg, CommandArg::Nextest) {
+        arguments.push("--no-run".into());
+    }
+    if profile == &OptimisationProfile::Release {
+        arguments.push("--release".into());
+    } else if let OptimisationProfile::Other(custom_profile) = profile {
+        arguments.extend(["--profile".into(), custom_profile.into()]);
+    }
+    if default_features == &DefaultFeatures::Disabled {
+        arguments.push("--no-default-features".into());
+    }
+    if let Some(features) = features {
+        let feature_flag = features.iter().cloned().collect::<Vec<String>>().join(",");
+        arguments.extend(["--features".into(), feature_flag.into()]);
+    }
+    if all_features == &AllFeatures::Enabled {
+        arguments.push("--all-features".into());
+    }
+    if let Some(unstable_features) = unstable_features {
+        for unstable_feature in unstable_features.iter().cloned() {
+            arguments.extend(["-Z".into(), unstable_feature.into()]);
+        }
+    }
+    if let Some(target) = target {
+        for target in target {
+            arguments.extend(["--target".into(), target.into()]);
+        }
+    }
+    if let Some(target_dir) = target_dir {
+        arguments.extend(["--target-dir".into(), target_dir.into()]);
+    }
+    if target_args.benches {
+        arguments.push("--benches".into());
+    }
+    if target_args.tests {
+        arguments.push("--tests".into());
+    }
+    if target_args.examples {
+        arguments.push("--examples".into());
+    }
+    if target_args.all_targets {
+        arguments.push("--all-targets".into());
+    }
+    if let Some(manifest_path) = manifest_path {
+        arguments.extend(["--manifest-path".into(), manifest_path.into()]);
+    }
+    if let Some(package) = package {
+        for package in package {
+            arguments.extend(["--package".into(), package.into()]);
+        }
+    }
+    if let Some(binary_target) = bin {
+        arguments.extend(["--bin".into(), binary_target.into()]);
+    }
+    if *workspace {
+        arguments.push("--workspace".into());
+    }
+    if let Some(exclude) = exclude {
+        for package in exclude {
+            arguments.extend(["--exclude".into(), package.into()]);
+        }
+    }
+    if *ignore_rust_version {
+        arguments.push("--ignore-rust-version".into());
+    }
+    if *offline {
+        arguments.push("--offline".into());
+    }
+    if *timings {
+        arguments.push("--timings".into());
+    }
+    arguments.extend(extra_arguments.iter().map(OsString::from));
+    if matches!(command_arg, CommandArg::Clippy) && !clippy_arguments.is_empty() {
+        // The extra arguments might already end with the arguments of clippy.
+        if !extra_arguments.iter().any(|argument| argument == "--") {
+            arguments.push("--".into());
+        }
+        arguments.extend(clippy_arguments.iter().map(OsString::from));
+    }
+
+    arguments
+}
+
+/// The channel pinned by the toolchain file of `skeleton`, installed first if `install` is set.
+/// `None` without rustup, whose proxy alone understands `cargo +<channel>`, and for custom
+/// toolchains, which the proxy picks from the toolchain file written to the skeleton.
+fn pinned_channel(skeleton: &Skeleton, install: bool) -> Result<Option<String>, anyhow::Error> {
+    let Some(file) = &skeleton.toolchain_file else {
+        return Ok(None);
+    };
+    if !rustup_is_installed() {
+        if install {
+            return Err(anyhow::anyhow!(
+                "rustup is not installed: the toolchain pinned in {} cannot be installed.",
+                file.relative_path.display()
+            ));
+        }
+        log::warn!(
+            "rustup is not installed: the toolchain pinned in {} is ignored and dependencies are \
+            built with the toolchain `cargo` belongs to. Make sure the final build uses the same one.",
+            file.relative_path.display()
+        );
+        return Ok(None);
+    }
+    let Some(toolchain) = PinnedToolchain::from_file(file)? else {
+        return Ok(None);
+    };
+    if install {
+        toolchain.install()?;
+    }
+    Ok(Some(toolchain.channel))
+}
+
+/// Fail before building anything if the toolchain is older than the `rust-version` of the
+/// workspace: dependencies that require a newer one would otherwise fail to compile midway,
+/// with errors that rarely point at the toolchain.
+fn check_rust_version(skeleton: &Skeleton, args: &CookArgs) -> Result<(), anyhow::Error> {
+    let Some((package, rust_version)) = skeleton.rust_version()? else {
+        return Ok(());
+    };
+    let rustc = rustc_version(args.toolchain.as_deref())?;
+    if rustc >= rust_version {
+        return Ok(());
+    }
+    if args.ignore_rust_version {
+        log::warn!(
+            "`{}` requires rustc {} or newer, while dependencies are cooked with rustc {}: \
+            building anyway, as the `rust-version` is ignored.",
+            package,
+            rust_version,
+            rustc
+        );
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "`{}` requires rustc {} or newer, while dependencies would be cooked with rustc {}: \
+        use a newer toolchain, or pass `--ignore-rust-version` to build anyway.",
+        package,
+        rust_version,
+        rustc
+    ))
+}
+
+/// Build the dependencies, the groups of [`CookArgs::best_effort`] last: if they fail to compile,
+/// the cook goes on with a warning, and what did compile stays in the target directory.
+fn build_dependencies_best_effort(args: &CookArgs, directory: &Path) -> Result<(), anyhow::Error> {
+    let with_dev_dependencies = args.target_args.tests
+        || args.target_args.benches
+        || args.target_args.examples
+        || args.target_args.all_targets;
+    let dev_dependencies =
+        with_dev_dependencies && args.best_effort.contains(&BestEffort::DevDependencies);
+    let best_effort_targets: Vec<&String> = args
+        .target
+        .iter()
+        .flatten()
+        .filter(|target| {
+            args.best_effort
+                .contains(&BestEffort::Target(target.to_string()))
+        })
+        .collect();
+    if !dev_dependencies && best_effort_targets.is_empty() {
+        return build_dependencies(args, directory);
+    }
+
+    let required_targets = args.target.as_ref().map(|targets| {
+        targets
+            .iter()
+            .filter(|target| !best_effort_targets.contains(target))
+            .cloned()
+            .collect::<Vec<_>>()
+    });
+    if required_targets
+        .as_ref()
+        .is_none_or(|targets| !targets.is_empty())
+    {
+        build_dependencies(
+            &CookArgs {
+                target: required_targets,
+                target_args: if dev_dependencies {
+                    TargetArgs {
+                        benches: false,
+                        tests: false,
+                        examples: false,
+                        all_targets: false,
+                    }
+                } else {
+                    args.target_args.clone()
+                },
+                ..args.clone()
+            },
+            directory,
+        )?;
+    }
+
+    let mut groups = vec![];
+    if dev_dependencies {
+        groups.push("the dev-dependencies".to_string());
+    }
+    groups.extend(
+        best_effort_targets
+            .iter()
+            .map(|target| format!("the dependencies of `{}`", target)),
+    );
+    let _span = tracing::info_span!("build_best_effort", groups = ?groups).entered();
+    let mut command = dependencies_command(
+        &CookArgs {
+            extra_arguments: std::iter::once("--keep-going".to_string())
+                .chain(args.extra_arguments.iter().cloned())
+                .collect(),
+            ..args.clone()
+        },
+        directory,
+    );
+    let status = spawn(&mut command)?
+        .wait()
+        .context("Failed to run command")?;
+    if !status.success() {
+        log::warn!(
+            "Some of {} failed to compile, which `--best-effort` allows: they are missing from the cooked layer, and the final build compiles them.",
+            groups.join(" and ")
+        );
+    }
+    Ok(())
+}
+
+fn build_dependencies(args: &CookArgs, directory: &Path) -> Result<(), anyhow::Error> {
+    let _span = tracing::info_span!(
+        "build",
+        packages = ?args.package.as_deref().unwrap_or_default(),
+        features = ?args.features.iter().flatten().collect::<Vec<_>>(),
+    )
+    .entered();
+    execute_command(&mut dependencies_command(args, directory))
+}
+
+/// The `cargo` invocation building the dependencies according to `args`.
+fn dependencies_command(args: &CookArgs, directory: &Path) -> Command {
+    let (cargo_path, toolchain) = match &args.toolchain {
+        // `cross` passes `+<toolchain>` on to the `cargo` of its container.
+        toolchain if args.cross => (
+            "cross".to_string(),
+            toolchain.as_ref().map(|toolchain| format!("+{}", toolchain)),
+        ),
+        // `+<toolchain>` is understood by the rustup proxy, not by the `cargo` of a toolchain.
+        Some(toolchain) => ("cargo".to_string(), Some(format!("+{}", toolchain))),
+        None => (std::env::var("CARGO").expect("The `CARGO` environment variable was not set. This is unexpected: it should always be provided by `cargo` when invoking a custom sub-command, allowing `cargo-chef` to correctly detect which toolchain should be used. Please file a bug."), None),
+    };
+    let mut command = match (&args.build_command, &args.cargo_command) {
+        (Some(template), _) => render_build_command(
+            template,
+            &cargo_path,
+            toolchain.as_deref(),
+            cargo_arguments(args),
+        ),
+        (None, Some(cargo_command)) => {
+            // The subcommand of `cargo build` is replaced as well.
+            let template: Vec<String> = cargo_command
+                .iter()
+                .enumerate()
+                .map(|(index, token)| match token.as_str() {
+                    "cargo" if index == 0 => "{cargo}".to_string(),
+                    token => token.to_string(),
+                })
+                .collect();
+            render_build_command(
+                &template,
+                &cargo_path,
+                toolchain.as_deref(),
+                cargo_arguments(args).into_iter().skip(1).collect(),
+            )
+        }
+        (None, None) => {
+            let mut command = Command::new(cargo_path);
+            command.args(&toolchain);
+            command.args(cargo_arguments(args));
+            command
+        }
+    };
+    command.current_dir(directory);
+    if args.coverage {
+        // They take precedence over the configuration: `cargo llvm-cov` appends to them.
+        if let Ok(rustflags) = std::env::var("CARGO_ENCODED_RUSTFLAGS") {
+            let mut flags: Vec<String> = rustflags
+                .split('\x1f')
+                .filter(|flag| !flag.is_empty())
+                .map(str::to_string)
+                .collect();
+            flags.extend(coverage_rustflags());
+            command.env("CARGO_ENCODED_RUSTFLAGS", flags.join("\x1f"));
+        } else if let Ok(rustflags) = std::env::var("RUSTFLAGS") {
+            command.env(
+                "RUSTFLAGS",
+                format!("{} {}", rustflags, coverage_rustflags().join(" ")),
+            );
+        }
+    }
+    let environment = cook_environment(args.incremental);
+    if args.cross {
+        command.env(
+            "CROSS_BUILD_ENV_PASSTHROUGH",
+            cross_passthrough(&environment),
+        );
+    }
+    command.envs(environment);
+    command
+}
+
+/// The targets `cook --cross` builds for when none is passed: `CARGO_BUILD_TARGET`, or the
+/// `build.target` of the configuration of the skeleton. `cross` builds for the host otherwise,
+/// which it is of no use for.
+fn cross_targets(skeleton: &Skeleton) -> Result<Vec<String>, anyhow::Error> {
+    if let Ok(target) = std::env::var("CARGO_BUILD_TARGET") {
+        return Ok(vec![target]);
+    }
+    let config: Option<toml::Value> = skeleton
+        .config_file
+        .as_deref()
+        .map(toml::from_str)
+        .transpose()?;
+    let targets = match config
+        .as_ref()
+        .and_then(|config| config.get("build")?.get("target"))
+    {
+        Some(toml::Value::String(target)) => vec![target.clone()],
+        Some(toml::Value::Array(targets)) => targets
+            .iter()
+            .filter_map(|target| target.as_str().map(String::from))
+            .collect(),
+        _ => vec![],
+    };
+    if targets.is_empty() {
+        return Err(anyhow::anyhow!(
+            "`cook --cross` needs a target to build for: pass `--target`, as the final `cross build` does."
+        ));
+    }
+    Ok(targets)
+}
+
+/// The environment variables `cross` forwards to its container: the ones it is already asked
+/// to, and those `cook` sets, which would otherwise only apply outside of the container.
+fn cross_passthrough(environment: &[(&str, String)]) -> String {
+    let mut passthrough: Vec<String> = std::env::var("CROSS_BUILD_ENV_PASSTHROUGH")
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+    for (name, _) in environment {
+        if !passthrough.iter().any(|existing| existing == name) {
+            passthrough.push(name.to_string());
+        }
+    }
+    passthrough.join(" ")
+}
+
+/// The feature selections of `cargo hack --each-feature`, for every package of `skeleton` that
+/// `args` select.
+fn build_each_feature(
+    skeleton: &Skeleton,
+    args: &CookArgs,
+    directory: &Path,
+) -> Result<(), anyhow::Error> {
+    for (package, features) in skeleton.package_features()? {
+        if let Some(selected) = &args.package {
+            if !selected
+                .iter()
+                .any(|spec| spec.split('@').next() == Some(package.as_str()))
+            {
+                continue;
+            }
+        }
+        let mut selections = vec![(None, AllFeatures::Disabled)];
+        selections.extend(
+            features
+                .iter()
+                .map(|feature| (Some(feature.clone()), AllFeatures::Disabled)),
+        );
+        if features.len() > 1 {
+            selections.push((None, AllFeatures::Enabled));
+        }
+        for (feature, all_features) in selections {
+            log::info!(
+                "Cooking `{}` with {}.",
+                package,
+                match (&feature, all_features) {
+                    (_, AllFeatures::Enabled) => "all features".to_string(),
+                    (Some(feature), _) => format!("the feature `{}`", feature),
+                    (None, _) => "no default features".to_string(),
+                }
+            );
+            let default_features = match all_features {
+                AllFeatures::Enabled => DefaultFeatures::Enabled,
+                AllFeatures::Disabled => DefaultFeatures::Disabled,
+            };
+            build_dependencies(
+                &CookArgs {
+                    package: Some(vec![package.clone()]),
+                    workspace: false,
+                    exclude: None,
+                    bin: None,
+                    default_features,
+                    all_features,
+                    features: feature.map(|feature| HashSet::from([feature])),
+                    ..args.clone()
+                },
+                directory,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn render_build_command(
+    template: &[String],
+    cargo_path: &str,
+    toolchain: Option<&str>,
+    arguments: Vec<OsString>,
+) -> Command {
+    let mut rendered: Vec<OsString> = vec![];
+    let mut arguments = Some(arguments);
+    for token in template {
+        match token.as_str() {
+            "{args}" => rendered.extend(arguments.take().into_iter().flatten()),
+            "{cargo}" => {
+                rendered.push(cargo_path.into());
+                rendered.extend(toolchain.map(OsString::from));
+            }
+            token => rendered.push(token.replace("{cargo}", cargo_path).into()),
+        }
+    }
+    rendered.extend(arguments.into_iter().flatten());
+
+    let mut rendered = rendered.into_iter();
+    let mut command = Command::new(rendered.next().unwrap_or_else(|| cargo_path.into()));
+    command.args(rendered);
+    command
+}
+
+/// `--build-command` and `--cargo-command` may name a program that is not installed.
+fn spawn(command: &mut Command) -> Result<std::process::Child, anyhow::Error> {
+    command.spawn().map_err(|e| {
+        anyhow::anyhow!(
+            "`{}` could not be started: {}",
+            command.get_program().to_string_lossy(),
+            e
+        )
+    })
+}
+
+fn execute_command(command: &mut Command) -> Result<(), anyhow::Error> {
+    let mut child = spawn(command)?;
+
+    let exit_status = child.wait().context("Failed to run command")?;
+
+    if !exit_status.success() {
+        return match exit_status.code() {
+            Some(code) => Err(anyhow::anyhow!("Exited with status code: {}", code)),
+            None => Err(anyhow::anyhow!("Process terminated by signal")),
+        };
+    }
+    Ok(())
+}
@@ -0,0 +1,401 @@
+//! What changed between two recipes (e.g. the ones of the base and of the head of a pull
+//! request), and what it costs to cook the new one on top of what was cooked from the old one:
+//! a concise report for CI to post as a comment.
+use crate::skeleton::delta::{locked_packages, LockedPackage};
+use crate::{Recipe, Skeleton, TimedUnit};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipeReport {
+    pub changed: bool,
+    /// The files of the recipe that were added, removed or modified, relative to the project.
+    pub changed_files: Vec<PathBuf>,
+    /// The external crates whose locked version (or source) changed.
+    pub dependencies: Vec<DependencyChange>,
+    /// The external crates that are rebuilt because they or one of their dependencies changed.
+    pub rebuilt: BTreeSet<String>,
+    /// Why every dependency is rebuilt, if they all are.
+    pub full_rebuild: Option<String>,
+    /// How long rebuilding takes, according to the timing report of a previous cook.
+    pub estimate: Option<Estimate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyChange {
+    pub name: String,
+    /// The locked version before, `None` if the crate was added.
+    pub before: Option<String>,
+    /// The locked version after, `None` if the crate was removed.
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    /// In seconds: the duration of the previous cook for a full rebuild, otherwise the sum of
+    /// the durations of the rebuilt units, as if they were built one after the other.
+    pub seconds: f64,
+    /// Rebuilt crates the timing report knows nothing about, e.g. new ones.
+    pub unknown: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    /// GitHub-flavoured markdown, to be posted as a comment.
+    Markdown,
+}
+
+impl RecipeReport {
+    /// Compare the recipe `head` with the recipe `base`.
+    pub fn compare(base: &Recipe, head: &Recipe) -> Result<Self, anyhow::Error> {
+        let base_skeletons = skeletons(base);
+        let head_skeletons = skeletons(head);
+        let mut changed_files = BTreeSet::new();
+        let mut full_rebuild = None;
+        for (path, head_skeleton) in &head_skeletons {
+            let Some(base_skeleton) = base_skeletons.get(path) else {
+                changed_files.insert(path.join("Cargo.toml"));
+                full_rebuild = Some("a workspace was added");
+                continue;
+            };
+            let base_files = files(base_skeleton);
+            let head_files = files(head_skeleton);
+            for file in base_files.keys().chain(head_files.keys()) {
+                if base_files.get(file) != head_files.get(file) {
+                    changed_files.insert(path.join(file));
+                }
+            }
+            let without_lock_file = |skeleton: &Skeleton| Skeleton {
+                lock_file: None,
+                ..skeleton.clone()
+            };
+            if without_lock_file(base_skeleton) != without_lock_file(head_skeleton) {
+                full_rebuild.get_or_insert("the recipe changed beyond Cargo.lock");
+            }
+        }
+        for path in base_skeletons.keys() {
+            if !head_skeletons.contains_key(path) {
+                changed_files.insert(path.join("Cargo.toml"));
+            }
+        }
+
+        let base_lock = LockFile::of(&base_skeletons)?;
+        let head_lock = LockFile::of(&head_skeletons)?;
+        let dependencies = dependency_changes(&base_lock.packages, &head_lock.packages);
+        let changed: BTreeSet<String> = head_lock
+            .packages
+            .iter()
+            .filter(|package| !base_lock.packages.contains(package))
+            .map(|package| package.name.clone())
+            .collect();
+        let rebuilt = match full_rebuild {
+            Some(_) => head_lock
+                .packages
+                .iter()
+                .map(|package| package.name.clone())
+                .collect(),
+            None => head_lock.dependents(&changed),
+        };
+        Ok(RecipeReport {
+            changed: base != head,
+            changed_files: changed_files.into_iter().collect(),
+            dependencies,
+            rebuilt,
+            full_rebuild: full_rebuild.map(str::to_string),
+            estimate: None,
+        })
+    }
+
+    /// Estimate how long rebuilding takes from the units of a previous cook.
+    pub fn estimate(&mut self, units: &[TimedUnit]) {
+        if self.full_rebuild.is_some() {
+            self.estimate = Some(Estimate {
+                seconds: units
+                    .iter()
+                    .map(|unit| unit.start + unit.duration)
+                    .fold(0.0, f64::max),
+                unknown: vec![],
+            });
+            return;
+        }
+        let seconds = units
+            .iter()
+            .filter(|unit| self.rebuilt.contains(&unit.name))
+            .map(|unit| unit.duration)
+            .sum();
+        let unknown = self
+            .rebuilt
+            .iter()
+            .filter(|name| !units.iter().any(|unit| &unit.name == *name))
+            .cloned()
+            .collect();
+        self.estimate = Some(Estimate { seconds, unknown });
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_string(),
+            ReportFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    fn headline(&self) -> String {
+        if !self.changed {
+            return "The recipe did not change: the cooked dependencies are reused as they are."
+                .to_string();
+        }
+        match &self.full_rebuild {
+            Some(reason) => format!(
+                "The recipe changed: every dependency is rebuilt, as {}.",
+                reason
+            ),
+            None if self.rebuilt.is_empty() => {
+                "The recipe changed, but no dependency has to be rebuilt.".to_string()
+            }
+            None => format!(
+                "The recipe changed: {} of the dependencies {} rebuilt.",
+                self.rebuilt.len(),
+                if self.rebuilt.len() == 1 { "is" } else { "are" }
+            ),
+        }
+    }
+
+    fn estimate_line(&self) -> Option<String> {
+        let estimate = self.estimate.as_ref()?;
+        let mut line = match self.full_rebuild {
+            Some(_) => format!(
+                "Estimated extra compile time: {:.1}s, as long as the previous cook.",
+                estimate.seconds
+            ),
+            None => format!(
+                "Estimated extra compile time: up to {:.1}s, building the rebuilt crates one after the other.",
+                estimate.seconds
+            ),
+        };
+        if !estimate.unknown.is_empty() {
+            line.push_str(&format!(
+                " Not counting {}, which the timing report does not know about.",
+                estimate.unknown.join(", ")
+            ));
+        }
+        Some(line)
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut markdown = format!("### cargo-chef\n\n{}\n", self.headline());
+        if !self.dependencies.is_empty() {
+            markdown.push_str("\n| Dependency | Before | After |\n|---|---|---|\n");
+            for change in &self.dependencies {
+                markdown.push_str(&format!(
+                    "| `{}` | {} | {} |\n",
+                    change.name,
+                    change.before.as_deref().unwrap_or("-"),
+                    change.after.as_deref().unwrap_or("-")
+                ));
+            }
+        }
+        if !self.changed_files.is_empty() {
+            let files: Vec<String> = self
+                .changed_files
+                .iter()
+                .map(|file| format!("`{}`", file.display()))
+                .collect();
+            markdown.push_str(&format!("\nChanged files: {}\n", files.join(", ")));
+        }
+        if let Some(line) = self.estimate_line() {
+            markdown.push_str(&format!("\n{}\n", line));
+        }
+        markdown
+    }
+}
+
+impl fmt::Display for RecipeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.headline())?;
+        if !self.dependencies.is_empty() {
+            writeln!(f, "Dependencies:")?;
+            for change in &self.dependencies {
+                writeln!(
+                    f,
+                    "  {} {} -> {}",
+                    change.name,
+                    change.before.as_deref().unwrap_or("(added)"),
+                    change.after.as_deref().unwrap_or("(removed)")
+                )?;
+            }
+        }
+        if !self.changed_files.is_empty() {
+            writeln!(f, "Changed files:")?;
+            for file in &self.changed_files {
+                writeln!(f, "  {}", file.display())?;
+            }
+        }
+        if let Some(line) = self.estimate_line() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// The skeletons of a recipe, by the path of their workspace.
+fn skeletons(recipe: &Recipe) -> BTreeMap<PathBuf, &Skeleton> {
+    let mut skeletons = BTreeMap::new();
+    if !recipe.skeleton.manifests.is_empty() {
+        skeletons.insert(PathBuf::new(), &recipe.skeleton);
+    }
+    for workspace in &recipe.workspaces {
+        skeletons.insert(workspace.relative_path.clone(), &workspace.skeleton);
+    }
+    skeletons
+}
+
+/// The files `cook` writes for a skeleton, by path.
+fn files(skeleton: &Skeleton) -> BTreeMap<PathBuf, &str> {
+    let mut files: BTreeMap<PathBuf, &str> = skeleton
+        .manifests
+        .iter()
+        .map(|manifest| (manifest.relative_path.clone(), manifest.contents.as_str()))
+        .chain(
+            skeleton
+                .files
+                .iter()
+                .chain(&skeleton.toolchain_file)
+                .map(|file| (file.relative_path.clone(), file.contents.as_str())),
+        )
+        .collect();
+    if let Some(lock_file) = &skeleton.lock_file {
+        files.insert(PathBuf::from("Cargo.lock"), lock_file);
+    }
+    if let Some(config_file) = &skeleton.config_file {
+        files.insert(Path::new(".cargo").join("config.toml"), config_file);
+    }
+    files
+}
+
+/// The external packages of the lock files of a recipe, and the edges between them.
+struct LockFile {
+    packages: Vec<LockedPackage>,
+    /// The names of the packages depending on each package.
+    dependents: HashMap<String, BTreeSet<String>>,
+}
+
+impl LockFile {
+    fn of(skeletons: &BTreeMap<PathBuf, &Skeleton>) -> Result<Self, anyhow::Error> {
+        let mut lock_file = LockFile {
+            packages: vec![],
+            dependents: HashMap::new(),
+        };
+        for skeleton in skeletons.values() {
+            let Some(contents) = &skeleton.lock_file else {
+                continue;
+            };
+            let contents: toml::Value = toml::from_str(contents)?;
+            for package in locked_packages(&contents) {
+                if !lock_file.packages.contains(&package) {
+                    lock_file.packages.push(package);
+                }
+            }
+            for package in contents
+                .get("package")
+                .and_then(|packages| packages.as_array())
+                .into_iter()
+                .flatten()
+            {
+                let Some(name) = package.get("name").and_then(|name| name.as_str()) else {
+                    continue;
+                };
+                // Dependencies are listed as `name`, `name version` or `name version (source)`.
+                for dependency in package
+                    .get("dependencies")
+                    .and_then(|dependencies| dependencies.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|dependency| dependency.as_str()?.split(' ').next())
+                {
+                    lock_file
+                        .dependents
+                        .entry(dependency.to_string())
+                        .or_default()
+                        .insert(name.to_string());
+                }
+            }
+        }
+        Ok(lock_file)
+    }
+
+    /// The external packages among `changed` and the ones depending on them, directly or not.
+    fn dependents(&self, changed: &BTreeSet<String>) -> BTreeSet<String> {
+        let external: BTreeSet<&str> = self.packages.iter().map(|p| p.name.as_str()).collect();
+        let mut affected = BTreeSet::new();
+        let mut queue: Vec<&String> = changed.iter().collect();
+        while let Some(name) = queue.pop() {
+            if !affected.insert(name.clone()) {
+                continue;
+            }
+            queue.extend(self.dependents.get(name).into_iter().flatten());
+        }
+        affected.retain(|name| external.contains(name.as_str()));
+        affected
+    }
+}
+
+fn dependency_changes(base: &[LockedPackage], head: &[LockedPackage]) -> Vec<DependencyChange> {
+    let versions = |packages: &[LockedPackage], name: &str| -> Vec<String> {
+        packages
+            .iter()
+            .filter(|package| package.name == name)
+            .map(describe)
+            .collect()
+    };
+    let names: BTreeSet<&str> = base
+        .iter()
+        .chain(head)
+        .map(|package| package.name.as_str())
+        .collect();
+    let mut changes = vec![];
+    for name in names {
+        let before = versions(base, name);
+        let after = versions(head, name);
+        let removed: Vec<&String> = before.iter().filter(|v| !after.contains(v)).collect();
+        let added: Vec<&String> = after.iter().filter(|v| !before.contains(v)).collect();
+        // A single version replaced by another one is an update, anything else is listed as
+        // additions and removals.
+        if let ([before], [after]) = (removed.as_slice(), added.as_slice()) {
+            changes.push(DependencyChange {
+                name: name.to_string(),
+                before: Some(before.to_string()),
+                after: Some(after.to_string()),
+            });
+            continue;
+        }
+        for version in removed {
+            changes.push(DependencyChange {
+                name: name.to_string(),
+                before: Some(version.clone()),
+                after: None,
+            });
+        }
+        for version in added {
+            changes.push(DependencyChange {
+                name: name.to_string(),
+                before: None,
+                after: Some(version.clone()),
+            });
+        }
+    }
+    changes
+}
+
+/// The version of a package, with the commit it is locked to for git sources.
+fn describe(package: &LockedPackage) -> String {
+    match package
+        .source
+        .strip_prefix("git+")
+        .and_then(|source| source.rsplit_once('#'))
+    {
+        Some((_, commit)) => format!("{} ({})", package.version, &commit[..commit.len().min(8)]),
+        None => package.version.clone(),
+    }
+}
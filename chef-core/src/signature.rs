@@ -0,0 +1,34 @@
+//! Detached ed25519 signatures for recipe files.
+//!
+//! Keys are expected in the PEM format produced by `openssl`:
+//! ```bash
+//! openssl genpkey -algorithm ed25519 -out chef.pem
+//! openssl pkey -in chef.pem -pubout -out chef.pub.pem
+//! ```
+//! Signatures are the raw 64 bytes computed over the serialized recipe, therefore they can
+//! be verified with `openssl pkeyutl -verify -rawin` as well.
+use anyhow::Context;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::convert::TryFrom;
+
+/// Sign a serialized recipe using a PEM-encoded ed25519 private key.
+pub fn sign_recipe(serialized: &str, private_key_pem: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let signing_key = SigningKey::from_pkcs8_pem(private_key_pem)
+        .context("Failed to parse the ed25519 signing key.")?;
+    Ok(signing_key.sign(serialized.as_bytes()).to_bytes().to_vec())
+}
+
+/// Verify the detached signature of a serialized recipe using a PEM-encoded ed25519 public key.
+pub fn verify_recipe_signature(
+    serialized: &str,
+    signature: &[u8],
+    public_key_pem: &str,
+) -> Result<(), anyhow::Error> {
+    let verifying_key = VerifyingKey::from_public_key_pem(public_key_pem)
+        .context("Failed to parse the ed25519 verifying key.")?;
+    let signature = Signature::try_from(signature).context("The signature is malformed.")?;
+    verifying_key
+        .verify(serialized.as_bytes(), &signature)
+        .context("The recipe does not match its signature.")
+}
@@ -0,0 +1,263 @@
+//! Build scripts kept in the recipe as they are, instead of being replaced by dummies: those
+//! of the packages passed to `prepare --keep-build-script`, or that opt in from their manifest.
+//!
+//! ```toml
+//! [package.metadata.chef]
+//! keep-build-script = true
+//! # The files the build script reads (headers, `.proto` files, embedded assets), relative to
+//! # the package. Modules of the build script must be listed as well.
+//! build-script-assets = ["wrapper.h", "proto/**/*.proto"]
+//! ```
+//!
+//! They run for real when cooking, so that whatever they compile or generate (e.g. native
+//! libraries) ends up in the cached layer, and their compilation units are not cleaned up
+//! afterwards. For the final build to reuse them, the versions of those packages are not
+//! masked (see `version_masking`): bumping them invalidates the recipe.
+//!
+//! With `prepare --detect-assets`, the build scripts of packages depending on crates whose
+//! inputs are well known (see `WELL_KNOWN_ASSETS`) are kept as well, along with those inputs.
+use super::target::TargetKind;
+use super::{standalone, version_masking, Manifest, Skeleton, SkeletonFile};
+use anyhow::Context;
+use cargo_metadata::semver::Version;
+use globset::{Glob, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+
+/// Crates whose build-time inputs live in well-known places, relative to the package using
+/// them. Workspace members are only compiled for real in the final build, so these inputs only
+/// matter to their build scripts when cooking.
+const WELL_KNOWN_ASSETS: &[(&[&str], &[&str])] = &[
+    // Protobuf code generation, for prost and tonic.
+    (
+        &[
+            "prost-build",
+            "tonic-build",
+            "tonic-prost-build",
+            "protobuf-codegen",
+        ],
+        &["**/*.proto"],
+    ),
+    // The offline data of `sqlx::query!` and the migrations of `sqlx::migrate!`.
+    (&["sqlx"], &[".sqlx/*.json", "migrations/**/*.sql"]),
+    // The migrations of `diesel_migrations::embed_migrations!`.
+    (
+        &["diesel_migrations"],
+        &["diesel.toml", "migrations/**/*.sql"],
+    ),
+];
+
+pub(super) fn keep(
+    skeleton: &Skeleton,
+    base_path: &Path,
+    packages: &[String],
+    detect_assets: bool,
+) -> Result<Skeleton, anyhow::Error> {
+    let mut files = skeleton.files.clone();
+    let mut kept_packages = vec![];
+    let mut unknown_packages: Vec<&String> = packages.iter().collect();
+    for manifest in &skeleton.manifests {
+        let contents: toml::Value = toml::from_str(&manifest.contents)?;
+        let Some(package) = contents.get("package") else {
+            continue;
+        };
+        let name = package.get("name").and_then(|name| name.as_str());
+        let requested = name.is_some_and(|name| packages.iter().any(|p| p == name));
+        unknown_packages.retain(|p| Some(p.as_str()) != name);
+        let metadata = package.get("metadata").and_then(|m| m.get("chef"));
+        let opted_in = metadata
+            .and_then(|m| m.get("keep-build-script"))
+            .and_then(|k| k.as_bool())
+            .unwrap_or(false);
+        let build_scripts: Vec<&PathBuf> = manifest
+            .targets
+            .iter()
+            .filter(|target| target.kind == TargetKind::BuildScript)
+            .map(|target| &target.path)
+            .collect();
+        let detected_assets = if detect_assets && !build_scripts.is_empty() {
+            well_known_assets(&contents)
+        } else {
+            vec![]
+        };
+        if !requested && !opted_in && detected_assets.is_empty() {
+            continue;
+        }
+        if !detected_assets.is_empty() {
+            log::info!(
+                "{}: keeping the build script, along with {}.",
+                manifest.relative_path.display(),
+                detected_assets.join(", ")
+            );
+        }
+        kept_packages.extend(name.map(str::to_string));
+
+        let directory = manifest.relative_path.parent().unwrap_or(Path::new(""));
+        if build_scripts.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{}: there is no build script to keep.",
+                manifest.relative_path.display()
+            ));
+        }
+        for build_script in build_scripts {
+            files.push(read_file(base_path, &directory.join(build_script))?);
+        }
+        let mut assets = metadata
+            .and_then(|m| m.get("build-script-assets"))
+            .map(|assets| {
+                assets
+                    .clone()
+                    .try_into::<Vec<String>>()
+                    .context("`build-script-assets` must be a list of globs.")
+            })
+            .transpose()
+            .with_context(|| format!("Failed to parse {:?}", manifest.relative_path))?
+            .unwrap_or_default();
+        assets.extend(detected_assets.into_iter().map(str::to_string));
+        for asset in assets_matching(base_path, directory, &assets)? {
+            files.push(read_file(base_path, &asset)?);
+        }
+    }
+    if let Some(package) = unknown_packages.first() {
+        return Err(anyhow::anyhow!(
+            "There is no package named `{}` in the workspace.",
+            package
+        ));
+    }
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    files.dedup_by(|a, b| a.relative_path == b.relative_path);
+
+    let mut skeleton = Skeleton {
+        files,
+        ..skeleton.clone()
+    };
+    unmask_versions(&mut skeleton, base_path, &kept_packages)?;
+    Ok(skeleton)
+}
+
+/// Restore the versions of `packages`, whose real sources are cooked, so that they are built
+/// as the same compilation units as in the final build.
+pub(super) fn unmask_versions(
+    skeleton: &mut Skeleton,
+    base_path: &Path,
+    packages: &[String],
+) -> Result<(), anyhow::Error> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+    // Read from the manifests rather than `cargo metadata`, for `derive_standalone`.
+    let versions: Vec<(String, Version)> = standalone::package_versions(base_path)?
+        .into_iter()
+        .filter(|(name, _)| packages.contains(name))
+        .collect();
+    version_masking::unmask_crate_versions(
+        &mut skeleton.manifests,
+        &mut skeleton.lock_file,
+        &versions,
+    )
+}
+
+/// The globs of `WELL_KNOWN_ASSETS` that apply to the dependencies of `manifest`.
+fn well_known_assets(manifest: &toml::Value) -> Vec<&'static str> {
+    let dependencies: Vec<&str> = super::declared_dependencies(manifest)
+        .into_iter()
+        .map(|(key, dependency)| {
+            dependency
+                .get("package")
+                .and_then(|package| package.as_str())
+                .unwrap_or(key)
+        })
+        .collect();
+    WELL_KNOWN_ASSETS
+        .iter()
+        .filter(|(crates, _)| crates.iter().any(|name| dependencies.contains(name)))
+        .flat_map(|(_, globs)| globs.iter().copied())
+        .collect()
+}
+
+/// Whether the build script of `manifest` is one of the files kept in `skeleton`.
+pub(super) fn is_kept(skeleton: &Skeleton, manifest: &Manifest) -> bool {
+    let directory = manifest.relative_path.parent().unwrap_or(Path::new(""));
+    manifest
+        .targets
+        .iter()
+        .filter(|target| target.kind == TargetKind::BuildScript)
+        .any(|target| {
+            let path = directory.join(&target.path);
+            skeleton.files.iter().any(|file| file.relative_path == path)
+        })
+}
+
+/// The files, relative to `base_path`, matching `globs` relative to `directory`. `target`
+/// directories are not searched.
+fn assets_matching(
+    base_path: &Path,
+    directory: &Path,
+    globs: &[String],
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    if globs.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).with_context(|| format!("`{}` is not a valid glob.", glob))?);
+    }
+    let matcher = builder.build()?;
+
+    let mut assets = vec![];
+    let mut queue = vec![PathBuf::new()];
+    while let Some(relative_directory) = queue.pop() {
+        let absolute_directory = base_path.join(directory).join(&relative_directory);
+        for entry in std::fs::read_dir(&absolute_directory)
+            .with_context(|| format!("Failed to scan {:?}", absolute_directory))?
+        {
+            let entry = entry?;
+            let path = relative_directory.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if entry.file_name() != "target" {
+                    queue.push(path);
+                }
+            } else if file_type.is_file() && matcher.is_match(&path) {
+                assets.push(directory.join(path));
+            }
+        }
+    }
+    Ok(assets)
+}
+
+pub(super) fn read_file(
+    base_path: &Path,
+    relative_path: &Path,
+) -> Result<SkeletonFile, anyhow::Error> {
+    let path = base_path.join(relative_path);
+    let contents = std::fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    let contents = String::from_utf8(contents).map_err(|_| {
+        anyhow::anyhow!(
+            "{:?} is not valid UTF-8: it cannot be kept in the recipe.",
+            path
+        )
+    })?;
+    Ok(SkeletonFile {
+        relative_path: relative_path.to_path_buf(),
+        contents,
+        mode: executable_mode(&path)?,
+    })
+}
+
+/// The permissions of `path` if it is executable: other files are written with the default
+/// ones, which keeps recipes independent of the umask they were prepared with.
+#[cfg(unix)]
+fn executable_mode(path: &Path) -> Result<Option<u32>, anyhow::Error> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read the permissions of {:?}", path))?
+        .permissions()
+        .mode()
+        & 0o777;
+    Ok((mode & 0o111 != 0).then_some(mode))
+}
+
+#[cfg(not(unix))]
+fn executable_mode(_path: &Path) -> Result<Option<u32>, anyhow::Error> {
+    Ok(None)
+}
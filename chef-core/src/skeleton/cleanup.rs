@@ -0,0 +1,157 @@
+//! Logic to remove the compilation artifacts of the dummy workspace members after cooking.
+//!
+//! `cargo` keeps track of every compilation unit under `.fingerprint/<package>-<hash>`, using
+//! the same `<hash>` for the related files in `deps/` and `build/`. We rely on that structure
+//! to find the units of local packages and remove exactly their artifacts - nothing that
+//! belongs to an external dependency, even if its name happens to share a prefix with a local
+//! package (e.g. `foo` and `foo-sys`).
+use fs_err as fs;
+use std::path::Path;
+
+pub(super) struct LocalPackage {
+    pub(super) name: String,
+    pub(super) lib_name: Option<String>,
+    /// A package with the same name is pulled from a registry or from git: we must look at
+    /// the sources each unit was built from to tell them apart.
+    pub(super) has_external_namesake: bool,
+    /// Its real build script was cooked: the units compiling and running it are left alone.
+    pub(super) keeps_build_script: bool,
+    /// Its real sources were cooked: its units are left alone.
+    pub(super) keeps_sources: bool,
+}
+
+pub(super) fn remove_local_units(
+    target_directory: &Path,
+    project_root: &Path,
+    local_packages: &[LocalPackage],
+) -> Result<(), anyhow::Error> {
+    let fingerprints = target_directory.join(".fingerprint");
+    if !fingerprints.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(&fingerprints)? {
+        let unit = entry?.file_name().to_string_lossy().into_owned();
+        let Some((name, hash)) = unit.rsplit_once('-') else {
+            continue;
+        };
+        if !is_metadata_hash(hash) {
+            continue;
+        }
+        let Some(package) = local_packages.iter().find(|package| package.name == name) else {
+            continue;
+        };
+        if package.keeps_sources {
+            continue;
+        }
+        if package.has_external_namesake
+            && !is_built_from(target_directory, &unit, hash, project_root)?
+        {
+            continue;
+        }
+        if package.keeps_build_script && is_build_script_unit(&fingerprints.join(&unit))? {
+            continue;
+        }
+
+        fs::remove_dir_all(fingerprints.join(&unit))?;
+        remove_files_with_hash(&target_directory.join("deps"), hash)?;
+        // Examples are built in a directory of their own.
+        remove_files_with_hash(&target_directory.join("examples"), hash)?;
+        // Build script compilation units are removed, but we leave the output directory of build
+        // script runs alone: it is overwritten as soon as the real build script runs.
+        let build_directory = target_directory.join("build").join(&unit);
+        if build_directory.is_dir() && !build_directory.join("out").exists() {
+            fs::remove_dir_all(build_directory)?;
+        }
+    }
+
+    // Libraries of workspace members are also copied over ("uplifted") to the root of the
+    // target directory.
+    for lib_name in local_packages
+        .iter()
+        .filter(|p| !p.keeps_sources)
+        .filter_map(|p| p.lib_name.as_ref())
+    {
+        let prefix = format!("lib{}.", lib_name);
+        for entry in fs::read_dir(target_directory)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with(&prefix)
+                && entry.file_type()?.is_file()
+            {
+                fs::remove_file(entry.path())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Units compiling or running a build script are recorded in `.fingerprint` as
+/// `build-script-<name>` and `run-build-script-<name>` respectively.
+fn is_build_script_unit(fingerprint: &Path) -> Result<bool, anyhow::Error> {
+    for entry in fs::read_dir(fingerprint)? {
+        let file_name = entry?.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with("build-script-") || file_name.starts_with("run-build-script-") {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// `cargo` uses 16 hex digits to disambiguate units of packages with the same name.
+fn is_metadata_hash(hash: &str) -> bool {
+    hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn remove_files_with_hash(directory: &Path, hash: &str) -> Result<(), anyhow::Error> {
+    if !directory.is_dir() {
+        return Ok(());
+    }
+    let suffix = format!("-{}", hash);
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let stem = file_name.split('.').next().unwrap_or_default();
+        if stem.ends_with(&suffix) && entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Check the dep-info files of a unit to determine if it was compiled from sources that live
+/// in the project.
+fn is_built_from(
+    target_directory: &Path,
+    unit: &str,
+    hash: &str,
+    project_root: &Path,
+) -> Result<bool, anyhow::Error> {
+    let suffix = format!("-{}.d", hash);
+    for directory in [
+        target_directory.join("deps"),
+        target_directory.join("build").join(unit),
+    ] {
+        if !directory.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            if !entry.file_name().to_string_lossy().ends_with(&suffix) {
+                continue;
+            }
+            let dep_info = fs::read_to_string(entry.path())?;
+            if dep_info
+                .split_whitespace()
+                .skip(1)
+                .map(|source| Path::new(source.trim_end_matches(':')))
+                .any(|source| {
+                    source.starts_with(project_root) && !source.starts_with(target_directory)
+                })
+            {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,154 @@
+//! Compatibility with recipes produced by upstream `cargo-chef` (LukeMathWalker/cargo-chef).
+//!
+//! Upstream recipes share most of their structure with ours, but:
+//! - older versions do not record the targets of each manifest;
+//! - newer versions carry the content of the `rust-toolchain` file tagged with its format
+//!   (`["Toml", "..."]`) rather than with its file name.
+//!
+//! We deserialize them leniently and fill in whatever is missing from the manifests themselves.
+use super::{Manifest, Skeleton, SkeletonFile};
+use crate::skeleton::target::{Target, TargetKind};
+use cargo_manifest::Product;
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct UpstreamSkeleton {
+    manifests: Vec<UpstreamManifest>,
+    #[serde(default)]
+    config_file: Option<String>,
+    #[serde(default)]
+    lock_file: Option<String>,
+    #[serde(default)]
+    rust_toolchain_file: Option<(UpstreamToolchainFile, String)>,
+}
+
+#[derive(Deserialize, Debug)]
+enum UpstreamToolchainFile {
+    Bare,
+    Toml,
+}
+
+#[derive(Deserialize, Debug)]
+struct UpstreamManifest {
+    #[serde(with = "super::portable_path")]
+    relative_path: PathBuf,
+    contents: String,
+    #[serde(default)]
+    targets: Option<Vec<Target>>,
+}
+
+impl TryFrom<UpstreamSkeleton> for Skeleton {
+    type Error = anyhow::Error;
+
+    fn try_from(upstream: UpstreamSkeleton) -> Result<Self, Self::Error> {
+        let toolchain_file = upstream
+            .rust_toolchain_file
+            .map(|(format, contents)| SkeletonFile {
+                relative_path: match format {
+                    UpstreamToolchainFile::Bare => "rust-toolchain",
+                    UpstreamToolchainFile::Toml => "rust-toolchain.toml",
+                }
+                .into(),
+                contents,
+                mode: None,
+            });
+
+        let manifests = upstream
+            .manifests
+            .into_iter()
+            .map(|manifest| {
+                let targets = match manifest.targets {
+                    Some(targets) => targets,
+                    None => infer_targets(&manifest.contents)?,
+                };
+                Ok(Manifest {
+                    relative_path: manifest.relative_path,
+                    contents: manifest.contents,
+                    targets,
+                })
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+
+        Ok(Skeleton {
+            manifests,
+            config_file: upstream.config_file,
+            external_config: None,
+            lock_file: upstream.lock_file,
+            toolchain_file,
+            vendored_sources: vec![],
+            files: vec![],
+        })
+    }
+}
+
+/// `prepare` always makes all targets explicit in the manifests it stores in the recipe,
+/// therefore we can recover them from there.
+fn infer_targets(contents: &str) -> Result<Vec<Target>, anyhow::Error> {
+    let manifest = cargo_manifest::Manifest::from_slice(contents.as_bytes())?;
+    let Some(package) = &manifest.package else {
+        // A virtual manifest, without any target.
+        return Ok(vec![]);
+    };
+
+    let mut targets = vec![];
+    let mut push = |products: &[Product], kind: fn(&Product) -> TargetKind, default: &str| {
+        for product in products {
+            let name = product.name.clone().unwrap_or_else(|| package.name.clone());
+            let path = product
+                .path
+                .clone()
+                .unwrap_or_else(|| default.replace("{name}", &name));
+            targets.push(Target {
+                path: path.into(),
+                kind: kind(product),
+                name,
+            });
+        }
+    };
+
+    push(
+        manifest.lib.as_slice(),
+        |lib| TargetKind::Lib {
+            is_proc_macro: lib.proc_macro,
+        },
+        "src/lib.rs",
+    );
+    push(
+        manifest.bin.as_deref().unwrap_or_default(),
+        |_| TargetKind::Bin,
+        "src/main.rs",
+    );
+    push(
+        manifest.test.as_deref().unwrap_or_default(),
+        |_| TargetKind::Test,
+        "tests/{name}.rs",
+    );
+    push(
+        manifest.bench.as_deref().unwrap_or_default(),
+        |_| TargetKind::Bench,
+        "benches/{name}.rs",
+    );
+    push(
+        manifest.example.as_deref().unwrap_or_default(),
+        |_| TargetKind::Example,
+        "examples/{name}.rs",
+    );
+
+    let build_script = match &package.build {
+        Some(build) if build.as_bool() == Some(false) => None,
+        Some(build) => Some(build.as_str().unwrap_or("build.rs").to_string()),
+        None => None,
+    };
+    if let Some(path) = build_script {
+        targets.push(Target {
+            path: path.into(),
+            kind: TargetKind::BuildScript,
+            name: "build-script-build".to_string(),
+        });
+    }
+
+    targets.sort();
+    Ok(targets)
+}
@@ -0,0 +1,77 @@
+//! Detection of dependencies that can only be fetched with credentials (private git
+//! repositories, private registries), which are usually missing from Docker builds unless
+//! mounted explicitly.
+use std::collections::BTreeMap;
+
+const CRATES_IO: [&str; 2] = [
+    "https://github.com/rust-lang/crates.io-index",
+    "https://index.crates.io/",
+];
+
+/// One warning per source that requires credentials, listing the packages fetched from it.
+pub(super) fn warnings(lock_file: &toml::Value, config: Option<&toml::Value>) -> Vec<String> {
+    let mut packages_by_source: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for package in lock_file
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let (Some(name), Some(source)) = (
+            package.get("name").and_then(|name| name.as_str()),
+            package.get("source").and_then(|source| source.as_str()),
+        ) else {
+            continue;
+        };
+        packages_by_source.entry(source).or_default().push(name);
+    }
+
+    let mut warnings = vec![];
+    for (source, packages) in packages_by_source {
+        let packages = packages
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Some(git) = source.strip_prefix("git+") {
+            let url = git.split(['?', '#']).next().unwrap_or(git);
+            if url.starts_with("ssh://") || !url.contains("://") {
+                warnings.push(format!(
+                    "{} fetched from {} over SSH: cooking requires an SSH agent or key (e.g. \
+                    `RUN --mount=type=ssh` with `CARGO_NET_GIT_FETCH_WITH_CLI=true`).",
+                    packages, url
+                ));
+            }
+        } else if let Some(index) = source
+            .strip_prefix("registry+")
+            .or_else(|| source.strip_prefix("sparse+"))
+        {
+            if CRATES_IO.contains(&index) {
+                continue;
+            }
+            let token = match registry_name(config, index) {
+                Some(name) => format!(
+                    "`CARGO_REGISTRIES_{}_TOKEN`",
+                    name.to_uppercase().replace('-', "_")
+                ),
+                None => "the token of the registry".to_string(),
+            };
+            warnings.push(format!(
+                "{} fetched from the registry {}: cooking requires {} (e.g. mounted with \
+                `RUN --mount=type=secret`).",
+                packages, index, token
+            ));
+        }
+    }
+    warnings
+}
+
+/// The name of the registry with the given index, as configured in `[registries]`.
+fn registry_name<'a>(config: Option<&'a toml::Value>, index: &str) -> Option<&'a str> {
+    let registries = config?.get("registries")?.as_table()?;
+    registries.iter().find_map(|(name, registry)| {
+        let configured = registry.get("index")?.as_str()?;
+        let configured = configured.strip_prefix("sparse+").unwrap_or(configured);
+        (configured.trim_end_matches('/') == index.trim_end_matches('/')).then_some(name.as_str())
+    })
+}
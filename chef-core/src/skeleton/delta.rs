@@ -0,0 +1,218 @@
+//! Selective cooks: when the target directory holds what was cooked from a previous recipe that
+//! only differs in `Cargo.lock`, only the external crates whose locked version (or source)
+//! changed need to be rebuilt, along with the external crates that depend on them. They are
+//! selected with `--package`, so that `cargo` does not even check the fingerprints of the
+//! others.
+//!
+//! `cargo` resolves the features of a selected dependency as it does when building the whole
+//! workspace, so the artifacts are the ones the final build expects. It only accepts crates that
+//! are part of the dependency graph of the platform being built for, though, and not those that
+//! are only used to build other crates (build-dependencies and their dependencies): they are
+//! rebuilt through a selected crate depending on them, or everything is cooked.
+use super::host::{is_proc_macro, metadata};
+use super::Skeleton;
+use crate::{AllFeatures, CookArgs, DefaultFeatures};
+use cargo_metadata::{DependencyKind, PackageId};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+/// What a cook based on a previous recipe has to build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Delta {
+    /// Everything, for the given reason.
+    Everything(String),
+    /// The `--package` specifications of the crates to rebuild, none if nothing changed.
+    Packages(Vec<String>),
+}
+
+/// The crates to rebuild in the skeleton `current`, already written to `directory`, on top of
+/// what was cooked from `previous` with the same `args`.
+pub(crate) fn delta(
+    directory: &Path,
+    previous: &Skeleton,
+    current: &Skeleton,
+    args: &CookArgs,
+) -> Result<Delta, anyhow::Error> {
+    if let Some(reason) = unsupported(previous, current, args) {
+        return Ok(Delta::Everything(reason.to_string()));
+    }
+    let (Some(previous_lock_file), Some(current_lock_file)) =
+        (&previous.lock_file, &current.lock_file)
+    else {
+        return Ok(Delta::Everything("there is no Cargo.lock".to_string()));
+    };
+    let previous_packages = locked_packages(&toml::from_str(previous_lock_file)?);
+    let changed: Vec<LockedPackage> = locked_packages(&toml::from_str(current_lock_file)?)
+        .into_iter()
+        .filter(|package| !previous_packages.contains(package))
+        .collect();
+    if changed.is_empty() {
+        return Ok(Delta::Packages(vec![]));
+    }
+
+    let metadata = metadata(directory, args)?;
+    let resolve = metadata
+        .resolve
+        .ok_or_else(|| anyhow::anyhow!("`cargo metadata` did not resolve the dependencies"))?;
+    let packages: HashMap<_, _> = metadata.packages.iter().map(|p| (&p.id, p)).collect();
+    let members: HashSet<&PackageId> = metadata.workspace_members.iter().collect();
+
+    // Edges from each crate to the crates depending on it, dev-dependencies aside.
+    let mut dependents: HashMap<&PackageId, Vec<&PackageId>> = HashMap::new();
+    // Crates `--package` accepts: those reachable from the members through regular
+    // dependencies, without going through a proc-macro.
+    let mut selectable = HashSet::new();
+    let mut queue: Vec<&PackageId> = metadata.workspace_members.iter().collect();
+    for node in &resolve.nodes {
+        for dependency in &node.deps {
+            if dependency
+                .dep_kinds
+                .iter()
+                .any(|info| info.kind != DependencyKind::Development)
+            {
+                dependents
+                    .entry(&dependency.pkg)
+                    .or_default()
+                    .push(&node.id);
+            }
+        }
+    }
+    let nodes: HashMap<_, _> = resolve.nodes.iter().map(|node| (&node.id, node)).collect();
+    while let Some(id) = queue.pop() {
+        if !selectable.insert(id) || packages.get(id).is_some_and(|p| is_proc_macro(p)) {
+            continue;
+        }
+        let Some(node) = nodes.get(id) else {
+            continue;
+        };
+        for dependency in &node.deps {
+            if dependency
+                .dep_kinds
+                .iter()
+                .any(|info| info.kind == DependencyKind::Normal)
+            {
+                queue.push(&dependency.pkg);
+            }
+        }
+    }
+
+    // Crates of other platforms are not in the graph: they do not need to be rebuilt.
+    let mut affected = HashSet::new();
+    let mut queue: Vec<&PackageId> = metadata
+        .packages
+        .iter()
+        .filter(|package| {
+            changed.iter().any(|changed| {
+                changed.name == package.name
+                    && changed.version == package.version.to_string()
+                    && Some(changed.source.as_str())
+                        == package.source.as_ref().map(|source| source.repr.as_str())
+            })
+        })
+        .map(|package| &package.id)
+        .collect();
+    while let Some(id) = queue.pop() {
+        if members.contains(id) || !affected.insert(id) {
+            continue;
+        }
+        queue.extend(dependents.get(id).into_iter().flatten());
+    }
+
+    let mut specifications = BTreeSet::new();
+    for id in &affected {
+        if selectable.contains(id) {
+            let package = packages[id];
+            if !specifications.insert(format!("{}@{}", package.name, package.version)) {
+                return Ok(Delta::Everything(format!(
+                    "`{}@{}` comes from several sources",
+                    package.name, package.version
+                )));
+            }
+        } else if !rebuilt_through_dependent(id, &dependents, &affected, &selectable) {
+            return Ok(Delta::Everything(format!(
+                "`{}` is only used to build other crates",
+                packages[id].name
+            )));
+        }
+    }
+    Ok(Delta::Packages(specifications.into_iter().collect()))
+}
+
+/// Why the crates to rebuild cannot be determined, if they cannot.
+fn unsupported(previous: &Skeleton, current: &Skeleton, args: &CookArgs) -> Option<&'static str> {
+    let without_lock_file = |skeleton: &Skeleton| Skeleton {
+        lock_file: None,
+        ..skeleton.clone()
+    };
+    if without_lock_file(previous) != without_lock_file(current) {
+        return Some("the recipe changed beyond Cargo.lock");
+    }
+    // `cargo` refuses feature flags along with `--package` specifications of dependencies.
+    if args.features.is_some()
+        || args.each_feature
+        || args.default_features == DefaultFeatures::Disabled
+        || args.all_features == AllFeatures::Enabled
+    {
+        return Some("features are selected");
+    }
+    // Dev-dependencies would change the features of the others.
+    let targets = &args.target_args;
+    if targets.benches || targets.tests || targets.examples || targets.all_targets {
+        return Some("tests, benches or examples are cooked");
+    }
+    if args
+        .target
+        .as_ref()
+        .is_some_and(|targets| targets.len() > 1)
+    {
+        return Some("several targets are cooked");
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LockedPackage {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) source: String,
+}
+
+/// The packages of `Cargo.lock` that do not live in the project.
+pub(crate) fn locked_packages(lock_file: &toml::Value) -> Vec<LockedPackage> {
+    lock_file
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            Some(LockedPackage {
+                name: package.get("name")?.as_str()?.to_string(),
+                version: package.get("version")?.as_str()?.to_string(),
+                source: package.get("source")?.as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Whether one of the crates depending on `id`, directly or not, is rebuilt.
+fn rebuilt_through_dependent(
+    id: &PackageId,
+    dependents: &HashMap<&PackageId, Vec<&PackageId>>,
+    affected: &HashSet<&PackageId>,
+    selectable: &HashSet<&PackageId>,
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut queue = vec![id];
+    while let Some(id) = queue.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        for dependent in dependents.get(id).into_iter().flatten() {
+            if affected.contains(dependent) && selectable.contains(dependent) {
+                return true;
+            }
+            queue.push(dependent);
+        }
+    }
+    false
+}
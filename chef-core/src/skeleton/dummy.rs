@@ -0,0 +1,77 @@
+//! The contents of the dummy source files written by `build_minimum_project`, which can be
+//! customised for the whole workspace or for each package, e.g. for targets with no `std`
+//! that need a panic handler or a custom entrypoint:
+//!
+//! ```toml
+//! [workspace.metadata.chef.dummy]
+//! bin = "#![no_std]\n#![no_main]\n..."
+//!
+//! [package.metadata.chef.dummy]
+//! lib = "#![no_std]"
+//! ```
+//!
+//! The settings of a package take precedence over those of the workspace, which take
+//! precedence over the built-in dummies.
+use super::target::TargetKind;
+use super::Manifest;
+use anyhow::Context;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(super) struct DummySources {
+    lib: Option<String>,
+    bin: Option<String>,
+    example: Option<String>,
+    test: Option<String>,
+    bench: Option<String>,
+    build_script: Option<String>,
+}
+
+impl DummySources {
+    /// The dummies configured in `[<section>.metadata.chef.dummy]` of `manifest`, where
+    /// `section` is either `package` or `workspace`.
+    pub(super) fn read(manifest: &Manifest, section: &str) -> Result<Self, anyhow::Error> {
+        let contents: toml::Value = toml::from_str(&manifest.contents)?;
+        let Some(dummy) = contents
+            .get(section)
+            .and_then(|s| s.get("metadata"))
+            .and_then(|m| m.get("chef"))
+            .and_then(|c| c.get("dummy"))
+        else {
+            return Ok(DummySources::default());
+        };
+        dummy.clone().try_into().with_context(|| {
+            format!(
+                "{}: invalid `{}.metadata.chef.dummy`",
+                manifest.relative_path.display(),
+                section
+            )
+        })
+    }
+
+    /// These dummies, falling back to those of `fallback` for the targets they leave alone.
+    pub(super) fn or(self, fallback: &DummySources) -> DummySources {
+        DummySources {
+            lib: self.lib.or_else(|| fallback.lib.clone()),
+            bin: self.bin.or_else(|| fallback.bin.clone()),
+            example: self.example.or_else(|| fallback.example.clone()),
+            test: self.test.or_else(|| fallback.test.clone()),
+            bench: self.bench.or_else(|| fallback.bench.clone()),
+            build_script: self.build_script.or_else(|| fallback.build_script.clone()),
+        }
+    }
+
+    /// The configured contents of the dummies for targets of `kind`, if any.
+    pub(super) fn get(&self, kind: &TargetKind) -> Option<&str> {
+        match kind {
+            TargetKind::Lib { .. } => &self.lib,
+            TargetKind::Bin => &self.bin,
+            TargetKind::Example => &self.example,
+            TargetKind::Test => &self.test,
+            TargetKind::Bench => &self.bench,
+            TargetKind::BuildScript => &self.build_script,
+        }
+        .as_deref()
+    }
+}
@@ -0,0 +1,365 @@
+//! Path dependencies living outside of the project root (e.g. `{ path = "../../shared/foo" }`),
+//! which `prepare --allow-external-paths` brings into the recipe.
+//!
+//! They are not workspace members, hence never built on their own: their stubs only declare
+//! what their dependents need - a library, a build script, dependencies and features - as
+//! reported by `cargo metadata`, with workspace inheritance already resolved. They are moved to
+//! `chef-external/<package name>` and every path dependency pointing to them is rewritten
+//! accordingly.
+use super::target::{Target, TargetKind};
+use super::{Manifest, Skeleton};
+use anyhow::Context;
+use cargo_metadata::{DependencyKind, Package};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+pub(super) const EXTERNAL_DIRECTORY: &str = "chef-external";
+
+pub(super) fn include(skeleton: &Skeleton, base_path: &Path) -> Result<Skeleton, anyhow::Error> {
+    let base_path = absolute(base_path)?;
+    let mut externals = Externals {
+        base_path: &base_path,
+        locations: BTreeMap::new(),
+        queue: vec![],
+    };
+
+    let mut manifests = vec![];
+    for manifest in &skeleton.manifests {
+        let directory = manifest.relative_path.parent().unwrap_or(Path::new(""));
+        let mut contents: toml::Value = toml::from_str(&manifest.contents)?;
+        let mut rewritten = false;
+        for dependency in path_dependencies(&mut contents) {
+            let Some(path) = dependency.get("path").and_then(|path| path.as_str()) else {
+                continue;
+            };
+            let absolute_path = absolute(&base_path.join(directory).join(path))?;
+            if absolute_path.starts_with(&base_path) || !absolute_path.join("Cargo.toml").is_file()
+            {
+                continue;
+            }
+            let location = externals.locate(&absolute_path)?;
+            dependency.insert("path".into(), relative_path(&location, directory).into());
+            rewritten = true;
+        }
+        let contents = if rewritten {
+            toml::to_string(&contents)?
+        } else {
+            manifest.contents.clone()
+        };
+        manifests.push(Manifest {
+            relative_path: manifest.relative_path.clone(),
+            contents,
+            targets: manifest.targets.clone(),
+        });
+    }
+    if externals.locations.is_empty() {
+        return Ok(skeleton.clone());
+    }
+
+    while let Some((absolute_path, location)) = externals.queue.pop() {
+        let package = package(&absolute_path)?;
+        let contents = externals.stub(&package, &location)?;
+        let targets = package
+            .targets
+            .iter()
+            .filter_map(|target| {
+                let kind = if target.is_custom_build() {
+                    TargetKind::BuildScript
+                } else if is_library(target) {
+                    TargetKind::Lib {
+                        is_proc_macro: is_proc_macro(target),
+                    }
+                } else {
+                    return None;
+                };
+                Some(Target {
+                    path: pathdiff::diff_paths(&target.src_path, &absolute_path)?,
+                    kind,
+                    name: target.name.clone(),
+                })
+            })
+            .collect();
+        manifests.push(Manifest {
+            relative_path: location.join("Cargo.toml"),
+            contents: toml::to_string(&contents)?,
+            targets,
+        });
+    }
+    manifests.sort_by_key(|manifest| manifest.relative_path.clone());
+
+    // External packages are not members of the workspace, and must not become members now that
+    // they live inside of it.
+    if let Some(root) = manifests
+        .iter_mut()
+        .find(|manifest| manifest.relative_path == Path::new("Cargo.toml"))
+    {
+        let mut contents: toml::Value = toml::from_str(&root.contents)?;
+        if let Some(workspace) = contents.get_mut("workspace").and_then(|w| w.as_table_mut()) {
+            if let Some(exclude) = workspace
+                .entry("exclude")
+                .or_insert_with(|| toml::Value::Array(vec![]))
+                .as_array_mut()
+            {
+                exclude.push(EXTERNAL_DIRECTORY.into());
+            }
+            root.contents = toml::to_string(&contents)?;
+        }
+    }
+
+    Ok(Skeleton {
+        manifests,
+        ..skeleton.clone()
+    })
+}
+
+struct Externals<'a> {
+    base_path: &'a Path,
+    /// Where each external package, by absolute path, lives in the skeleton.
+    locations: BTreeMap<PathBuf, PathBuf>,
+    /// External packages whose stub has yet to be written.
+    queue: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Externals<'_> {
+    /// The location in the skeleton of the package in `absolute_path`.
+    fn locate(&mut self, absolute_path: &Path) -> Result<PathBuf, anyhow::Error> {
+        if let Ok(relative_path) = absolute_path.strip_prefix(self.base_path) {
+            return Ok(relative_path.to_path_buf());
+        }
+        if let Some(location) = self.locations.get(absolute_path) {
+            return Ok(location.clone());
+        }
+        let name = package(absolute_path)?.name;
+        let mut location = Path::new(EXTERNAL_DIRECTORY).join(&name);
+        let mut suffix = 1;
+        while self.locations.values().any(|taken| taken == &location) {
+            suffix += 1;
+            location = Path::new(EXTERNAL_DIRECTORY).join(format!("{}-{}", name, suffix));
+        }
+        self.locations
+            .insert(absolute_path.to_path_buf(), location.clone());
+        self.queue
+            .push((absolute_path.to_path_buf(), location.clone()));
+        Ok(location)
+    }
+
+    /// A manifest declaring the library, build script, features and (non-dev) dependencies of
+    /// `package`, to be written in `location`.
+    fn stub(&mut self, package: &Package, location: &Path) -> Result<toml::Value, anyhow::Error> {
+        let directory = package.manifest_path.parent().unwrap().as_std_path();
+        let mut manifest = toml::map::Map::new();
+
+        let mut section = toml::map::Map::new();
+        section.insert("name".into(), package.name.clone().into());
+        section.insert("version".into(), package.version.to_string().into());
+        section.insert("edition".into(), package.edition.as_str().into());
+        if let Some(links) = &package.links {
+            section.insert("links".into(), links.clone().into());
+        }
+        let build_script = package.targets.iter().find(|t| t.is_custom_build());
+        section.insert(
+            "build".into(),
+            match build_script {
+                Some(target) => relative_path(target.src_path.as_std_path(), directory).into(),
+                None => false.into(),
+            },
+        );
+        for key in ["autobins", "autoexamples", "autotests", "autobenches"] {
+            section.insert(key.into(), false.into());
+        }
+        manifest.insert("package".into(), section.into());
+
+        if let Some(library) = package.targets.iter().find(|target| is_library(target)) {
+            let mut section = toml::map::Map::new();
+            section.insert("name".into(), library.name.clone().into());
+            section.insert(
+                "path".into(),
+                relative_path(library.src_path.as_std_path(), directory).into(),
+            );
+            if is_proc_macro(library) {
+                section.insert("proc-macro".into(), true.into());
+            }
+            manifest.insert("lib".into(), section.into());
+        }
+
+        let features: toml::map::Map<_, _> = package
+            .features
+            .iter()
+            .map(|(name, enables)| (name.clone(), enables.clone().into()))
+            .collect();
+        manifest.insert("features".into(), features.into());
+
+        for dependency in &package.dependencies {
+            let kind = match dependency.kind {
+                DependencyKind::Normal => "dependencies",
+                DependencyKind::Build => "build-dependencies",
+                _ => continue,
+            };
+            let mut declaration = toml::map::Map::new();
+            match &dependency.path {
+                Some(path) => {
+                    let location_of_dependency = self.locate(&absolute(path.as_std_path())?)?;
+                    declaration.insert(
+                        "path".into(),
+                        relative_path(&location_of_dependency, location).into(),
+                    );
+                    if dependency.req.to_string() != "*" {
+                        declaration.insert("version".into(), dependency.req.to_string().into());
+                    }
+                }
+                None => {
+                    declaration.insert("version".into(), dependency.req.to_string().into());
+                }
+            }
+            if let Some(source) = &dependency.source {
+                if let Some(git) = source.strip_prefix("git+") {
+                    let (url, query) = git.split_once('?').unwrap_or((git, ""));
+                    declaration.remove("version");
+                    declaration.insert("git".into(), url.into());
+                    for (key, value) in query.split('&').filter_map(|kv| kv.split_once('=')) {
+                        if ["branch", "tag", "rev"].contains(&key) {
+                            declaration.insert(key.into(), value.into());
+                        }
+                    }
+                } else if let Some(index) = source.strip_prefix("registry+") {
+                    if index != "https://github.com/rust-lang/crates.io-index" {
+                        declaration.insert("registry-index".into(), index.into());
+                    }
+                } else if source.starts_with("sparse+")
+                    && source != "sparse+https://index.crates.io/"
+                {
+                    declaration.insert("registry-index".into(), source.clone().into());
+                }
+            }
+            if dependency.rename.is_some() {
+                declaration.insert("package".into(), dependency.name.clone().into());
+            }
+            if dependency.optional {
+                declaration.insert("optional".into(), true.into());
+            }
+            if !dependency.uses_default_features {
+                declaration.insert("default-features".into(), false.into());
+            }
+            if !dependency.features.is_empty() {
+                declaration.insert("features".into(), dependency.features.clone().into());
+            }
+
+            let table = match &dependency.target {
+                Some(platform) => manifest["target"][&platform.to_string()]
+                    .as_table_mut()
+                    .unwrap(),
+                None => &mut manifest,
+            };
+            table
+                .entry(kind)
+                .or_insert_with(|| toml::map::Map::new().into())
+                .as_table_mut()
+                .unwrap()
+                .insert(
+                    dependency.rename.clone().unwrap_or(dependency.name.clone()),
+                    declaration.into(),
+                );
+        }
+        Ok(manifest.into())
+    }
+}
+
+/// Libraries with custom crate types (e.g. `cdylib`) are libraries as well.
+fn is_library(target: &cargo_metadata::Target) -> bool {
+    target
+        .kind
+        .iter()
+        .any(|kind| kind.contains("lib") || kind == "proc-macro")
+}
+
+fn is_proc_macro(target: &cargo_metadata::Target) -> bool {
+    target.kind.iter().any(|kind| kind == "proc-macro")
+}
+
+/// The package whose manifest lives in `directory`, with workspace inheritance resolved.
+fn package(directory: &Path) -> Result<Package, anyhow::Error> {
+    let manifest_path = directory.join("Cargo.toml");
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(&manifest_path)
+        .no_deps()
+        .exec()
+        .with_context(|| format!("Cannot extract Cargo metadata of {:?}", directory))?;
+    metadata
+        .packages
+        .into_iter()
+        .find(|package| package.manifest_path.as_std_path() == manifest_path)
+        .ok_or_else(|| anyhow::anyhow!("There is no package in {:?}", directory))
+}
+
+/// The (mutable) declarations of all dependencies in `manifest`, including the ones in
+/// `[workspace.dependencies]` and `[patch]`.
+pub(super) fn path_dependencies(manifest: &mut toml::Value) -> Vec<&mut toml::value::Table> {
+    let Some(manifest) = manifest.as_table_mut() else {
+        return vec![];
+    };
+    let mut sections: Vec<&mut toml::Value> = vec![];
+    for (key, value) in manifest.iter_mut() {
+        match key.as_str() {
+            "dependencies" | "dev-dependencies" | "build-dependencies" | "dev_dependencies"
+            | "build_dependencies" => sections.push(value),
+            "target" | "patch" => {
+                let Some(entries) = value.as_table_mut() else {
+                    continue;
+                };
+                for (_, entry) in entries.iter_mut() {
+                    if key == "patch" {
+                        sections.push(entry);
+                        continue;
+                    }
+                    let Some(entry) = entry.as_table_mut() else {
+                        continue;
+                    };
+                    for (kind, dependencies) in entry.iter_mut() {
+                        if kind.contains("dependencies") {
+                            sections.push(dependencies);
+                        }
+                    }
+                }
+            }
+            "workspace" => {
+                if let Some(dependencies) = value.get_mut("dependencies") {
+                    sections.push(dependencies);
+                }
+            }
+            _ => {}
+        }
+    }
+    sections
+        .into_iter()
+        .filter_map(|section| section.as_table_mut())
+        .flat_map(|dependencies| dependencies.iter_mut())
+        .filter_map(|(_, dependency)| dependency.as_table_mut())
+        .collect()
+}
+
+/// `path`, made absolute, with `.` and `..` resolved without following symlinks: that is how
+/// `cargo` interprets path dependencies, e.g. `../common` next to a symlinked crate directory
+/// points to its sibling in the workspace rather than to the sibling of the symlink target.
+fn absolute(path: &Path) -> Result<PathBuf, anyhow::Error> {
+    let mut absolute = PathBuf::new();
+    for component in std::env::current_dir()?.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    Ok(absolute)
+}
+
+/// `path` relative to `directory`, with forward slashes.
+fn relative_path(path: &Path, directory: &Path) -> String {
+    pathdiff::diff_paths(path, directory)
+        .unwrap_or_else(|| path.to_path_buf())
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
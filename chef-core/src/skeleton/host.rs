@@ -0,0 +1,407 @@
+//! Build-dependencies and proc-macros (the "host" dependencies) of a workspace, gathered in
+//! a throwaway package so that they can be cooked on their own.
+//!
+//! Selecting them with `--package` is not an option: `cargo` does not support it for crates
+//! that are only used as build-dependencies. Declaring them as the build-dependencies of
+//! another package, with the versions and features they were resolved to, gets them
+//! compiled exactly like they will be when building the workspace - same resolver and profile
+//! overrides included.
+use super::target::{Target, TargetKind};
+use super::{Manifest, Skeleton};
+use crate::{AllFeatures, CookArgs, DefaultFeatures};
+use cargo_metadata::{Dependency, DependencyKind, Node, Package, PackageId};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+pub(crate) const PACKAGE_NAME: &str = "chef-build-dependencies";
+
+/// The skeleton of the throwaway package, `None` if the workspace - already written to
+/// `directory` - has no host dependencies.
+pub(crate) fn skeleton(
+    directory: &Path,
+    workspace: &Skeleton,
+    args: &CookArgs,
+) -> Result<Option<Skeleton>, anyhow::Error> {
+    let metadata = metadata(directory, args)?;
+    let resolve = metadata
+        .resolve
+        .ok_or_else(|| anyhow::anyhow!("`cargo metadata` did not resolve the dependencies"))?;
+    let nodes: HashMap<_, _> = resolve.nodes.iter().map(|node| (&node.id, node)).collect();
+    let packages: HashMap<_, _> = metadata.packages.iter().map(|p| (&p.id, p)).collect();
+
+    // Walk the dependency graph from the workspace members, ignoring dev-dependencies.
+    let mut roots = vec![];
+    let mut visited = HashSet::new();
+    let mut queue: Vec<_> = metadata.workspace_members.iter().collect();
+    while let Some(id) = queue.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let Some(node) = nodes.get(id) else {
+            continue;
+        };
+        for dependency in &node.deps {
+            let kinds = || dependency.dep_kinds.iter().map(|info| info.kind);
+            if kinds().all(|kind| kind == DependencyKind::Development) {
+                continue;
+            }
+            let Some(package) = packages.get(&dependency.pkg) else {
+                continue;
+            };
+            let is_member = metadata.workspace_members.contains(&dependency.pkg);
+            if !is_member
+                && (is_proc_macro(package) || kinds().any(|kind| kind == DependencyKind::Build))
+                && !roots.contains(&dependency.pkg)
+            {
+                roots.push(dependency.pkg.clone());
+            }
+            queue.push(&dependency.pkg);
+        }
+    }
+    if roots.is_empty() {
+        return Ok(None);
+    }
+    roots.sort();
+
+    let root = workspace
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == Path::new("Cargo.toml"))
+        .map(|manifest| toml::from_str::<toml::Value>(&manifest.contents))
+        .transpose()?;
+    let resolver = resolver(root.as_ref());
+    // `cargo metadata` unifies the features of the whole graph, like resolver 1 does.
+    let features = if resolver == "1" {
+        roots
+            .iter()
+            .map(|id| (id, nodes[id].features.iter().cloned().collect()))
+            .collect()
+    } else {
+        host_features(&metadata.workspace_members, &nodes, &packages)
+    };
+
+    let mut build_dependencies = toml::map::Map::new();
+    for (index, id) in roots.iter().enumerate() {
+        let package = packages[id];
+        let Some(mut declaration) = declaration(package) else {
+            log::warn!(
+                "{} {} does not come from crates.io or git, it will not be cooked.",
+                package.name,
+                package.version
+            );
+            continue;
+        };
+        declaration.insert("package".into(), package.name.clone().into());
+        declaration.insert("default-features".into(), false.into());
+        let features: Vec<_> = features
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter(|feature| package.features.contains_key(*feature))
+            .cloned()
+            .collect();
+        declaration.insert("features".into(), features.into());
+        build_dependencies.insert(format!("dependency{}", index), declaration.into());
+    }
+
+    let mut manifest = toml::map::Map::new();
+    let mut package = toml::map::Map::new();
+    package.insert("name".into(), PACKAGE_NAME.into());
+    package.insert("version".into(), "0.0.0".into());
+    package.insert("edition".into(), "2018".into());
+    package.insert("build".into(), "build.rs".into());
+    manifest.insert("package".into(), package.into());
+    manifest.insert("build-dependencies".into(), build_dependencies.into());
+    // A workspace of its own, which shares the resolver and the profiles of the actual one.
+    let mut workspace = toml::map::Map::new();
+    workspace.insert("resolver".into(), resolver.into());
+    manifest.insert("workspace".into(), workspace.into());
+    if let Some(profiles) = root.as_ref().and_then(|root| root.get("profile").cloned()) {
+        manifest.insert("profile".into(), profiles);
+    }
+
+    let lock_file = match std::fs::read_to_string(directory.join("Cargo.lock")) {
+        Ok(lock_file) => Some(lock_file),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(Skeleton {
+        manifests: vec![Manifest {
+            relative_path: "Cargo.toml".into(),
+            contents: toml::to_string(&manifest)?,
+            targets: vec![
+                Target {
+                    path: "src/lib.rs".into(),
+                    kind: TargetKind::Lib {
+                        is_proc_macro: false,
+                    },
+                    name: PACKAGE_NAME.to_string(),
+                },
+                Target {
+                    path: "build.rs".into(),
+                    kind: TargetKind::BuildScript,
+                    name: "build-script-build".to_string(),
+                },
+            ],
+        }],
+        config_file: None,
+        external_config: None,
+        lock_file,
+        toolchain_file: None,
+        vendored_sources: vec![],
+        files: vec![],
+    }))
+}
+
+/// The feature resolver of the workspace whose root manifest is `root`: the one it sets, or the
+/// default of the edition of its root package.
+fn resolver(root: Option<&toml::Value>) -> String {
+    let workspace = root.and_then(|root| root.get("workspace"));
+    let package = root.and_then(|root| root.get("package"));
+    let resolver = workspace
+        .and_then(|workspace| workspace.get("resolver"))
+        .or_else(|| package.and_then(|package| package.get("resolver")))
+        .and_then(toml::Value::as_str);
+    if let Some(resolver) = resolver {
+        return resolver.to_string();
+    }
+    let edition = match package.and_then(|package| package.get("edition")) {
+        // `edition.workspace = true`
+        Some(toml::Value::Table(_)) => workspace
+            .and_then(|workspace| workspace.get("package"))
+            .and_then(|package| package.get("edition")),
+        edition => edition,
+    };
+    match edition.and_then(toml::Value::as_str) {
+        Some("2024") => "3",
+        Some("2021") => "2",
+        _ => "1",
+    }
+    .to_string()
+}
+
+/// The features of the host dependencies from resolver 2 onwards, which does not unify them
+/// with those of the packages built for the target: starting from what those packages request
+/// of their build-dependencies and proc-macros, features are propagated through the host side
+/// of the graph only.
+fn host_features<'a>(
+    members: &'a [PackageId],
+    nodes: &HashMap<&'a PackageId, &'a Node>,
+    packages: &HashMap<&'a PackageId, &'a Package>,
+) -> HashMap<&'a PackageId, BTreeSet<String>> {
+    // A feature to enable in a host package, `None` to only build it.
+    let mut requests: Vec<(&PackageId, Option<String>)> = vec![];
+    let mut targets: Vec<_> = members.iter().collect();
+    let mut visited = HashSet::new();
+    while let Some(id) = targets.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let (Some(node), Some(package)) = (nodes.get(id), packages.get(id)) else {
+            continue;
+        };
+        for (dependency, declarations) in dependencies(id, nodes, packages) {
+            let host: Vec<_> = if is_proc_macro(packages[dependency]) {
+                declarations
+            } else {
+                if declarations
+                    .iter()
+                    .any(|declaration| declaration.kind == DependencyKind::Normal)
+                {
+                    targets.push(dependency);
+                }
+                declarations
+                    .into_iter()
+                    .filter(|declaration| declaration.kind == DependencyKind::Build)
+                    .collect()
+            };
+            if host.is_empty() {
+                continue;
+            }
+            requests.extend(declared(dependency, &host));
+            for feature in &node.features {
+                requests.extend(
+                    enabled_by(package, feature, &host)
+                        .into_iter()
+                        .map(|enabled| (dependency, Some(enabled))),
+                );
+            }
+        }
+    }
+
+    let mut features: HashMap<&PackageId, BTreeSet<String>> = HashMap::new();
+    while let Some((id, feature)) = requests.pop() {
+        let Some(package) = packages.get(id) else {
+            continue;
+        };
+        let dependencies = dependencies(id, nodes, packages);
+        if !features.contains_key(id) {
+            features.insert(id, BTreeSet::new());
+            for (dependency, declarations) in &dependencies {
+                requests.extend(declared(dependency, declarations));
+            }
+        }
+        let Some(feature) = feature else {
+            continue;
+        };
+        if !features.get_mut(id).unwrap().insert(feature.clone()) {
+            continue;
+        }
+        for (dependency, declarations) in &dependencies {
+            requests.extend(
+                enabled_by(package, &feature, declarations)
+                    .into_iter()
+                    .map(|enabled| (*dependency, Some(enabled))),
+            );
+        }
+        for value in package.features.get(&feature).into_iter().flatten() {
+            if !value.contains('/') && !value.starts_with("dep:") {
+                requests.push((id, Some(value.clone())));
+            }
+        }
+    }
+    features
+}
+
+/// The dependencies of `id` that are built along with it, with the declarations of each.
+fn dependencies<'a>(
+    id: &PackageId,
+    nodes: &HashMap<&'a PackageId, &'a Node>,
+    packages: &HashMap<&'a PackageId, &'a Package>,
+) -> Vec<(&'a PackageId, Vec<&'a Dependency>)> {
+    let (Some(node), Some(package)) = (nodes.get(id), packages.get(id)) else {
+        return vec![];
+    };
+    node.deps
+        .iter()
+        .filter_map(|dependency| {
+            let target = packages.get(&dependency.pkg)?;
+            let declarations: Vec<_> = package
+                .dependencies
+                .iter()
+                .filter(|declaration| {
+                    declaration.name == target.name
+                        && declaration.kind != DependencyKind::Development
+                })
+                .collect();
+            // Requirements only tell apart several versions of the same package.
+            let matching: Vec<_> = declarations
+                .iter()
+                .copied()
+                .filter(|declaration| declaration.req.matches(&target.version))
+                .collect();
+            let declarations = if matching.is_empty() {
+                declarations
+            } else {
+                matching
+            };
+            (!declarations.is_empty()).then_some((&dependency.pkg, declarations))
+        })
+        .collect()
+}
+
+/// What `declarations` of `dependency` enable: its default features and the listed ones.
+fn declared<'a>(
+    dependency: &'a PackageId,
+    declarations: &[&Dependency],
+) -> Vec<(&'a PackageId, Option<String>)> {
+    let mut requests = vec![(dependency, None)];
+    for declaration in declarations {
+        if declaration.uses_default_features {
+            requests.push((dependency, Some("default".to_string())));
+        }
+        requests.extend(
+            declaration
+                .features
+                .iter()
+                .map(|feature| (dependency, Some(feature.clone()))),
+        );
+    }
+    requests
+}
+
+/// The features of the dependency declared as `declarations` that `feature` of `package`
+/// enables, with `<dependency>/<feature>` or `<dependency>?/<feature>`.
+fn enabled_by(package: &Package, feature: &str, declarations: &[&Dependency]) -> Vec<String> {
+    package
+        .features
+        .get(feature)
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.split_once('/'))
+        .filter(|(name, _)| {
+            let name = name.trim_end_matches('?');
+            declarations.iter().any(|declaration| {
+                declaration.rename.as_deref().unwrap_or(&declaration.name) == name
+            })
+        })
+        .map(|(_, enabled)| enabled.to_string())
+        .collect()
+}
+
+pub(super) fn metadata(
+    directory: &Path,
+    args: &CookArgs,
+) -> Result<cargo_metadata::Metadata, anyhow::Error> {
+    let mut command = cargo_metadata::MetadataCommand::new();
+    command.current_dir(directory);
+    if let Some(manifest_path) = &args.manifest_path {
+        command.manifest_path(manifest_path);
+    }
+    if args.default_features == DefaultFeatures::Disabled {
+        command.features(cargo_metadata::CargoOpt::NoDefaultFeatures);
+    }
+    if args.all_features == AllFeatures::Enabled {
+        command.features(cargo_metadata::CargoOpt::AllFeatures);
+    }
+    if let Some(features) = &args.features {
+        command.features(cargo_metadata::CargoOpt::SomeFeatures(
+            features.iter().cloned().collect(),
+        ));
+    }
+    let mut options = vec![];
+    // Only consider the dependencies of the platforms we are building for.
+    match &args.target {
+        Some(targets) => {
+            for target in targets.iter().filter(|target| !target.ends_with(".json")) {
+                let target = super::without_glibc_version(target);
+                options.extend(["--filter-platform".to_string(), target.to_string()]);
+            }
+        }
+        None => {
+            if let Some(host) = crate::fingerprint::toolchain()?.remove("host") {
+                options.extend(["--filter-platform".to_string(), host]);
+            }
+        }
+    }
+    if args.offline {
+        options.push("--offline".to_string());
+    }
+    Ok(command.other_options(options).exec()?)
+}
+
+pub(super) fn is_proc_macro(package: &Package) -> bool {
+    package
+        .targets
+        .iter()
+        .any(|target| target.kind.iter().any(|kind| kind == "proc-macro"))
+}
+
+/// How to depend on exactly the same `package`.
+fn declaration(package: &Package) -> Option<toml::map::Map<String, toml::Value>> {
+    let source = package.source.as_ref()?.repr.as_str();
+    let mut declaration = toml::map::Map::new();
+    if source == "registry+https://github.com/rust-lang/crates.io-index"
+        || source == "sparse+https://index.crates.io/"
+    {
+        declaration.insert("version".into(), format!("={}", package.version).into());
+    } else if let Some(git) = source.strip_prefix("git+") {
+        let (url, commit) = git.split_once('#')?;
+        let url = url.split('?').next().unwrap_or(url);
+        declaration.insert("git".into(), url.into());
+        declaration.insert("rev".into(), commit.into());
+    } else {
+        return None;
+    }
+    Some(declaration)
+}
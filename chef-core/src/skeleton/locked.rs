@@ -0,0 +1,159 @@
+//! Check, at `prepare` time, that `Cargo.lock` satisfies every dependency the manifests of the
+//! skeleton declare: otherwise `cook --locked` is bound to fail, after the recipe has been
+//! shipped. Versions of local crates are compared once masked, as `cook` sees them.
+use super::Skeleton;
+use cargo_metadata::semver::{Version, VersionReq};
+use std::path::Path;
+
+struct LockedPackage<'a> {
+    name: &'a str,
+    /// `None` for local crates.
+    source: Option<&'a str>,
+    version: Option<Version>,
+    /// As listed in `Cargo.lock`: `name`, `name version` or `name version (source)`.
+    dependencies: Vec<&'a str>,
+}
+
+pub(super) fn mismatches(skeleton: &Skeleton) -> Result<Vec<String>, anyhow::Error> {
+    let Some(lock_file) = &skeleton.lock_file else {
+        return Ok(vec![
+            "Cargo.lock is missing: it is generated from scratch when cooking.".to_string(),
+        ]);
+    };
+    let lock_file: toml::Value = toml::from_str(lock_file)?;
+    let packages: Vec<LockedPackage> = lock_file
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            Some(LockedPackage {
+                name: package.get("name")?.as_str()?,
+                source: package.get("source").and_then(|source| source.as_str()),
+                version: package
+                    .get("version")
+                    .and_then(|version| version.as_str())
+                    .and_then(|version| Version::parse(version).ok()),
+                dependencies: package
+                    .get("dependencies")
+                    .and_then(|dependencies| dependencies.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|dependency| dependency.as_str())
+                    .collect(),
+            })
+        })
+        .collect();
+    let workspace_dependencies = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == Path::new("Cargo.toml"))
+        .map(|manifest| toml::from_str::<toml::Value>(&manifest.contents))
+        .transpose()?
+        .and_then(|root| root.get("workspace")?.get("dependencies").cloned());
+
+    let mut mismatches = vec![];
+    for manifest in &skeleton.manifests {
+        let contents: toml::Value = toml::from_str(&manifest.contents)?;
+        let Some(name) = contents
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(|name| name.as_str())
+        else {
+            continue;
+        };
+        let location = manifest.relative_path.display();
+        let Some(member) = packages
+            .iter()
+            .find(|package| package.name == name && package.source.is_none())
+        else {
+            mismatches.push(format!("{}: `{}` is not in Cargo.lock.", location, name));
+            continue;
+        };
+        for (key, dependency) in dependencies(&contents) {
+            let dependency = match dependency.get("workspace").and_then(|w| w.as_bool()) {
+                Some(true) => workspace_dependencies
+                    .as_ref()
+                    .and_then(|dependencies| dependencies.get(key))
+                    .unwrap_or(dependency),
+                _ => dependency,
+            };
+            let package = dependency
+                .get("package")
+                .and_then(|package| package.as_str())
+                .unwrap_or(key);
+            let requirement = match dependency {
+                toml::Value::String(requirement) => Some(requirement.as_str()),
+                dependency => dependency.get("version").and_then(|v| v.as_str()),
+            };
+
+            let Some(spec) = member
+                .dependencies
+                .iter()
+                .find(|spec| spec.split(' ').next() == Some(package))
+            else {
+                mismatches.push(format!(
+                    "{}: `{}` is not among the dependencies of `{}` in Cargo.lock.",
+                    location, package, name
+                ));
+                continue;
+            };
+            // The version is only spelled out when several versions of the package are locked.
+            let version = spec.split(' ').nth(1);
+            let locked = packages.iter().find(|locked| {
+                locked.name == package
+                    && (version.is_none()
+                        || locked.version.as_ref().map(|v| v.to_string()).as_deref() == version)
+            });
+            let Some(locked) = locked else {
+                mismatches.push(format!(
+                    "{}: `{}` is missing from Cargo.lock.",
+                    location, spec
+                ));
+                continue;
+            };
+            let Some(requirement) = requirement.and_then(|r| VersionReq::parse(r).ok()) else {
+                continue;
+            };
+            if let Some(version) = locked
+                .version
+                .as_ref()
+                .filter(|version| !requirement.matches(version))
+            {
+                mismatches.push(format!(
+                    "{}: `{}` requires `{} {}`, but Cargo.lock has {}.",
+                    location, key, package, requirement, version
+                ));
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+/// The dependencies the manifest declares, for all kinds and targets.
+fn dependencies(manifest: &toml::Value) -> Vec<(&str, &toml::Value)> {
+    let targets = manifest
+        .get("target")
+        .and_then(|targets| targets.as_table())
+        .into_iter()
+        .flat_map(|targets| targets.values());
+    std::iter::once(manifest)
+        .chain(targets)
+        .flat_map(|section| {
+            [
+                "dependencies",
+                "dev-dependencies",
+                "dev_dependencies",
+                "build-dependencies",
+                "build_dependencies",
+            ]
+            .iter()
+            .filter_map(move |key| section.get(key).and_then(|d| d.as_table()))
+        })
+        .flat_map(|dependencies| {
+            dependencies
+                .iter()
+                .map(|(name, dependency)| (name.as_str(), dependency))
+        })
+        .collect()
+}
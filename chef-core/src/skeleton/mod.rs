@@ -0,0 +1,1178 @@
+mod build_scripts;
+mod cleanup;
+mod compat;
+mod credentials;
+pub(crate) mod delta;
+mod dummy;
+mod external;
+pub(crate) mod host;
+mod locked;
+mod network;
+mod placeholders;
+pub(crate) mod portable_path;
+mod read;
+mod required_features;
+mod sources;
+mod split;
+mod standalone;
+mod stub;
+mod target;
+mod vendor;
+mod version_masking;
+
+pub(crate) use compat::UpstreamSkeleton;
+pub use network::NetworkSettings;
+pub use stub::PackageStub;
+
+use crate::skeleton::target::{Target, TargetKind};
+use crate::{CookArgs, OptimisationProfile, ProfileSetting};
+use anyhow::Context;
+use cargo_manifest::Product;
+use cargo_metadata::semver::Version;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Skeleton {
+    pub manifests: Vec<Manifest>,
+    pub config_file: Option<String>,
+    /// The settings of the `cargo` configuration files outside of the project that change the
+    /// compiled artifacts (profiles, flags, ...): `cook` does not write them, it runs with its
+    /// own, but changing them changes the recipe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_config: Option<String>,
+    pub lock_file: Option<String>,
+    /// The `rust-toolchain` or `rust-toolchain.toml` file at the root of the project: `cook`
+    /// writes it back and builds dependencies with the toolchain it pins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolchain_file: Option<SkeletonFile>,
+    /// Directories of vendored crates, which `cook` expects to find on disk as they are.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vendored_sources: Vec<VendoredSource>,
+    /// Files `cook` writes as they are, on top of the dummies: kept build scripts and the
+    /// assets they read, and the kept sources of packages.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<SkeletonFile>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SkeletonFile {
+    /// Relative path with respect to the project root.
+    #[serde(with = "portable_path")]
+    pub relative_path: PathBuf,
+    pub contents: String,
+    /// Unix permissions of executable files, e.g. scripts run by a kept build script, which
+    /// would lose their executable bit otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct VendoredSource {
+    /// Relative path with respect to the project root.
+    #[serde(with = "portable_path")]
+    pub relative_path: PathBuf,
+    /// SHA-256 of the `.cargo-checksum.json` files of the vendored crates.
+    pub checksum: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// Relative path with respect to the project root.
+    #[serde(with = "portable_path")]
+    pub relative_path: PathBuf,
+    pub contents: String,
+    pub targets: Vec<Target>,
+}
+
+pub(in crate::skeleton) struct ParsedManifest {
+    relative_path: PathBuf,
+    contents: toml::Value,
+    targets: Vec<Target>,
+}
+
+impl Skeleton {
+    /// Find all Cargo.toml files in `base_path` by traversing sub-directories recursively.
+    pub fn derive<P: AsRef<Path>>(
+        base_path: P,
+        member: Option<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let packages = {
+            let _span = tracing::info_span!("discovery").entered();
+            read::workspace_packages(&base_path, extract_cargo_metadata(base_path.as_ref())?)
+        };
+        Self::from_packages(base_path, member, packages)
+    }
+
+    /// Like `derive`, but the workspace members are discovered without invoking `cargo`: it
+    /// works without a Rust toolchain, e.g. in a planner stage holding nothing but the chef
+    /// binary and the manifests.
+    pub fn derive_standalone<P: AsRef<Path>>(
+        base_path: P,
+        member: Option<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let packages = {
+            let _span = tracing::info_span!("discovery").entered();
+            standalone::workspace_packages(base_path.as_ref())?
+        };
+        Self::from_packages(base_path, member, packages)
+    }
+
+    fn from_packages<P: AsRef<Path>>(
+        base_path: P,
+        member: Option<String>,
+        packages: BTreeMap<PathBuf, (BTreeSet<Target>, Version)>,
+    ) -> Result<Self, anyhow::Error> {
+        // Read relevant files from the filesystem
+        let parsing = tracing::info_span!("parsing").entered();
+        let config_file = read::config(&base_path)?;
+        let external_config = read::external_config(&base_path)?;
+        let toolchain_file = read::toolchain_file(&base_path)?;
+        let mut manifests = read::manifests(&base_path, packages)?;
+        let vendored_sources = vendor::vendored_sources(base_path.as_ref())?;
+        // Vendored crates are used as they are, there is nothing to stub out.
+        manifests.retain(|manifest| {
+            !vendored_sources
+                .iter()
+                .any(|source| manifest.relative_path.starts_with(&source.relative_path))
+        });
+
+        let mut lock_file = read::lockfile(&base_path)?;
+        drop(parsing);
+
+        let masking = tracing::info_span!("masking").entered();
+        version_masking::mask_local_crate_versions(&mut manifests, &mut lock_file);
+        drop(masking);
+
+        let lock_file = lock_file.map(|l| toml::to_string(&l)).transpose()?;
+
+        let mut serialised_manifests = serialize_manifests(manifests)?;
+        // We don't want an ordering issue (e.g. related to how files are read from the filesystem)
+        // to make our skeleton generation logic non-reproducible - therefore we sort!
+        serialised_manifests.sort_by_key(|m| m.relative_path.clone());
+
+        let skeleton = Skeleton {
+            manifests: serialised_manifests,
+            config_file,
+            external_config,
+            lock_file,
+            toolchain_file,
+            vendored_sources,
+            files: vec![],
+        };
+        match member {
+            Some(member) => split::select(&skeleton, &member),
+            None => Ok(skeleton),
+        }
+    }
+
+    /// Like `derive`, but workspace members matching `pattern` that are declared in the root
+    /// manifest and do not exist (yet) are replaced by placeholder libraries.
+    pub fn derive_allowing_missing_members<P: AsRef<Path>>(
+        base_path: P,
+        member: Option<String>,
+        pattern: &str,
+    ) -> Result<Self, anyhow::Error> {
+        let _placeholders = placeholders::synthesize(base_path.as_ref(), pattern)?;
+        Self::derive(base_path, member)
+    }
+
+    /// Bring the path dependencies that live outside of `base_path`, the project root the
+    /// skeleton was derived from, into the skeleton: their stubs are stored under
+    /// `chef-external/` and the paths pointing to them are rewritten.
+    pub fn include_external_paths<P: AsRef<Path>>(
+        &self,
+        base_path: P,
+    ) -> Result<Skeleton, anyhow::Error> {
+        external::include(self, base_path.as_ref())
+    }
+
+    /// Keep the real build scripts of `packages`, and of the packages that opt in with
+    /// `package.metadata.chef.keep-build-script`, along with the assets they declare, instead
+    /// of using dummies. `base_path` is the project root the skeleton was derived from.
+    ///
+    /// If `detect_assets` is set, so are those of packages depending on crates whose build-time
+    /// inputs are well known (`.proto` files, `sqlx` offline data, migrations), with them.
+    pub fn keep_build_scripts<P: AsRef<Path>>(
+        &self,
+        base_path: P,
+        packages: &[String],
+        detect_assets: bool,
+    ) -> Result<Skeleton, anyhow::Error> {
+        build_scripts::keep(self, base_path.as_ref(), packages, detect_assets)
+    }
+
+    /// Keep the real sources of `packages`, and of the packages that opt in with
+    /// `package.metadata.chef.keep-source`, instead of using dummies: e.g. proc-macro crates,
+    /// which the dependencies of other members may use. `base_path` is the project root the
+    /// skeleton was derived from.
+    pub fn keep_sources<P: AsRef<Path>>(
+        &self,
+        base_path: P,
+        packages: &[String],
+    ) -> Result<Skeleton, anyhow::Error> {
+        sources::keep(self, base_path.as_ref(), packages)
+    }
+
+    /// The package `binary` belongs to and the `required-features` of the binary that `args` do
+    /// not enable, `None` if they are all enabled.
+    pub(crate) fn missing_required_features(
+        &self,
+        binary: &str,
+        args: &CookArgs,
+    ) -> Result<Option<(String, Vec<String>)>, anyhow::Error> {
+        required_features::missing(self, binary, args)
+    }
+
+    /// All the files, relative to `base_path`, that `derive` reads to compute the skeleton.
+    pub fn inputs<P: AsRef<Path>>(base_path: P) -> Result<Vec<PathBuf>, anyhow::Error> {
+        let skeleton = Self::derive(&base_path, None)?
+            .keep_build_scripts(&base_path, &[], false)?
+            .keep_sources(&base_path, &[])?;
+        let mut inputs: Vec<PathBuf> = skeleton
+            .manifests
+            .into_iter()
+            .map(|manifest| manifest.relative_path)
+            .collect();
+        if skeleton.lock_file.is_some() {
+            inputs.push("Cargo.lock".into());
+        }
+        inputs.extend(read::config_path(&base_path));
+        if base_path
+            .as_ref()
+            .join(crate::CHEF_CONFIG_FILE_NAME)
+            .is_file()
+        {
+            inputs.push(crate::CHEF_CONFIG_FILE_NAME.into());
+        }
+        inputs.extend(
+            skeleton
+                .vendored_sources
+                .into_iter()
+                .map(|source| source.relative_path),
+        );
+        inputs.extend(skeleton.files.into_iter().map(|file| file.relative_path));
+        Ok(inputs)
+    }
+
+    /// The roots, relative to `base_path`, of the workspaces nested inside the one at
+    /// `base_path` (e.g. a fuzzing workspace listed in `workspace.exclude`). They are never part
+    /// of its skeleton, but can be prepared on their own.
+    ///
+    /// The search is restricted to `scan_roots` (relative to `base_path`), if any.
+    pub fn nested_workspaces<P: AsRef<Path>>(
+        base_path: P,
+        scan_roots: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, anyhow::Error> {
+        read::nested_workspaces(&base_path, scan_roots)
+    }
+
+    /// Given the manifests in the current skeleton, create the minimum set of files required to
+    /// have a valid Rust project (i.e. write all manifests to disk and create dummy `lib.rs`,
+    /// `main.rs` and `build.rs` files where needed).
+    ///
+    /// This function should be called on an empty canvas - i.e. an empty directory apart from
+    /// the recipe file used to restore the skeleton.
+    pub fn build_minimum_project(
+        &self,
+        base_path: &Path,
+        no_std: bool,
+    ) -> Result<(), anyhow::Error> {
+        fs::create_dir_all(base_path)?;
+        let mtime = skeleton_mtime()?;
+        // Save lockfile to disk, if available
+        if let Some(lock_file) = &self.lock_file {
+            let lock_file_path = base_path.join("Cargo.lock");
+            write_skeleton_file(&lock_file_path, lock_file, mtime)?;
+        }
+
+        // save config file to disk, if available
+        if let Some(config_file) = &self.config_file {
+            let parent_dir = base_path.join(".cargo");
+            let config_file_path = parent_dir.join("config.toml");
+            fs::create_dir_all(parent_dir)?;
+            write_skeleton_file(&config_file_path, config_file, mtime)?;
+        }
+
+        if let Some(toolchain_file) = &self.toolchain_file {
+            let toolchain_file_path = base_path.join(&toolchain_file.relative_path);
+            write_skeleton_file(&toolchain_file_path, &toolchain_file.contents, mtime)?;
+        }
+
+        const NO_STD_ENTRYPOINT: &str = "#![no_std]
+#![no_main]
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+";
+        const NO_STD_HARNESS_ENTRYPOINT: &str = r#"#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(test_runner)]
+
+#[no_mangle]
+pub extern "C" fn _init() {}
+
+fn test_runner(_: &[&dyn Fn()]) {}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+"#;
+
+        let get_test_like_entrypoint = |harness: bool| -> &str {
+            match (no_std, harness) {
+                (true, true) => NO_STD_HARNESS_ENTRYPOINT,
+                (true, false) => NO_STD_ENTRYPOINT,
+                (false, true) => "",
+                (false, false) => "fn main() {}",
+            }
+        };
+
+        let workspace_dummies = match self
+            .manifests
+            .iter()
+            .find(|manifest| manifest.relative_path == Path::new("Cargo.toml"))
+        {
+            Some(root) => dummy::DummySources::read(root, "workspace")?,
+            None => Default::default(),
+        };
+
+        // Save all manifests to disks
+        for manifest in &self.manifests {
+            // Persist manifest
+            let manifest_path = base_path.join(&manifest.relative_path);
+            let parent_directory = if let Some(parent_directory) = manifest_path.parent() {
+                fs::create_dir_all(parent_directory)?;
+                parent_directory.to_path_buf()
+            } else {
+                base_path.to_path_buf()
+            };
+            let contents = without_dangling_default_run(manifest)?;
+            write_skeleton_file(&manifest_path, &contents, mtime)?;
+            let parsed_manifest =
+                cargo_manifest::Manifest::from_slice(manifest.contents.as_bytes())?;
+
+            let is_harness = |products: &Option<Vec<Product>>, name: &str| -> bool {
+                products
+                    .as_ref()
+                    .and_then(|v| {
+                        v.iter()
+                            .find(|product| product.name.as_deref() == Some(name))
+                            .map(|p| p.harness)
+                    })
+                    .unwrap_or(true)
+            };
+
+            let package_dummies = dummy::DummySources::read(manifest, "package")?;
+            let dummies = package_dummies.clone().or(&workspace_dummies);
+
+            // Create dummy entrypoints for all targets
+            for target in &manifest.targets {
+                // Like `--no-std`, the dummies of the workspace do not affect `proc-macro` crates.
+                let configured = match target.kind {
+                    TargetKind::Lib {
+                        is_proc_macro: true,
+                    } => package_dummies.get(&target.kind),
+                    _ => dummies.get(&target.kind),
+                };
+                let built_in = match target.kind {
+                    TargetKind::BuildScript => "fn main() {}",
+                    TargetKind::Bin | TargetKind::Example => {
+                        if no_std {
+                            NO_STD_ENTRYPOINT
+                        } else {
+                            "fn main() {}"
+                        }
+                    }
+                    TargetKind::Lib { is_proc_macro } => {
+                        if no_std && !is_proc_macro {
+                            "#![no_std]"
+                        } else {
+                            ""
+                        }
+                    }
+                    TargetKind::Bench => {
+                        get_test_like_entrypoint(is_harness(&parsed_manifest.bench, &target.name))
+                    }
+                    TargetKind::Test => {
+                        get_test_like_entrypoint(is_harness(&parsed_manifest.test, &target.name))
+                    }
+                };
+                let content = configured.unwrap_or(built_in);
+                let path = parent_directory.join(&target.path);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                write_skeleton_file(&path, content, mtime)?;
+            }
+            if let Some(build_script) = missing_build_script(manifest)? {
+                let path = parent_directory.join(build_script);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                let content = dummies
+                    .get(&TargetKind::BuildScript)
+                    .unwrap_or("fn main() {}");
+                write_skeleton_file(&path, content, mtime)?;
+            }
+        }
+        for file in &self.files {
+            let path = base_path.join(&file.relative_path);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            write_skeleton_file(&path, &file.contents, mtime)?;
+            #[cfg(unix)]
+            if let Some(mode) = file.mode {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))
+                    .with_context(|| format!("Failed to set the permissions of {:?}", path))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the vendored sources the recipe was prepared with have been copied over to
+    /// `base_path`, as they are.
+    pub fn check_vendored_sources(&self, base_path: &Path) -> Result<(), anyhow::Error> {
+        for source in &self.vendored_sources {
+            let directory = base_path.join(&source.relative_path);
+            match vendor::checksum(&directory)? {
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "The vendored sources in {:?} are missing: copy them over before cooking.",
+                        source.relative_path
+                    ))
+                }
+                Some(checksum) if checksum != source.checksum => log::warn!(
+                    "The vendored sources in {:?} differ from the ones the recipe was prepared with.",
+                    source.relative_path
+                ),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The skeleton restricted to the packages for which `keep` returns `true` and to their
+    /// path dependencies, e.g. to honour `--package` and `--exclude`.
+    pub fn keep_packages(&self, keep: impl Fn(&str) -> bool) -> Result<Skeleton, anyhow::Error> {
+        split::keep_packages(self, keep)
+    }
+
+    /// The skeleton without the workspace members whose package name or path matches any of
+    /// `patterns` (globs are allowed). Excluded members are recorded in `workspace.exclude`, and
+    /// are kept as path dependencies if other members depend on them.
+    pub fn exclude_members(&self, patterns: &[String]) -> Result<Skeleton, anyhow::Error> {
+        split::exclude(self, patterns)
+    }
+
+    /// Restrict the skeleton to the `default-members` of the workspace, the members `cargo`
+    /// builds when no package is selected. Returns the skeleton as it is if there are none.
+    pub fn keep_default_members(&self) -> Result<Skeleton, anyhow::Error> {
+        split::keep_default_members(self)
+    }
+
+    /// One skeleton per workspace member, named after its package, containing only the
+    /// manifests of the member and of its path dependencies (on top of the root manifest).
+    pub fn split_per_member(&self) -> Result<Vec<(String, Skeleton)>, anyhow::Error> {
+        split::split_per_member(self)
+    }
+
+    /// The skeleton with the local crates named in `stubs` replaced by stubs, which `cargo`
+    /// can compile without their dependencies nor build script.
+    pub fn with_stubbed_packages(
+        &self,
+        stubs: &[(String, PackageStub)],
+    ) -> Result<Skeleton, anyhow::Error> {
+        stub::stub_packages(self, stubs)
+    }
+
+    /// The skeleton without dev-dependencies, including target-specific ones, in any manifest.
+    pub fn without_dev_dependencies(&self) -> Result<Skeleton, anyhow::Error> {
+        self.edit_manifests(split::remove_dev_dependencies)
+    }
+
+    /// The skeleton without `package.metadata` and `workspace.metadata` in any manifest, except
+    /// for the `chef` settings `cook` relies on.
+    pub fn without_metadata(&self) -> Result<Skeleton, anyhow::Error> {
+        self.edit_manifests(|contents| {
+            for section in ["package", "workspace"] {
+                let Some(section) = contents
+                    .get_mut(section)
+                    .and_then(|section| section.as_table_mut())
+                else {
+                    continue;
+                };
+                let chef = section
+                    .remove("metadata")
+                    .and_then(|metadata| metadata.get("chef").cloned());
+                if let Some(chef) = chef {
+                    let mut metadata = toml::Table::new();
+                    metadata.insert("chef".into(), chef);
+                    section.insert("metadata".into(), metadata.into());
+                }
+            }
+        })
+    }
+
+    fn edit_manifests(&self, edit: impl Fn(&mut toml::Value)) -> Result<Skeleton, anyhow::Error> {
+        let mut skeleton = self.clone();
+        for manifest in &mut skeleton.manifests {
+            let mut contents: toml::Value = toml::from_str(&manifest.contents)?;
+            edit(&mut contents);
+            manifest.contents = toml::to_string(&contents)?;
+        }
+        Ok(skeleton)
+    }
+
+    /// The skeleton with `overrides` applied, when building with `profile`, to every package
+    /// that is not a workspace member: they are merged into `[profile.<profile>.package."*"]`
+    /// in the root manifest.
+    pub(crate) fn with_dependency_profile_overrides(
+        &self,
+        profile: &str,
+        overrides: &[(&str, toml::Value)],
+    ) -> Result<Skeleton, anyhow::Error> {
+        self.with_root_manifest_settings(overrides.iter().map(|(key, value)| {
+            let path = ["profile", profile, "package", "*", key];
+            (
+                path.iter().map(|key| key.to_string()).collect(),
+                value.clone(),
+            )
+        }))
+    }
+
+    /// The skeleton with `settings` patched into the `[profile]` table of the root manifest.
+    pub(crate) fn with_profile_settings(
+        &self,
+        settings: &[ProfileSetting],
+    ) -> Result<Skeleton, anyhow::Error> {
+        self.with_root_manifest_settings(settings.iter().map(|setting| {
+            let path = std::iter::once("profile".to_string())
+                .chain(setting.path.iter().cloned())
+                .collect();
+            (path, setting.value.clone())
+        }))
+    }
+
+    /// The skeleton with `settings` written into its cargo configuration, for `cook` to fetch
+    /// dependencies with them.
+    pub fn with_network_settings(
+        &self,
+        settings: &NetworkSettings,
+    ) -> Result<Skeleton, anyhow::Error> {
+        network::apply(self, settings)
+    }
+
+    /// The skeleton with `rustflags` appended to the `rustflags` of its cargo configuration:
+    /// to `build.rustflags`, and to the `rustflags` of every `[target]` table setting them,
+    /// which take precedence.
+    pub(crate) fn with_rustflags(&self, rustflags: &[String]) -> Result<Skeleton, anyhow::Error> {
+        let mut skeleton = self.clone();
+        let mut config: toml::Table = match &skeleton.config_file {
+            Some(config) => {
+                toml::from_str(config).context("Failed to parse the cargo configuration.")?
+            }
+            None => toml::Table::new(),
+        };
+        let append = |existing: &mut toml::Value| match existing {
+            toml::Value::String(flags) => {
+                for flag in rustflags {
+                    flags.push(' ');
+                    flags.push_str(flag);
+                }
+            }
+            toml::Value::Array(flags) => {
+                flags.extend(rustflags.iter().cloned().map(toml::Value::String));
+            }
+            _ => {}
+        };
+        if let Some(targets) = config.get_mut("target").and_then(|t| t.as_table_mut()) {
+            for (_, table) in targets.iter_mut() {
+                if let Some(existing) = table.get_mut("rustflags") {
+                    append(existing);
+                }
+            }
+        }
+        let build = config
+            .entry("build")
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .context("`build` is not a table in the cargo configuration.")?;
+        match build.get_mut("rustflags") {
+            Some(existing) => append(existing),
+            None => {
+                build.insert(
+                    "rustflags".to_string(),
+                    toml::Value::Array(
+                        rustflags.iter().cloned().map(toml::Value::String).collect(),
+                    ),
+                );
+            }
+        }
+        skeleton.config_file = Some(toml::to_string(&config)?);
+        Ok(skeleton)
+    }
+
+    /// The skeleton with each value set at its path of keys in the root manifest, creating the
+    /// tables on the way as needed.
+    fn with_root_manifest_settings(
+        &self,
+        settings: impl Iterator<Item = (Vec<String>, toml::Value)>,
+    ) -> Result<Skeleton, anyhow::Error> {
+        let mut skeleton = self.clone();
+        let root = skeleton
+            .manifests
+            .iter_mut()
+            .find(|manifest| manifest.relative_path == Path::new("Cargo.toml"))
+            .context("The skeleton has no root manifest to declare profile overrides in.")?;
+        let mut contents: toml::Value = toml::from_str(&root.contents)?;
+        for (path, value) in settings {
+            let Some((last, tables)) = path.split_last() else {
+                continue;
+            };
+            let mut table = contents
+                .as_table_mut()
+                .context("The root manifest is not a table.")?;
+            for key in tables {
+                table = table
+                    .entry(key)
+                    .or_insert_with(|| toml::Value::Table(Default::default()))
+                    .as_table_mut()
+                    .with_context(|| format!("`{}` is not a table in the root manifest.", key))?;
+            }
+            table.insert(last.clone(), value);
+        }
+        root.contents = toml::to_string(&contents)?;
+        Ok(skeleton)
+    }
+
+    /// Whether any manifest declares dev-dependencies, including target-specific ones.
+    pub fn has_dev_dependencies(&self) -> Result<bool, anyhow::Error> {
+        for manifest in &self.manifests {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            let targets = contents
+                .get("target")
+                .and_then(|targets| targets.as_table())
+                .into_iter()
+                .flat_map(|targets| targets.values());
+            if std::iter::once(&contents).chain(targets).any(|section| {
+                section
+                    .get("dev-dependencies")
+                    .and_then(|d| d.as_table())
+                    .is_some_and(|d| !d.is_empty())
+            }) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Whether any manifest declares an artifact dependency, which requires `-Z bindeps`.
+    pub fn uses_artifact_dependencies(&self) -> Result<bool, anyhow::Error> {
+        for manifest in &self.manifests {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            if declared_dependencies(&contents)
+                .iter()
+                .any(|(_, dependency)| dependency.get("artifact").is_some())
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Dependencies in the lockfile that can only be fetched with credentials (SSH keys for
+    /// git repositories, tokens for private registries), with what must be made available when
+    /// cooking.
+    pub fn credential_warnings(&self) -> Result<Vec<String>, anyhow::Error> {
+        let Some(lock_file) = &self.lock_file else {
+            return Ok(vec![]);
+        };
+        let config = self
+            .config_file
+            .as_deref()
+            .map(toml::from_str::<toml::Value>)
+            .transpose()?;
+        Ok(credentials::warnings(
+            &toml::from_str(lock_file)?,
+            config.as_ref(),
+        ))
+    }
+
+    /// Why `Cargo.lock` does not satisfy the dependencies the manifests declare, if it doesn't:
+    /// cooking the skeleton with `--locked` would fail.
+    pub fn lock_file_mismatches(&self) -> Result<Vec<String>, anyhow::Error> {
+        locked::mismatches(self)
+    }
+
+    /// The features the manifests enable on their dependencies, as `crate/feature`, whether
+    /// in a dependency declaration or forwarded by one of their own features.
+    pub(crate) fn declared_features(&self) -> Result<BTreeSet<String>, anyhow::Error> {
+        let mut features = BTreeSet::new();
+        for manifest in &self.manifests {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            for (name, dependency) in declared_dependencies(&contents) {
+                let name = dependency
+                    .get("package")
+                    .and_then(|package| package.as_str())
+                    .unwrap_or(name);
+                let enabled = dependency
+                    .get("features")
+                    .and_then(|features| features.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|feature| feature.as_str());
+                features.extend(enabled.map(|feature| format!("{}/{}", name, feature)));
+            }
+            let forwarded = contents
+                .get("features")
+                .and_then(|features| features.as_table())
+                .into_iter()
+                .flat_map(|features| features.values())
+                .filter_map(|enabled| enabled.as_array())
+                .flatten()
+                .filter_map(|feature| feature.as_str())
+                .filter(|feature| feature.contains('/'));
+            features.extend(forwarded.map(|feature| feature.replacen("?/", "/", 1)));
+        }
+        Ok(features)
+    }
+
+    /// The names of the packages of the skeleton.
+    pub fn package_names(&self) -> Result<Vec<String>, anyhow::Error> {
+        Ok(self
+            .package_features()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    /// The features each package of the skeleton declares, by package name. Implicit features
+    /// of optional dependencies are left out.
+    pub(crate) fn package_features(&self) -> Result<Vec<(String, Vec<String>)>, anyhow::Error> {
+        let mut packages = vec![];
+        for manifest in &self.manifests {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            let Some(name) = contents
+                .get("package")
+                .and_then(|package| package.get("name"))
+                .and_then(|name| name.as_str())
+            else {
+                continue;
+            };
+            let features = contents
+                .get("features")
+                .and_then(|features| features.as_table())
+                .into_iter()
+                .flat_map(|features| features.keys().cloned())
+                .collect();
+            packages.push((name.to_string(), features));
+        }
+        Ok(packages)
+    }
+
+    /// The highest `rust-version` declared by the packages of the workspace, together with the
+    /// package declaring it.
+    pub fn rust_version(&self) -> Result<Option<(String, Version)>, anyhow::Error> {
+        let root: Option<toml::Value> = self
+            .manifests
+            .iter()
+            .find(|manifest| manifest.relative_path == Path::new("Cargo.toml"))
+            .map(|root| toml::from_str(&root.contents))
+            .transpose()?;
+        let inherited = root
+            .as_ref()
+            .and_then(|root| root.get("workspace")?.get("package")?.get("rust-version"));
+        let mut highest: Option<(String, Version)> = None;
+        for manifest in &self.manifests {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            let Some(package) = contents.get("package") else {
+                continue;
+            };
+            let declared = match package.get("rust-version") {
+                Some(version) if version.get("workspace").is_some() => inherited,
+                declared => declared,
+            };
+            let Some(declared) = declared.and_then(|version| version.as_str()) else {
+                continue;
+            };
+            let version = crate::toolchain::parse_rust_version(declared).with_context(|| {
+                format!(
+                    "Invalid `rust-version` in {}",
+                    manifest.relative_path.display()
+                )
+            })?;
+            if highest
+                .as_ref()
+                .is_none_or(|(_, highest)| &version > highest)
+            {
+                let name = package.get("name").and_then(|name| name.as_str());
+                highest = Some((name.unwrap_or_default().to_string(), version));
+            }
+        }
+        Ok(highest)
+    }
+
+    /// Potential issues, detected at `prepare` time, that will get in the way of cooking the
+    /// recipe or of reusing the cooked dependencies.
+    pub fn warnings(&self) -> Result<Vec<String>, anyhow::Error> {
+        let mut warnings = vec![];
+        match &self.lock_file {
+            None => warnings.push(
+                "There is no Cargo.lock: dependencies will be resolved from scratch (and might \
+                change) every time the recipe is cooked."
+                    .to_string(),
+            ),
+            Some(lock_file) => {
+                warnings.extend(self.credential_warnings()?);
+                let lock_file: toml::Value = toml::from_str(lock_file)?;
+                for (local, dependent) in version_masking::externally_required_crates(&lock_file) {
+                    warnings.push(format!(
+                        "Cargo.lock: the local crate `{}` is a dependency of `{}`, which is not \
+                        part of the workspace: its version is not masked and the recipe will \
+                        change every time it is bumped.",
+                        local, dependent
+                    ));
+                }
+            }
+        }
+
+        let package_directories: Vec<&Path> = self
+            .manifests
+            .iter()
+            .map(|manifest| manifest.relative_path.parent().unwrap_or(Path::new("")))
+            .collect();
+        for manifest in &self.manifests {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            let directory = manifest.relative_path.parent().unwrap_or(Path::new(""));
+            for (name, dependency) in declared_dependencies(&contents) {
+                let Some(path) = dependency.get("path").and_then(|path| path.as_str()) else {
+                    continue;
+                };
+                match normalize(&directory.join(path)) {
+                    None => warnings.push(format!(
+                        "{}: the path dependency `{}` ({}) lives outside of the project root, \
+                        it will be missing from the recipe (unless `--allow-external-paths` is \
+                        passed).",
+                        manifest.relative_path.display(),
+                        name,
+                        path
+                    )),
+                    Some(resolved)
+                        if !package_directories.contains(&resolved.as_path())
+                            && !self
+                                .vendored_sources
+                                .iter()
+                                .any(|source| resolved.starts_with(&source.relative_path)) =>
+                    {
+                        warnings.push(format!(
+                            "{}: the path dependency `{}` ({}) is not part of the recipe.",
+                            manifest.relative_path.display(),
+                            name,
+                            path
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Scan the target directory and remove all compilation artifacts of the packages in the
+    /// current workspace, leaving those of external dependencies untouched.
+    /// Given the usage of dummy `lib.rs` and `build.rs` files, keeping them around leads to funny
+    /// compilation errors.
+    pub fn remove_compiled_dummies<P: AsRef<Path>>(
+        &self,
+        base_path: P,
+        profile: OptimisationProfile,
+        target: Option<Vec<String>>,
+        target_dir: Option<PathBuf>,
+    ) -> Result<(), anyhow::Error> {
+        let target_directories = artifact_directories(
+            base_path.as_ref(),
+            &profile,
+            target.as_deref(),
+            target_dir.as_deref(),
+        );
+
+        let external_package_names: Vec<String> = match &self.lock_file {
+            Some(lock_file) => toml::from_str::<toml::Value>(lock_file)?
+                .get("package")
+                .and_then(|packages| packages.as_array())
+                .into_iter()
+                .flatten()
+                .filter(|package| package.get("source").is_some())
+                .filter_map(|package| package.get("name")?.as_str().map(str::to_string))
+                .collect(),
+            None => vec![],
+        };
+        let mut local_packages = vec![];
+        for manifest in &self.manifests {
+            let parsed_manifest =
+                cargo_manifest::Manifest::from_slice(manifest.contents.as_bytes())?;
+            if let Some(package) = parsed_manifest.package {
+                local_packages.push(cleanup::LocalPackage {
+                    lib_name: parsed_manifest.lib.map(|lib| {
+                        lib.name
+                            .unwrap_or_else(|| package.name.clone())
+                            .replace('-', "_")
+                    }),
+                    has_external_namesake: external_package_names.contains(&package.name),
+                    keeps_build_script: build_scripts::is_kept(self, manifest),
+                    keeps_sources: sources::is_kept(self, manifest),
+                    name: package.name,
+                });
+            }
+        }
+
+        for target_directory in &target_directories {
+            cleanup::remove_local_units(target_directory, base_path.as_ref(), &local_packages)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The directories `cargo` writes compilation artifacts to, one per target triple (`--target`,
+/// or `CARGO_BUILD_TARGET`). When cross-compiling, the units that run on the host (build
+/// scripts, proc macros and their dependencies) land in the directory of the profile itself,
+/// which comes last.
+pub(crate) fn artifact_directories(
+    base_path: &Path,
+    profile: &OptimisationProfile,
+    target: Option<&[String]>,
+    target_dir: Option<&Path>,
+) -> Vec<PathBuf> {
+    let target_dir = match target_dir {
+        None => base_path.join("target"),
+        Some(target_dir) => target_dir.to_path_buf(),
+    };
+
+    // Like `cargo` does, the built-in `bench` and `test` profiles share the directory of the
+    // profile they inherit from, any other one gets its own.
+    let profile = match profile {
+        OptimisationProfile::Release => "release",
+        OptimisationProfile::Debug => "debug",
+        OptimisationProfile::Other(custom_profile) if custom_profile == "bench" => "release",
+        OptimisationProfile::Other(custom_profile) if custom_profile == "test" => "debug",
+        OptimisationProfile::Other(custom_profile) => custom_profile,
+    };
+
+    let targets = match target {
+        Some(targets) => targets.to_vec(),
+        None => std::env::var("CARGO_BUILD_TARGET")
+            .ok()
+            .filter(|target| !target.is_empty())
+            .into_iter()
+            .collect(),
+    };
+    let mut directories: Vec<PathBuf> = targets
+        .iter()
+        .map(|target| target_dir.join(target_str(target)).join(profile))
+        .collect();
+    directories.push(target_dir.join(profile));
+    directories
+}
+
+/// All the dependencies declared in a manifest as `(name, declaration)` pairs, wherever they
+/// are declared: top-level, target-specific or in `[workspace.dependencies]`.
+fn declared_dependencies(manifest: &toml::Value) -> Vec<(&str, &toml::Value)> {
+    let targets = manifest
+        .get("target")
+        .and_then(|targets| targets.as_table())
+        .into_iter()
+        .flat_map(|targets| targets.values());
+    std::iter::once(manifest)
+        .chain(targets)
+        .chain(manifest.get("workspace"))
+        .flat_map(|section| {
+            ["dependencies", "dev-dependencies", "build-dependencies"]
+                .iter()
+                .filter_map(move |key| section.get(key).and_then(|d| d.as_table()))
+        })
+        .flat_map(|dependencies| {
+            dependencies
+                .iter()
+                .map(|(name, dependency)| (name.as_str(), dependency))
+        })
+        .collect()
+}
+
+/// The modification time of every file written by `build_minimum_project`, unless
+/// `SOURCE_DATE_EPOCH` is set.
+///
+/// Cooking the same recipe twice must produce byte-identical skeletons, timestamps included,
+/// for `cargo` to consider what it built the first time fresh. Keeping it in the past also
+/// guarantees that the actual sources, copied over the skeleton later on, are always newer
+/// than the dummy files they replace.
+const SKELETON_MTIME: std::time::Duration = std::time::Duration::from_secs(315_532_800);
+
+/// `SOURCE_DATE_EPOCH` (seconds since the Unix epoch), if set, or `SKELETON_MTIME`.
+fn skeleton_mtime() -> Result<std::time::SystemTime, anyhow::Error> {
+    let since_epoch = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => std::time::Duration::from_secs(epoch.trim().parse().with_context(|| {
+            format!("`SOURCE_DATE_EPOCH` is not a valid timestamp: {:?}", epoch)
+        })?),
+        Err(_) => SKELETON_MTIME,
+    };
+    Ok(std::time::UNIX_EPOCH + since_epoch)
+}
+
+/// The contents of `manifest`, without `package.default-run` if it names a binary the skeleton
+/// does not have (e.g. dropped by a postprocessing command): `cargo` would refuse to load it.
+fn without_dangling_default_run(manifest: &Manifest) -> Result<Cow<'_, str>, anyhow::Error> {
+    let mut contents: toml::Value = toml::from_str(&manifest.contents)?;
+    let Some(package) = contents
+        .get_mut("package")
+        .and_then(|package| package.as_table_mut())
+    else {
+        return Ok(Cow::Borrowed(&manifest.contents));
+    };
+    let Some(default_run) = package.get("default-run").and_then(|d| d.as_str()) else {
+        return Ok(Cow::Borrowed(&manifest.contents));
+    };
+    let is_dangling = !manifest
+        .targets
+        .iter()
+        .any(|target| target.kind == TargetKind::Bin && target.name == default_run);
+    if !is_dangling {
+        return Ok(Cow::Borrowed(&manifest.contents));
+    }
+    log::warn!(
+        "{}: there is no binary named `{}`, ignoring `default-run`.",
+        manifest.relative_path.display(),
+        default_run
+    );
+    package.remove("default-run");
+    Ok(Cow::Owned(toml::to_string(&contents)?))
+}
+
+/// The path of the build script `manifest` requires, if it is missing from its targets (e.g.
+/// dropped by a postprocessing command): either `package.build`, or `build.rs` if the package
+/// sets `links`, as `cargo` refuses to load such a package without a build script.
+fn missing_build_script(manifest: &Manifest) -> Result<Option<PathBuf>, anyhow::Error> {
+    if manifest
+        .targets
+        .iter()
+        .any(|target| target.kind == TargetKind::BuildScript)
+    {
+        return Ok(None);
+    }
+    let contents: toml::Value = toml::from_str(&manifest.contents)?;
+    let Some(package) = contents.get("package") else {
+        return Ok(None);
+    };
+    let path = match package.get("build") {
+        Some(toml::Value::String(path)) => PathBuf::from(path),
+        Some(toml::Value::Boolean(false)) => return Ok(None),
+        _ if package.get("links").is_some() => PathBuf::from("build.rs"),
+        _ => return Ok(None),
+    };
+    log::warn!(
+        "{}: the build script is missing from the targets of the recipe, using a dummy one.",
+        manifest.relative_path.display()
+    );
+    Ok(Some(path))
+}
+
+fn write_skeleton_file(
+    path: &Path,
+    contents: &str,
+    mtime: std::time::SystemTime,
+) -> Result<(), anyhow::Error> {
+    // Symlinks (e.g. to crate directories or manifests shared with other projects) are
+    // replaced by regular files: `cargo` cannot tell the difference, and their targets, which
+    // might live outside of the skeleton, are left untouched.
+    if fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        fs::remove_file(path)?;
+    }
+    fs::write(path, contents)?;
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(mtime))
+        .with_context(|| format!("Failed to set the modification time of {:?}", path))?;
+    Ok(())
+}
+
+/// Resolve `.` and `..` in a relative path, returning `None` if it escapes its root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            component => normalized.push(component),
+        }
+    }
+    Some(normalized)
+}
+
+/// If a custom target spec file is used,
+/// (Part of the unstable cargo feature 'build-std'; c.f. https://doc.rust-lang.org/rustc/targets/custom.html )
+/// the `--target` flag refers to a `.json` file, possibly in another directory.
+/// In this case, the actual name of the target is the name of the file without the `.json` suffix.
+fn target_str(target: &str) -> &str {
+    match target.strip_suffix(".json") {
+        Some(spec) => Path::new(spec)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(spec),
+        None => without_glibc_version(target),
+    }
+}
+
+/// `cargo zigbuild` accepts targets suffixed with the version of glibc to link against, e.g.
+/// `x86_64-unknown-linux-gnu.2.17`, and strips the suffix before handing the target to `cargo`.
+pub(crate) fn without_glibc_version(target: &str) -> &str {
+    match target.split_once('.') {
+        Some((triple, version))
+            if !version.is_empty()
+                && version.split('.').all(|part| {
+                    !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit())
+                }) =>
+        {
+            triple
+        }
+        _ => target,
+    }
+}
+
+fn serialize_manifests(manifests: Vec<ParsedManifest>) -> Result<Vec<Manifest>, anyhow::Error> {
+    let mut serialised_manifests = vec![];
+    for manifest in manifests {
+        // The serialised contents might be different from the original manifest!
+        let mut manifest = manifest;
+        portable_path::normalize_manifest(&mut manifest.contents);
+        let contents = toml::to_string(&manifest.contents)?;
+        serialised_manifests.push(Manifest {
+            relative_path: manifest.relative_path,
+            contents,
+            targets: manifest.targets,
+        });
+    }
+    Ok(serialised_manifests)
+}
+
+fn extract_cargo_metadata(path: &Path) -> Result<cargo_metadata::Metadata, anyhow::Error> {
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.current_dir(path);
+    cmd.no_deps();
+
+    cmd.exec().context("Cannot extract Cargo metadata")
+}
@@ -0,0 +1,63 @@
+//! Settings of the `[http]` and `[net]` tables of the cargo configuration that tune how `cook`
+//! fetches dependencies, e.g. on small CI runners or behind proxies that choke on the default
+//! concurrency. They are written into the cargo configuration of the recipe, and do not change
+//! the compiled artifacts.
+//!
+//! Cargo has no setting capping the number of concurrent downloads: without HTTP/2
+//! multiplexing, it opens at most two connections per host and downloads one crate at a time
+//! over each of them, instead of multiplexing every download over the same connections.
+use super::Skeleton;
+use anyhow::Context;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkSettings {
+    /// `http.multiplexing`.
+    pub http_multiplexing: Option<bool>,
+    /// `http.timeout`, in seconds.
+    pub http_timeout: Option<u32>,
+    /// `net.retry`.
+    pub net_retry: Option<u32>,
+}
+
+impl NetworkSettings {
+    fn entries(&self) -> Vec<(&'static str, &'static str, toml::Value)> {
+        let mut entries = vec![];
+        if let Some(multiplexing) = self.http_multiplexing {
+            entries.push(("http", "multiplexing", toml::Value::Boolean(multiplexing)));
+        }
+        if let Some(timeout) = self.http_timeout {
+            entries.push(("http", "timeout", toml::Value::Integer(timeout.into())));
+        }
+        if let Some(retry) = self.net_retry {
+            entries.push(("net", "retry", toml::Value::Integer(retry.into())));
+        }
+        entries
+    }
+}
+
+pub(super) fn apply(
+    skeleton: &Skeleton,
+    settings: &NetworkSettings,
+) -> Result<Skeleton, anyhow::Error> {
+    let entries = settings.entries();
+    let mut skeleton = skeleton.clone();
+    if entries.is_empty() {
+        return Ok(skeleton);
+    }
+    let mut config: toml::Table = match &skeleton.config_file {
+        Some(config) => {
+            toml::from_str(config).context("Failed to parse the cargo configuration.")?
+        }
+        None => toml::Table::new(),
+    };
+    for (table, key, value) in entries {
+        config
+            .entry(table)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .with_context(|| format!("`{}` is not a table in the cargo configuration.", table))?
+            .insert(key.to_string(), value);
+    }
+    skeleton.config_file = Some(toml::to_string(&config)?);
+    Ok(skeleton)
+}
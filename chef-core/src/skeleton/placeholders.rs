@@ -0,0 +1,132 @@
+//! Placeholders for workspace members that are generated at build time (e.g. by a codegen step
+//! that runs after `prepare`).
+//!
+//! `cargo metadata` refuses to load a workspace if one of its members is missing. We create a
+//! minimal library crate for each missing member matching the pattern provided by the user,
+//! for as long as the skeleton is being computed, and remove it afterwards. The placeholders
+//! end up in the recipe like any other member: `cook` builds them as dummies and cleans them up,
+//! leaving room for the real members once they are generated.
+use anyhow::Context;
+use globset::Glob;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files and directories created for the placeholders, removed when dropped.
+pub(super) struct Placeholders {
+    created: Vec<PathBuf>,
+}
+
+impl Drop for Placeholders {
+    fn drop(&mut self) {
+        for path in self.created.iter().rev() {
+            let outcome = if path.is_dir() {
+                fs::remove_dir(path)
+            } else {
+                fs::remove_file(path)
+            };
+            if let Err(e) = outcome {
+                log::warn!("Failed to remove placeholder {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+pub(super) fn synthesize(base_path: &Path, pattern: &str) -> Result<Placeholders, anyhow::Error> {
+    let matcher = Glob::new(pattern)
+        .with_context(|| format!("Invalid pattern for missing members: {:?}", pattern))?
+        .compile_matcher();
+    let mut placeholders = Placeholders { created: vec![] };
+
+    let root_manifest = base_path.join("Cargo.toml");
+    let Ok(contents) = fs::read_to_string(&root_manifest) else {
+        return Ok(placeholders);
+    };
+    let root_manifest: toml::Value = toml::from_str(&contents)
+        .with_context(|| format!("Failed to parse {:?}", root_manifest))?;
+    let Some(workspace) = root_manifest.get("workspace") else {
+        return Ok(placeholders);
+    };
+    let members = workspace
+        .get("members")
+        .and_then(|members| members.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|member| member.as_str());
+
+    for member in members {
+        // Glob members are expanded by cargo: they never point to a missing directory.
+        if member.contains(['*', '?', '[']) || !matcher.is_match(member) {
+            continue;
+        }
+        let member_path = base_path.join(member);
+        let manifest_path = member_path.join("Cargo.toml");
+        if manifest_path.exists() {
+            continue;
+        }
+
+        let name = package_name(workspace, member).unwrap_or_else(|| {
+            member_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| member.to_string())
+        });
+        log::warn!(
+            "Workspace member {:?} is missing, using the placeholder package {:?} instead.",
+            member,
+            name
+        );
+        let lib_path = member_path.join("src").join("lib.rs");
+        placeholders.create_dir_all(&member_path)?;
+        placeholders.write(
+            &manifest_path,
+            &format!(
+                "[package]\nname = \"{}\"\nversion = \"0.0.1\"\nedition = \"2018\"\n",
+                name
+            ),
+        )?;
+        if !lib_path.exists() {
+            placeholders.create_dir_all(lib_path.parent().unwrap())?;
+            placeholders.write(&lib_path, "")?;
+        }
+    }
+
+    Ok(placeholders)
+}
+
+/// Members are usually depended upon through `[workspace.dependencies]`, which tells us which
+/// name the placeholder package should have.
+fn package_name(workspace: &toml::Value, member: &str) -> Option<String> {
+    let member = Path::new(member);
+    workspace
+        .get("dependencies")?
+        .as_table()?
+        .iter()
+        .find_map(|(name, dependency)| {
+            let path = dependency.get("path")?.as_str()?;
+            if Path::new(path).components().ne(member.components()) {
+                return None;
+            }
+            let package = dependency.get("package").and_then(|p| p.as_str());
+            Some(package.unwrap_or(name).to_string())
+        })
+}
+
+impl Placeholders {
+    fn create_dir_all(&mut self, path: &Path) -> Result<(), anyhow::Error> {
+        let mut missing: Vec<_> = path.ancestors().take_while(|p| !p.exists()).collect();
+        missing.reverse();
+        for directory in missing {
+            fs::create_dir(directory)
+                .with_context(|| format!("Failed to create placeholder {:?}", directory))?;
+            self.created.push(directory.to_path_buf());
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, path: &Path, contents: &str) -> Result<(), anyhow::Error> {
+        fs::write(path, contents)
+            .with_context(|| format!("Failed to create placeholder {:?}", path))?;
+        self.created.push(path.to_path_buf());
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! Paths stored in a recipe use forward slashes, whatever the platform it was prepared on: a
+//! recipe prepared on Windows must be usable to cook in a Linux container, and vice versa.
+//!
+//! Use with `#[serde(with = "crate::skeleton::portable_path")]` on `PathBuf` fields.
+use serde::{Deserialize, Deserializer, Serializer};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+pub(crate) fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| serde::ser::Error::custom(format!("{:?} is not valid UTF-8", path)))?;
+    serializer.serialize_str(&to_forward_slashes(path))
+}
+
+/// Backslashes are translated too, to cook recipes prepared on Windows by versions of
+/// `cargo-chef` that stored native paths. Forward slashes are understood everywhere.
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    let path = String::deserialize(deserializer)?;
+    Ok(PathBuf::from(path.replace('\\', "/")))
+}
+
+/// Rewrite the paths written in a manifest (targets, build script, workspace members and path
+/// dependencies) with forward slashes.
+pub(super) fn normalize_manifest(manifest: &mut toml::Value) {
+    if MAIN_SEPARATOR == '/' {
+        return;
+    }
+    for dependency in super::external::path_dependencies(manifest) {
+        normalize(dependency.get_mut("path"));
+    }
+    let Some(manifest) = manifest.as_table_mut() else {
+        return;
+    };
+    for (key, value) in manifest.iter_mut() {
+        match key.as_str() {
+            "package" | "project" => {
+                normalize(value.get_mut("build"));
+                normalize(value.get_mut("workspace"));
+            }
+            "lib" => normalize(value.get_mut("path")),
+            "bin" | "example" | "test" | "bench" => {
+                for target in value.as_array_mut().into_iter().flatten() {
+                    normalize(target.get_mut("path"));
+                }
+            }
+            "workspace" => {
+                for key in ["members", "exclude", "default-members"] {
+                    let members = value
+                        .get_mut(key)
+                        .and_then(|members| members.as_array_mut());
+                    for member in members.into_iter().flatten() {
+                        normalize(Some(member));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Rewrite `path` with forward slashes, if it is a string (`build = false` is left alone).
+fn normalize(path: Option<&mut toml::Value>) {
+    if let Some(path) = path {
+        if let Some(normalized) = path.as_str().map(to_forward_slashes) {
+            *path = toml::Value::String(normalized);
+        }
+    }
+}
+
+fn to_forward_slashes(path: &str) -> String {
+    path.replace(MAIN_SEPARATOR, "/")
+}
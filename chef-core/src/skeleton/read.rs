@@ -0,0 +1,504 @@
+//! Logic to read all the files required to build a caching layer for a project.
+use super::{ParsedManifest, SkeletonFile};
+use crate::skeleton::target::{Target, TargetKind};
+use anyhow::Context;
+use cargo_manifest::{MaybeInherited, True};
+use cargo_metadata::semver::Version;
+use cargo_metadata::{Metadata, Package};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The toolchain file at the root of the project, if any, as rustup would pick it.
+pub(super) fn toolchain_file<P: AsRef<Path>>(
+    base_path: &P,
+) -> Result<Option<SkeletonFile>, anyhow::Error> {
+    let Some(name) = crate::toolchain::TOOLCHAIN_FILES
+        .iter()
+        .find(|file| base_path.as_ref().join(file).is_file())
+    else {
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(base_path.as_ref().join(name))
+        .with_context(|| format!("Failed to read the {} file.", name))?;
+    Ok(Some(SkeletonFile {
+        relative_path: PathBuf::from(name),
+        contents,
+        mode: None,
+    }))
+}
+
+/// The path of the cargo configuration file, relative to `base_path`.
+pub(super) fn config_path<P: AsRef<Path>>(base_path: &P) -> Option<PathBuf> {
+    // Given that we run primarily in Docker, assume to find config or config.toml at root level.
+    // We give priority to config over config.toml since this is cargo's default behavior.
+    ["config", "config.toml"]
+        .iter()
+        .map(|file| Path::new(".cargo").join(file))
+        .find(|path| base_path.as_ref().join(path).is_file())
+}
+
+pub(super) fn config<P: AsRef<Path>>(base_path: &P) -> Result<Option<String>, anyhow::Error> {
+    let Some(path) = config_path(base_path) else {
+        return Ok(None);
+    };
+    let config = fs::read_to_string(base_path.as_ref().join(path))
+        .map_err(|e| anyhow::Error::from(e).context("Failed to read .cargo/config.toml file."))?;
+    Ok(Some(without_tokens(config)?))
+}
+
+/// The configuration without the registry tokens it may hold, which have no place in a recipe
+/// (nor in the Docker layers it ends up in): `cook` must be given them through the environment.
+/// The file is kept as it is if there are none.
+fn without_tokens(config: String) -> Result<String, anyhow::Error> {
+    let mut parsed: toml::Table =
+        toml::from_str(&config).context("Failed to parse .cargo/config.toml file.")?;
+    let mut removed = vec![];
+    if let Some(registry) = parsed.get_mut("registry").and_then(|r| r.as_table_mut()) {
+        if registry.remove("token").is_some() {
+            removed.push("CARGO_REGISTRY_TOKEN".to_string());
+        }
+    }
+    if let Some(registries) = parsed.get_mut("registries").and_then(|r| r.as_table_mut()) {
+        for (name, registry) in registries.iter_mut() {
+            let Some(registry) = registry.as_table_mut() else {
+                continue;
+            };
+            if registry.remove("token").is_some() {
+                removed.push(format!(
+                    "CARGO_REGISTRIES_{}_TOKEN",
+                    name.to_uppercase().replace('-', "_")
+                ));
+            }
+        }
+    }
+    if removed.is_empty() {
+        return Ok(config);
+    }
+    log::warn!(
+        "Registry tokens are left out of the cargo configuration stored in the recipe: \
+        provide them to `cook` with {}.",
+        removed.join(", ")
+    );
+    Ok(toml::to_string(&parsed)?)
+}
+
+/// The settings that change how crates are compiled in the `cargo` configuration files outside
+/// of the project: those of its parent directories and of `CARGO_HOME`, which `cargo` reads too.
+/// They are merged as `cargo` does, the closest file taking precedence.
+pub(super) fn external_config<P: AsRef<Path>>(
+    base_path: &P,
+) -> Result<Option<String>, anyhow::Error> {
+    let base_path = fs::canonicalize(base_path.as_ref())
+        .with_context(|| format!("Failed to resolve {:?}", base_path.as_ref()))?;
+    let mut candidates: Vec<PathBuf> = base_path
+        .ancestors()
+        .skip(1)
+        .filter_map(|directory| config_path(&directory).map(|path| directory.join(path)))
+        .collect();
+    if let Some(cargo_home) = crate::cargo_home() {
+        candidates.extend(
+            ["config", "config.toml"]
+                .iter()
+                .map(|file| cargo_home.join(file))
+                .find(|path| path.is_file()),
+        );
+    }
+    // `CARGO_HOME` is often `~/.cargo`, which is also found among the parent directories.
+    let mut paths: Vec<PathBuf> = vec![];
+    for path in candidates {
+        let path = fs::canonicalize(&path).unwrap_or(path);
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    let mut merged = toml::Table::new();
+    for path in paths.iter().rev() {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the cargo configuration file {:?}", path))?;
+        let config: toml::Table = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse the cargo configuration file {:?}", path))?;
+        merge_config(&mut merged, cache_relevant_config(config));
+    }
+    if merged.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(toml::to_string(&merged)?))
+}
+
+/// The sections of a `cargo` configuration file that change the compiled artifacts: profiles,
+/// compiler flags, the target and linkers, and the environment of build scripts.
+fn cache_relevant_config(mut config: toml::Table) -> toml::Table {
+    let mut relevant = toml::Table::new();
+    for section in ["profile", "env", "unstable"] {
+        if let Some(value) = config.remove(section) {
+            relevant.insert(section.to_string(), value);
+        }
+    }
+    let keep = |table: Option<toml::Value>, keys: &[&str]| -> Option<toml::Table> {
+        let mut table = match table? {
+            toml::Value::Table(table) => table,
+            _ => return None,
+        };
+        table.retain(|key, _| keys.contains(&key));
+        (!table.is_empty()).then_some(table)
+    };
+    if let Some(build) = keep(
+        config.remove("build"),
+        &["rustflags", "rustdocflags", "target", "rustc"],
+    ) {
+        relevant.insert("build".to_string(), build.into());
+    }
+    if let Some(toml::Value::Table(targets)) = config.remove("target") {
+        let targets: toml::Table = targets
+            .into_iter()
+            .filter_map(|(target, settings)| {
+                keep(Some(settings), &["rustflags", "linker"]).map(|s| (target, s.into()))
+            })
+            .collect();
+        if !targets.is_empty() {
+            relevant.insert("target".to_string(), targets.into());
+        }
+    }
+    relevant
+}
+
+/// Merge `config` into `merged`, the former taking precedence: arrays are concatenated, as
+/// `cargo` does for flags.
+fn merge_config(merged: &mut toml::Table, config: toml::Table) {
+    for (key, value) in config {
+        match (merged.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(value)) => {
+                merge_config(existing, value)
+            }
+            (Some(toml::Value::Array(existing)), toml::Value::Array(value)) => {
+                existing.extend(value)
+            }
+            (_, value) => {
+                merged.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The roots of the workspaces nested in the one at `base_path`, relative to it: manifests
+/// with a `[workspace]` section of their own, which `cargo` never considers members of the
+/// outer workspace.
+///
+/// Only `scan_roots` are searched, if any. Hidden, `target` and `node_modules` directories
+/// never are, nor are symlinks to directories (e.g. `bazel-out`, which points to a large tree
+/// outside of the project).
+pub(super) fn nested_workspaces<P: AsRef<Path>>(
+    base_path: &P,
+    scan_roots: &[PathBuf],
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut nested = vec![];
+    let mut queue = if scan_roots.is_empty() {
+        vec![PathBuf::new()]
+    } else {
+        scan_roots.to_vec()
+    };
+    while let Some(directory) = queue.pop() {
+        let absolute_path = base_path.as_ref().join(&directory);
+        let manifest = absolute_path.join("Cargo.toml");
+        if directory != Path::new("") && manifest.is_file() {
+            let contents: toml::Value = toml::from_str(&fs::read_to_string(&manifest)?)
+                .with_context(|| format!("Failed to parse {:?}", manifest))?;
+            if contents.get("workspace").is_some() {
+                nested.push(directory.clone());
+            }
+        }
+        let entries = fs::read_dir(&absolute_path)
+            .with_context(|| format!("Failed to scan {:?}", absolute_path))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            if entry.file_type()?.is_dir()
+                && !name.to_string_lossy().starts_with('.')
+                && name != "target"
+                && name != "node_modules"
+            {
+                queue.push(directory.join(&name));
+            }
+        }
+    }
+    nested.sort();
+    nested.dedup();
+    Ok(nested)
+}
+
+/// The targets and the version of every workspace member, keyed by the absolute path of its
+/// manifest.
+///
+/// We do not expand `members` globs, nor apply `exclude`, on our own: `cargo metadata` tells
+/// us which packages belong to the workspace, therefore the recipe always agrees with `cargo`
+/// (nested workspaces, implicit members via path dependencies, trailing slashes, etc.).
+pub(super) fn workspace_packages<P: AsRef<Path>>(
+    base_path: &P,
+    metadata: Metadata,
+) -> BTreeMap<PathBuf, (BTreeSet<Target>, Version)> {
+    let mut packages: BTreeMap<_, _> = metadata
+        .workspace_packages()
+        .iter()
+        .copied()
+        .chain(metadata.root_package())
+        .map(|p| {
+            (
+                p.manifest_path.clone().into_std_path_buf(),
+                (gather_targets(p), p.version.clone()),
+            )
+        })
+        .collect();
+
+    if metadata.root_package().is_none() {
+        // At the root, there might be a Cargo.toml manifest with a [workspace] section.
+        // However, if this root manifest doesn't contain [package], it is not considered a package
+        // by cargo metadata. Therefore, we have to add it manually.
+        // Workspaces currently cannot be nested, so this should only happen at the root.
+        packages.insert(
+            base_path.as_ref().join("Cargo.toml"),
+            (Default::default(), Version::new(0, 0, 1)),
+        );
+    }
+    packages
+}
+
+/// The manifests of all workspace `packages`, as computed by `workspace_packages`.
+pub(super) fn manifests<P: AsRef<Path>>(
+    base_path: &P,
+    packages: BTreeMap<PathBuf, (BTreeSet<Target>, Version)>,
+) -> Result<Vec<ParsedManifest>, anyhow::Error> {
+    let mut manifests = vec![];
+    for (absolute_path, (targets, version)) in packages {
+        let contents = fs::read_to_string(&absolute_path)?;
+
+        let mut parsed = cargo_manifest::Manifest::from_str(&contents)?;
+        // Required to detect bin/libs when the related section is omitted from the manifest
+        parsed.complete_from_path(&absolute_path)?;
+
+        if let Some(package) = &mut parsed.package {
+            if let MaybeInherited::Inherited { workspace: True } = package.version {
+                package.version = MaybeInherited::Local(version.to_string());
+            }
+        }
+
+        let mut intermediate = toml::Value::try_from(parsed)?;
+        let original: toml::Value = toml::from_str(&contents)?;
+        restore_artifact_dependency_keys(&original, &mut intermediate);
+        restore_package_keys(&original, &mut intermediate);
+        restore_workspace_chef_metadata(&original, &mut intermediate);
+        strip_packaging_fields(&mut intermediate);
+
+        // Specifically, toml gives no guarantees to the ordering of the auto binaries
+        // in its results. We will manually sort these to ensure that the output
+        // manifest will match.
+        let bins = intermediate
+            .get_mut("bin")
+            .and_then(|bins| bins.as_array_mut());
+        if let Some(bins) = bins {
+            bins.sort_by(|bin_a, bin_b| {
+                let bin_a_path = bin_a
+                    .as_table()
+                    .and_then(|table| table.get("path").or_else(|| table.get("name")))
+                    .and_then(|path| path.as_str())
+                    .unwrap();
+                let bin_b_path = bin_b
+                    .as_table()
+                    .and_then(|table| table.get("path").or_else(|| table.get("name")))
+                    .and_then(|path| path.as_str())
+                    .unwrap();
+                bin_a_path.cmp(bin_b_path)
+            });
+        }
+
+        let relative_path = pathdiff::diff_paths(&absolute_path, base_path).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Failed to compute relative path of manifest {:?}",
+                &absolute_path
+            )
+        })?;
+
+        manifests.push(ParsedManifest {
+            relative_path,
+            contents: intermediate,
+            targets: targets.into_iter().collect(),
+        });
+    }
+
+    Ok(manifests)
+}
+
+/// `include` and `exclude` only select the files that go into the `.crate` archive (and that
+/// `cargo` watches for changes when a build script does not say otherwise): they refer to
+/// sources and assets that are not part of the skeleton, and editing them should not
+/// invalidate the recipe.
+fn strip_packaging_fields(manifest: &mut toml::Value) {
+    const PACKAGING_FIELDS: [&str; 2] = ["include", "exclude"];
+
+    if let Some(package) = manifest.get_mut("package").and_then(|p| p.as_table_mut()) {
+        package.retain(|key, _| !PACKAGING_FIELDS.contains(&key));
+    }
+    // Members can inherit them with `include.workspace = true`, which is gone as well.
+    if let Some(package) = manifest
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.get_mut("package"))
+        .and_then(|p| p.as_table_mut())
+    {
+        package.retain(|key, _| !PACKAGING_FIELDS.contains(&key));
+    }
+}
+
+/// Package keys that `cargo_manifest` does not know about, and would silently drop when
+/// round-tripping the manifest through it: it only understands `default_run`, the deprecated
+/// spelling of `default-run`, and predates `autolib`.
+fn restore_package_keys(original: &toml::Value, intermediate: &mut toml::Value) {
+    const PACKAGE_KEYS: [&str; 2] = ["default-run", "autolib"];
+
+    let (Some(original_package), Some(package)) = (
+        original.get("package").and_then(|p| p.as_table()),
+        intermediate
+            .get_mut("package")
+            .and_then(|p| p.as_table_mut()),
+    ) else {
+        return;
+    };
+    for key in PACKAGE_KEYS {
+        if let Some(value) = original_package.get(key) {
+            package.insert(key.to_string(), value.clone());
+        }
+    }
+    // `complete_from_path` declares `src/lib.rs` as the library whenever it exists on disk,
+    // while `cargo` ignores it if `autolib = false`.
+    let autolib = original_package.get("autolib").and_then(|a| a.as_bool());
+    if autolib == Some(false) && original.get("lib").is_none() {
+        if let Some(manifest) = intermediate.as_table_mut() {
+            manifest.remove("lib");
+        }
+    }
+}
+
+/// `cargo_manifest` drops `workspace.metadata` altogether. We only bring back the settings of
+/// `cargo-chef` (e.g. `workspace.metadata.chef.dummy`): the metadata of other tools does not
+/// affect the build, and has never invalidated the recipe.
+fn restore_workspace_chef_metadata(original: &toml::Value, intermediate: &mut toml::Value) {
+    let Some(chef) = original
+        .get("workspace")
+        .and_then(|w| w.get("metadata"))
+        .and_then(|m| m.get("chef"))
+    else {
+        return;
+    };
+    if let Some(workspace) = intermediate
+        .get_mut("workspace")
+        .and_then(|w| w.as_table_mut())
+    {
+        let mut metadata = toml::Table::new();
+        metadata.insert("chef".into(), chef.clone());
+        workspace.insert("metadata".into(), metadata.into());
+    }
+}
+
+/// Artifact dependencies (`-Z bindeps`) are declared using keys that `cargo_manifest` does not
+/// know about - they would be silently dropped when round-tripping the manifest through it.
+/// We copy them over from the original manifest.
+fn restore_artifact_dependency_keys(original: &toml::Value, intermediate: &mut toml::Value) {
+    const ARTIFACT_KEYS: [&str; 3] = ["artifact", "target", "lib"];
+
+    fn _restore(original: &toml::Value, intermediate: &mut toml::Value) {
+        for dependency_key in ["dependencies", "dev-dependencies", "build-dependencies"] {
+            let (Some(original), Some(intermediate)) = (
+                original.get(dependency_key).and_then(|d| d.as_table()),
+                intermediate
+                    .get_mut(dependency_key)
+                    .and_then(|d| d.as_table_mut()),
+            ) else {
+                continue;
+            };
+            for (name, dependency) in original {
+                let Some(restored) = intermediate.get_mut(name).and_then(|d| d.as_table_mut())
+                else {
+                    continue;
+                };
+                for key in ARTIFACT_KEYS {
+                    if let Some(value) = dependency.get(key) {
+                        restored.insert(key.to_string(), value.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    // Same places as the ones that are masked, see `version_masking`.
+    _restore(original, intermediate);
+    if let Some(targets) = original.get("target").and_then(|t| t.as_table()) {
+        for (target, target_config) in targets {
+            if let Some(restored) = intermediate
+                .get_mut("target")
+                .and_then(|t| t.get_mut(target))
+            {
+                _restore(target_config, restored);
+            }
+        }
+    }
+    if let (Some(original), Some(restored)) =
+        (original.get("workspace"), intermediate.get_mut("workspace"))
+    {
+        _restore(original, restored);
+    }
+}
+
+fn gather_targets(package: &Package) -> BTreeSet<Target> {
+    let manifest_path = package.manifest_path.clone().into_std_path_buf();
+    let root_dir = manifest_path.parent().unwrap();
+    package
+        .targets
+        .iter()
+        .map(|target| {
+            let relative_path = pathdiff::diff_paths(&target.src_path, root_dir).unwrap();
+            let kind = if target.is_bench() {
+                TargetKind::Bench
+            } else if target.is_example() {
+                TargetKind::Example
+            } else if target.is_test() {
+                TargetKind::Test
+            } else if target.is_bin() {
+                TargetKind::Bin
+            } else if target.is_custom_build() {
+                TargetKind::BuildScript
+            } else {
+                // If a library has custom crate type (e.g. "cdylib"), it's kind will be "cdylib"
+                // instead of just "lib". Therefore, we assume that this target is a library.
+                TargetKind::Lib {
+                    is_proc_macro: target
+                        .crate_types
+                        .iter()
+                        .any(|t| t.as_str() == "proc-macro"),
+                }
+            };
+
+            Target {
+                path: relative_path,
+                kind,
+                name: target.name.clone(),
+            }
+        })
+        .collect()
+}
+
+pub(super) fn lockfile<P: AsRef<Path>>(
+    base_path: &P,
+) -> Result<Option<toml::Value>, anyhow::Error> {
+    match fs::read_to_string(base_path.as_ref().join("Cargo.lock")) {
+        Ok(lock) => {
+            let lock: toml::Value = toml::from_str(&lock)?;
+            Ok(Some(lock))
+        }
+        Err(e) => {
+            if std::io::ErrorKind::NotFound != e.kind() {
+                return Err(anyhow::Error::from(e).context("Failed to read Cargo.lock file."));
+            }
+            Ok(None)
+        }
+    }
+}
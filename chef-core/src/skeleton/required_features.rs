@@ -0,0 +1,91 @@
+//! The `required-features` of binary targets. `cargo build` leaves out the targets whose
+//! required features are not enabled, but fails if one of them is asked for with `--bin`: when
+//! cooking, the binary is left out instead, and the rest of its package is cooked.
+use super::Skeleton;
+use crate::{AllFeatures, CookArgs, DefaultFeatures};
+use std::collections::BTreeSet;
+
+/// The package `binary` belongs to and the features it requires that `args` do not enable,
+/// `None` if they are all enabled (or if there is no such binary).
+pub(super) fn missing(
+    skeleton: &Skeleton,
+    binary: &str,
+    args: &CookArgs,
+) -> Result<Option<(String, Vec<String>)>, anyhow::Error> {
+    if args.all_features == AllFeatures::Enabled {
+        return Ok(None);
+    }
+    for manifest in &skeleton.manifests {
+        let contents: toml::Value = toml::from_str(&manifest.contents)?;
+        let Some(name) = contents
+            .get("package")
+            .and_then(|package| package.get("name"))
+            .and_then(|name| name.as_str())
+        else {
+            continue;
+        };
+        let Some(target) = contents
+            .get("bin")
+            .and_then(|bins| bins.as_array())
+            .into_iter()
+            .flatten()
+            .find(|bin| bin.get("name").and_then(|n| n.as_str()) == Some(binary))
+        else {
+            continue;
+        };
+        let required: Vec<&str> = target
+            .get("required-features")
+            .and_then(|features| features.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|feature| feature.as_str())
+            .collect();
+        let enabled = enabled_features(&contents, name, args);
+        let missing: Vec<String> = required
+            .into_iter()
+            .filter(|feature| !enabled.contains(*feature))
+            .map(str::to_string)
+            .collect();
+        return Ok((!missing.is_empty()).then(|| (name.to_string(), missing)));
+    }
+    Ok(None)
+}
+
+/// The features of `package` that `args` enable, along with the features of its dependencies
+/// they forward to (as `dependency/feature`).
+fn enabled_features(manifest: &toml::Value, package: &str, args: &CookArgs) -> BTreeSet<String> {
+    let mut queue: Vec<String> = args
+        .features
+        .iter()
+        .flatten()
+        .flat_map(|features| features.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|feature| !feature.is_empty())
+        .map(|feature| {
+            feature
+                .strip_prefix(package)
+                .and_then(|feature| feature.strip_prefix('/'))
+                .unwrap_or(feature)
+                .to_string()
+        })
+        .collect();
+    if args.default_features == DefaultFeatures::Enabled {
+        queue.push("default".to_string());
+    }
+    let table = manifest.get("features").and_then(|f| f.as_table());
+    let mut enabled = BTreeSet::new();
+    while let Some(feature) = queue.pop() {
+        if !enabled.insert(feature.clone()) || feature.contains('/') {
+            continue;
+        }
+        let forwarded = table
+            .and_then(|table| table.get(&feature))
+            .and_then(|values| values.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|value| value.as_str())
+            .filter(|value| !value.starts_with("dep:"))
+            .map(|value| value.replacen("?/", "/", 1));
+        queue.extend(forwarded);
+    }
+    enabled
+}
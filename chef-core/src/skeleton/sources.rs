@@ -0,0 +1,173 @@
+//! Workspace members whose real sources are kept in the recipe, instead of dummies: those
+//! passed to `prepare --keep-source`, or that opt in from their manifest.
+//!
+//! ```toml
+//! [package.metadata.chef]
+//! keep-source = true
+//! ```
+//!
+//! Proc-macro and code generation crates are compiled as part of the dependency graph of the
+//! other members: with a dummy in their place, whatever depends on them is cooked against the
+//! dummy and built again in the final build. Every file of a kept package is carried over
+//! (hidden files, `target` directories and nested packages excepted), its compilation units are
+//! not cleaned up after cooking and, as for kept build scripts, its version is not masked:
+//! changing its sources invalidates the recipe.
+use super::build_scripts::{read_file, unmask_versions};
+use super::target::TargetKind;
+use super::{Manifest, Skeleton};
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+pub(super) fn keep(
+    skeleton: &Skeleton,
+    base_path: &Path,
+    packages: &[String],
+) -> Result<Skeleton, anyhow::Error> {
+    let parsed = skeleton
+        .manifests
+        .iter()
+        .map(|manifest| Ok((manifest, toml::from_str(&manifest.contents)?)))
+        .collect::<Result<Vec<(&Manifest, toml::Value)>, anyhow::Error>>()?;
+    let members: Vec<&str> = parsed
+        .iter()
+        .filter_map(|(_, contents)| package_name(contents))
+        .collect();
+
+    let mut files = skeleton.files.clone();
+    let mut kept_packages = vec![];
+    let mut unknown_packages: Vec<&String> = packages.iter().collect();
+    for (manifest, contents) in &parsed {
+        let Some(name) = package_name(contents) else {
+            continue;
+        };
+        unknown_packages.retain(|p| p.as_str() != name);
+        let opted_in = contents
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("chef"))
+            .and_then(|chef| chef.get("keep-source"))
+            .and_then(|keep| keep.as_bool())
+            .unwrap_or(false);
+        if !opted_in && !packages.iter().any(|p| p == name) {
+            continue;
+        }
+        kept_packages.push(name.to_string());
+
+        for path in package_files(base_path, directory_of(manifest))? {
+            if path != manifest.relative_path && path != Path::new("Cargo.lock") {
+                files.push(read_file(base_path, &path)?);
+            }
+        }
+    }
+    if let Some(package) = unknown_packages.first() {
+        return Err(anyhow::anyhow!(
+            "There is no package named `{}` in the workspace.",
+            package
+        ));
+    }
+    // The members a kept package depends on must be real as well, or it does not compile.
+    for (_, contents) in &parsed {
+        let Some(name) = package_name(contents) else {
+            continue;
+        };
+        if !kept_packages.iter().any(|p| p == name) {
+            continue;
+        }
+        for dependency in member_dependencies(contents, &members) {
+            if !kept_packages.iter().any(|p| p == dependency) {
+                return Err(anyhow::anyhow!(
+                    "`{}` depends on the workspace member `{}`: its sources must be kept as well.",
+                    name,
+                    dependency
+                ));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    files.dedup_by(|a, b| a.relative_path == b.relative_path);
+
+    let mut skeleton = Skeleton {
+        files,
+        ..skeleton.clone()
+    };
+    unmask_versions(&mut skeleton, base_path, &kept_packages)?;
+    Ok(skeleton)
+}
+
+/// Whether the sources of `manifest` are among the files kept in `skeleton`.
+pub(super) fn is_kept(skeleton: &Skeleton, manifest: &Manifest) -> bool {
+    let directory = directory_of(manifest);
+    manifest
+        .targets
+        .iter()
+        .filter(|target| target.kind != TargetKind::BuildScript)
+        .any(|target| {
+            let path = directory.join(&target.path);
+            skeleton.files.iter().any(|file| file.relative_path == path)
+        })
+}
+
+fn package_name(manifest: &toml::Value) -> Option<&str> {
+    manifest.get("package")?.get("name")?.as_str()
+}
+
+/// The workspace members among the regular and build dependencies of `manifest`.
+fn member_dependencies<'a>(manifest: &'a toml::Value, members: &[&str]) -> Vec<&'a str> {
+    let targets = manifest
+        .get("target")
+        .and_then(|targets| targets.as_table())
+        .into_iter()
+        .flat_map(|targets| targets.values());
+    std::iter::once(manifest)
+        .chain(targets)
+        .flat_map(|section| {
+            ["dependencies", "build-dependencies"]
+                .iter()
+                .filter_map(move |key| section.get(key).and_then(|d| d.as_table()))
+        })
+        .flatten()
+        .filter(|(_, dependency)| {
+            dependency.get("path").is_some() || dependency.get("workspace").is_some()
+        })
+        .map(|(key, dependency)| {
+            dependency
+                .get("package")
+                .and_then(|package| package.as_str())
+                .unwrap_or(key)
+        })
+        .filter(|name| members.contains(name))
+        .collect()
+}
+
+/// The files, relative to `base_path`, of the package in `directory`: hidden files, `target`
+/// directories and the directories of nested packages are left out.
+fn package_files(base_path: &Path, directory: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut files = vec![];
+    let mut queue = vec![directory.to_path_buf()];
+    while let Some(relative_directory) = queue.pop() {
+        let absolute_directory = base_path.join(&relative_directory);
+        for entry in std::fs::read_dir(&absolute_directory)
+            .with_context(|| format!("Failed to scan {:?}", absolute_directory))?
+        {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            if file_name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = relative_directory.join(&file_name);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if file_name != "target" && !entry.path().join("Cargo.toml").exists() {
+                    queue.push(path);
+                }
+            } else if file_type.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn directory_of(manifest: &Manifest) -> &Path {
+    manifest.relative_path.parent().unwrap_or(Path::new(""))
+}
@@ -0,0 +1,468 @@
+//! Logic to restrict the skeleton of a workspace to some of its members, keeping only the
+//! manifests of the members and of their (transitive) path dependencies.
+use super::{declared_dependencies, normalize, Manifest, Skeleton, TargetKind};
+use anyhow::Context;
+use globset::Glob;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// The packages of a skeleton, by directory relative to the project root.
+struct Packages {
+    packages: BTreeMap<PathBuf, (String, toml::Value)>,
+    workspace_dependencies: Option<toml::value::Table>,
+}
+
+pub(super) fn split_per_member(
+    skeleton: &Skeleton,
+) -> Result<Vec<(String, Skeleton)>, anyhow::Error> {
+    let packages = Packages::new(skeleton)?;
+    packages
+        .packages
+        .iter()
+        .map(|(member, (name, _))| {
+            let members = [member.clone()];
+            Ok((name.clone(), restrict(skeleton, &packages, &members, true)?))
+        })
+        .collect()
+}
+
+/// Restrict the skeleton to what is needed to build `selection`, which is either the name of
+/// a binary, the name of a package or the path of a workspace member. Dev-dependencies are
+/// left out.
+pub(super) fn select(skeleton: &Skeleton, selection: &str) -> Result<Skeleton, anyhow::Error> {
+    let packages = Packages::new(skeleton)?;
+    let binary_owner = skeleton.manifests.iter().find(|manifest| {
+        manifest
+            .targets
+            .iter()
+            .any(|target| target.kind == TargetKind::Bin && target.name == selection)
+    });
+    let member = match binary_owner {
+        Some(manifest) => directory_of(manifest).to_path_buf(),
+        None => packages
+            .packages
+            .iter()
+            .find(|(_, (name, _))| name == selection)
+            .map(|(directory, _)| directory.clone())
+            .or_else(|| normalize(Path::new(selection)))
+            .filter(|directory| packages.packages.contains_key(directory))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "There is no binary, package or workspace member named `{}`.",
+                    selection
+                )
+            })?,
+    };
+    restrict(skeleton, &packages, &[member], false)
+}
+
+/// Restrict the skeleton to the packages for which `keep` returns `true` (by name) and to
+/// their path dependencies. Returns the skeleton as it is if no package is kept.
+pub(super) fn keep_packages(
+    skeleton: &Skeleton,
+    keep: impl Fn(&str) -> bool,
+) -> Result<Skeleton, anyhow::Error> {
+    let packages = Packages::new(skeleton)?;
+    let members: Vec<PathBuf> = packages
+        .packages
+        .iter()
+        .filter(|(_, (name, _))| keep(name))
+        .map(|(directory, _)| directory.clone())
+        .collect();
+    if members.is_empty() {
+        return Ok(skeleton.clone());
+    }
+    restrict(skeleton, &packages, &members, true)
+}
+
+pub(super) fn keep_default_members(skeleton: &Skeleton) -> Result<Skeleton, anyhow::Error> {
+    let packages = Packages::new(skeleton)?;
+    let Some(root) = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| directory_of(manifest) == Path::new(""))
+    else {
+        return Ok(skeleton.clone());
+    };
+    let contents: toml::Value = toml::from_str(&root.contents)?;
+    let members: Vec<PathBuf> = contents
+        .get("workspace")
+        .and_then(|workspace| workspace.get("default-members"))
+        .and_then(|members| members.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|member| member.as_str())
+        .filter_map(|member| normalize(Path::new(member)))
+        .filter(|member| packages.packages.contains_key(member))
+        .collect();
+    if members.is_empty() {
+        return Ok(skeleton.clone());
+    }
+    restrict(skeleton, &packages, &members, true)
+}
+
+/// Remove the workspace members matching any of `patterns` (package names or member paths,
+/// globs allowed). They are added to `workspace.exclude`, kept as path dependencies if other
+/// members depend on them, and whatever only they depend on is pruned from the lockfile.
+pub(super) fn exclude(skeleton: &Skeleton, patterns: &[String]) -> Result<Skeleton, anyhow::Error> {
+    let packages = Packages::new(skeleton)?;
+    let mut excluded = BTreeSet::new();
+    for pattern in patterns {
+        let matcher = Glob::new(pattern)
+            .with_context(|| format!("`{}` is not a valid glob.", pattern))?
+            .compile_matcher();
+        let matches: Vec<&PathBuf> = packages
+            .packages
+            .iter()
+            .filter(|(directory, (name, _))| matcher.is_match(name) || matcher.is_match(directory))
+            .map(|(directory, _)| directory)
+            .collect();
+        if matches.is_empty() {
+            anyhow::bail!("`{}` does not match any workspace member.", pattern);
+        }
+        excluded.extend(matches);
+    }
+    let members: Vec<PathBuf> = packages
+        .packages
+        .keys()
+        .filter(|directory| !excluded.contains(directory))
+        .cloned()
+        .collect();
+    if members.is_empty() {
+        anyhow::bail!("Every workspace member is excluded.");
+    }
+
+    let mut restricted = restrict(skeleton, &packages, &members, true)?;
+    if let Some(root) = restricted
+        .manifests
+        .iter_mut()
+        .find(|manifest| directory_of(manifest) == Path::new(""))
+    {
+        let mut contents: toml::Value = toml::from_str(&root.contents)?;
+        if let Some(workspace) = contents
+            .get_mut("workspace")
+            .and_then(|workspace| workspace.as_table_mut())
+        {
+            let exclude = workspace
+                .entry("exclude")
+                .or_insert_with(|| toml::Value::Array(vec![]));
+            if let Some(exclude) = exclude.as_array_mut() {
+                exclude.extend(
+                    excluded
+                        .iter()
+                        .map(|directory| toml::Value::String(directory.to_string_lossy().into())),
+                );
+            }
+        }
+        root.contents = toml::to_string(&contents)?;
+    }
+    let roots: BTreeSet<String> = Packages::new(&restricted)?
+        .packages
+        .into_values()
+        .map(|(name, _)| name)
+        .collect();
+    if let Some(lock_file) = &restricted.lock_file {
+        let mut lock_file: toml::Value = toml::from_str(lock_file)?;
+        prune_lock_file(&mut lock_file, &roots);
+        restricted.lock_file = Some(toml::to_string(&lock_file)?);
+    }
+    Ok(restricted)
+}
+
+impl Packages {
+    fn new(skeleton: &Skeleton) -> Result<Self, anyhow::Error> {
+        let mut packages = BTreeMap::new();
+        let mut workspace_dependencies = None;
+        for manifest in &skeleton.manifests {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            let directory = directory_of(manifest).to_path_buf();
+            if directory == Path::new("") {
+                workspace_dependencies = contents
+                    .get("workspace")
+                    .and_then(|workspace| workspace.get("dependencies"))
+                    .and_then(|dependencies| dependencies.as_table())
+                    .cloned();
+            }
+            if let Some(name) = contents
+                .get("package")
+                .and_then(|package| package.get("name"))
+                .and_then(|name| name.as_str())
+            {
+                packages.insert(directory, (name.to_string(), contents));
+            }
+        }
+        Ok(Packages {
+            packages,
+            workspace_dependencies,
+        })
+    }
+
+    /// `member` and the packages it depends on through path dependencies, transitively.
+    fn closure(&self, member: &Path, dev_dependencies: bool) -> BTreeSet<PathBuf> {
+        let mut closure = BTreeSet::new();
+        let mut queue = vec![member.to_path_buf()];
+        while let Some(directory) = queue.pop() {
+            if !closure.insert(directory.clone()) {
+                continue;
+            }
+            let Some((_, contents)) = self.packages.get(&directory) else {
+                continue;
+            };
+            let mut contents = contents.clone();
+            if !dev_dependencies {
+                remove_dev_dependencies(&mut contents);
+            }
+            for (name, dependency) in declared_dependencies(&contents) {
+                let inherited = dependency.get("workspace").and_then(|w| w.as_bool()) == Some(true);
+                let path = if inherited {
+                    self.workspace_dependencies
+                        .as_ref()
+                        .and_then(|dependencies| dependencies.get(name))
+                        .and_then(|dependency| dependency.get("path"))
+                        .and_then(|path| path.as_str())
+                        .and_then(|path| normalize(Path::new(path)))
+                } else {
+                    dependency
+                        .get("path")
+                        .and_then(|path| path.as_str())
+                        .and_then(|path| normalize(&directory.join(path)))
+                };
+                queue.extend(path);
+            }
+        }
+        closure
+    }
+}
+
+fn restrict(
+    skeleton: &Skeleton,
+    packages: &Packages,
+    members: &[PathBuf],
+    dev_dependencies: bool,
+) -> Result<Skeleton, anyhow::Error> {
+    let closure: BTreeSet<PathBuf> = members
+        .iter()
+        .flat_map(|member| packages.closure(member, dev_dependencies))
+        .collect();
+    let mut manifests = vec![];
+    for manifest in &skeleton.manifests {
+        let directory = directory_of(manifest);
+        if directory == Path::new("") || closure.contains(directory) {
+            let mut contents: toml::Value = toml::from_str(&manifest.contents)?;
+            if directory == Path::new("") {
+                restrict_members(&mut contents, members);
+            }
+            if !dev_dependencies {
+                remove_dev_dependencies(&mut contents);
+            }
+            manifests.push(Manifest {
+                relative_path: manifest.relative_path.clone(),
+                contents: toml::to_string(&contents)?,
+                targets: manifest.targets.clone(),
+            });
+        }
+    }
+    // Kept files belong to the package they are nested the deepest in.
+    let files = skeleton
+        .files
+        .iter()
+        .filter(|file| {
+            let owner = skeleton
+                .manifests
+                .iter()
+                .filter(|manifest| file.relative_path.starts_with(directory_of(manifest)))
+                .max_by_key(|manifest| directory_of(manifest).components().count());
+            owner.is_some_and(|owner| {
+                manifests
+                    .iter()
+                    .any(|manifest| manifest.relative_path == owner.relative_path)
+            })
+        })
+        .cloned()
+        .collect();
+    Ok(Skeleton {
+        manifests,
+        config_file: skeleton.config_file.clone(),
+        external_config: skeleton.external_config.clone(),
+        lock_file: skeleton.lock_file.clone(),
+        toolchain_file: skeleton.toolchain_file.clone(),
+        vendored_sources: skeleton.vendored_sources.clone(),
+        files,
+    })
+}
+
+/// Restrict the members of the workspace to `members`: their path dependencies are picked up
+/// by `cargo` on their own.
+fn restrict_members(contents: &mut toml::Value, members: &[PathBuf]) {
+    if let Some(workspace) = contents
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.as_table_mut())
+    {
+        let members = members
+            .iter()
+            .map(|member| {
+                let member = if member == Path::new("") {
+                    Path::new(".")
+                } else {
+                    member
+                };
+                toml::Value::String(member.to_string_lossy().into_owned())
+            })
+            .collect::<Vec<_>>();
+        // Default members might have been pruned away.
+        let default_members: Vec<toml::Value> = workspace
+            .get("default-members")
+            .and_then(|default_members| default_members.as_array())
+            .into_iter()
+            .flatten()
+            .filter(|default_member| {
+                let path = default_member
+                    .as_str()
+                    .and_then(|p| normalize(Path::new(p)));
+                members
+                    .iter()
+                    .any(|member| normalize(Path::new(member.as_str().unwrap_or_default())) == path)
+            })
+            .cloned()
+            .collect();
+        if default_members.is_empty() {
+            workspace.remove("default-members");
+        } else {
+            workspace.insert(
+                "default-members".to_string(),
+                toml::Value::Array(default_members),
+            );
+        }
+        workspace.insert("members".to_string(), toml::Value::Array(members));
+    }
+}
+
+/// Remove from the lockfile the packages that cannot be reached from the local packages named
+/// `roots`.
+fn prune_lock_file(lock_file: &mut toml::Value, roots: &BTreeSet<String>) {
+    let Some(packages) = lock_file
+        .get_mut("package")
+        .and_then(|packages| packages.as_array_mut())
+    else {
+        return;
+    };
+    let field = |package: &toml::Value, key: &str| {
+        package
+            .get(key)
+            .and_then(|value| value.as_str())
+            .map(str::to_string)
+    };
+    let mut reachable = BTreeSet::new();
+    let mut queue: Vec<usize> = packages
+        .iter()
+        .enumerate()
+        .filter(|(_, package)| {
+            package.get("source").is_none()
+                && field(package, "name").is_some_and(|name| roots.contains(&name))
+        })
+        .map(|(index, _)| index)
+        .collect();
+    while let Some(index) = queue.pop() {
+        if !reachable.insert(index) {
+            continue;
+        }
+        let dependencies = packages[index]
+            .get("dependencies")
+            .and_then(|dependencies| dependencies.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|dependency| dependency.as_str());
+        for dependency in dependencies {
+            // Dependencies are written as `name`, `name version` or `name version (source)`,
+            // with as little as is needed to be unambiguous.
+            let mut parts = dependency.splitn(3, ' ');
+            let name = parts.next();
+            let version = parts.next();
+            let source = parts
+                .next()
+                .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+            queue.extend(
+                packages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, package)| {
+                        field(package, "name").as_deref() == name
+                            && (version.is_none()
+                                || field(package, "version").as_deref() == version)
+                            && (source.is_none() || field(package, "source").as_deref() == source)
+                    })
+                    .map(|(index, _)| index),
+            );
+        }
+    }
+    let mut index = 0;
+    packages.retain(|_| {
+        index += 1;
+        reachable.contains(&(index - 1))
+    });
+}
+
+pub(super) fn remove_dev_dependencies(contents: &mut toml::Value) {
+    if let Some(table) = contents.as_table_mut() {
+        table.remove("dev-dependencies");
+        table.remove("dev_dependencies");
+    }
+    if let Some(targets) = contents.get_mut("target").and_then(|t| t.as_table_mut()) {
+        for (_, target) in targets.iter_mut() {
+            let Some(target) = target.as_table_mut() else {
+                continue;
+            };
+            target.remove("dev-dependencies");
+            target.remove("dev_dependencies");
+        }
+    }
+    remove_dangling_feature_references(contents);
+}
+
+/// Features can enable features of dev-dependencies (`dev-dependency/feature`): once they are
+/// gone, `cargo` refuses the manifest. Such entries are dropped, the rest of the features table
+/// (`dep:` and weak `dependency?/feature` entries included) is left as it is.
+fn remove_dangling_feature_references(contents: &mut toml::Value) {
+    let targets = contents
+        .get("target")
+        .and_then(|targets| targets.as_table())
+        .into_iter()
+        .flat_map(|targets| targets.values());
+    let declared: BTreeSet<String> = std::iter::once(&*contents)
+        .chain(targets)
+        .flat_map(|section| {
+            ["dependencies", "build-dependencies", "build_dependencies"]
+                .iter()
+                .filter_map(move |key| section.get(key).and_then(|d| d.as_table()))
+        })
+        .flat_map(|dependencies| dependencies.keys().cloned())
+        .collect();
+    let Some(features) = contents
+        .get_mut("features")
+        .and_then(|features| features.as_table_mut())
+    else {
+        return;
+    };
+    for (_, enables) in features.iter_mut() {
+        let Some(enables) = enables.as_array_mut() else {
+            continue;
+        };
+        enables.retain(|value| {
+            let Some(value) = value.as_str() else {
+                return true;
+            };
+            let dependency = match value.strip_prefix("dep:") {
+                Some(dependency) => dependency,
+                None => match value.split_once('/') {
+                    Some((dependency, _)) => dependency.trim_end_matches('?'),
+                    None => return true,
+                },
+            };
+            declared.contains(dependency)
+        });
+    }
+}
+
+fn directory_of(manifest: &Manifest) -> &Path {
+    manifest.relative_path.parent().unwrap_or(Path::new(""))
+}
@@ -0,0 +1,406 @@
+//! Discovery of the workspace members without `cargo metadata`, for `prepare` to run where there
+//! is no Rust toolchain: e.g. a planner stage built from `scratch`, holding nothing but the
+//! (static) chef binary and the manifests.
+//!
+//! It follows the rules of `cargo` for the layouts `cargo metadata` would otherwise resolve for
+//! us: `members` globs and `exclude`, path dependencies living in the workspace directory,
+//! automatic targets and versions inherited from `workspace.package`.
+use crate::skeleton::target::{Target, TargetKind};
+use anyhow::Context;
+use cargo_metadata::semver::Version;
+use fs_err as fs;
+use globset::Glob;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+/// The targets and the version of every workspace member, keyed by the absolute path of its
+/// manifest, like `read::workspace_packages` computes them from `cargo metadata`.
+pub(super) fn workspace_packages(
+    base_path: &Path,
+) -> Result<BTreeMap<PathBuf, (BTreeSet<Target>, Version)>, anyhow::Error> {
+    let root_manifest_path = base_path.join("Cargo.toml");
+    let root = read_manifest(&root_manifest_path)?;
+    let workspace = root.get("workspace");
+    let members = match workspace {
+        Some(workspace) => members(base_path, &root, workspace)?,
+        None => vec![root_manifest_path.clone()],
+    };
+
+    let mut packages = BTreeMap::new();
+    for manifest_path in members {
+        let manifest = read_manifest(&manifest_path)?;
+        let Some(package) = manifest.get("package") else {
+            continue;
+        };
+        let version = version(package, &root)
+            .with_context(|| format!("Failed to resolve the version of {:?}", manifest_path))?;
+        let targets = targets(&manifest_path, &manifest)?;
+        packages.insert(manifest_path, (targets, version));
+    }
+    if workspace.is_some() && root.get("package").is_none() {
+        // Same as for `cargo metadata`: the root of a virtual workspace is not a package.
+        packages.insert(
+            root_manifest_path,
+            (Default::default(), Version::new(0, 0, 1)),
+        );
+    }
+    Ok(packages)
+}
+
+/// The name and the version of every package of the workspace.
+pub(super) fn package_versions(base_path: &Path) -> Result<Vec<(String, Version)>, anyhow::Error> {
+    let mut versions = vec![];
+    for (manifest_path, (_, version)) in workspace_packages(base_path)? {
+        let manifest = read_manifest(&manifest_path)?;
+        if let Some(name) = manifest
+            .get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+        {
+            versions.push((name.to_string(), version));
+        }
+    }
+    Ok(versions)
+}
+
+fn read_manifest(path: &Path) -> Result<toml::Value, anyhow::Error> {
+    let contents = fs::read_to_string(path)?;
+    toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+}
+
+/// The manifests of the members of the workspace rooted at `base_path`: the root package, the
+/// expanded `members` globs, and the path dependencies of those that live in the workspace
+/// directory, minus `exclude`.
+fn members(
+    base_path: &Path,
+    root: &toml::Value,
+    workspace: &toml::Value,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let base_path = normalize(base_path);
+    let strings = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect()
+    };
+    let declared = strings("members");
+    let exclude = strings("exclude");
+    // As `cargo` does, a path listed in `members` (but not a glob) wins over `exclude`.
+    let is_excluded = |directory: &Path| {
+        exclude
+            .iter()
+            .any(|excluded| directory.starts_with(normalize(&base_path.join(excluded))))
+            && !declared
+                .iter()
+                .any(|member| directory.starts_with(normalize(&base_path.join(member))))
+    };
+
+    // The directories to visit, and whether they are the target of a path dependency.
+    let mut queue = vec![];
+    if root.get("package").is_some() {
+        queue.push((base_path.clone(), false));
+    }
+    for pattern in &declared {
+        let directories = expand(&base_path, pattern)
+            .with_context(|| format!("Failed to expand the workspace member `{}`", pattern))?;
+        queue.extend(
+            directories
+                .into_iter()
+                .filter(|directory| directory.join("Cargo.toml").is_file())
+                .map(|directory| (directory, false)),
+        );
+    }
+
+    let mut members = BTreeSet::new();
+    while let Some((directory, is_path_dependency)) = queue.pop() {
+        let directory = normalize(&directory);
+        let manifest_path = directory.join("Cargo.toml");
+        if members.contains(&manifest_path) || is_excluded(&directory) {
+            continue;
+        }
+        if is_path_dependency && !directory.starts_with(&base_path) {
+            continue;
+        }
+        let manifest = read_manifest(&manifest_path)?;
+        if directory != base_path && manifest.get("workspace").is_some() {
+            // A nested workspace, which `cargo` never considers a member.
+            continue;
+        }
+        for dependency in path_dependencies(&manifest, workspace) {
+            let dependency_directory = match dependency {
+                DependencyPath::Member(path) => directory.join(path),
+                DependencyPath::Workspace(path) => base_path.join(path),
+            };
+            queue.push((dependency_directory, true));
+        }
+        members.insert(manifest_path);
+    }
+    Ok(members.into_iter().collect())
+}
+
+enum DependencyPath<'a> {
+    /// Relative to the member declaring the dependency.
+    Member(&'a str),
+    /// Relative to the workspace root, inherited from `workspace.dependencies`.
+    Workspace(&'a str),
+}
+
+/// The paths of the path dependencies of the package in `manifest`, its target-specific
+/// dependencies included. `[workspace.dependencies]` only counts if members inherit from it.
+fn path_dependencies<'a>(
+    manifest: &'a toml::Value,
+    workspace: &'a toml::Value,
+) -> Vec<DependencyPath<'a>> {
+    let targets = manifest
+        .get("target")
+        .and_then(|targets| targets.as_table())
+        .into_iter()
+        .flat_map(|targets| targets.values());
+    std::iter::once(manifest)
+        .chain(targets)
+        .flat_map(|section| {
+            ["dependencies", "dev-dependencies", "build-dependencies"]
+                .iter()
+                .filter_map(move |key| section.get(key).and_then(|d| d.as_table()))
+        })
+        .flatten()
+        .filter_map(|(name, dependency)| {
+            if let Some(path) = dependency.get("path").and_then(|p| p.as_str()) {
+                return Some(DependencyPath::Member(path));
+            }
+            if dependency.get("workspace").and_then(|w| w.as_bool()) != Some(true) {
+                return None;
+            }
+            workspace
+                .get("dependencies")
+                .and_then(|d| d.get(name))
+                .and_then(|d| d.get("path"))
+                .and_then(|p| p.as_str())
+                .map(DependencyPath::Workspace)
+        })
+        .collect()
+}
+
+/// The directories matching the `members` glob `pattern`, relative to `base_path`.
+fn expand(base_path: &Path, pattern: &str) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let mut directories = vec![];
+    expand_components(base_path.to_path_buf(), &components, &mut directories)?;
+    Ok(directories)
+}
+
+fn expand_components(
+    directory: PathBuf,
+    components: &[&str],
+    directories: &mut Vec<PathBuf>,
+) -> Result<(), anyhow::Error> {
+    let Some((component, rest)) = components.split_first() else {
+        if directory.is_dir() {
+            directories.push(directory);
+        }
+        return Ok(());
+    };
+    if !component.contains(['*', '?', '[']) {
+        return expand_components(directory.join(component), rest, directories);
+    }
+    if !directory.is_dir() {
+        return Ok(());
+    }
+    let subdirectories: Vec<PathBuf> = fs::read_dir(&directory)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| entry.path())
+        .collect();
+    if *component == "**" {
+        // Zero or more directories.
+        expand_components(directory, rest, directories)?;
+        for subdirectory in subdirectories {
+            expand_components(subdirectory, components, directories)?;
+        }
+        return Ok(());
+    }
+    let matcher = Glob::new(component)?.compile_matcher();
+    for subdirectory in subdirectories {
+        if subdirectory
+            .file_name()
+            .is_some_and(|name| matcher.is_match(name))
+        {
+            expand_components(subdirectory, rest, directories)?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolve `.` and `..` without touching the filesystem, as `cargo` does for member paths.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The version of `package`, inherited from `workspace.package.version` of the `root` manifest
+/// if it says so. Packages without a version are `0.0.0`.
+fn version(package: &toml::Value, root: &toml::Value) -> Result<Version, anyhow::Error> {
+    let version = match package.get("version") {
+        None => return Ok(Version::new(0, 0, 0)),
+        Some(toml::Value::String(version)) => version,
+        Some(_) => root
+            .get("workspace")
+            .and_then(|w| w.get("package"))
+            .and_then(|p| p.get("version"))
+            .and_then(|v| v.as_str())
+            .context("The version is inherited, but the workspace does not declare one.")?,
+    };
+    Ok(Version::parse(version)?)
+}
+
+/// The targets of the package in `manifest`, declared or discovered on disk with the same rules
+/// as `cargo`, with their path relative to the package.
+fn targets(
+    manifest_path: &Path,
+    manifest: &toml::Value,
+) -> Result<BTreeSet<Target>, anyhow::Error> {
+    let directory = manifest_path.parent().unwrap();
+    let package = &manifest["package"];
+    let package_name = package
+        .get("name")
+        .and_then(|n| n.as_str())
+        .with_context(|| format!("{:?} has no package name", manifest_path))?;
+    let is_enabled = |key: &str| package.get(key).and_then(|v| v.as_bool()) != Some(false);
+    let mut targets = BTreeSet::new();
+
+    let library = manifest.get("lib");
+    let library_path = library
+        .and_then(|lib| lib.get("path"))
+        .and_then(|p| p.as_str())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("src/lib.rs"));
+    if library.is_some() || (is_enabled("autolib") && directory.join(&library_path).is_file()) {
+        let is_proc_macro = library.is_some_and(|lib| {
+            lib.get("proc-macro").and_then(|p| p.as_bool()) == Some(true)
+                || lib
+                    .get("crate-type")
+                    .and_then(|c| c.as_array())
+                    .is_some_and(|types| types.iter().any(|t| t.as_str() == Some("proc-macro")))
+        });
+        let name = library
+            .and_then(|lib| lib.get("name"))
+            .and_then(|n| n.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| package_name.replace('-', "_"));
+        targets.insert(Target {
+            path: library_path,
+            kind: TargetKind::Lib { is_proc_macro },
+            name,
+        });
+    }
+
+    let kinds = [
+        ("bin", "autobins", "src/bin", TargetKind::Bin),
+        ("example", "autoexamples", "examples", TargetKind::Example),
+        ("test", "autotests", "tests", TargetKind::Test),
+        ("bench", "autobenches", "benches", TargetKind::Bench),
+    ];
+    for (key, auto_key, auto_directory, kind) in kinds {
+        let mut declared = vec![];
+        for product in manifest
+            .get(key)
+            .and_then(|p| p.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let name = product
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or(package_name)
+                .to_string();
+            let path = match product.get("path").and_then(|p| p.as_str()) {
+                Some(path) => PathBuf::from(path),
+                None if kind == TargetKind::Bin && name == package_name => {
+                    PathBuf::from("src/main.rs")
+                }
+                None => Path::new(auto_directory).join(format!("{}.rs", name)),
+            };
+            declared.push(Target {
+                path,
+                kind: kind.clone(),
+                name,
+            });
+        }
+
+        let mut discovered = vec![];
+        if is_enabled(auto_key) {
+            if kind == TargetKind::Bin && directory.join("src/main.rs").is_file() {
+                discovered.push((package_name.to_string(), PathBuf::from("src/main.rs")));
+            }
+            discovered.extend(discover(directory, auto_directory)?);
+        }
+        for (name, path) in discovered {
+            // Declared targets take precedence over the ones sharing their name or path.
+            if !declared.iter().any(|t| t.name == name || t.path == path) {
+                declared.push(Target {
+                    path,
+                    kind: kind.clone(),
+                    name,
+                });
+            }
+        }
+        targets.extend(declared);
+    }
+
+    let build_script = match package.get("build") {
+        Some(toml::Value::String(path)) => Some(PathBuf::from(path)),
+        Some(toml::Value::Boolean(false)) => None,
+        Some(_) => Some(PathBuf::from("build.rs")),
+        None => Some(PathBuf::from("build.rs")).filter(|path| directory.join(path).is_file()),
+    };
+    if let Some(path) = build_script {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        targets.insert(Target {
+            path,
+            kind: TargetKind::BuildScript,
+            name: format!("build-script-{}", stem),
+        });
+    }
+    Ok(targets)
+}
+
+/// The targets `cargo` discovers in `auto_directory` of the package in `directory`: its `.rs`
+/// files and its subdirectories with a `main.rs`.
+fn discover(
+    directory: &Path,
+    auto_directory: &str,
+) -> Result<Vec<(String, PathBuf)>, anyhow::Error> {
+    let absolute_path = directory.join(auto_directory);
+    if !absolute_path.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut discovered = vec![];
+    for entry in fs::read_dir(&absolute_path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = Path::new(auto_directory).join(&name);
+        if entry.file_type()?.is_dir() {
+            if absolute_path.join(&name).join("main.rs").is_file() {
+                discovered.push((name, path.join("main.rs")));
+            }
+        } else if let Some(stem) = name.strip_suffix(".rs") {
+            discovered.push((stem.to_string(), path));
+        }
+    }
+    Ok(discovered)
+}
@@ -0,0 +1,137 @@
+//! Local crates that cannot be compiled where `cook` runs (e.g. FFI crates whose native library
+//! is only available in the final build) are replaced by stubs: a manifest without dependencies
+//! nor build script, declaring the features the rest of the workspace enables, or a mock manifest
+//! provided by the user. The rest of the dependency tree is cooked as usual.
+use super::{Skeleton, TargetKind};
+use anyhow::Context;
+use std::path::Path;
+
+/// What a local crate is replaced with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackageStub {
+    /// A manifest without dependencies declaring these features, all of them empty. `None`
+    /// declares every feature of the original manifest (emptied as well).
+    Features(Option<Vec<String>>),
+    /// The contents of a mock manifest, for a package with the same name.
+    Manifest(String),
+}
+
+/// The sections of a manifest that belong to the workspace rather than to its root package.
+const WORKSPACE_SECTIONS: [&str; 4] = ["workspace", "profile", "patch", "replace"];
+
+pub(super) fn stub_packages(
+    skeleton: &Skeleton,
+    stubs: &[(String, PackageStub)],
+) -> Result<Skeleton, anyhow::Error> {
+    let mut skeleton = skeleton.clone();
+    for (name, stub) in stubs {
+        let mut found = None;
+        for (index, manifest) in skeleton.manifests.iter().enumerate() {
+            let contents: toml::Value = toml::from_str(&manifest.contents)?;
+            if package_name(&contents) == Some(name.as_str()) {
+                found = Some((index, contents));
+                break;
+            }
+        }
+        let (index, original) =
+            found.with_context(|| format!("`{}` is not a local crate of the recipe.", name))?;
+        let stubbed = stub_manifest(name, &original, stub)?;
+        let manifest = &mut skeleton.manifests[index];
+        manifest.contents = toml::to_string(&stubbed)?;
+        manifest
+            .targets
+            .retain(|target| matches!(target.kind, TargetKind::Lib { .. }));
+        // Kept build scripts and sources of the package are of no use to its stub, unlike
+        // those of the packages nested in its directory.
+        let directory = directory_of(&manifest.relative_path).to_path_buf();
+        let nested: Vec<&Path> = skeleton
+            .manifests
+            .iter()
+            .map(|other| directory_of(&other.relative_path))
+            .filter(|other| other != &directory && other.starts_with(&directory))
+            .collect();
+        skeleton.files.retain(|file| {
+            !file.relative_path.starts_with(&directory)
+                || nested
+                    .iter()
+                    .any(|other| file.relative_path.starts_with(other))
+        });
+    }
+    Ok(skeleton)
+}
+
+fn stub_manifest(
+    name: &str,
+    original: &toml::Value,
+    stub: &PackageStub,
+) -> Result<toml::Value, anyhow::Error> {
+    let mut stubbed = toml::Table::new();
+    for section in WORKSPACE_SECTIONS {
+        if let Some(value) = original.get(section) {
+            stubbed.insert(section.to_string(), value.clone());
+        }
+    }
+    match stub {
+        PackageStub::Features(features) => {
+            let mut package = original
+                .get("package")
+                .and_then(|package| package.as_table())
+                .cloned()
+                .unwrap_or_default();
+            // `links` requires a build script.
+            package.remove("links");
+            package.insert("build".to_string(), false.into());
+            stubbed.insert("package".to_string(), package.into());
+            if let Some(lib) = original.get("lib") {
+                stubbed.insert("lib".to_string(), lib.clone());
+            }
+            let names: Vec<String> = match features {
+                Some(features) => features.clone(),
+                None => original
+                    .get("features")
+                    .and_then(|features| features.as_table())
+                    .map(|features| features.keys().cloned().collect())
+                    .unwrap_or_default(),
+            };
+            let features: toml::Table = names
+                .into_iter()
+                .map(|feature| (feature, toml::Value::Array(vec![])))
+                .collect();
+            if !features.is_empty() {
+                stubbed.insert("features".to_string(), features.into());
+            }
+        }
+        PackageStub::Manifest(contents) => {
+            let mock: toml::Value = toml::from_str(contents)
+                .with_context(|| format!("Failed to parse the mock manifest of `{}`.", name))?;
+            if package_name(&mock) != Some(name) {
+                return Err(anyhow::anyhow!(
+                    "The mock manifest of `{}` must be for a package of the same name, not {:?}.",
+                    name,
+                    package_name(&mock).unwrap_or_default()
+                ));
+            }
+            let toml::Value::Table(mock) = mock else {
+                unreachable!("a manifest with a package name is a table");
+            };
+            for (key, value) in mock {
+                if !WORKSPACE_SECTIONS.contains(&key.as_str()) {
+                    stubbed.insert(key, value);
+                }
+            }
+            // The dummy library is written where the original one lives.
+            if let (None, Some(lib)) = (stubbed.get("lib"), original.get("lib")) {
+                stubbed.insert("lib".to_string(), lib.clone());
+            }
+        }
+    }
+    Ok(stubbed.into())
+}
+
+fn directory_of(manifest_path: &Path) -> &Path {
+    manifest_path.parent().unwrap_or(Path::new(""))
+}
+
+fn package_name(contents: &toml::Value) -> Option<&str> {
+    contents.get("package")?.get("name")?.as_str()
+}
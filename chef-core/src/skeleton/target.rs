@@ -12,6 +12,7 @@ pub enum TargetKind {
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, Ord, PartialOrd)]
 pub struct Target {
+    #[serde(with = "super::portable_path")]
     pub(crate) path: PathBuf,
     pub(crate) kind: TargetKind,
     pub(crate) name: String,
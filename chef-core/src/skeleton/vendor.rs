@@ -0,0 +1,95 @@
+//! Vendored sources, as produced by `cargo vendor`.
+//!
+//! Vendored crates are full-blown packages - manifests, `Cargo.toml.orig`, sources and
+//! `.cargo-checksum.json` files - that `cargo` uses as a replacement for a registry. They are
+//! not part of the workspace and there is nothing to stub out: we never include them in the
+//! recipe, nor touch them when cooking. They are expected to be copied over as they are.
+//!
+//! The recipe only records a checksum of each vendored directory, computed over the
+//! checksum files of its crates, so that it changes whenever the vendored crates do.
+use super::read;
+use super::VendoredSource;
+use anyhow::Context;
+use fs_err as fs;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+const CHECKSUM_FILE_NAME: &str = ".cargo-checksum.json";
+
+/// The vendored directories of the project, either `vendor` (the default of `cargo vendor`)
+/// or the directories configured as sources in the cargo configuration.
+pub(super) fn vendored_sources(base_path: &Path) -> Result<Vec<VendoredSource>, anyhow::Error> {
+    let mut directories = vec![PathBuf::from("vendor")];
+    if let Some(config) = read::config(&base_path)? {
+        let config: toml::Value =
+            toml::from_str(&config).context("Failed to parse the cargo configuration")?;
+        let configured = config
+            .get("source")
+            .and_then(|sources| sources.as_table())
+            .into_iter()
+            .flat_map(|sources| sources.values())
+            .filter_map(|source| source.get("directory")?.as_str());
+        for directory in configured {
+            // Relative directories are resolved with respect to the parent of `.cargo`.
+            let directory = Path::new(directory);
+            let relative = match directory.strip_prefix(base_path) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) if directory.is_relative() => directory.to_path_buf(),
+                Err(_) => continue,
+            };
+            if let Some(relative) = super::normalize(&relative) {
+                if !directories.contains(&relative) {
+                    directories.push(relative);
+                }
+            }
+        }
+    }
+
+    let mut vendored_sources = vec![];
+    for relative_path in directories {
+        let Some(checksum) = checksum(&base_path.join(&relative_path))? else {
+            continue;
+        };
+        vendored_sources.push(VendoredSource {
+            relative_path,
+            checksum,
+        });
+    }
+    vendored_sources.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(vendored_sources)
+}
+
+/// SHA-256 of the checksum files of all the crates in `directory`, `None` if it does not
+/// contain vendored crates.
+pub(super) fn checksum(directory: &Path) -> Result<Option<String>, anyhow::Error> {
+    if !directory.is_dir() {
+        return Ok(None);
+    }
+    let mut checksum_files = vec![];
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path().join(CHECKSUM_FILE_NAME);
+        if path.is_file() {
+            checksum_files.push(path);
+        }
+    }
+    if checksum_files.is_empty() {
+        return Ok(None);
+    }
+    checksum_files.sort();
+
+    let mut hasher = Sha256::new();
+    for path in checksum_files {
+        let crate_directory = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .unwrap_or_default();
+        hasher.update(crate_directory.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(&path)?);
+        hasher.update([0]);
+    }
+    let digest = hasher.finalize();
+    Ok(Some(
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    ))
+}
@@ -0,0 +1,763 @@
+use super::{Manifest, ParsedManifest};
+use cargo_metadata::semver::{Op, Version, VersionReq};
+
+/// All local dependencies are emptied out when running `prepare`.
+/// We do not want the recipe file to change if the only difference with
+/// the previous docker build attempt is the version of a local crate
+/// encoded in `Cargo.lock` (while the remote dependency tree
+/// is unchanged) or in the corresponding `Cargo.toml` manifest.
+/// We replace versions of local crates in `Cargo.lock` and in all `Cargo.toml`s, including
+/// when specified as dependency of another crate in the workspace.
+///
+/// Local crates that a git or registry package depends on (through a `[patch]`, e.g. vendored
+/// forks) are left alone: the requirement of the external package, which we cannot rewrite,
+/// would no longer be satisfied by the masked version.
+pub(super) fn mask_local_crate_versions(
+    manifests: &mut [ParsedManifest],
+    lock_file: &mut Option<toml::Value>,
+) {
+    let required_externally: Vec<String> = lock_file
+        .as_ref()
+        .map(externally_required_crates)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(local, _)| local)
+        .collect();
+    let local_packages: Vec<LocalPackage> = manifests
+        .iter()
+        .filter_map(|manifest| LocalPackage::from_manifest(&manifest.contents))
+        .filter(|local| !required_externally.contains(&local.name))
+        .collect();
+    for manifest in manifests.iter_mut() {
+        mask_local_versions_in_manifest(&mut manifest.contents, &local_packages);
+    }
+    if let Some(lock_file) = lock_file {
+        mask_local_versions_in_lockfile(lock_file, &local_packages);
+        normalize_lockfile_order(lock_file);
+    }
+}
+
+/// Dummy version used for all local crates.
+const CONST_VERSION: &str = "0.0.1";
+
+/// A package of the workspace, whose version gets masked.
+#[derive(Debug, Clone, PartialEq)]
+struct LocalPackage {
+    name: String,
+    version: Version,
+}
+
+impl LocalPackage {
+    fn from_manifest(manifest: &toml::Value) -> Option<Self> {
+        let package = manifest.get("package")?;
+        Some(LocalPackage {
+            name: package.get("name")?.as_str()?.to_string(),
+            // Inherited versions have been resolved when reading the manifest.
+            version: Version::parse(package.get("version")?.as_str()?).ok()?,
+        })
+    }
+}
+
+/// A `[[package]]` entry of `Cargo.lock`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LockfilePackage<'a> {
+    name: &'a str,
+    version: &'a str,
+    /// `None` for packages built from a path, i.e. local crates.
+    source: Option<&'a str>,
+}
+
+impl<'a> LockfilePackage<'a> {
+    fn from_lockfile(package: &'a toml::Value) -> Option<Self> {
+        Some(LockfilePackage {
+            name: package.get("name")?.as_str()?,
+            version: package.get("version")?.as_str()?,
+            source: package.get("source").and_then(|source| source.as_str()),
+        })
+    }
+
+    /// Whether this is the lock file entry of one of `local_packages`. A registry or git
+    /// package can share its name with a local crate: it is left alone.
+    fn is_local(&self, local_packages: &[LocalPackage]) -> bool {
+        self.source.is_none() && local_packages.iter().any(|local| local.name == self.name)
+    }
+}
+
+/// The local crates of `lock_file` that a git or registry package depends on, along with the
+/// first such dependent (as `name version`).
+pub(super) fn externally_required_crates(lock_file: &toml::Value) -> Vec<(String, String)> {
+    let entries = lock_file
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let packages: Vec<LockfilePackage> = entries
+        .iter()
+        .filter_map(LockfilePackage::from_lockfile)
+        .collect();
+    let mut required: Vec<(String, String)> = vec![];
+    for entry in entries {
+        let Some(dependent) = LockfilePackage::from_lockfile(entry) else {
+            continue;
+        };
+        if dependent.source.is_none() {
+            continue;
+        }
+        let dependencies = entry
+            .get("dependencies")
+            .and_then(|dependencies| dependencies.as_array());
+        for dependency in dependencies
+            .into_iter()
+            .flatten()
+            .filter_map(|d| d.as_str())
+        {
+            let Some(local) = resolve_lockfile_dependency(dependency, &packages)
+                .filter(|package| package.source.is_none())
+            else {
+                continue;
+            };
+            if !required.iter().any(|(name, _)| name == local.name) {
+                required.push((
+                    local.name.to_string(),
+                    format!("{} {}", dependent.name, dependent.version),
+                ));
+            }
+        }
+    }
+    required
+}
+
+/// The package an entry of the `dependencies` of a `[[package]]` in `Cargo.lock` refers to:
+/// cargo only spells out the version (and the source) when the name (and the version) is
+/// ambiguous.
+fn resolve_lockfile_dependency<'a>(
+    dependency: &str,
+    packages: &[LockfilePackage<'a>],
+) -> Option<LockfilePackage<'a>> {
+    let mut parts = dependency.splitn(3, ' ');
+    let name = parts.next()?;
+    let version = parts.next();
+    let source = parts
+        .next()
+        .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+    packages.iter().copied().find(|package| {
+        package.name == name
+            && version.is_none_or(|version| package.version == version)
+            && source.is_none_or(|source| package.source == Some(source))
+    })
+}
+
+/// A dependency declared in a manifest, e.g. `renamed = { package = "foo", version = "1.2" }`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DependencyEntry<'a> {
+    /// The key the dependency is declared with.
+    key: &'a str,
+    /// The actual name of the package, if renamed.
+    package: Option<&'a str>,
+    /// The version requirement of a path dependency. Missing for path dependencies without one,
+    /// for dependencies inherited from the workspace, whose version is masked in
+    /// `workspace.dependencies`, and for dependencies from a registry or git.
+    version: Option<&'a str>,
+}
+
+impl<'a> DependencyEntry<'a> {
+    fn from_manifest(key: &'a str, dependency: &'a toml::Value) -> Self {
+        DependencyEntry {
+            key,
+            package: dependency.get("package").and_then(|p| p.as_str()),
+            // A registry namesake of a local crate, e.g. an older release it depends on, must
+            // not be masked: whether its requirement matches would change with every bump.
+            version: dependency
+                .get("version")
+                .filter(|_| dependency.get("path").is_some())
+                .and_then(|v| v.as_str()),
+        }
+    }
+
+    /// Whether the dependency is on one of `local_packages`, i.e. its version requirement is
+    /// satisfied by the version of a local crate with the same name.
+    ///
+    /// A requirement that does not match, e.g. `foo = "1"` while the local `foo` is at `2.0.0`,
+    /// refers to a namesake from a registry.
+    fn is_local(&self, local_packages: &[LocalPackage]) -> bool {
+        let Some(requirement) = self.version.and_then(|v| VersionReq::parse(v).ok()) else {
+            return false;
+        };
+        let name = self.package.unwrap_or(self.key);
+        local_packages
+            .iter()
+            .any(|local| local.name == name && requirement.matches(&local.version))
+    }
+}
+
+/// The operators a requirement on a local crate keeps once masked, e.g. `=0.0.1`. Any other
+/// requirement becomes `0.0.1`, which only the masked version satisfies as well.
+const MASKED_OPERATORS: [&str; 2] = ["=", ">="];
+
+/// The masked form of a version requirement on a local crate. An exact requirement (`=1.2.3`,
+/// or a multi-requirement string such as `=1.2.3, <2`) stays exact, so that unmasking restores
+/// a requirement that only the unmasked version satisfies, as in the original manifest.
+fn masked_requirement(requirement: &VersionReq) -> String {
+    let comparators = &requirement.comparators;
+    let operator = if comparators.iter().any(|c| c.op == Op::Exact) {
+        "="
+    } else if !comparators.is_empty() && comparators.iter().all(|c| c.op == Op::GreaterEq) {
+        ">="
+    } else {
+        ""
+    };
+    format!("{}{}", operator, CONST_VERSION)
+}
+
+/// The operator of a masked requirement, `None` if `requirement` was not masked.
+fn masked_operator(requirement: &str) -> Option<&str> {
+    let operator = requirement.strip_suffix(CONST_VERSION)?;
+    (operator.is_empty() || MASKED_OPERATORS.contains(&operator)).then_some(operator)
+}
+
+/// The masked form of an entry of the `dependencies` of a `[[package]]` in `Cargo.lock`
+/// (`name`, `name version` or `name version (source)`), if it refers to one of `masked`.
+fn masked_lockfile_dependency(dependency: &str, masked: &[LockfilePackage]) -> Option<String> {
+    let mut parts = dependency.split(' ');
+    let (name, version) = (parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        // Only packages from a registry or from git carry their source.
+        return None;
+    }
+    masked
+        .iter()
+        .any(|package| package.name == name && package.version == version)
+        .then(|| format!("{} {}", name, CONST_VERSION))
+}
+
+fn mask_local_versions_in_lockfile(lock_file: &mut toml::Value, local_packages: &[LocalPackage]) {
+    let Some(packages) = lock_file
+        .get_mut("package")
+        .and_then(|packages| packages.as_array_mut())
+    else {
+        return;
+    };
+    let masked: Vec<(String, String)> = packages
+        .iter()
+        .filter_map(LockfilePackage::from_lockfile)
+        .filter(|package| package.is_local(local_packages))
+        .map(|package| (package.name.to_string(), package.version.to_string()))
+        .collect();
+    let masked: Vec<LockfilePackage> = masked
+        .iter()
+        .map(|(name, version)| LockfilePackage {
+            name,
+            version,
+            source: None,
+        })
+        .collect();
+
+    for package in packages.iter_mut() {
+        let is_masked = LockfilePackage::from_lockfile(package)
+            .is_some_and(|package| masked.contains(&package));
+        if is_masked {
+            if let Some(version) = package.get_mut("version") {
+                *version = toml::Value::String(CONST_VERSION.to_string());
+            }
+        }
+        let dependencies = package
+            .get_mut("dependencies")
+            .and_then(|dependencies| dependencies.as_array_mut());
+        for dependency in dependencies.into_iter().flatten() {
+            if let Some(masked) = dependency
+                .as_str()
+                .and_then(|dependency| masked_lockfile_dependency(dependency, &masked))
+            {
+                *dependency = toml::Value::String(masked);
+            }
+        }
+    }
+}
+
+/// Sort the `[[package]]` entries of `Cargo.lock` and their dependencies once masked: cargo
+/// orders packages by name, then version, so a local crate and its registry namesake swap places
+/// when the local version is bumped past the registry one. Masked, the lock file must not depend
+/// on which release automation (or none) last bumped the workspace.
+fn normalize_lockfile_order(lock_file: &mut toml::Value) {
+    let Some(packages) = lock_file
+        .get_mut("package")
+        .and_then(|packages| packages.as_array_mut())
+    else {
+        return;
+    };
+    packages.sort_by_cached_key(|package| {
+        let package = LockfilePackage::from_lockfile(package);
+        (
+            package.map(|package| package.name.to_string()),
+            package.and_then(|package| Version::parse(package.version).ok()),
+            package.and_then(|package| package.source.map(String::from)),
+        )
+    });
+    for package in packages.iter_mut() {
+        if let Some(dependencies) = package
+            .get_mut("dependencies")
+            .and_then(|dependencies| dependencies.as_array_mut())
+        {
+            dependencies.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+        }
+    }
+}
+
+fn mask_local_versions_in_manifest(manifest: &mut toml::Value, local_packages: &[LocalPackage]) {
+    let is_masked = LocalPackage::from_manifest(manifest)
+        .is_none_or(|package| local_packages.contains(&package));
+    if let Some(version) = manifest
+        .get_mut("package")
+        .filter(|_| is_masked)
+        .and_then(|package| package.get_mut("version"))
+    {
+        // `version.workspace = true` is masked in `workspace.package`.
+        if version.is_str() {
+            *version = toml::Value::String(CONST_VERSION.to_string());
+        }
+    }
+
+    // There are three ways to specify dependencies:
+    // - top-level
+    // ```toml
+    // [dependencies]
+    // # [...]
+    // ```
+    // - target-specific (e.g. Windows-only)
+    // ```toml
+    // [target.'cfg(windows)'.dependencies]
+    // winhttp = "0.4.0"
+    // ```
+    // The inner structure for target-specific dependencies mirrors the structure expected
+    // for top-level dependencies.
+    // Check out cargo's documentation (https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html)
+    // for more details.
+    mask_local_dependency_versions(manifest, local_packages);
+    if let Some(targets) = manifest.get_mut("target").and_then(|t| t.as_table_mut()) {
+        for (_, target_config) in targets.iter_mut() {
+            mask_local_dependency_versions(target_config, local_packages);
+        }
+    }
+
+    // The third way to specify dependencies was introduced in rust 1.64: workspace inheritance.
+    // ```toml
+    // [workspace.dependencies]
+    // anyhow = "1.0.66"
+    // project_a = { path = "project_a", version = "0.2.0" }
+    // ```
+    // Check out cargo's documentation (https://doc.rust-lang.org/cargo/reference/workspaces.html#the-workspacedependencies-table)
+    // for more details.
+    if let Some(workspace) = manifest.get_mut("workspace") {
+        // Mask the workspace package version
+        if let Some(version) = workspace
+            .get_mut("package")
+            .and_then(|package| package.get_mut("version"))
+        {
+            *version = toml::Value::String(CONST_VERSION.to_string());
+        }
+        // Mask the local crates in the workspace dependencies
+        mask_local_dependency_versions(workspace, local_packages);
+    }
+}
+
+/// Mask the version requirements on local crates in the dependency tables of `section`.
+fn mask_local_dependency_versions(section: &mut toml::Value, local_packages: &[LocalPackage]) {
+    for dependency_key in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        let Some(dependencies) = section
+            .get_mut(dependency_key)
+            .and_then(|dependencies| dependencies.as_table_mut())
+        else {
+            continue;
+        };
+        for (key, dependency) in dependencies.iter_mut() {
+            let entry = DependencyEntry::from_manifest(key, dependency);
+            if !entry.is_local(local_packages) {
+                continue;
+            }
+            let masked = entry
+                .version
+                .and_then(|version| VersionReq::parse(version).ok())
+                .map(|requirement| masked_requirement(&requirement));
+            if let Some(masked) = masked {
+                dependency["version"] = toml::Value::String(masked);
+            }
+        }
+    }
+}
+
+/// Undo the masking of `packages`, i.e. restore their version in their own manifest, in the
+/// requirements of their dependents and in `Cargo.lock`: packages whose build script or
+/// sources are kept must be cooked as the same compilation units as in the final build.
+pub(super) fn unmask_crate_versions(
+    manifests: &mut [Manifest],
+    lock_file: &mut Option<String>,
+    packages: &[(String, Version)],
+) -> Result<(), anyhow::Error> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let version_of = |name: &str| {
+        packages
+            .iter()
+            .find(|(package, _)| package == name)
+            .map(|(_, version)| version.to_string())
+    };
+    for manifest in manifests.iter_mut() {
+        let mut contents: toml::Value = toml::from_str(&manifest.contents)?;
+        if let Some(package) = contents.get_mut("package").and_then(|p| p.as_table_mut()) {
+            let name = package
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default();
+            if let Some(version) = version_of(name) {
+                package.insert("version".into(), toml::Value::String(version));
+            }
+        }
+        unmask_dependency_versions(&mut contents, &version_of);
+        if let Some(targets) = contents.get_mut("target").and_then(|t| t.as_table_mut()) {
+            for (_, target_config) in targets.iter_mut() {
+                unmask_dependency_versions(target_config, &version_of);
+            }
+        }
+        if let Some(workspace) = contents.get_mut("workspace") {
+            unmask_dependency_versions(workspace, &version_of);
+        }
+        manifest.contents = toml::to_string(&contents)?;
+    }
+
+    let Some(lock_file) = lock_file else {
+        return Ok(());
+    };
+    let mut contents: toml::Value = toml::from_str(lock_file)?;
+    let entries = contents
+        .get_mut("package")
+        .and_then(|packages| packages.as_array_mut());
+    for entry in entries.into_iter().flatten() {
+        let package = LockfilePackage::from_lockfile(entry);
+        let version = package
+            .filter(|package| package.source.is_none() && package.version == CONST_VERSION)
+            .and_then(|package| version_of(package.name));
+        if let Some(version) = version {
+            entry["version"] = toml::Value::String(version);
+        }
+        let dependencies = entry
+            .get_mut("dependencies")
+            .and_then(|dependencies| dependencies.as_array_mut());
+        for dependency in dependencies.into_iter().flatten() {
+            let unmasked = dependency.as_str().and_then(|dependency| {
+                let (name, version) = dependency.split_once(' ')?;
+                (version == CONST_VERSION)
+                    .then(|| version_of(name))
+                    .flatten()
+                    .map(|version| format!("{} {}", name, version))
+            });
+            if let Some(unmasked) = unmasked {
+                *dependency = toml::Value::String(unmasked);
+            }
+        }
+    }
+    *lock_file = toml::to_string(&contents)?;
+    Ok(())
+}
+
+/// Restore the masked version requirements on `version_of` packages in the dependency tables
+/// of `section`.
+fn unmask_dependency_versions(
+    section: &mut toml::Value,
+    version_of: &impl Fn(&str) -> Option<String>,
+) {
+    for dependency_key in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        let Some(dependencies) = section
+            .get_mut(dependency_key)
+            .and_then(|dependencies| dependencies.as_table_mut())
+        else {
+            continue;
+        };
+        for (key, dependency) in dependencies.iter_mut() {
+            let entry = DependencyEntry::from_manifest(key, dependency);
+            let Some(operator) = entry.version.and_then(masked_operator) else {
+                continue;
+            };
+            if let Some(version) = version_of(entry.package.unwrap_or(key)) {
+                dependency["version"] = toml::Value::String(format!("{}{}", operator, version));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(name: &str, version: &str) -> LocalPackage {
+        LocalPackage {
+            name: name.to_string(),
+            version: Version::parse(version).unwrap(),
+        }
+    }
+
+    fn dependency<'a>(version: Option<&'a str>) -> DependencyEntry<'a> {
+        DependencyEntry {
+            key: "foo",
+            package: None,
+            version,
+        }
+    }
+
+    #[test]
+    fn requirements_on_local_crates() {
+        let cases = [
+            // (local version, requirement, is local)
+            ("1.2.3", "1.2.3", true),
+            ("1.2.3", "1.2", true),
+            ("1.2.3", "1", true),
+            ("1.2.3", "^1.0", true),
+            ("1.2.3", "^1.3", false),
+            ("1.2.3", "2", false),
+            ("1.2.3", "~1.2", true),
+            ("1.2.3", "~1.2.4", false),
+            ("1.2.3", "~1", true),
+            ("1.2.3", "=1.2.3", true),
+            ("1.2.3", "=1.2.2", false),
+            ("1.2.3", "*", true),
+            ("1.2.3", "1.*", true),
+            ("1.2.3", "2.*", false),
+            ("1.2.3", ">=1.0, <2.0", true),
+            ("1.2.3", ">1.2.3", false),
+            // 0.x: the minor version is the breaking one.
+            ("0.2.3", "0.2", true),
+            ("0.2.3", "0.2.1", true),
+            ("0.2.3", "0.3", false),
+            ("0.2.3", "0", true),
+            ("0.2.3", "~0.2", true),
+            // 0.0.x: every version is breaking.
+            ("0.0.3", "0.0.3", true),
+            ("0.0.3", "0.0.2", false),
+            ("0.0.3", "0.0", true),
+            // Pre-releases only match requirements that mention them.
+            ("1.0.0-alpha.2", "1.0.0-alpha.1", true),
+            ("1.0.0-alpha.2", "1", false),
+            // Not a valid requirement: left alone.
+            ("1.2.3", "latest", false),
+        ];
+        for (version, requirement, expected) in cases {
+            assert_eq!(
+                dependency(Some(requirement)).is_local(&[local("foo", version)]),
+                expected,
+                "`foo = \"{}\"` with a local `foo` at {}",
+                requirement,
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn masked_requirements_on_local_crates() {
+        let cases = [
+            // (requirement, masked)
+            ("1.2.3", "0.0.1"),
+            ("^1.2", "0.0.1"),
+            ("~1.2", "0.0.1"),
+            ("1.*", "0.0.1"),
+            ("=1.2.3", "=0.0.1"),
+            ("= 1.2.3", "=0.0.1"),
+            (">=1.2", ">=0.0.1"),
+            (">=1.2, >=1.2.3", ">=0.0.1"),
+            (">=1.0, <2.0", "0.0.1"),
+            (">1.0, <=1.2.3", "0.0.1"),
+            ("=1.2.3, <2", "=0.0.1"),
+            (">=1.0, =1.2.3", "=0.0.1"),
+        ];
+        for (requirement, expected) in cases {
+            let masked = masked_requirement(&VersionReq::parse(requirement).unwrap());
+            assert_eq!(masked, expected, "`{}`", requirement);
+            // The masked version satisfies the masked requirement and it can be unmasked.
+            assert!(VersionReq::parse(&masked)
+                .unwrap()
+                .matches(&Version::parse(CONST_VERSION).unwrap()));
+            assert!(masked_operator(&masked).is_some());
+        }
+        assert_eq!(masked_operator("1.2.3"), None);
+        assert_eq!(masked_operator("<0.0.1"), None);
+    }
+
+    #[test]
+    fn dependencies_without_a_version_are_left_alone() {
+        // e.g. `foo = { path = "../foo" }` or `foo = { workspace = true }`
+        assert!(!dependency(None).is_local(&[local("foo", "1.2.3")]));
+    }
+
+    #[test]
+    fn renamed_dependencies_match_on_the_package_name() {
+        let locals = [local("foo", "1.2.3")];
+        let renamed = DependencyEntry {
+            key: "bar",
+            package: Some("foo"),
+            version: Some("1.2"),
+        };
+        assert!(renamed.is_local(&locals));
+        let namesake = DependencyEntry {
+            key: "foo",
+            package: Some("bar"),
+            version: Some("1.2"),
+        };
+        assert!(!namesake.is_local(&locals));
+    }
+
+    #[test]
+    fn dependencies_are_parsed_from_manifests() {
+        let manifest: toml::Value = toml::from_str(
+            r#"
+            [dependencies]
+            plain = "1.0"
+            registry = { version = "0.2", features = ["std"] }
+            renamed = { package = "foo", version = "0.2", path = "../foo" }
+            inherited = { workspace = true }
+            "#,
+        )
+        .unwrap();
+        let dependencies = manifest["dependencies"].as_table().unwrap();
+        let entry = |key| DependencyEntry::from_manifest(key, &dependencies[key]);
+        // Without a `path`, a dependency cannot be on a local crate.
+        assert_eq!(entry("plain").version, None);
+        assert_eq!(entry("registry").version, None);
+        assert_eq!(
+            entry("renamed"),
+            DependencyEntry {
+                key: "renamed",
+                package: Some("foo"),
+                version: Some("0.2"),
+            }
+        );
+        assert_eq!(entry("inherited").version, None);
+    }
+
+    #[test]
+    fn local_packages_are_parsed_from_manifests() {
+        let manifest = |s: &str| toml::from_str::<toml::Value>(s).unwrap();
+        assert_eq!(
+            LocalPackage::from_manifest(&manifest(
+                "[package]\nname = \"foo\"\nversion = \"0.2.0\""
+            )),
+            Some(local("foo", "0.2.0"))
+        );
+        assert_eq!(
+            LocalPackage::from_manifest(&manifest("[workspace]\nmembers = []")),
+            None
+        );
+        assert_eq!(
+            LocalPackage::from_manifest(&manifest(
+                "[package]\nname = \"foo\"\nversion.workspace = true"
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn lockfile_packages_of_local_crates() {
+        let locals = [local("foo", "1.2.3")];
+        let package = |name, source| LockfilePackage {
+            name,
+            version: "1.2.3",
+            source,
+        };
+        assert!(package("foo", None).is_local(&locals));
+        assert!(!package("bar", None).is_local(&locals));
+        // A namesake from crates.io.
+        let registry = "registry+https://github.com/rust-lang/crates.io-index";
+        assert!(!package("foo", Some(registry)).is_local(&locals));
+    }
+
+    #[test]
+    fn masked_lockfiles_are_sorted() {
+        let registry = "registry+https://github.com/rust-lang/crates.io-index";
+        let masked = |version: &str| {
+            let mut packages = [
+                format!(
+                    "[[package]]\nname = \"app\"\nversion = \"0.1.0\"\n\
+                    dependencies = [\"foo 1.5.0 ({})\", \"foo {}\"]",
+                    registry, version
+                ),
+                format!("[[package]]\nname = \"foo\"\nversion = \"{}\"", version),
+                format!(
+                    "[[package]]\nname = \"foo\"\nversion = \"1.5.0\"\nsource = \"{}\"",
+                    registry
+                ),
+            ];
+            // Cargo sorts by version: the local `foo` comes first until it is bumped past the
+            // one from the registry.
+            if Version::parse(version).unwrap() > Version::new(1, 5, 0) {
+                packages.swap(1, 2);
+            }
+            let mut lock_file: toml::Value = toml::from_str(&packages.join("\n\n")).unwrap();
+            let locals = [local("app", "0.1.0"), local("foo", version)];
+            mask_local_versions_in_lockfile(&mut lock_file, &locals);
+            normalize_lockfile_order(&mut lock_file);
+            lock_file
+        };
+        assert_eq!(masked("1.2.3"), masked("2.0.0"));
+        let lock_file = masked("2.0.0");
+        let packages = lock_file["package"].as_array().unwrap();
+        assert_eq!(packages[1]["version"].as_str(), Some("0.0.1"));
+        assert_eq!(packages[2]["version"].as_str(), Some("1.5.0"));
+        assert_eq!(packages[0]["dependencies"][0].as_str(), Some("foo 0.0.1"));
+    }
+
+    #[test]
+    fn local_crates_required_by_external_packages() {
+        let lock_file: toml::Value = toml::from_str(
+            r#"
+            [[package]]
+            name = "app"
+            version = "0.1.0"
+            dependencies = ["fork", "serde 1.0.2"]
+
+            [[package]]
+            name = "fork"
+            version = "0.3.0"
+
+            [[package]]
+            name = "patched"
+            version = "0.2.0"
+            source = "git+https://github.com/example/patched#abc"
+            dependencies = ["fork", "serde 1.0.1"]
+
+            [[package]]
+            name = "serde"
+            version = "1.0.1"
+
+            [[package]]
+            name = "serde"
+            version = "1.0.2"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            externally_required_crates(&lock_file),
+            [
+                ("fork".to_string(), "patched 0.2.0".to_string()),
+                ("serde".to_string(), "patched 0.2.0".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lockfile_dependencies_on_local_crates() {
+        let masked = [LockfilePackage {
+            name: "foo",
+            version: "1.2.3",
+            source: None,
+        }];
+        let mask = |dependency| masked_lockfile_dependency(dependency, &masked);
+        assert_eq!(mask("foo 1.2.3").as_deref(), Some("foo 0.0.1"));
+        // Only one version of `foo` is locked: there is nothing to mask.
+        assert_eq!(mask("foo"), None);
+        assert_eq!(mask("foo 1.0.0"), None);
+        assert_eq!(mask("bar 1.2.3"), None);
+        assert_eq!(
+            mask("foo 1.2.3 (registry+https://github.com/rust-lang/crates.io-index)"),
+            None
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! A read-only snapshot of `CARGO_HOME` (e.g. a bind-mounted OCI artifact or volume), with the
+//! registry index, the downloaded crates and the git checkouts an offline cook needs.
+//!
+//! `cargo` writes to `CARGO_HOME` even when offline: it takes locks there and extracts crates to
+//! `registry/src`. The snapshot is overlaid onto a writable scratch directory instead, used as
+//! `CARGO_HOME`: its top-level directories (`registry/src/<index>`, `git/db/<repository>`, ...)
+//! are created for real, everything below is symlinked to the snapshot.
+use anyhow::Context;
+use fs_err as fs;
+use std::path::Path;
+
+/// Directories down to this depth are created in the scratch directory, entries below it are
+/// symlinked: `registry/src/<index>` is writable, `registry/src/<index>/<crate>` points to the
+/// snapshot.
+const MIRRORED_DEPTH: usize = 3;
+
+/// Overlay `snapshot` onto `scratch`, to be used as `CARGO_HOME`. Entries already in `scratch`
+/// (e.g. from a previous cook) are left alone.
+pub fn overlay_registry_snapshot(snapshot: &Path, scratch: &Path) -> Result<(), anyhow::Error> {
+    if !["registry", "git"]
+        .iter()
+        .any(|directory| snapshot.join(directory).is_dir())
+    {
+        return Err(anyhow::anyhow!(
+            "{:?} does not look like a snapshot of CARGO_HOME: it has neither a `registry` nor a `git` directory.",
+            snapshot
+        ));
+    }
+    let mut queue = vec![(snapshot.to_path_buf(), scratch.to_path_buf(), 0)];
+    while let Some((source, destination, depth)) = queue.pop() {
+        fs::create_dir_all(&destination)?;
+        for entry in fs::read_dir(&source)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            // Locks of the snapshot are of no use, `cargo` needs its own.
+            if file_name.to_string_lossy().starts_with(".package-cache") {
+                continue;
+            }
+            let target = destination.join(&file_name);
+            if entry.file_type()?.is_dir() && depth < MIRRORED_DEPTH {
+                queue.push((entry.path(), target, depth + 1));
+            } else {
+                link(&entry.path(), &target)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn link(original: &Path, link: &Path) -> Result<(), anyhow::Error> {
+    if fs::symlink_metadata(link).is_ok() {
+        return Ok(());
+    }
+    symlink(original, link).with_context(|| format!("Failed to link {:?} to {:?}", link, original))
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    if original.is_dir() {
+        std::os::windows::fs::symlink_dir(original, link)
+    } else {
+        std::os::windows::fs::symlink_file(original, link)
+    }
+}
@@ -0,0 +1,197 @@
+//! An experimental content-addressed store for cooked artifacts, shared by the images of
+//! several services (e.g. through a common base layer or a cache volume): identical files,
+//! typically the artifacts of the dependencies they have in common, are stored once.
+//!
+//! ```text
+//! <store>/objects/<2 hex digits>/<rest of the SHA-256>[-x]
+//! <store>/indexes/<key>.json
+//! ```
+//!
+//! After cooking, every file of the target directory gets an object, named after its contents
+//! (`-x` for executables), and the file is replaced by a hardlink to it. The index lists the
+//! files of the target directory with their objects, under a key derived from the recipe: when
+//! cooking the same recipe again, the target directory is rebuilt with hardlinks from the index
+//! first, and `cargo` finds everything fresh. Files are copied where hardlinks cannot be made
+//! (e.g. the store lives on another filesystem).
+use crate::artifacts::{scan, sha256};
+use crate::{Recipe, RecipeFormat};
+use anyhow::Context;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+}
+
+/// The files of a target directory, as they were when it was last stored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Index {
+    /// Sorted by path.
+    files: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct IndexEntry {
+    /// Relative to the target directory.
+    path: PathBuf,
+    sha256: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    executable: bool,
+}
+
+/// What was done to the files of a target directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    pub files: usize,
+    /// Files whose contents were not in the store yet (when storing) or were missing from it
+    /// (when restoring).
+    pub new_objects: usize,
+}
+
+impl ArtifactStore {
+    pub fn new(root: PathBuf) -> Self {
+        ArtifactStore { root }
+    }
+
+    /// The key of the index of `target_directory`, cooked from `recipe`.
+    pub fn index_key(recipe: &Recipe, target_directory: &Path) -> Result<String, anyhow::Error> {
+        let mut hasher = Sha256::new();
+        hasher.update(recipe.serialize(RecipeFormat::Json)?);
+        hasher.update([0]);
+        hasher.update(target_directory.to_string_lossy().as_bytes());
+        Ok(hex(&hasher.finalize()))
+    }
+
+    /// Rebuild `target_directory` from the index `key`, leaving the files it already has alone.
+    /// `None` if there is no such index.
+    pub fn restore(
+        &self,
+        key: &str,
+        target_directory: &Path,
+    ) -> Result<Option<StoreStats>, anyhow::Error> {
+        let index_path = self.index_path(key);
+        if !index_path.is_file() {
+            return Ok(None);
+        }
+        let index: Index = serde_json::from_str(&fs::read_to_string(&index_path)?)
+            .with_context(|| format!("Failed to parse the store index {:?}", index_path))?;
+        let mut stats = StoreStats::default();
+        for entry in index.files {
+            let path = target_directory.join(&entry.path);
+            if fs::symlink_metadata(&path).is_ok() {
+                continue;
+            }
+            let object = self.object_path(&entry.sha256, entry.executable);
+            if !object.is_file() {
+                log::warn!("{:?} is missing from the store, skipping it.", object);
+                stats.new_objects += 1;
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            link_or_copy(&object, &path)?;
+            stats.files += 1;
+        }
+        Ok(Some(stats))
+    }
+
+    /// Add the files of `target_directory` to the store, replacing them with hardlinks to their
+    /// objects, and record them in the index `key`.
+    pub fn store(&self, key: &str, target_directory: &Path) -> Result<StoreStats, anyhow::Error> {
+        let mut stats = StoreStats::default();
+        let mut files = vec![];
+        for path in scan(&[target_directory.to_path_buf()])?.into_keys() {
+            // The lock `cargo` takes on the directory must not be shared with other ones.
+            if path.file_name().is_some_and(|name| name == ".cargo-lock") {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            let entry = IndexEntry {
+                path: path.strip_prefix(target_directory)?.to_path_buf(),
+                sha256: sha256(&path)?,
+                executable: is_executable(&metadata),
+            };
+            let object = self.object_path(&entry.sha256, entry.executable);
+            match fs::metadata(&object) {
+                Ok(object_metadata) => {
+                    if !is_same_file(&metadata, &object_metadata) {
+                        fs::remove_file(&path)?;
+                        link_or_copy(&object, &path)?;
+                    }
+                }
+                Err(_) => {
+                    fs::create_dir_all(object.parent().unwrap())?;
+                    link_or_copy(&path, &object)?;
+                    stats.new_objects += 1;
+                }
+            }
+            stats.files += 1;
+            files.push(entry);
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let index_path = self.index_path(key);
+        fs::create_dir_all(index_path.parent().unwrap())?;
+        fs::write(&index_path, serde_json::to_string_pretty(&Index { files })?)?;
+        Ok(stats)
+    }
+
+    fn object_path(&self, sha256: &str, executable: bool) -> PathBuf {
+        let (prefix, rest) = sha256.split_at(2.min(sha256.len()));
+        let name = if executable {
+            format!("{}-x", rest)
+        } else {
+            rest.to_string()
+        };
+        self.root.join("objects").join(prefix).join(name)
+    }
+
+    fn index_path(&self, key: &str) -> PathBuf {
+        self.root.join("indexes").join(format!("{}.json", key))
+    }
+}
+
+/// Hardlink `destination` to `source`, or copy it (modification time included) if they are
+/// not on the same filesystem.
+fn link_or_copy(source: &Path, destination: &Path) -> Result<(), anyhow::Error> {
+    if fs::hard_link(source, destination).is_ok() {
+        return Ok(());
+    }
+    fs::copy(source, destination)?;
+    let modified = fs::metadata(source)?.modified()?;
+    std::fs::File::options()
+        .write(true)
+        .open(destination)?
+        .set_modified(modified)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_a: &std::fs::Metadata, _b: &std::fs::Metadata) -> bool {
+    false
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,147 @@
+//! The critical path of a cook: the chain of compilation units, each one waiting on the
+//! previous one, that bounds the duration of the build. Speeding up any other unit does not make
+//! the build faster; trimming the features of these, or baking them into a base image, does.
+//!
+//! It is computed from the report `cargo build --timings` writes to
+//! `<target dir>/cargo-timings/cargo-timing.html`, which embeds the data of every unit it built:
+//! each unit records the units it unblocked when it finished, or when its metadata was ready.
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where `cargo build --timings` writes its latest report, relative to the target directory.
+const TIMING_REPORT: &str = "cargo-timings/cargo-timing.html";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CriticalPath {
+    /// When the last unit finished, in seconds since the start of the build.
+    pub duration: f64,
+    /// The number of units that were built, fresh ones excluded.
+    pub built_units: usize,
+    /// From the first unit to be built to the last one to finish.
+    pub units: Vec<TimedUnit>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimedUnit {
+    pub name: String,
+    pub version: String,
+    /// The target of the package the unit builds, e.g. `app "bin"`, or empty for a library.
+    pub target: String,
+    /// In seconds since the start of the build.
+    pub start: f64,
+    /// In seconds.
+    pub duration: f64,
+}
+
+/// An entry of `UNIT_DATA` in the timing report.
+#[derive(Deserialize)]
+struct UnitData {
+    i: usize,
+    name: String,
+    version: String,
+    #[serde(default)]
+    target: String,
+    start: f64,
+    duration: f64,
+    // Called `unlocked_units` and `unlocked_rmeta_units` by older versions of cargo.
+    #[serde(default, alias = "unlocked_units")]
+    unblocked_units: Vec<usize>,
+    #[serde(default, alias = "unlocked_rmeta_units")]
+    unblocked_rmeta_units: Vec<usize>,
+}
+
+impl CriticalPath {
+    /// The path of the timing report in `target_directory`, if `cargo` wrote one.
+    pub fn report_path(target_directory: &Path) -> Option<PathBuf> {
+        let path = target_directory.join(TIMING_REPORT);
+        path.is_file().then_some(path)
+    }
+
+    /// The critical path of the build described by a timing report, `None` if it did not
+    /// build anything.
+    pub fn from_timing_report(report: &str) -> Result<Option<Self>, anyhow::Error> {
+        let units = unit_data(report)?;
+        let Some(last) = units
+            .iter()
+            .max_by(|a, b| (a.start + a.duration).total_cmp(&(b.start + b.duration)))
+        else {
+            return Ok(None);
+        };
+        // A unit is unblocked by the last of its dependencies to be ready: walk them back.
+        let mut path = vec![last];
+        while let Some(previous) = units.iter().find(|unit| {
+            let current = path[path.len() - 1].i;
+            unit.unblocked_units.contains(&current) || unit.unblocked_rmeta_units.contains(&current)
+        }) {
+            if path.iter().any(|unit| unit.i == previous.i) {
+                break;
+            }
+            path.push(previous);
+        }
+        path.reverse();
+
+        Ok(Some(CriticalPath {
+            duration: last.start + last.duration,
+            built_units: units.len(),
+            units: path.into_iter().map(TimedUnit::from).collect(),
+        }))
+    }
+}
+
+impl TimedUnit {
+    /// Every unit built according to a timing report.
+    pub fn from_timing_report(report: &str) -> Result<Vec<Self>, anyhow::Error> {
+        Ok(unit_data(report)?.iter().map(TimedUnit::from).collect())
+    }
+}
+
+impl From<&UnitData> for TimedUnit {
+    fn from(unit: &UnitData) -> Self {
+        TimedUnit {
+            name: unit.name.clone(),
+            version: unit.version.clone(),
+            target: unit.target.trim().to_string(),
+            start: unit.start,
+            duration: unit.duration,
+        }
+    }
+}
+
+fn unit_data(report: &str) -> Result<Vec<UnitData>, anyhow::Error> {
+    const UNIT_DATA: &str = "const UNIT_DATA = ";
+    let start = report
+        .find(UNIT_DATA)
+        .context("The timing report has no unit data.")?
+        + UNIT_DATA.len();
+    serde_json::Deserializer::from_str(&report[start..])
+        .into_iter()
+        .next()
+        .context("The timing report has no unit data.")?
+        .context("Failed to parse the unit data of the timing report.")
+}
+
+impl std::fmt::Display for CriticalPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Critical path: {} of {} units, {:.1}s in total",
+            self.units.len(),
+            self.built_units,
+            self.duration
+        )?;
+        writeln!(f, "  {:>8}  {:>8}  unit", "start", "duration")?;
+        for unit in &self.units {
+            write!(
+                f,
+                "  {:>7.1}s  {:>7.1}s  {} v{}",
+                unit.start, unit.duration, unit.name, unit.version
+            )?;
+            if !unit.target.is_empty() {
+                write!(f, " ({})", unit.target)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
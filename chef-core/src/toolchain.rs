@@ -0,0 +1,255 @@
+//! Toolchain files (`rust-toolchain.toml`) are a rustup feature: plain toolchains, e.g. the ones
+//! shipped by Linux distributions or Docker images built without rustup, ignore them.
+//!
+//! The recipe carries the toolchain file at the root of the project, and `cook` builds the
+//! dependencies with `cargo +<channel>`: `cargo chef` runs with the toolchain `cargo` was invoked
+//! with, which is not necessarily the one the final build picks from the file. `rustup` itself
+//! is only invoked when asked to install the toolchain; without it, `cook` warns that the pinned
+//! toolchain cannot be honored.
+use crate::skeleton::SkeletonFile;
+use anyhow::Context;
+use cargo_metadata::semver::Version;
+use std::path::Path;
+use std::process::Command;
+
+/// The files rustup reads the toolchain to use from, by order of precedence.
+pub(crate) const TOOLCHAIN_FILES: [&str; 2] = ["rust-toolchain", "rust-toolchain.toml"];
+
+/// The toolchain a toolchain file pins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinnedToolchain {
+    pub channel: String,
+    pub components: Vec<String>,
+    pub targets: Vec<String>,
+}
+
+impl PinnedToolchain {
+    /// The toolchain pinned by `file`, `None` for a custom toolchain (`path = "..."`), which
+    /// rustup cannot select with `+<channel>`.
+    pub fn from_file(file: &SkeletonFile) -> Result<Option<Self>, anyhow::Error> {
+        let contents = file.contents.trim();
+        // The legacy format is the name of the channel alone, TOML is accepted in both files.
+        if !contents.contains('=') && !contents.starts_with('[') {
+            return Ok(Some(PinnedToolchain {
+                channel: contents.to_string(),
+                components: vec![],
+                targets: vec![],
+            }));
+        }
+        let parsed: toml::Value = toml::from_str(contents).with_context(|| {
+            format!("Failed to parse the {} file.", file.relative_path.display())
+        })?;
+        let Some(toolchain) = parsed.get("toolchain") else {
+            return Err(anyhow::anyhow!(
+                "The {} file has no `[toolchain]` table.",
+                file.relative_path.display()
+            ));
+        };
+        let Some(channel) = toolchain.get("channel").and_then(|c| c.as_str()) else {
+            if toolchain.get("path").is_none() {
+                return Err(anyhow::anyhow!(
+                    "The {} file pins neither a `channel` nor a `path`.",
+                    file.relative_path.display()
+                ));
+            }
+            return Ok(None);
+        };
+        let strings = |key: &str| -> Vec<String> {
+            toolchain
+                .get(key)
+                .and_then(|values| values.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|value| value.as_str().map(String::from))
+                .collect()
+        };
+        Ok(Some(PinnedToolchain {
+            channel: channel.to_string(),
+            components: strings("components"),
+            targets: strings("targets"),
+        }))
+    }
+
+    /// Install the toolchain with rustup, with its components and targets, if it is not
+    /// already.
+    pub fn install(&self) -> Result<(), anyhow::Error> {
+        let mut command = Command::new("rustup");
+        command.args(["toolchain", "install", &self.channel]).args([
+            "--profile",
+            "minimal",
+            "--no-self-update",
+        ]);
+        for component in &self.components {
+            command.args(["--component", component]);
+        }
+        for target in &self.targets {
+            command.args(["--target", target]);
+        }
+        let status = command
+            .status()
+            .with_context(|| format!("Failed to run `rustup` to install `{}`", self.channel))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to install the toolchain `{}`: rustup exited with {}",
+                self.channel,
+                status
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Add the `targets` that are not installed to `toolchain` (or to the one rustup picks) with
+/// `rustup target add`. Custom targets, i.e. paths to a `.json` specification, are left alone:
+/// rustup does not ship them.
+pub(crate) fn install_targets(
+    toolchain: Option<&str>,
+    targets: &[String],
+) -> Result<(), anyhow::Error> {
+    let targets: Vec<&String> = targets
+        .iter()
+        .filter(|target| !target.ends_with(".json"))
+        .collect();
+    if targets.is_empty() {
+        return Ok(());
+    }
+    if !rustup_is_installed() {
+        return Err(anyhow::anyhow!(
+            "rustup is not installed: the targets `{}` cannot be installed automatically. \
+            Install their standard library along with the toolchain instead.",
+            targets
+                .iter()
+                .map(|target| target.as_str())
+                .collect::<Vec<_>>()
+                .join("`, `")
+        ));
+    }
+    let rustup = |args: &[&str]| {
+        let mut command = Command::new("rustup");
+        command.args(args);
+        if let Some(toolchain) = toolchain {
+            command.args(["--toolchain", toolchain]);
+        }
+        command
+    };
+    let output = rustup(&["target", "list", "--installed"])
+        .output()
+        .context("Failed to run `rustup target list`")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to list the installed targets: rustup exited with {}\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    let installed = String::from_utf8(output.stdout)?;
+    let missing: Vec<&str> = targets
+        .iter()
+        .map(|target| target.as_str())
+        .filter(|target| !installed.lines().any(|line| line.trim() == *target))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    log::info!("Installing the targets `{}`", missing.join("`, `"));
+    let mut args = vec!["target", "add"];
+    args.extend(&missing);
+    let status = rustup(&args)
+        .status()
+        .context("Failed to run `rustup target add`")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "Failed to install the targets `{}`: rustup exited with {}",
+            missing.join("`, `"),
+            status
+        ));
+    }
+    Ok(())
+}
+
+/// A warning if a toolchain file applies to `directory` while rustup is not installed: cargo
+/// uses whatever toolchain it belongs to instead of the pinned one.
+pub fn ignored_toolchain_file(directory: &Path) -> Option<String> {
+    if rustup_is_installed() {
+        return None;
+    }
+    let toolchain_file = directory.ancestors().find_map(|directory| {
+        TOOLCHAIN_FILES
+            .iter()
+            .map(|file| directory.join(file))
+            .find(|path| path.is_file())
+    })?;
+    Some(format!(
+        "rustup is not installed: the toolchain pinned in {} is ignored and dependencies are \
+        built with the toolchain `cargo` belongs to. Make sure the final build uses the same one.",
+        toolchain_file.display()
+    ))
+}
+
+/// Whether rustup manages the toolchain, without running it: its proxies set `RUSTUP_HOME` and
+/// `RUSTUP_TOOLCHAIN` for the processes they spawn, otherwise we look for it in `PATH`.
+pub(crate) fn rustup_is_installed() -> bool {
+    if std::env::var_os("RUSTUP_TOOLCHAIN").is_some() || std::env::var_os("RUSTUP_HOME").is_some() {
+        return true;
+    }
+    is_in_path("rustup")
+}
+
+/// Whether the executable `name` can be found in `PATH`, without running it.
+pub(crate) fn is_in_path(name: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|directory| {
+        [name.to_string(), format!("{}.exe", name)]
+            .iter()
+            .map(|binary| directory.join(binary))
+            .any(|binary| binary.is_file())
+    })
+}
+
+/// The version of `rustc` in `toolchain` (`rustc +<toolchain>`), or of the one `cargo` uses,
+/// without its pre-release tag: `cargo` accepts nightlies of the version a package requires.
+pub(crate) fn rustc_version(toolchain: Option<&str>) -> Result<Version, anyhow::Error> {
+    let mut command = match toolchain {
+        Some(toolchain) => {
+            let mut command = Command::new("rustc");
+            command.arg(format!("+{}", toolchain));
+            command
+        }
+        None => Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string())),
+    };
+    let output = command
+        .arg("-vV")
+        .output()
+        .context("Failed to run `rustc -vV`")?;
+    let output = String::from_utf8(output.stdout)?;
+    let release = output
+        .lines()
+        .find_map(|line| line.strip_prefix("release: "))
+        .context("Failed to determine the version of rustc from `rustc -vV`")?;
+    let version = Version::parse(release)
+        .with_context(|| format!("Failed to parse the version of rustc, {:?}", release))?;
+    Ok(Version::new(version.major, version.minor, version.patch))
+}
+
+/// A `rust-version`, where the minor and patch versions may be left out (`1.70`).
+pub(crate) fn parse_rust_version(rust_version: &str) -> Result<Version, anyhow::Error> {
+    let mut parts = rust_version.trim().split('.');
+    let mut next = || -> Result<u64, anyhow::Error> {
+        match parts.next() {
+            Some(part) => part
+                .parse()
+                .with_context(|| format!("{:?} is not a version of rustc", rust_version)),
+            None => Ok(0),
+        }
+    };
+    let version = Version::new(next()?, next()?, next()?);
+    if parts.next().is_some() {
+        return Err(anyhow::anyhow!(
+            "{:?} is not a version of rustc",
+            rust_version
+        ));
+    }
+    Ok(version)
+}
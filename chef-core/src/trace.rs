@@ -0,0 +1,227 @@
+//! Timings of the phases of `prepare` and `cook` (discovery, parsing, masking, writing the
+//! skeleton, building, cleaning up), recorded as `tracing` spans and written to a file once the
+//! command is over, to profile them on large repositories.
+//!
+//! Two formats are supported: the Chrome trace event format, which `chrome://tracing` and
+//! Perfetto open, and OTLP/JSON, as read by the `otlpjsonfile` receiver of the OpenTelemetry
+//! Collector to forward spans to any OTLP backend.
+use anyhow::Context;
+use fs_err as fs;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// The Chrome trace event format.
+    Chrome,
+    /// OTLP/JSON, one `ExportTraceServiceRequest`.
+    Otlp,
+}
+
+/// Collects the spans of the process, once installed as the global `tracing` subscriber.
+#[derive(Debug, Clone)]
+pub struct TraceRecorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+}
+
+#[derive(Debug, Clone)]
+struct RecordedSpan {
+    /// Unique within the process, unlike `tracing` span ids which are recycled.
+    id: u64,
+    parent: Option<u64>,
+    name: &'static str,
+    fields: Vec<(&'static str, String)>,
+    thread: u64,
+    start: SystemTime,
+    duration: Duration,
+}
+
+/// What is known about a span while it is open, stored in its extensions.
+struct OpenSpan {
+    id: u64,
+    fields: Vec<(&'static str, String)>,
+    start: SystemTime,
+}
+
+struct FieldVisitor<'a>(&'a mut Vec<(&'static str, String)>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.push((field.name(), format!("{:?}", value)));
+    }
+}
+
+static NEXT_SPAN_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+impl<S> Layer<S> for TraceRecorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: LayerContext<'_, S>) {
+        let Some(span) = context.span(id) else {
+            return;
+        };
+        let mut fields = vec![];
+        attributes.record(&mut FieldVisitor(&mut fields));
+        span.extensions_mut().insert(OpenSpan {
+            id: NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed),
+            fields,
+            start: SystemTime::now(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, context: LayerContext<'_, S>) {
+        if let Some(span) = context.span(id) {
+            if let Some(open) = span.extensions_mut().get_mut::<OpenSpan>() {
+                values.record(&mut FieldVisitor(&mut open.fields));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, context: LayerContext<'_, S>) {
+        let Some(span) = context.span(&id) else {
+            return;
+        };
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<OpenSpan>().map(|open| open.id));
+        let Some(open) = span.extensions_mut().remove::<OpenSpan>() else {
+            return;
+        };
+        let recorded = RecordedSpan {
+            id: open.id,
+            parent,
+            name: span.name(),
+            fields: open.fields,
+            thread: THREAD_ID.with(|id| *id),
+            duration: open.start.elapsed().unwrap_or_default(),
+            start: open.start,
+        };
+        self.spans.lock().unwrap().push(recorded);
+    }
+}
+
+impl TraceRecorder {
+    /// Record the spans of the whole process from now on.
+    pub fn install() -> Result<Self, anyhow::Error> {
+        let recorder = TraceRecorder {
+            spans: Arc::new(Mutex::new(vec![])),
+        };
+        tracing::subscriber::set_global_default(Registry::default().with(recorder.clone()))
+            .context("Failed to install the trace recorder.")?;
+        Ok(recorder)
+    }
+
+    /// Write the spans closed so far to `path`.
+    pub fn write(&self, path: &Path, format: TraceFormat) -> Result<(), anyhow::Error> {
+        let mut spans = self.spans.lock().unwrap().clone();
+        spans.sort_by_key(|span| (span.start, span.id));
+        let trace = match format {
+            TraceFormat::Chrome => chrome_trace(&spans),
+            TraceFormat::Otlp => otlp_trace(&spans),
+        };
+        fs::write(path, serde_json::to_string_pretty(&trace)?)
+            .with_context(|| format!("Failed to write the trace to {:?}", path))
+    }
+}
+
+fn chrome_trace(spans: &[RecordedSpan]) -> serde_json::Value {
+    let events: Vec<_> = spans
+        .iter()
+        .map(|span| {
+            let args: serde_json::Map<_, _> = span
+                .fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone().into()))
+                .collect();
+            json!({
+                "name": span.name,
+                "cat": "cargo-chef",
+                "ph": "X",
+                "ts": micros(span.start),
+                "dur": span.duration.as_micros() as u64,
+                "pid": std::process::id(),
+                "tid": span.thread,
+                "args": args,
+            })
+        })
+        .collect();
+    json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+}
+
+fn otlp_trace(spans: &[RecordedSpan]) -> serde_json::Value {
+    // A trace id of 16 bytes, unique enough for a single process.
+    let mut hasher = Sha256::new();
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(nanos(spans.first().map_or(SystemTime::now(), |s| s.start)).to_le_bytes());
+    let trace_id = hex(&hasher.finalize()[..16]);
+    let spans: Vec<_> = spans
+        .iter()
+        .map(|span| {
+            let attributes: Vec<_> = span
+                .fields
+                .iter()
+                .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+                .collect();
+            let mut otlp = json!({
+                "traceId": trace_id,
+                "spanId": format!("{:016x}", span.id),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": nanos(span.start).to_string(),
+                "endTimeUnixNano": nanos(span.start + span.duration).to_string(),
+                "attributes": attributes,
+            });
+            if let Some(parent) = span.parent {
+                otlp["parentSpanId"] = format!("{:016x}", parent).into();
+            }
+            otlp
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": "cargo-chef" } },
+                    { "key": "service.version", "value": { "stringValue": env!("CARGO_PKG_VERSION") } },
+                ],
+            },
+            "scopeSpans": [{ "scope": { "name": "cargo-chef" }, "spans": spans }],
+        }],
+    })
+}
+
+fn micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+fn nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
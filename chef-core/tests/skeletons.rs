@@ -0,0 +1,4306 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use chef_core::{NetworkSettings, PackageStub, PinnedToolchain, Skeleton, SkeletonFile};
+use expect_test::{expect, Expect};
+use predicates::prelude::*;
+
+#[test]
+pub fn no_workspace() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+
+[[bin]]
+name = "test-dummy"
+path = "src/main.rs"
+
+[dependencies]
+"#,
+        )
+        .touch("src/main.rs")
+        .touch("Cargo.lock")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory
+        .child("src")
+        .child("main.rs")
+        .assert("fn main() {}");
+    cook_directory
+        .child("Cargo.lock")
+        .assert(predicate::path::exists());
+
+    // Act (no_std)
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), true)
+        .unwrap();
+
+    // Assert (no_std)
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory.child("src").child("main.rs").assert(
+        r#"#![no_std]
+#![no_main]
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+"#,
+    );
+    cook_directory
+        .child("Cargo.lock")
+        .assert(predicate::path::exists());
+}
+
+#[test]
+pub fn workspace() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = [
+    "src/project_a",
+    "src/project_b",
+]        
+"#,
+        )
+        .bin_package(
+            "src/project_a",
+            r#"
+[package]
+name = "project_a"
+version = "0.1.0"
+edition = "2018"
+
+[[bin]]
+name = "test-dummy"
+path = "src/main.rs"
+
+[dependencies]
+uuid = { version = "=0.8.0", features = ["v4"] }        
+"#,
+        )
+        .lib_package(
+            "src/project_b",
+            r#"
+[package]
+name = "project_b"
+version = "0.1.0"
+edition = "2018"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+uuid = { version = "=0.8.0", features = ["v4"] }        
+"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(3, skeleton.manifests.len());
+    cook_directory
+        .child("src")
+        .child("project_a")
+        .child("src")
+        .child("main.rs")
+        .assert("fn main() {}");
+    cook_directory
+        .child("src")
+        .child("project_b")
+        .child("src")
+        .child("lib.rs")
+        .assert("");
+
+    // Act (no_std)
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), true)
+        .unwrap();
+
+    // Assert (no_std)
+    assert_eq!(3, skeleton.manifests.len());
+    cook_directory
+        .child("src")
+        .child("project_a")
+        .child("src")
+        .child("main.rs")
+        .assert(
+            r#"#![no_std]
+#![no_main]
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+"#,
+        );
+    cook_directory
+        .child("src")
+        .child("project_b")
+        .child("src")
+        .child("lib.rs")
+        .assert("#![no_std]");
+}
+
+#[test]
+pub fn benches() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+
+[[bench]]
+name = "basics"
+harness = false
+
+[dependencies]
+"#,
+        )
+        .touch("benches/basics.rs")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory
+        .child("benches")
+        .child("basics.rs")
+        .assert("fn main() {}");
+
+    // no_std benches are not a thing yet
+}
+
+#[test]
+pub fn tests() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+
+[[test]]
+name = "foo"
+"#,
+        )
+        .touch("tests/foo.rs")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory.child("tests").child("foo.rs").assert("");
+
+    // Act (no_std)
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), true)
+        .unwrap();
+
+    // Assert (no_std)
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory.child("tests").child("foo.rs").assert(
+        r#"#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(test_runner)]
+
+#[no_mangle]
+pub extern "C" fn _init() {}
+
+fn test_runner(_: &[&dyn Fn()]) {}
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+"#,
+    );
+}
+
+#[test]
+pub fn tests_no_harness() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+
+[[test]]
+name = "foo"
+harness = false
+"#,
+        )
+        .touch("tests/foo.rs")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    cook_directory
+        .child("tests")
+        .child("foo.rs")
+        .assert("fn main() {}");
+}
+
+#[test]
+pub fn harness_is_set_per_target() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+
+[[bench]]
+name = "criterion"
+harness = false
+
+[[test]]
+name = "custom"
+harness = false
+"#,
+        )
+        .touch_multiple(&[
+            "benches/criterion.rs",
+            "benches/libtest.rs",
+            "tests/custom.rs",
+            "tests/libtest.rs",
+        ])
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    cook_directory
+        .child("benches/criterion.rs")
+        .assert("fn main() {}");
+    cook_directory.child("benches/libtest.rs").assert("");
+    cook_directory
+        .child("tests/custom.rs")
+        .assert("fn main() {}");
+    cook_directory.child("tests/libtest.rs").assert("");
+}
+
+#[test]
+pub fn examples() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+
+[[example]]
+name = "foo"
+"#,
+        )
+        .touch("examples/foo.rs")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory
+        .child("examples")
+        .child("foo.rs")
+        .assert("fn main() {}");
+
+    // Act (no_std)
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), true)
+        .unwrap();
+
+    // Assert (no_std)
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory.child("examples").child("foo.rs").assert(
+        r#"#![no_std]
+#![no_main]
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
+"#,
+    );
+}
+
+#[test]
+pub fn test_auto_bin_ordering() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+"#,
+        )
+        .touch_multiple(&[
+            "src/bin/a.rs",
+            "src/bin/b.rs",
+            "src/bin/c.rs",
+            "src/bin/d.rs",
+            "src/bin/e.rs",
+            "src/bin/f.rs",
+        ])
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // What we're testing is that auto-directories come back in the same order.
+    // Since it's possible that the directories just happen to come back in the
+    // same order randomly, we'll run this a few times to increase the
+    // likelihood of triggering the problem if it exists.
+    for _ in 0..5 {
+        let skeleton2 = Skeleton::derive(project.path(), None).unwrap();
+        assert_eq!(
+            skeleton, skeleton2,
+            "Skeletons of equal directories are not equal. Check [[bin]] ordering in manifest?"
+        );
+    }
+}
+
+#[test]
+pub fn config_toml() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .bin_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+"#,
+        )
+        .touch(".cargo/config.toml")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = &skeleton.manifests[0];
+    assert_eq!(Path::new("Cargo.toml"), manifest.relative_path);
+    cook_directory
+        .child("src")
+        .child("main.rs")
+        .assert("fn main() {}");
+    cook_directory
+        .child(".cargo")
+        .child("config.toml")
+        .assert(predicate::path::exists());
+}
+
+#[test]
+pub fn version() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .bin_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "1.2.3"
+edition = "2018"
+
+[dependencies]
+"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(1, skeleton.manifests.len());
+    let manifest = skeleton.manifests[0].clone();
+    assert!(manifest.contents.contains(r#"version = "0.0.1""#));
+    assert!(!manifest.contents.contains(r#"version = "1.2.3""#));
+}
+
+#[test]
+pub fn version_lock() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .bin_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "1.2.3"
+edition = "2018"
+
+[dependencies]        
+"#,
+        )
+        .file(
+            "Cargo.lock",
+            r#"
+# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "test-dummy"
+version = "1.2.3"
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    let lock_file = skeleton.lock_file.expect("there should be a lock_file");
+    assert!(!lock_file.contains(
+        r#"
+[[package]]
+name = "test-dummy"
+version = "1.2.3"
+"#
+    ));
+    assert!(lock_file.contains(
+        r#"
+[[package]]
+name = "test-dummy"
+version = "0.0.1"
+"#
+    ));
+}
+
+#[test]
+pub fn workspace_version_lock() {
+    // Arrange
+    // project-a is named with a dash to test that such unnormalized name can be handled.
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = [
+    "src/project-a",
+    "src/project_b",
+]
+"#,
+        )
+        .bin_package(
+            "src/project-a",
+            r#"
+[package]
+name = "project-a"
+version = "1.2.3"
+edition = "2018"
+
+[[bin]]
+name = "test-dummy"
+path = "src/main.rs"
+
+[dependencies]
+either = { version = "=1.8.1" }        
+"#,
+        )
+        .lib_package(
+            "src/project_b",
+            r#"
+[package]
+name = "project_b"
+version = "4.5.6"
+edition = "2018"
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+either = { version = "=1.8.1" }
+project-a = { version = "1.2.3", path = "../project-a" }   
+"#,
+        )
+        .file(
+            "Cargo.lock",
+            r#"
+# This file is automatically @generated by Cargo.
+# It is not intended for manual editing.
+version = 3
+
+[[package]]
+name = "either"
+version = "1.8.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "7fcaabb2fef8c910e7f4c7ce9f67a1283a1715879a7c230ca9d6d1ae31f16d91"
+
+[[package]]
+name = "project-a"
+version = "1.2.3"
+dependencies = [
+ "either",
+]
+
+[[package]]
+name = "project_b"
+version = "4.5.6"
+dependencies = [
+ "either",
+ "project_a",
+]
+"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    let lock_file = skeleton.lock_file.expect("there should be a lock_file");
+    assert!(!lock_file.contains(
+        r#"
+[[package]]
+name = "project-a"
+version = "1.2.3"
+"#
+    ));
+    assert!(lock_file.contains(
+        r#"
+[[package]]
+name = "project-a"
+version = "0.0.1"
+"#
+    ));
+    assert!(!lock_file.contains(
+        r#"
+[[package]]
+name = "project_b"
+version = "4.5.6"
+"#
+    ));
+    assert!(lock_file.contains(
+        r#"
+[[package]]
+name = "project_b"
+version = "0.0.1"
+"#
+    ));
+    assert!(lock_file.contains(
+        r#"
+[[package]]
+name = "either"
+version = "1.8.1"
+"#
+    ));
+
+    let first = skeleton.manifests[0].clone();
+    check(
+        &first.contents,
+        expect_test::expect![[r#"
+        [workspace]
+        members = ["src/project-a", "src/project_b"]
+    "#]],
+    );
+    let second = skeleton.manifests[1].clone();
+    check(
+        &second.contents,
+        expect_test::expect![[r#"
+            bench = []
+            test = []
+            example = []
+
+            [package]
+            name = "project-a"
+            edition = "2018"
+            version = "0.0.1"
+            autobins = true
+            autoexamples = true
+            autotests = true
+            autobenches = true
+
+            [dependencies.either]
+            version = "=1.8.1"
+
+            [[bin]]
+            path = "src/main.rs"
+            name = "test-dummy"
+            test = true
+            doctest = true
+            bench = true
+            doc = true
+            plugin = false
+            proc-macro = false
+            harness = true
+            required-features = []
+        "#]],
+    );
+    let third = skeleton.manifests[2].clone();
+    check(
+        &third.contents,
+        expect_test::expect![[r#"
+            bin = []
+            bench = []
+            test = []
+            example = []
+
+            [package]
+            name = "project_b"
+            edition = "2018"
+            version = "0.0.1"
+            autobins = true
+            autoexamples = true
+            autotests = true
+            autobenches = true
+
+            [dependencies.either]
+            version = "=1.8.1"
+
+            [dependencies.project-a]
+            version = "0.0.1"
+            path = "../project-a"
+
+            [lib]
+            test = true
+            doctest = true
+            bench = true
+            doc = true
+            plugin = false
+            proc-macro = false
+            harness = true
+            required-features = []
+            crate-type = ["cdylib"]
+        "#]],
+    );
+}
+
+#[test]
+pub fn ignore_vendored_directory() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .bin_package(
+            ".",
+            r#"
+[package]
+name = "test-dummy"
+version = "1.2.3"
+edition = "2018"
+
+[dependencies]
+rocket = "0.5.0-rc.1"
+    "#,
+        )
+        .file(
+            ".cargo/config.toml",
+            r#"
+[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "vendor"
+"#,
+        )
+        .lib_package(
+            "vendor/rocket",
+            r#"
+[package]
+edition = "2018"
+name = "rocket"
+version = "0.5.0-rc.1"
+authors = ["Sergio Benitez <sb@sergio.bz>"]
+build = "build.rs"
+description = "Web framework with a focus on usability, security, extensibility, and speed.\n"
+homepage = "https://rocket.rs"
+documentation = "https://api.rocket.rs/v0.5-rc/rocket/"
+readme = "../../README.md"
+keywords = ["rocket", "web", "framework", "server"]
+categories = ["web-programming::http-server"]
+license = "MIT OR Apache-2.0"
+repository = "https://github.com/SergioBenitez/Rocket"
+
+[package.metadata.docs.rs]
+all-features = true
+
+[dependencies.rocket_dep]
+version = "0.3.2"
+"#,
+        )
+        .file(
+            "vendor/rocket/.cargo-checksum.json",
+            r#"
+{"files": {}}
+"#,
+        )
+        .lib_package(
+            "vendor/rocket_dep",
+            r#"
+[package]
+edition = "2018"
+name = "rocket_dep"
+version = "0.3.2"
+authors = ["Test author"]
+description = "sample package representing all of rocket's dependencies"
+"#,
+        )
+        .file(
+            "vendor/rocket_dep/.cargo-checksum.json",
+            r#"
+{"files": {}}
+"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    assert_eq!(1, skeleton.manifests.len());
+}
+
+#[test]
+pub fn specify_member_in_workspace() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = [
+    "backend",
+    "ci",
+]
+    "#,
+        )
+        .bin_package(
+            "backend",
+            r#"
+[package]
+name = "backend"
+version = "0.1.0"
+edition = "2018"
+    "#,
+        )
+        .bin_package(
+            "ci",
+            r#"
+[package]
+name = "ci"
+version = "0.1.0"
+edition = "2018"
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), "backend".to_string().into()).unwrap();
+
+    // Assert:
+    // - that "ci" is *still* in the list of `skeleton`'s manifests
+    assert!(skeleton
+        .manifests
+        .iter()
+        .any(|manifest| !manifest.contents.contains("ci")));
+
+    // - that the list of members has been cut down to "backend", as expected
+    let gold = r#"[workspace]
+members = ["backend"]
+"#;
+    assert!(
+        skeleton
+            .manifests
+            .iter()
+            .find(|manifest| manifest.relative_path == Path::new("Cargo.toml"))
+            .unwrap()
+            .contents
+            == gold
+    );
+}
+
+#[test]
+pub fn mask_workspace_dependencies() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = [
+    "project_a",
+    "project_b",
+]
+
+[workspace.package]
+version = "0.2.0"
+edition = "2021"
+license = "Apache-2.0"
+
+[workspace.dependencies]
+anyhow = "1.0.66"
+project_a = { path = "project_a", version = "0.2.0" }
+    "#,
+        )
+        .bin_package(
+            "project_a",
+            r#"
+[package]
+name = "project_a"
+version.workspace = true
+edition.workspace = true
+license.workspace = true
+
+[dependencies]
+anyhow = { workspace = true }
+    "#,
+        )
+        .lib_package(
+            "project_b",
+            r#"
+[package]
+name = "project_b"
+version.workspace = true
+edition.workspace = true
+license.workspace = true
+
+[lib]
+crate-type = ["cdylib"]
+
+[dependencies]
+project_a = { workspace = true }
+anyhow = { workspace = true }
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    let first = skeleton.manifests[0].clone();
+    check(
+        &first.contents,
+        expect_test::expect![[r#"
+            [workspace]
+            members = ["project_a", "project_b"]
+
+            [workspace.dependencies]
+            anyhow = "1.0.66"
+
+            [workspace.dependencies.project_a]
+            version = "0.0.1"
+            path = "project_a"
+
+            [workspace.package]
+            edition = "2021"
+            version = "0.0.1"
+            license = "Apache-2.0"
+        "#]],
+    );
+
+    let second = skeleton.manifests[1].clone();
+    check(
+        &second.contents,
+        expect_test::expect![[r#"
+            bench = []
+            test = []
+            example = []
+
+            [package]
+            name = "project_a"
+            version = "0.0.1"
+            autobins = true
+            autoexamples = true
+            autotests = true
+            autobenches = true
+
+            [package.edition]
+            workspace = true
+
+            [package.license]
+            workspace = true
+
+            [dependencies.anyhow]
+            workspace = true
+
+            [[bin]]
+            path = "src/main.rs"
+            name = "project_a"
+            test = true
+            doctest = true
+            bench = true
+            doc = true
+            plugin = false
+            proc-macro = false
+            harness = true
+            required-features = []
+        "#]],
+    );
+
+    let third = skeleton.manifests[2].clone();
+    check(
+        &third.contents,
+        expect_test::expect![[r#"
+            bin = []
+            bench = []
+            test = []
+            example = []
+
+            [package]
+            name = "project_b"
+            version = "0.0.1"
+            autobins = true
+            autoexamples = true
+            autotests = true
+            autobenches = true
+
+            [package.edition]
+            workspace = true
+
+            [package.license]
+            workspace = true
+
+            [dependencies.anyhow]
+            workspace = true
+
+            [dependencies.project_a]
+            workspace = true
+
+            [lib]
+            test = true
+            doctest = true
+            bench = true
+            doc = true
+            plugin = false
+            proc-macro = false
+            harness = true
+            required-features = []
+            crate-type = ["cdylib"]
+        "#]],
+    );
+}
+
+#[test]
+pub fn workspace_glob_members() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["crates/*"]
+    "#,
+        )
+        .bin_package(
+            "crates/project_a",
+            r#"
+[package]
+name = "project_a"
+version = "0.0.1"
+    "#,
+        )
+        .lib_package(
+            "crates/project_b",
+            r#"
+[package]
+name = "project_b"
+version = "0.0.1"
+    "#,
+        )
+        .lib_package(
+            "crates-unused/project_c",
+            r#"
+[package]
+name = "project_c"
+version = "0.0.1"
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    assert_eq!(skeleton.manifests.len(), 3);
+}
+
+#[test]
+pub fn renamed_local_dependencies() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a", "b"]
+    "#,
+        )
+        .lib_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.5.0"
+
+[dependencies.c]
+version = "0.2.1"
+package = "b"
+path = "../b"
+    "#,
+        )
+        .lib_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.2.1"
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    check(
+        &skeleton.manifests[1].contents,
+        expect![[r#"
+        bin = []
+        bench = []
+        test = []
+        example = []
+
+        [package]
+        name = "a"
+        version = "0.0.1"
+        autobins = true
+        autoexamples = true
+        autotests = true
+        autobenches = true
+
+        [dependencies.c]
+        version = "0.0.1"
+        path = "../b"
+        package = "b"
+
+        [lib]
+        path = "src/lib.rs"
+        name = "a"
+        test = true
+        doctest = true
+        bench = true
+        doc = true
+        plugin = false
+        proc-macro = false
+        harness = true
+        required-features = []
+        crate-type = ["rlib"]
+    "#]],
+    );
+}
+
+#[test]
+pub fn artifact_dependencies() {
+    // `cargo metadata` refuses to parse artifact dependencies unless `-Z bindeps` is enabled.
+    std::env::set_var("RUSTC_BOOTSTRAP", "1");
+    std::env::set_var("CARGO_UNSTABLE_BINDEPS", "true");
+
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a", "b"]
+    "#,
+        )
+        .lib_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[build-dependencies]
+b = { path = "../b", artifact = "bin", target = "target", lib = true }
+    "#,
+        )
+        .bin_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.1.0"
+    "#,
+        )
+        .touch("b/src/lib.rs")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    assert!(skeleton.uses_artifact_dependencies().unwrap());
+    check(
+        &skeleton.manifests[1].contents,
+        expect![[r#"
+            bin = []
+            bench = []
+            test = []
+            example = []
+
+            [package]
+            name = "a"
+            version = "0.0.1"
+            autobins = true
+            autoexamples = true
+            autotests = true
+            autobenches = true
+
+            [build-dependencies.b]
+            path = "../b"
+            artifact = "bin"
+            target = "target"
+            lib = true
+
+            [lib]
+            path = "src/lib.rs"
+            name = "a"
+            test = true
+            doctest = true
+            bench = true
+            doc = true
+            plugin = false
+            proc-macro = false
+            harness = true
+            required-features = []
+            crate-type = ["rlib"]
+        "#]],
+    );
+}
+
+#[test]
+pub fn warnings() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            "app",
+            r#"
+[workspace]
+members = ["a"]
+    "#,
+        )
+        .lib_package(
+            "app/a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+shared = { path = "../../shared" }
+    "#,
+        )
+        .lib_package(
+            "shared",
+            r#"
+[package]
+name = "shared"
+version = "0.1.0"
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path().join("app"), None).unwrap();
+    let warnings = skeleton.warnings().unwrap();
+
+    // Assert
+    assert_eq!(2, warnings.len());
+    assert!(warnings[0].contains("There is no Cargo.lock"));
+    assert!(warnings[1].contains("`shared` (../../shared) lives outside of the project root"));
+}
+
+#[test]
+pub fn external_path_dependencies() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            "app",
+            r#"
+[workspace]
+members = ["a"]
+    "#,
+        )
+        .lib_package(
+            "app/a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+shared = { path = "../../shared/shared", features = ["x"] }
+    "#,
+        )
+        .manifest(
+            "shared",
+            r#"
+[workspace]
+members = ["shared", "utils"]
+
+[workspace.package]
+version = "0.3.0"
+edition = "2021"
+    "#,
+        )
+        .lib_package(
+            "shared/shared",
+            r#"
+[package]
+name = "shared"
+version.workspace = true
+edition.workspace = true
+
+[dependencies]
+utils = { path = "../utils" }
+
+[dev-dependencies]
+tokio = "1"
+
+[features]
+x = []
+    "#,
+        )
+        .lib_package(
+            "shared/utils",
+            r#"
+[package]
+name = "utils"
+version.workspace = true
+edition.workspace = true
+    "#,
+        )
+        .build();
+    let skeleton = Skeleton::derive(project.path().join("app"), None).unwrap();
+
+    // Act
+    let included = skeleton
+        .include_external_paths(project.path().join("app"))
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        manifests_of(&included),
+        [
+            "Cargo.toml",
+            "a/Cargo.toml",
+            "chef-external/shared/Cargo.toml",
+            "chef-external/utils/Cargo.toml"
+        ]
+    );
+    let manifest = |index: usize| -> toml::Value {
+        toml::from_str(&included.manifests[index].contents).unwrap()
+    };
+    assert_eq!(
+        manifest(0)["workspace"]["exclude"],
+        toml::Value::from(vec!["chef-external"])
+    );
+    assert_eq!(
+        manifest(1)["dependencies"]["shared"]["path"].as_str(),
+        Some("../chef-external/shared")
+    );
+    let shared = manifest(2);
+    assert_eq!(shared["package"]["version"].as_str(), Some("0.3.0"));
+    assert_eq!(shared["package"]["edition"].as_str(), Some("2021"));
+    assert_eq!(
+        shared["dependencies"]["utils"]["path"].as_str(),
+        Some("../utils")
+    );
+    assert!(shared.get("dev-dependencies").is_none());
+    assert!(shared["features"].get("x").is_some());
+    // Only the missing lockfile is left to warn about.
+    assert_eq!(1, included.warnings().unwrap().len());
+
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+    "#,
+        )
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    assert_eq!(
+        skeleton,
+        skeleton.include_external_paths(project.path()).unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+pub fn symlinks() {
+    use std::os::unix::fs::symlink;
+
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            "app",
+            r#"
+[workspace]
+members = ["crates/*"]
+    "#,
+        )
+        .lib_package(
+            "shared/foo",
+            r#"
+[package]
+name = "foo"
+version = "0.1.0"
+
+[dependencies]
+common = { path = "../common" }
+    "#,
+        )
+        .lib_package(
+            "shared/common",
+            r#"
+[package]
+name = "common"
+version = "0.1.0"
+    "#,
+        )
+        .build();
+    let app = project.path().join("app");
+    std::fs::create_dir_all(app.join("crates")).unwrap();
+    symlink("../../shared/foo", app.join("crates/foo")).unwrap();
+    symlink("../../shared/common", app.join("crates/common")).unwrap();
+
+    // Act
+    let skeleton = Skeleton::derive(&app, None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    std::fs::create_dir_all(cook_directory.path().join("crates/foo/src")).unwrap();
+    symlink(
+        project.path().join("shared/foo/src/lib.rs"),
+        cook_directory.path().join("crates/foo/src/lib.rs"),
+    )
+    .unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        manifests_of(&skeleton),
+        [
+            "Cargo.toml",
+            "crates/common/Cargo.toml",
+            "crates/foo/Cargo.toml"
+        ]
+    );
+    // `../common` is resolved next to the symlink, like `cargo` does: it is not external.
+    assert_eq!(skeleton, skeleton.include_external_paths(&app).unwrap());
+    // Symlinks in the way are replaced, their targets are left untouched.
+    let lib = cook_directory.path().join("crates/foo/src/lib.rs");
+    assert!(!lib.symlink_metadata().unwrap().file_type().is_symlink());
+    assert!(project.path().join("shared/foo/src/lib.rs").is_file());
+}
+
+#[test]
+pub fn lock_file_mismatches() {
+    // Arrange
+    let manifests = |dependencies: &str| {
+        CargoWorkspace::new()
+            .manifest(
+                ".",
+                r#"
+[workspace]
+members = ["app", "utils"]
+
+[workspace.dependencies]
+log = "0.4"
+    "#,
+            )
+            .lib_package(
+                "app",
+                &format!(
+                    r#"
+[package]
+name = "app"
+version = "1.2.0"
+
+[dependencies]
+utils = {{ path = "../utils", version = "0.3" }}
+log = {{ workspace = true }}
+{}
+    "#,
+                    dependencies
+                ),
+            )
+            .lib_package(
+                "utils",
+                r#"
+[package]
+name = "utils"
+version = "0.3.1"
+    "#,
+            )
+            .file(
+                "Cargo.lock",
+                r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "1.2.0"
+dependencies = ["log", "rand 0.8.5", "utils"]
+
+[[package]]
+name = "log"
+version = "0.4.20"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "rand"
+version = "0.7.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "rand"
+version = "0.8.5"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "utils"
+version = "0.3.1"
+    "#,
+            )
+            .build()
+    };
+
+    // Act
+    let satisfied = manifests(r#"random = { package = "rand", version = "0.8" }"#);
+    let satisfied = Skeleton::derive(satisfied.path(), None).unwrap();
+    let outdated = manifests("rand = \"0.7\"\nserde = \"1\"");
+    let outdated = Skeleton::derive(outdated.path(), None).unwrap();
+
+    // Assert
+    assert!(satisfied.lock_file_mismatches().unwrap().is_empty());
+    assert_eq!(
+        outdated.lock_file_mismatches().unwrap(),
+        [
+            "app/Cargo.toml: `rand` requires `rand ^0.7`, but Cargo.lock has 0.8.5.",
+            "app/Cargo.toml: `serde` is not among the dependencies of `app` in Cargo.lock.",
+        ]
+    );
+    let mut without_lock_file = satisfied;
+    without_lock_file.lock_file = None;
+    assert_eq!(without_lock_file.lock_file_mismatches().unwrap().len(), 1);
+}
+
+#[test]
+pub fn packaging_fields_are_stripped() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["app"]
+
+[workspace.package]
+exclude = ["fixtures/**"]
+    "#,
+        )
+        .lib_package(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+include = ["src/**", "templates/**"]
+exclude.workspace = true
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    for manifest in &skeleton.manifests {
+        let contents: toml::Value = toml::from_str(&manifest.contents).unwrap();
+        let packages = [
+            contents.get("package"),
+            contents.get("workspace").and_then(|w| w.get("package")),
+        ];
+        for package in packages.iter().flatten() {
+            assert!(package.get("include").is_none());
+            assert!(package.get("exclude").is_none());
+        }
+    }
+    cook_directory
+        .child("app/src/lib.rs")
+        .assert(predicate::path::is_file());
+}
+
+#[test]
+pub fn custom_target_paths() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+build = "build/main.rs"
+
+[lib]
+path = "lib/app.rs"
+
+[[bin]]
+name = "gen"
+path = "tools/gen.rs"
+
+[[example]]
+name = "demo"
+path = "demos/demo.rs"
+
+[[test]]
+name = "integration"
+path = "checks/integration.rs"
+
+[[bench]]
+name = "throughput"
+path = "perf/throughput.rs"
+harness = false
+    "#,
+        )
+        .touch_multiple(&[
+            "build/main.rs",
+            "lib/app.rs",
+            "tools/gen.rs",
+            "demos/demo.rs",
+            "checks/integration.rs",
+            "perf/throughput.rs",
+        ])
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    let dummies = [
+        ("build/main.rs", "fn main() {}"),
+        ("lib/app.rs", ""),
+        ("tools/gen.rs", "fn main() {}"),
+        ("demos/demo.rs", "fn main() {}"),
+        ("checks/integration.rs", ""),
+        ("perf/throughput.rs", "fn main() {}"),
+    ];
+    for (path, contents) in &dummies {
+        cook_directory.child(path).assert(*contents);
+    }
+    cook_directory
+        .child("src")
+        .assert(predicate::path::missing());
+}
+
+#[test]
+pub fn crate_types() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["ffi", "static"]
+    "#,
+        )
+        .manifest(
+            "ffi",
+            r#"
+[package]
+name = "ffi"
+version = "0.1.0"
+
+[lib]
+crate-type = ["cdylib", "rlib"]
+    "#,
+        )
+        .manifest(
+            "static",
+            r#"
+[package]
+name = "static"
+version = "0.1.0"
+
+[lib]
+crate_type = ["staticlib"]
+
+[[example]]
+name = "plugin"
+crate-type = ["cdylib"]
+    "#,
+        )
+        .touch_multiple(&[
+            "ffi/src/lib.rs",
+            "static/src/lib.rs",
+            "static/examples/plugin.rs",
+        ])
+        .build();
+    let crate_types = |manifest: &toml::Value| {
+        manifest["crate-type"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|crate_type| crate_type.as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    let manifest = |path: &str| -> toml::Value {
+        let manifest = skeleton
+            .manifests
+            .iter()
+            .find(|manifest| manifest.relative_path == Path::new(path))
+            .unwrap();
+        toml::from_str(&manifest.contents).unwrap()
+    };
+    let ffi = manifest("ffi/Cargo.toml");
+    assert_eq!(vec!["cdylib", "rlib"], crate_types(&ffi["lib"]));
+    let static_ = manifest("static/Cargo.toml");
+    assert_eq!(vec!["staticlib"], crate_types(&static_["lib"]));
+    assert_eq!(vec!["cdylib"], crate_types(&static_["example"][0]));
+}
+
+#[test]
+pub fn auto_discovery_disabled() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+autolib = false
+autobins = false
+autoexamples = false
+autotests = false
+autobenches = false
+
+[[bin]]
+name = "cli"
+path = "src/cli.rs"
+
+[[test]]
+name = "integration"
+path = "tests/integration/main.rs"
+    "#,
+        )
+        .touch_multiple(&[
+            "src/lib.rs",
+            "src/main.rs",
+            "src/cli.rs",
+            "src/bin/other.rs",
+            "tests/integration/main.rs",
+            "tests/other.rs",
+            "examples/demo.rs",
+            "benches/throughput.rs",
+        ])
+        .build();
+    let targets = |path: &Path| {
+        let metadata = cargo_metadata::MetadataCommand::new()
+            .current_dir(path)
+            .no_deps()
+            .exec()
+            .unwrap();
+        let mut targets: Vec<_> = metadata.packages[0]
+            .targets
+            .iter()
+            .map(|target| target.name.clone())
+            .collect();
+        targets.sort();
+        targets
+    };
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    cook_directory.child("src/cli.rs").assert("fn main() {}");
+    cook_directory.child("tests/integration/main.rs").assert("");
+    for path in &[
+        "src/lib.rs",
+        "src/main.rs",
+        "src/bin",
+        "tests/other.rs",
+        "examples",
+        "benches",
+    ] {
+        cook_directory
+            .child(path)
+            .assert(predicate::path::missing());
+    }
+    let manifest: toml::Value = toml::from_str(&skeleton.manifests[0].contents).unwrap();
+    assert_eq!(manifest["package"]["autolib"].as_bool(), Some(false));
+    assert!(manifest.get("lib").is_none());
+    assert_eq!(vec!["cli", "integration"], targets(&project.path()));
+    assert_eq!(targets(&project.path()), targets(cook_directory.path()));
+}
+
+#[test]
+pub fn default_run() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+default-run = "server"
+    "#,
+        )
+        .touch_multiple(&["src/main.rs", "src/bin/server.rs"])
+        .build();
+    let default_run = |directory: &Path| {
+        let manifest = std::fs::read_to_string(directory.join("Cargo.toml")).unwrap();
+        let manifest: toml::Value = toml::from_str(&manifest).unwrap();
+        manifest["package"]
+            .get("default-run")
+            .map(|default_run| default_run.as_str().unwrap().to_string())
+    };
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+    // e.g. a postprocessing command dropping the binary
+    let mut without_server = serde_json::to_value(&skeleton).unwrap();
+    without_server["manifests"][0]["targets"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|target| target["name"] != "server");
+    let without_server: Skeleton = serde_json::from_value(without_server).unwrap();
+    let cook_directory_without_server = TempDir::new().unwrap();
+    without_server
+        .build_minimum_project(cook_directory_without_server.path(), false)
+        .unwrap();
+
+    // Assert
+    cook_directory
+        .child("src/bin/server.rs")
+        .assert("fn main() {}");
+    assert_eq!(
+        default_run(cook_directory.path()).as_deref(),
+        Some("server")
+    );
+    assert_eq!(default_run(cook_directory_without_server.path()), None);
+}
+
+#[test]
+pub fn links_without_build_script_target() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[package]
+name = "foo-sys"
+version = "0.1.0"
+links = "foo"
+    "#,
+        )
+        .touch_multiple(&["src/lib.rs", "build.rs"])
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    // e.g. a postprocessing command dropping the build script
+    let mut without_build_script = serde_json::to_value(&skeleton).unwrap();
+    without_build_script["manifests"][0]["targets"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|target| target["kind"] != "BuildScript");
+    let without_build_script: Skeleton = serde_json::from_value(without_build_script).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    without_build_script
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    cook_directory.child("build.rs").assert("fn main() {}");
+    cargo_metadata::MetadataCommand::new()
+        .current_dir(cook_directory.path())
+        .no_deps()
+        .exec()
+        .unwrap();
+}
+
+#[test]
+pub fn kept_build_scripts() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["ffi", "proto", "app"]
+    "#,
+        )
+        .manifest(
+            "ffi",
+            r#"
+[package]
+name = "ffi"
+version = "0.1.0"
+    "#,
+        )
+        .file("ffi/build.rs", "fn main() { /* ffi */ }")
+        .file("ffi/wrapper.h", "int answer(void);")
+        .touch("ffi/src/lib.rs")
+        .manifest(
+            "proto",
+            r#"
+[package]
+name = "proto"
+version = "0.1.0"
+
+[package.metadata.chef]
+keep-build-script = true
+build-script-assets = ["proto/**/*.proto"]
+    "#,
+        )
+        .file("proto/build.rs", "fn main() { /* proto */ }")
+        .file("proto/proto/v1/service.proto", "syntax = \"proto3\";")
+        .file("proto/proto/README.md", "not an asset")
+        .touch("proto/src/lib.rs")
+        .manifest(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+ffi = { path = "../ffi", version = "0.1.0" }
+    "#,
+        )
+        .file("app/build.rs", "fn main() { /* app */ }")
+        .touch("app/src/main.rs")
+        .file(
+            "Cargo.lock",
+            r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["ffi 0.1.0"]
+
+[[package]]
+name = "ffi"
+version = "0.1.0"
+
+[[package]]
+name = "proto"
+version = "0.1.0"
+"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None)
+        .unwrap()
+        .keep_build_scripts(project.path(), &["ffi".to_string()], false)
+        .unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    let kept: Vec<_> = skeleton
+        .files
+        .iter()
+        .map(|file| file.relative_path.to_str().unwrap())
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            "ffi/build.rs",
+            "proto/build.rs",
+            "proto/proto/v1/service.proto"
+        ]
+    );
+    cook_directory
+        .child("ffi/build.rs")
+        .assert("fn main() { /* ffi */ }");
+    cook_directory
+        .child("proto/build.rs")
+        .assert("fn main() { /* proto */ }");
+    cook_directory
+        .child("proto/proto/v1/service.proto")
+        .assert("syntax = \"proto3\";");
+    cook_directory.child("app/build.rs").assert("fn main() {}");
+    // The versions of packages with a kept build script are not masked.
+    let app = &skeleton.manifests[1];
+    assert!(app.contents.contains(r#"version = "0.0.1""#));
+    assert!(app
+        .contents
+        .contains("[dependencies.ffi]\nversion = \"0.1.0\""));
+    assert!(skeleton.manifests[2]
+        .contents
+        .contains(r#"version = "0.1.0""#));
+    let lock_file = skeleton.lock_file.as_ref().unwrap();
+    assert!(lock_file.contains(r#"dependencies = ["ffi 0.1.0"]"#));
+    assert!(lock_file.contains("name = \"proto\"\nversion = \"0.1.0\""));
+    cook_directory
+        .child("proto/proto/README.md")
+        .assert(predicates::path::missing());
+    assert_eq!(
+        Skeleton::inputs(project.path())
+            .unwrap()
+            .iter()
+            .filter(|input| input.starts_with("proto"))
+            .count(),
+        3
+    );
+    let error = Skeleton::derive(project.path(), None)
+        .unwrap()
+        .keep_build_scripts(project.path(), &["ffi-sys".to_string()], false)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "There is no package named `ffi-sys` in the workspace."
+    );
+}
+
+#[test]
+pub fn detected_build_script_assets() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["grpc", "db", "api"]
+    "#,
+        )
+        .manifest(
+            "grpc",
+            r#"
+[package]
+name = "grpc"
+version = "0.1.0"
+
+[build-dependencies]
+tonic-build = "0.12"
+    "#,
+        )
+        .touch_multiple(&["grpc/build.rs", "grpc/src/lib.rs"])
+        .file("grpc/proto/v1/api.proto", "syntax = \"proto3\";")
+        .manifest(
+            "db",
+            r#"
+[package]
+name = "db"
+version = "0.1.0"
+
+[dependencies]
+database = { package = "sqlx", version = "0.8" }
+    "#,
+        )
+        .touch_multiple(&["db/build.rs", "db/src/lib.rs"])
+        .file("db/.sqlx/query-0123.json", "{}")
+        .file("db/migrations/0001_init.sql", "CREATE TABLE users ();")
+        .touch("db/migrations/README.md")
+        .manifest(
+            "api",
+            r#"
+[package]
+name = "api"
+version = "0.1.0"
+
+[dependencies]
+sqlx = "0.8"
+    "#,
+        )
+        .touch("api/src/main.rs")
+        .file("api/.sqlx/query-4567.json", "{}")
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Act
+    let detected = skeleton
+        .keep_build_scripts(project.path(), &[], true)
+        .unwrap();
+    let undetected = skeleton
+        .keep_build_scripts(project.path(), &[], false)
+        .unwrap();
+
+    // Assert
+    let kept: Vec<_> = detected
+        .files
+        .iter()
+        .map(|file| file.relative_path.to_str().unwrap())
+        .collect();
+    // `api` has no build script, nothing reads its offline data when cooking.
+    assert_eq!(
+        kept,
+        vec![
+            "db/.sqlx/query-0123.json",
+            "db/build.rs",
+            "db/migrations/0001_init.sql",
+            "grpc/build.rs",
+            "grpc/proto/v1/api.proto",
+        ]
+    );
+    assert!(undetected.files.is_empty());
+}
+
+#[test]
+pub fn custom_dummies() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r##"
+[workspace]
+members = ["firmware", "hal", "macros"]
+
+[workspace.metadata.chef.dummy]
+lib = "#![no_std]"
+bin = "#![no_std]\n#![no_main]\n#[panic_handler]\nfn panic(_: &core::panic::PanicInfo) -> ! { loop {} }\n"
+    "##,
+        )
+        .manifest(
+            "firmware",
+            r#"
+[package]
+name = "firmware"
+version = "0.1.0"
+    "#,
+        )
+        .touch_multiple(&["firmware/src/main.rs", "firmware/build.rs"])
+        .manifest(
+            "hal",
+            r##"
+[package]
+name = "hal"
+version = "0.1.0"
+
+[package.metadata.chef.dummy]
+lib = "#![no_std]\n#![feature(asm_experimental_arch)]"
+build-script = "fn main() { println!(\"cargo:rustc-link-arg=-Tlink.x\"); }"
+    "##,
+        )
+        .touch_multiple(&["hal/src/lib.rs", "hal/build.rs"])
+        .manifest(
+            "macros",
+            r#"
+[package]
+name = "macros"
+version = "0.1.0"
+
+[lib]
+proc-macro = true
+    "#,
+        )
+        .touch("macros/src/lib.rs")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    cook_directory.child("firmware/src/main.rs").assert(
+        "#![no_std]\n#![no_main]\n#[panic_handler]\nfn panic(_: &core::panic::PanicInfo) -> ! { loop {} }\n",
+    );
+    cook_directory
+        .child("firmware/build.rs")
+        .assert("fn main() {}");
+    cook_directory
+        .child("hal/src/lib.rs")
+        .assert("#![no_std]\n#![feature(asm_experimental_arch)]");
+    cook_directory
+        .child("hal/build.rs")
+        .assert("fn main() { println!(\"cargo:rustc-link-arg=-Tlink.x\"); }");
+    // The dummies of the workspace do not affect proc-macro crates.
+    cook_directory.child("macros/src/lib.rs").assert("");
+    // They survive stripping the metadata.
+    let stripped = skeleton.without_metadata().unwrap();
+    let stripped_directory = TempDir::new().unwrap();
+    stripped
+        .build_minimum_project(stripped_directory.path(), false)
+        .unwrap();
+    stripped_directory
+        .child("hal/src/lib.rs")
+        .assert("#![no_std]\n#![feature(asm_experimental_arch)]");
+}
+
+#[test]
+pub fn invalid_custom_dummies() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[package]
+name = "firmware"
+version = "0.1.0"
+
+[package.metadata.chef.dummy]
+main = "fn main() {}"
+    "#,
+        )
+        .touch("src/main.rs")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    let error = skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap_err();
+
+    // Assert
+    assert_eq!(
+        error.to_string(),
+        "Cargo.toml: invalid `package.metadata.chef.dummy`"
+    );
+}
+
+#[test]
+pub fn kept_sources() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["macros", "shared", "app"]
+    "#,
+        )
+        .manifest(
+            "macros",
+            r#"
+[package]
+name = "macros"
+version = "0.2.0"
+
+[lib]
+proc-macro = true
+    "#,
+        )
+        .file("macros/src/lib.rs", "mod expand;")
+        .file("macros/src/expand.rs", "// expand")
+        .file("macros/.gitignore", "/target")
+        .touch("macros/target/debug/stale.rs")
+        .manifest(
+            "macros/fixtures",
+            r#"
+[package]
+name = "fixtures"
+version = "0.1.0"
+    "#,
+        )
+        .file("macros/fixtures/src/lib.rs", "// fixtures")
+        .manifest(
+            "shared",
+            r#"
+[package]
+name = "shared"
+version = "0.1.0"
+
+[package.metadata.chef]
+keep-source = true
+
+[dependencies]
+macros = { path = "../macros" }
+    "#,
+        )
+        .file("shared/src/lib.rs", "// shared")
+        .manifest(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+shared = { path = "../shared" }
+    "#,
+        )
+        .file("app/src/main.rs", "// app")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None)
+        .unwrap()
+        .keep_sources(project.path(), &["macros".to_string()])
+        .unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    let kept: Vec<_> = skeleton
+        .files
+        .iter()
+        .map(|file| file.relative_path.to_str().unwrap())
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            "macros/src/expand.rs",
+            "macros/src/lib.rs",
+            "shared/src/lib.rs"
+        ]
+    );
+    cook_directory
+        .child("macros/src/lib.rs")
+        .assert("mod expand;");
+    cook_directory
+        .child("macros/fixtures/src/lib.rs")
+        .assert(predicates::path::missing());
+    cook_directory
+        .child("app/src/main.rs")
+        .assert("fn main() {}");
+    // The versions of packages with kept sources are not masked.
+    let macros = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == Path::new("macros/Cargo.toml"))
+        .unwrap();
+    assert!(macros.contents.contains(r#"version = "0.2.0""#));
+    let app = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == Path::new("app/Cargo.toml"))
+        .unwrap();
+    assert!(app.contents.contains(r#"version = "0.0.1""#));
+
+    // The members a kept package depends on must be kept as well.
+    let error = Skeleton::derive(project.path(), None)
+        .unwrap()
+        .keep_sources(project.path(), &[])
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "`shared` depends on the workspace member `macros`: its sources must be kept as well."
+    );
+}
+
+#[test]
+pub fn credential_warnings() {
+    // Arrange
+    let skeleton = Skeleton {
+        manifests: vec![],
+        config_file: Some(
+            r#"
+[registries.my-registry]
+index = "sparse+https://cargo.example.com/index/"
+"#
+            .to_string(),
+        ),
+        external_config: None,
+        toolchain_file: None,
+        lock_file: Some(
+            r#"
+version = 3
+
+[[package]]
+name = "public"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "private"
+version = "0.1.0"
+source = "git+ssh://git@github.com/org/private.git?branch=main#5f2e6c"
+
+[[package]]
+name = "internal"
+version = "0.2.0"
+source = "sparse+https://cargo.example.com/index/"
+
+[[package]]
+name = "https"
+version = "0.3.0"
+source = "git+https://github.com/org/public.git#0c1d2e"
+
+[[package]]
+name = "local"
+version = "0.4.0"
+"#
+            .to_string(),
+        ),
+        vendored_sources: vec![],
+        files: vec![],
+    };
+
+    // Act
+    let warnings = skeleton.credential_warnings().unwrap();
+
+    // Assert
+    assert_eq!(2, warnings.len());
+    assert!(warnings[0].contains("`private` fetched from ssh://git@github.com/org/private.git"));
+    assert!(warnings[0].contains("--mount=type=ssh"));
+    assert!(warnings[1].contains("`internal` fetched from the registry"));
+    assert!(warnings[1].contains("`CARGO_REGISTRIES_MY_REGISTRY_TOKEN`"));
+    assert_eq!(warnings, skeleton.warnings().unwrap());
+}
+
+#[test]
+pub fn remove_compiled_dummies_keeps_external_units() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "foo"
+version = "0.1.0"
+build = "build.rs"
+    "#,
+        )
+        .touch("build.rs")
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    let profile_directory = cook_directory.child("target").child("debug");
+    // Units of the local `foo` package...
+    profile_directory
+        .child(".fingerprint/foo-0123456789abcdef/lib-foo.json")
+        .touch()
+        .unwrap();
+    profile_directory
+        .child(".fingerprint/foo-1111111111111111/build-script-build-script-build.json")
+        .touch()
+        .unwrap();
+    profile_directory
+        .child("deps/libfoo-0123456789abcdef.rlib")
+        .touch()
+        .unwrap();
+    profile_directory
+        .child("build/foo-1111111111111111/build-script-build")
+        .touch()
+        .unwrap();
+    profile_directory.child("libfoo.rlib").touch().unwrap();
+    // ...and of the external `foo-sys` package.
+    profile_directory
+        .child(".fingerprint/foo-sys-fedcba9876543210/lib-foo_sys.json")
+        .touch()
+        .unwrap();
+    profile_directory
+        .child("deps/libfoo_sys-fedcba9876543210.rlib")
+        .touch()
+        .unwrap();
+    profile_directory
+        .child("build/foo-sys-2222222222222222/build-script-build")
+        .touch()
+        .unwrap();
+
+    // Act
+    skeleton
+        .remove_compiled_dummies(
+            cook_directory.path(),
+            chef_core::OptimisationProfile::Debug,
+            None,
+            None,
+        )
+        .unwrap();
+
+    // Assert
+    for removed in [
+        ".fingerprint/foo-0123456789abcdef",
+        ".fingerprint/foo-1111111111111111",
+        "deps/libfoo-0123456789abcdef.rlib",
+        "build/foo-1111111111111111",
+        "libfoo.rlib",
+    ] {
+        profile_directory
+            .child(removed)
+            .assert(predicate::path::missing());
+    }
+    for kept in [
+        ".fingerprint/foo-sys-fedcba9876543210",
+        "deps/libfoo_sys-fedcba9876543210.rlib",
+        "build/foo-sys-2222222222222222/build-script-build",
+    ] {
+        profile_directory
+            .child(kept)
+            .assert(predicate::path::exists());
+    }
+}
+
+#[test]
+pub fn inputs() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a"]
+    "#,
+        )
+        .lib_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+    "#,
+        )
+        .touch("Cargo.lock")
+        .touch(".cargo/config")
+        .touch("README.md")
+        .build();
+
+    // Act
+    let inputs = Skeleton::inputs(project.path()).unwrap();
+
+    // Assert
+    assert_eq!(
+        vec![
+            PathBuf::from("Cargo.toml"),
+            PathBuf::from("a/Cargo.toml"),
+            PathBuf::from("Cargo.lock"),
+            PathBuf::from(".cargo/config"),
+        ],
+        inputs
+    );
+}
+
+#[test]
+pub fn missing_members() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["app", "generated/api"]
+
+[workspace.dependencies]
+api-client = { path = "generated/api" }
+    "#,
+        )
+        .bin_package(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+api-client = { workspace = true }
+    "#,
+        )
+        .build();
+
+    // Act
+    assert!(Skeleton::derive(project.path(), None).is_err());
+    let skeleton =
+        Skeleton::derive_allowing_missing_members(project.path(), None, "generated/*").unwrap();
+
+    // Assert
+    let placeholder = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == Path::new("generated/api/Cargo.toml"))
+        .unwrap();
+    assert!(placeholder.contents.contains(r#"name = "api-client""#));
+    assert!(!project.path().join("generated").exists());
+}
+
+#[test]
+pub fn split_per_member() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a", "b", "c"]
+
+[workspace.dependencies]
+b = { path = "b" }
+    "#,
+        )
+        .bin_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+b = { path = "../b" }
+    "#,
+        )
+        .lib_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.1.0"
+    "#,
+        )
+        .lib_package(
+            "c",
+            r#"
+[package]
+name = "c"
+version = "0.1.0"
+
+[dev-dependencies]
+b = { workspace = true }
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeletons = Skeleton::derive(project.path(), None)
+        .unwrap()
+        .split_per_member()
+        .unwrap();
+
+    // Assert
+    let manifests: Vec<(&str, Vec<&str>)> = skeletons
+        .iter()
+        .map(|(name, skeleton)| {
+            let paths = skeleton
+                .manifests
+                .iter()
+                .map(|manifest| manifest.relative_path.to_str().unwrap())
+                .collect();
+            (name.as_str(), paths)
+        })
+        .collect();
+    assert_eq!(
+        manifests,
+        [
+            ("a", vec!["Cargo.toml", "a/Cargo.toml", "b/Cargo.toml"]),
+            ("b", vec!["Cargo.toml", "b/Cargo.toml"]),
+            ("c", vec!["Cargo.toml", "b/Cargo.toml", "c/Cargo.toml"]),
+        ]
+    );
+    let root: toml::Value = toml::from_str(&skeletons[0].1.manifests[0].contents).unwrap();
+    assert_eq!(root["workspace"]["members"], toml::Value::from(vec!["a"]));
+
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let kept = |keep: &dyn Fn(&str) -> bool| manifests_of(&skeleton.keep_packages(keep).unwrap());
+    // `b` is a dependency of `a`, it cannot be left out.
+    assert_eq!(
+        kept(&|name| name != "b"),
+        ["Cargo.toml", "a/Cargo.toml", "b/Cargo.toml", "c/Cargo.toml"]
+    );
+    assert_eq!(
+        kept(&|name| name == "a"),
+        ["Cargo.toml", "a/Cargo.toml", "b/Cargo.toml"]
+    );
+    assert_eq!(kept(&|_| false), manifests_of(&skeleton));
+}
+
+#[test]
+pub fn exclude_members() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a", "b", "tools/*"]
+    "#,
+        )
+        .bin_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+b = { path = "../b" }
+    "#,
+        )
+        .lib_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.1.0"
+    "#,
+        )
+        .bin_package(
+            "tools/xtask",
+            r#"
+[package]
+name = "xtask"
+version = "0.1.0"
+    "#,
+        )
+        .lib_package(
+            "tools/fuzz",
+            r#"
+[package]
+name = "fuzz"
+version = "0.1.0"
+    "#,
+        )
+        .file(
+            "Cargo.lock",
+            r#"
+version = 3
+
+[[package]]
+name = "a"
+version = "0.1.0"
+dependencies = ["b"]
+
+[[package]]
+name = "b"
+version = "0.1.0"
+
+[[package]]
+name = "fuzz"
+version = "0.1.0"
+
+[[package]]
+name = "xtask"
+version = "0.1.0"
+"#,
+        )
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Act
+    let excluded = skeleton
+        .exclude_members(&["tools/*".to_string(), "b".to_string()])
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        manifests_of(&excluded),
+        ["Cargo.toml", "a/Cargo.toml", "b/Cargo.toml"]
+    );
+    let root: toml::Value = toml::from_str(&excluded.manifests[0].contents).unwrap();
+    assert_eq!(root["workspace"]["members"], toml::Value::from(vec!["a"]));
+    assert_eq!(
+        root["workspace"]["exclude"],
+        toml::Value::from(vec!["b", "tools/fuzz", "tools/xtask"])
+    );
+    let lock_file: toml::Value = toml::from_str(excluded.lock_file.as_ref().unwrap()).unwrap();
+    let locked: Vec<&str> = lock_file["package"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|package| package["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(locked, ["a", "b"]);
+
+    assert!(skeleton.exclude_members(&["missing".to_string()]).is_err());
+    assert!(skeleton.exclude_members(&["*".to_string()]).is_err());
+}
+
+#[test]
+pub fn keep_default_members() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a", "b", "c"]
+default-members = ["./a"]
+    "#,
+        )
+        .bin_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+c = { path = "../c" }
+    "#,
+        )
+        .bin_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.1.0"
+    "#,
+        )
+        .lib_package(
+            "c",
+            r#"
+[package]
+name = "c"
+version = "0.1.0"
+    "#,
+        )
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Act
+    let kept = skeleton.keep_default_members().unwrap();
+
+    // Assert
+    assert_eq!(
+        manifests_of(&kept),
+        ["Cargo.toml", "a/Cargo.toml", "c/Cargo.toml"]
+    );
+    let root: toml::Value = toml::from_str(&kept.manifests[0].contents).unwrap();
+    assert_eq!(root["workspace"]["members"], toml::Value::from(vec!["a"]));
+    assert_eq!(
+        root["workspace"]["default-members"],
+        toml::Value::from(vec!["./a"])
+    );
+    let without_a = skeleton.keep_packages(|name| name == "b").unwrap();
+    let root: toml::Value = toml::from_str(&without_a.manifests[0].contents).unwrap();
+    assert!(root["workspace"].get("default-members").is_none());
+}
+
+#[test]
+pub fn nested_workspaces() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a"]
+exclude = ["fuzz"]
+    "#,
+        )
+        .lib_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+    "#,
+        )
+        .manifest(
+            "fuzz",
+            r#"
+[workspace]
+members = ["targets"]
+    "#,
+        )
+        .bin_package(
+            "fuzz/targets",
+            r#"
+[package]
+name = "targets"
+version = "0.1.0"
+    "#,
+        )
+        .lib_package(
+            "tools/inner",
+            r#"
+[package]
+name = "inner"
+version = "0.1.0"
+
+[workspace]
+    "#,
+        )
+        .manifest("target/package/copy", "[workspace]")
+        .manifest("web/node_modules/package", "[workspace]")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let nested = Skeleton::nested_workspaces(project.path(), &[]).unwrap();
+    let scanned = Skeleton::nested_workspaces(project.path(), &["fuzz".into()]).unwrap();
+
+    // Assert
+    assert_eq!(manifests_of(&skeleton), ["Cargo.toml", "a/Cargo.toml"]);
+    assert_eq!(
+        nested,
+        [PathBuf::from("fuzz"), PathBuf::from("tools/inner")]
+    );
+    assert_eq!(scanned, [PathBuf::from("fuzz")]);
+}
+
+fn manifests_of(skeleton: &Skeleton) -> Vec<String> {
+    skeleton
+        .manifests
+        .iter()
+        .map(|manifest| manifest.relative_path.to_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+pub fn dev_dependencies() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a", "b"]
+    "#,
+        )
+        .lib_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+    "#,
+        )
+        .lib_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.1.0"
+
+[target.'cfg(unix)'.dev-dependencies]
+a = { path = "../a" }
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let split = skeleton.split_per_member().unwrap();
+
+    // Assert
+    assert!(skeleton.has_dev_dependencies().unwrap());
+    assert!(!split[0].1.has_dev_dependencies().unwrap());
+}
+
+#[test]
+pub fn features_of_removed_dev_dependencies() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a", "b"]
+    "#,
+        )
+        .lib_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[features]
+std = []
+    "#,
+        )
+        .lib_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.1.0"
+
+[dependencies]
+a = { path = "../a", optional = true }
+
+[dev-dependencies]
+c = { package = "a", path = "../a" }
+
+[features]
+tls = ["dep:a", "a?/std"]
+testing = ["c/std", "tls"]
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None)
+        .unwrap()
+        .without_dev_dependencies()
+        .unwrap();
+
+    // Assert
+    let manifest = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == std::path::Path::new("b/Cargo.toml"))
+        .unwrap();
+    let contents: toml::Value = toml::from_str(&manifest.contents).unwrap();
+    let features = contents["features"].as_table().unwrap();
+    assert_eq!(
+        &toml::Value::from(vec!["dep:a", "a?/std"]),
+        &features["tls"]
+    );
+    assert_eq!(&toml::Value::from(vec!["tls"]), &features["testing"]);
+}
+
+#[test]
+pub fn external_cargo_config_is_recorded() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .file(
+            ".cargo/config.toml",
+            r#"
+[build]
+rustflags = ["-Cforce-frame-pointers=yes"]
+
+[net]
+retry = 3
+
+[target.x86_64-unknown-linux-gnu]
+linker = "clang"
+runner = "qemu"
+    "#,
+        )
+        .file(
+            "nested/.cargo/config.toml",
+            r#"
+[build]
+rustflags = ["-Ctarget-cpu=native"]
+
+[profile.release]
+lto = "thin"
+    "#,
+        )
+        .bin_package(
+            "nested/project",
+            r#"
+[package]
+name = "project"
+version = "0.1.0"
+    "#,
+        )
+        .file("nested/project/.cargo/config.toml", "[build]\njobs = 2\n")
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path().join("nested/project"), None).unwrap();
+
+    // Assert
+    assert_eq!(Some("[build]\njobs = 2\n"), skeleton.config_file.as_deref());
+    let external: toml::Value = toml::from_str(&skeleton.external_config.unwrap()).unwrap();
+    // The closest file comes last, as `cargo` merges them.
+    assert_eq!(
+        &toml::Value::from(vec!["-Cforce-frame-pointers=yes", "-Ctarget-cpu=native"]),
+        &external["build"]["rustflags"]
+    );
+    assert_eq!(Some("thin"), external["profile"]["release"]["lto"].as_str());
+    let target = external["target"]["x86_64-unknown-linux-gnu"]
+        .as_table()
+        .unwrap();
+    assert_eq!(vec!["linker"], target.keys().collect::<Vec<_>>());
+    assert!(external.get("net").is_none());
+}
+
+#[test]
+pub fn stubbed_packages() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["app", "ffi"]
+    "#,
+        )
+        .bin_package(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+ffi = { path = "../ffi", features = ["static"] }
+    "#,
+        )
+        .lib_package(
+            "ffi",
+            r#"
+[package]
+name = "ffi"
+version = "0.1.0"
+links = "native"
+
+[dependencies]
+libc = { version = "0.2", optional = true }
+
+[build-dependencies]
+cc = "1"
+
+[features]
+default = ["static"]
+static = []
+bindgen = ["dep:libc"]
+    "#,
+        )
+        .touch("ffi/build.rs")
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Act
+    let stubbed = skeleton
+        .with_stubbed_packages(&[("ffi".to_string(), PackageStub::Features(None))])
+        .unwrap();
+    let with_features = skeleton
+        .with_stubbed_packages(&[(
+            "ffi".to_string(),
+            PackageStub::Features(Some(vec!["static".to_string()])),
+        )])
+        .unwrap();
+    let mock = skeleton.with_stubbed_packages(&[(
+        "ffi".to_string(),
+        PackageStub::Manifest("[package]\nname = \"ffi-mock\"\nversion = \"0.1.0\"\n".to_string()),
+    )]);
+    let missing =
+        skeleton.with_stubbed_packages(&[("native".to_string(), PackageStub::Features(None))]);
+
+    // Assert
+    let ffi = |skeleton: &Skeleton| -> (toml::Value, usize) {
+        let manifest = skeleton
+            .manifests
+            .iter()
+            .find(|manifest| manifest.relative_path == Path::new("ffi/Cargo.toml"))
+            .unwrap();
+        (
+            toml::from_str(&manifest.contents).unwrap(),
+            manifest.targets.len(),
+        )
+    };
+    let (contents, targets) = ffi(&stubbed);
+    expect![[r#"
+        [package]
+        name = "ffi"
+        version = "0.0.1"
+        build = false
+        autobins = true
+        autoexamples = true
+        autotests = true
+        autobenches = true
+
+        [lib]
+        path = "src/lib.rs"
+        name = "ffi"
+        test = true
+        doctest = true
+        bench = true
+        doc = true
+        plugin = false
+        proc-macro = false
+        harness = true
+        required-features = []
+        crate-type = ["rlib"]
+
+        [features]
+        bindgen = []
+        default = []
+        static = []
+    "#]]
+    .assert_eq(&toml::to_string(&contents).unwrap());
+    // The build script is gone, only the library is left.
+    assert_eq!(1, targets);
+    let (contents, _) = ffi(&with_features);
+    assert_eq!(
+        vec!["static"],
+        contents["features"]
+            .as_table()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>()
+    );
+    assert!(mock
+        .unwrap_err()
+        .to_string()
+        .contains("must be for a package of the same name"));
+    assert!(missing.is_err());
+}
+
+#[test]
+pub fn registry_tokens_are_left_out_of_the_config() {
+    // Arrange
+    let config = r#"
+[registries.my-registry]
+index = "sparse+https://cargo.example.com/index/"
+token = "secret"
+
+[registry]
+default = "my-registry"
+token = "other-secret"
+
+[build]
+rustflags = ["-Ctarget-cpu=native"]
+    "#;
+    let project = CargoWorkspace::new()
+        .bin_package(
+            ".",
+            r#"
+[package]
+name = "project"
+version = "0.1.0"
+    "#,
+        )
+        .file(".cargo/config.toml", config)
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    let config_file = skeleton.config_file.unwrap();
+    assert!(!config_file.contains("secret"));
+    let config: toml::Value = toml::from_str(&config_file).unwrap();
+    assert_eq!(
+        Some("sparse+https://cargo.example.com/index/"),
+        config["registries"]["my-registry"]["index"].as_str()
+    );
+    assert_eq!(Some("my-registry"), config["registry"]["default"].as_str());
+    assert!(config["build"]["rustflags"].is_array());
+}
+
+#[test]
+pub fn toolchain_file_is_recorded() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .file(
+            "rust-toolchain.toml",
+            r#"
+[toolchain]
+channel = "1.75.0"
+components = ["clippy"]
+targets = ["wasm32-unknown-unknown"]
+    "#,
+        )
+        .bin_package(
+            ".",
+            r#"
+[package]
+name = "project"
+version = "0.1.0"
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    let toolchain_file = skeleton.toolchain_file.unwrap();
+    assert_eq!(
+        toolchain_file.relative_path,
+        Path::new("rust-toolchain.toml")
+    );
+    assert_eq!(
+        PinnedToolchain::from_file(&toolchain_file).unwrap(),
+        Some(PinnedToolchain {
+            channel: "1.75.0".to_string(),
+            components: vec!["clippy".to_string()],
+            targets: vec!["wasm32-unknown-unknown".to_string()],
+        })
+    );
+    cook_directory
+        .child("rust-toolchain.toml")
+        .assert(predicate::str::contains("channel = \"1.75.0\""));
+}
+
+#[test]
+pub fn pinned_toolchains() {
+    // Arrange
+    let file = |relative_path: &str, contents: &str| SkeletonFile {
+        relative_path: relative_path.into(),
+        contents: contents.to_string(),
+        mode: None,
+    };
+
+    // Act
+    let legacy = PinnedToolchain::from_file(&file("rust-toolchain", "nightly-2024-01-01\n"));
+    let toml = PinnedToolchain::from_file(&file(
+        "rust-toolchain",
+        "[toolchain]\nchannel = \"stable\"\n",
+    ));
+    let custom = PinnedToolchain::from_file(&file(
+        "rust-toolchain.toml",
+        "[toolchain]\npath = \"/opt/rust\"\n",
+    ));
+    let invalid = PinnedToolchain::from_file(&file("rust-toolchain.toml", "[toolchain]\n"));
+
+    // Assert
+    assert_eq!(legacy.unwrap().unwrap().channel, "nightly-2024-01-01");
+    assert_eq!(toml.unwrap().unwrap().channel, "stable");
+    assert_eq!(custom.unwrap(), None);
+    assert!(invalid.is_err());
+}
+
+#[test]
+pub fn rust_version_is_the_highest_of_the_workspace() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["app", "lib"]
+
+[workspace.package]
+rust-version = "1.74"
+    "#,
+        )
+        .bin_package(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+rust-version.workspace = true
+    "#,
+        )
+        .lib_package(
+            "lib",
+            r#"
+[package]
+name = "lib"
+version = "0.1.0"
+rust-version = "1.70.1"
+    "#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    let (package, rust_version) = skeleton.rust_version().unwrap().unwrap();
+    assert_eq!(package, "app");
+    assert_eq!(rust_version.to_string(), "1.74.0");
+}
+
+#[test]
+pub fn remove_compiled_dummies_cleans_up_host_units_when_cross_compiling() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .lib_package(
+            ".",
+            r#"
+[package]
+name = "foo"
+version = "0.1.0"
+build = "build.rs"
+    "#,
+        )
+        .touch("build.rs")
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    let target_directory = cook_directory
+        .child("target")
+        .child("custom")
+        .child("debug");
+    let host_directory = cook_directory.child("target").child("debug");
+    // The library of `foo` is built for the target...
+    target_directory
+        .child(".fingerprint/foo-0123456789abcdef/lib-foo.json")
+        .touch()
+        .unwrap();
+    target_directory
+        .child("deps/libfoo-0123456789abcdef.rlib")
+        .touch()
+        .unwrap();
+    // ...its build script for the host, next to the external proc macros.
+    host_directory
+        .child(".fingerprint/foo-1111111111111111/build-script-build-script-build.json")
+        .touch()
+        .unwrap();
+    host_directory
+        .child("build/foo-1111111111111111/build-script-build")
+        .touch()
+        .unwrap();
+    host_directory
+        .child(".fingerprint/serde_derive-fedcba9876543210/lib-serde_derive.json")
+        .touch()
+        .unwrap();
+    host_directory
+        .child("deps/libserde_derive-fedcba9876543210.so")
+        .touch()
+        .unwrap();
+
+    // Act
+    skeleton
+        .remove_compiled_dummies(
+            cook_directory.path(),
+            chef_core::OptimisationProfile::Debug,
+            Some(vec!["specs/custom.json".to_string()]),
+            None,
+        )
+        .unwrap();
+
+    // Assert
+    for removed in [
+        target_directory.child(".fingerprint/foo-0123456789abcdef"),
+        target_directory.child("deps/libfoo-0123456789abcdef.rlib"),
+        host_directory.child(".fingerprint/foo-1111111111111111"),
+        host_directory.child("build/foo-1111111111111111"),
+    ] {
+        removed.assert(predicate::path::missing());
+    }
+    for kept in [
+        ".fingerprint/serde_derive-fedcba9876543210",
+        "deps/libserde_derive-fedcba9876543210.so",
+    ] {
+        host_directory.child(kept).assert(predicate::path::exists());
+    }
+}
+
+#[test]
+pub fn masked_requirements_on_local_crates_stay_satisfiable() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["core", "exact", "minimum", "range"]
+    "#,
+        )
+        .lib_package(
+            "core",
+            r#"
+[package]
+name = "core"
+version = "1.2.3"
+    "#,
+        )
+        .file("core/build.rs", "fn main() {}")
+        .lib_package(
+            "exact",
+            r#"
+[package]
+name = "exact"
+version = "0.1.0"
+
+[dependencies]
+core = { path = "../core", version = "=1.2.3" }
+    "#,
+        )
+        .lib_package(
+            "minimum",
+            r#"
+[package]
+name = "minimum"
+version = "0.1.0"
+
+[dependencies]
+core = { path = "../core", version = ">=1.2" }
+    "#,
+        )
+        .lib_package(
+            "range",
+            r#"
+[package]
+name = "range"
+version = "0.1.0"
+
+[dependencies]
+core = { path = "../core", version = ">=1.0, <2.0" }
+    "#,
+        )
+        .file(
+            "Cargo.lock",
+            r#"
+version = 3
+
+[[package]]
+name = "core"
+version = "1.2.3"
+
+[[package]]
+name = "exact"
+version = "0.1.0"
+dependencies = ["core"]
+
+[[package]]
+name = "minimum"
+version = "0.1.0"
+dependencies = ["core"]
+
+[[package]]
+name = "range"
+version = "0.1.0"
+dependencies = ["core"]
+"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+    let kept = skeleton
+        .clone()
+        .keep_build_scripts(project.path(), &["core".to_string()], false)
+        .unwrap();
+    let cook_directory = TempDir::new().unwrap();
+    skeleton
+        .build_minimum_project(cook_directory.path(), false)
+        .unwrap();
+
+    // Assert
+    let requirement = |skeleton: &Skeleton, index: usize| {
+        let contents: toml::Value = toml::from_str(&skeleton.manifests[index].contents).unwrap();
+        contents["dependencies"]["core"]["version"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(requirement(&skeleton, 2), "=0.0.1");
+    assert_eq!(requirement(&skeleton, 3), ">=0.0.1");
+    assert_eq!(requirement(&skeleton, 4), "0.0.1");
+    // The masked skeleton resolves against its masked lock file.
+    cargo_metadata::MetadataCommand::new()
+        .current_dir(cook_directory.path())
+        .no_deps()
+        .other_options(vec!["--locked".to_string(), "--offline".to_string()])
+        .exec()
+        .unwrap();
+    // Unmasking restores requirements with the same semantics.
+    assert_eq!(requirement(&kept, 2), "=1.2.3");
+    assert_eq!(requirement(&kept, 3), ">=1.2.3");
+    assert_eq!(requirement(&kept, 4), "1.2.3");
+}
+
+#[test]
+pub fn release_bumps_do_not_change_the_skeleton() {
+    // Arrange
+    // `core` and `app` inherit the workspace version, `tool` opts out of it.
+    let release = |workspace_version: &str, tool_version: &str| {
+        CargoWorkspace::new()
+            .manifest(
+                ".",
+                &format!(
+                    r#"
+[workspace]
+members = ["app", "core", "tool"]
+
+[workspace.package]
+version = "{0}"
+edition = "2021"
+
+[workspace.dependencies]
+core = {{ path = "core", version = "{0}" }}
+    "#,
+                    workspace_version
+                ),
+            )
+            .lib_package(
+                "core",
+                r#"
+[package]
+name = "core"
+version.workspace = true
+edition.workspace = true
+    "#,
+            )
+            .bin_package(
+                "app",
+                &format!(
+                    r#"
+[package]
+name = "app"
+version.workspace = true
+edition.workspace = true
+
+[dependencies]
+core = {{ workspace = true }}
+tool = {{ path = "../tool", version = "{}" }}
+    "#,
+                    tool_version
+                ),
+            )
+            .lib_package(
+                "tool",
+                &format!(
+                    r#"
+[package]
+name = "tool"
+version = "{}"
+edition = "2021"
+
+[dependencies]
+kernel = {{ package = "core", path = "../core", version = "{}" }}
+    "#,
+                    tool_version, workspace_version
+                ),
+            )
+            .file(
+                "Cargo.lock",
+                &format!(
+                    r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "{0}"
+dependencies = ["core", "tool"]
+
+[[package]]
+name = "core"
+version = "{0}"
+
+[[package]]
+name = "tool"
+version = "{1}"
+dependencies = ["core"]
+"#,
+                    workspace_version, tool_version
+                ),
+            )
+            .build()
+    };
+    let skeleton = |workspace_version: &str, tool_version: &str| {
+        let project = release(workspace_version, tool_version);
+        Skeleton::derive(project.path(), None).unwrap()
+    };
+
+    // Act
+    let before = skeleton("0.1.0", "2.3.0");
+    // release-plz: the workspace version, and the requirements on it, are bumped.
+    let workspace_bump = skeleton("0.2.0", "2.3.0");
+    // cargo-release: a member that opted out of inheritance is bumped on its own.
+    let member_bump = skeleton("0.1.0", "2.4.0");
+    let pre_release = skeleton("1.0.0-rc.1", "3.0.0-alpha.2");
+
+    // Assert
+    assert_eq!(before, workspace_bump);
+    assert_eq!(before, member_bump);
+    assert_eq!(before, pre_release);
+    let lock_file = before.lock_file.unwrap();
+    assert!(!lock_file.contains("0.1.0") && !lock_file.contains("2.3.0"));
+}
+
+#[test]
+pub fn local_crates_required_by_git_dependencies_are_not_masked() {
+    // Arrange
+    // `patched` is a git dependency pinning `fork = "=0.3.0"`, a vendored fork of a crate
+    // that is patched with the local one.
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["app", "fork"]
+
+[patch.crates-io]
+fork = { path = "fork" }
+    "#,
+        )
+        .bin_package(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+fork = { path = "../fork", version = "0.3" }
+patched = { git = "https://github.com/example/patched" }
+    "#,
+        )
+        .lib_package(
+            "fork",
+            r#"
+[package]
+name = "fork"
+version = "0.3.0"
+edition = "2021"
+    "#,
+        )
+        .file(
+            "Cargo.lock",
+            r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["fork", "patched"]
+
+[[package]]
+name = "fork"
+version = "0.3.0"
+
+[[package]]
+name = "patched"
+version = "0.2.0"
+source = "git+https://github.com/example/patched#4f7c9d2"
+dependencies = ["fork"]
+"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    let lock_file = skeleton.lock_file.as_deref().unwrap();
+    assert!(lock_file.contains("name = \"fork\"\nversion = \"0.3.0\""));
+    assert!(!lock_file.contains("name = \"app\"\nversion = \"0.1.0\""));
+    let fork = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == Path::new("fork/Cargo.toml"))
+        .unwrap();
+    assert!(fork.contents.contains("version = \"0.3.0\""));
+    let app = skeleton
+        .manifests
+        .iter()
+        .find(|manifest| manifest.relative_path == Path::new("app/Cargo.toml"))
+        .unwrap();
+    assert!(app.contents.contains("version = \"0.3\""));
+    let warnings = skeleton.warnings().unwrap();
+    assert_eq!(
+        warnings,
+        [
+            "Cargo.lock: the local crate `fork` is a dependency of `patched 0.2.0`, which is not \
+        part of the workspace: its version is not masked and the recipe will change every time \
+        it is bumped."
+        ]
+    );
+}
+
+#[test]
+pub fn network_settings_are_recorded_in_the_cargo_configuration() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .bin_package(
+            ".",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+    "#,
+        )
+        .file(
+            ".cargo/config.toml",
+            "[build]\njobs = 2\n\n[net]\nretry = 5\n",
+        )
+        .build();
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Act
+    let tuned = skeleton
+        .with_network_settings(&NetworkSettings {
+            http_multiplexing: Some(false),
+            http_timeout: Some(60),
+            net_retry: Some(10),
+        })
+        .unwrap();
+    let untouched = skeleton
+        .with_network_settings(&NetworkSettings::default())
+        .unwrap();
+
+    // Assert
+    let config: toml::Value = toml::from_str(tuned.config_file.as_deref().unwrap()).unwrap();
+    assert_eq!(config["build"]["jobs"].as_integer(), Some(2));
+    assert_eq!(config["http"]["multiplexing"].as_bool(), Some(false));
+    assert_eq!(config["http"]["timeout"].as_integer(), Some(60));
+    assert_eq!(config["net"]["retry"].as_integer(), Some(10));
+    assert_eq!(untouched, skeleton);
+}
+
+#[test]
+pub fn member_globs_match_cargo() {
+    let package = |name: &str| {
+        format!(
+            r#"
+[package]
+name = "{}"
+version = "0.1.0"
+"#,
+            name
+        )
+    };
+    let fixtures = [
+        // Globs, with and without trailing slashes, and excluded members.
+        CargoWorkspace::new()
+            .manifest(
+                ".",
+                r#"
+[workspace]
+members = ["crates/*", "tools/*/", "single/"]
+exclude = ["crates/excluded"]
+"#,
+            )
+            .lib_package("crates/a", &package("a"))
+            .lib_package("crates/b", &package("b"))
+            .lib_package("crates/excluded", &package("excluded"))
+            .lib_package("tools/c", &package("c"))
+            .lib_package("single", &package("single"))
+            .touch("crates/README.md")
+            .build(),
+        // A nested workspace and a nested crate that is not a member.
+        CargoWorkspace::new()
+            .manifest(
+                ".",
+                r#"
+[workspace]
+members = ["crates/a"]
+exclude = ["nested"]
+"#,
+            )
+            .lib_package("crates/a", &package("a"))
+            .lib_package("crates/a/examples/not-a-member", &package("not-a-member"))
+            .manifest(
+                "nested",
+                r#"
+[workspace]
+members = ["d"]
+"#,
+            )
+            .lib_package("nested/d", &package("d"))
+            .build(),
+        // Path dependencies inside the workspace directory are implicit members.
+        CargoWorkspace::new()
+            .manifest(
+                ".",
+                r#"
+[package]
+name = "root"
+version = "0.1.0"
+
+[dependencies]
+e = { path = "vendored/e" }
+
+[workspace]
+members = ["crates/*"]
+"#,
+            )
+            .touch("src/lib.rs")
+            .lib_package("vendored/e", &package("e"))
+            .lib_package("crates/f", &package("f"))
+            .build(),
+    ];
+
+    for project in fixtures {
+        // Act
+        let skeleton = Skeleton::derive(project.path(), None).unwrap();
+        let cooked = TempDir::new().unwrap();
+        skeleton
+            .build_minimum_project(cooked.path(), false)
+            .unwrap();
+
+        // Assert
+        let expected = workspace_members(&project.path());
+        assert_eq!(expected, workspace_members(&cooked.canonicalize().unwrap()));
+        let mut packages: Vec<_> = skeleton
+            .manifests
+            .iter()
+            .filter(|manifest| manifest.contents.contains("[package]"))
+            .map(|manifest| manifest.relative_path.clone())
+            .collect();
+        packages.sort();
+        assert_eq!(expected, packages);
+        assert_eq!(
+            skeleton,
+            Skeleton::derive_standalone(project.path(), None).unwrap()
+        );
+    }
+}
+
+#[test]
+pub fn standalone_discovery_matches_cargo() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["app", "macros"]
+
+[workspace.package]
+version = "1.2.3"
+
+[workspace.dependencies]
+shared = { path = "libs/shared" }
+"#,
+        )
+        .manifest(
+            "app",
+            r#"
+[package]
+name = "app"
+version.workspace = true
+edition = "2021"
+
+[[bin]]
+name = "cli"
+path = "src/cli.rs"
+
+[[example]]
+name = "explicit"
+
+[dependencies]
+shared = { workspace = true }
+macros = { path = "../macros" }
+
+[target.'cfg(unix)'.dev-dependencies]
+testing = { path = "../libs/testing" }
+"#,
+        )
+        .touch_multiple(&[
+            "app/src/main.rs",
+            "app/src/cli.rs",
+            "app/src/bin/other.rs",
+            "app/src/bin/nested/main.rs",
+            "app/examples/explicit.rs",
+            "app/examples/discovered.rs",
+            "app/tests/integration.rs",
+            "app/benches/bench.rs",
+            "app/build.rs",
+        ])
+        .manifest(
+            "macros",
+            r#"
+[package]
+name = "my-macros"
+version = "0.1.0"
+autotests = false
+
+[lib]
+proc-macro = true
+"#,
+        )
+        .touch_multiple(&["macros/src/lib.rs", "macros/tests/ignored.rs"])
+        .lib_package(
+            "libs/shared",
+            r#"
+[package]
+name = "shared"
+version = "0.2.0"
+build = "generate.rs"
+"#,
+        )
+        .touch("libs/shared/generate.rs")
+        .lib_package(
+            "libs/testing",
+            r#"
+[package]
+name = "testing"
+version = "0.1.0"
+"#,
+        )
+        .lib_package(
+            "libs/unused",
+            r#"
+[package]
+name = "unused"
+version = "0.1.0"
+"#,
+        )
+        .build();
+
+    // Act
+    let standalone = Skeleton::derive_standalone(project.path(), None).unwrap();
+
+    // Assert
+    assert_eq!(Skeleton::derive(project.path(), None).unwrap(), standalone);
+    assert_eq!(
+        Skeleton::derive(project.path(), Some("cli".to_string())).unwrap(),
+        Skeleton::derive_standalone(project.path(), Some("cli".to_string())).unwrap()
+    );
+}
+
+/// The manifests of the workspace members, according to `cargo`.
+fn workspace_members(path: &Path) -> Vec<PathBuf> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .current_dir(path)
+        .no_deps()
+        .exec()
+        .unwrap();
+    let mut members: Vec<_> = metadata
+        .workspace_packages()
+        .iter()
+        .map(|package| {
+            package
+                .manifest_path
+                .as_std_path()
+                .strip_prefix(path)
+                .unwrap()
+                .to_path_buf()
+        })
+        .collect();
+    members.sort();
+    members
+}
+
+#[test]
+pub fn vendored_sources() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["a"]
+    "#,
+        )
+        .lib_package(
+            "a",
+            r#"
+[package]
+name = "a"
+version = "0.1.0"
+
+[dependencies]
+foo = { path = "../third-party/foo" }
+    "#,
+        )
+        .file(
+            ".cargo/config.toml",
+            r#"
+[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "third-party"
+    "#,
+        )
+        .lib_package(
+            "third-party/foo",
+            r#"
+[package]
+name = "foo"
+version = "1.0.0"
+    "#,
+        )
+        .touch("third-party/foo/Cargo.toml.orig")
+        .file(
+            "third-party/foo/.cargo-checksum.json",
+            r#"{"files":{},"package":"abc"}"#,
+        )
+        .build();
+
+    // Act
+    let skeleton = Skeleton::derive(project.path(), None).unwrap();
+
+    // Assert
+    let manifests: Vec<_> = skeleton
+        .manifests
+        .iter()
+        .map(|manifest| manifest.relative_path.to_str().unwrap())
+        .collect();
+    assert_eq!(manifests, ["Cargo.toml", "a/Cargo.toml"]);
+    assert_eq!(skeleton.vendored_sources.len(), 1);
+    assert_eq!(
+        skeleton.vendored_sources[0].relative_path,
+        Path::new("third-party")
+    );
+    assert!(!skeleton
+        .warnings()
+        .unwrap()
+        .iter()
+        .any(|warning| warning.contains("third-party")));
+    skeleton.check_vendored_sources(&project.path()).unwrap();
+    assert!(skeleton
+        .check_vendored_sources(TempDir::new().unwrap().path())
+        .is_err());
+
+    std::fs::write(
+        project.path().join("third-party/foo/.cargo-checksum.json"),
+        r#"{"files":{},"package":"def"}"#,
+    )
+    .unwrap();
+    let updated = Skeleton::derive(project.path(), None).unwrap();
+    assert_ne!(
+        skeleton.vendored_sources[0].checksum,
+        updated.vendored_sources[0].checksum
+    );
+}
+
+#[test]
+pub fn select_member_closure() {
+    // Arrange
+    let project = CargoWorkspace::new()
+        .manifest(
+            ".",
+            r#"
+[workspace]
+members = ["app", "b", "c", "d"]
+    "#,
+        )
+        .manifest(
+            "app",
+            r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[[bin]]
+name = "server"
+path = "src/main.rs"
+
+[dependencies]
+b = { path = "../b" }
+
+[dev-dependencies]
+c = { path = "../c" }
+    "#,
+        )
+        .touch("app/src/main.rs")
+        .lib_package(
+            "b",
+            r#"
+[package]
+name = "b"
+version = "0.1.0"
+    "#,
+        )
+        .lib_package(
+            "c",
+            r#"
+[package]
+name = "c"
+version = "0.1.0"
+    "#,
+        )
+        .lib_package(
+            "d",
+            r#"
+[package]
+name = "d"
+version = "0.1.0"
+    "#,
+        )
+        .build();
+    let manifests = |selection: &str| -> Vec<String> {
+        Skeleton::derive(project.path(), Some(selection.to_string()))
+            .unwrap()
+            .manifests
+            .into_iter()
+            .map(|manifest| manifest.relative_path.to_str().unwrap().to_string())
+            .collect()
+    };
+
+    // Act & Assert
+    assert_eq!(
+        manifests("server"),
+        ["Cargo.toml", "app/Cargo.toml", "b/Cargo.toml"]
+    );
+    assert_eq!(manifests("b"), ["Cargo.toml", "b/Cargo.toml"]);
+    assert_eq!(manifests("d/"), ["Cargo.toml", "d/Cargo.toml"]);
+    let skeleton = Skeleton::derive(project.path(), Some("app".to_string())).unwrap();
+    assert!(!skeleton.has_dev_dependencies().unwrap());
+    assert!(Skeleton::derive(project.path(), Some("missing".to_string())).is_err());
+}
+
+fn check(actual: &str, expect: Expect) {
+    let actual = actual.to_string();
+    expect.assert_eq(&actual);
+}
+
+#[derive(Default)]
+struct CargoWorkspace {
+    files: HashMap<PathBuf, String>,
+}
+impl CargoWorkspace {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn manifest<P: AsRef<Path>>(&mut self, directory: P, content: &str) -> &mut Self {
+        self.file(directory.as_ref().join("Cargo.toml"), content)
+    }
+
+    fn lib_package<P: AsRef<Path>>(&mut self, directory: P, content: &str) -> &mut Self {
+        let directory = directory.as_ref();
+        self.manifest(directory, content)
+            .file(directory.join("src/lib.rs"), "")
+    }
+
+    fn bin_package<P: AsRef<Path>>(&mut self, directory: P, content: &str) -> &mut Self {
+        let directory = directory.as_ref();
+        self.manifest(directory, content)
+            .file(directory.join("src/main.rs"), "")
+    }
+
+    fn file<P: AsRef<Path>>(&mut self, path: P, content: &str) -> &mut Self {
+        let path = PathBuf::from(path.as_ref());
+
+        assert!(self.files.insert(path, content.to_string()).is_none());
+        self
+    }
+
+    fn touch<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.file(path, "")
+    }
+    fn touch_multiple<P: AsRef<Path>>(&mut self, paths: &[P]) -> &mut Self {
+        for path in paths {
+            self.touch(path);
+        }
+        self
+    }
+
+    fn build(&mut self) -> BuiltWorkspace {
+        let directory = TempDir::new().unwrap();
+        for (file, content) in &self.files {
+            let path = directory.join(file);
+            let content = content.trim_start();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        BuiltWorkspace { directory }
+    }
+}
+
+struct BuiltWorkspace {
+    directory: TempDir,
+}
+impl BuiltWorkspace {
+    fn path(&self) -> PathBuf {
+        self.directory.canonicalize().unwrap()
+    }
+}
@@ -1,7 +0,0 @@
-mod recipe;
-mod skeleton;
-
-pub use recipe::{
-    AllFeatures, CommandArg, CookArgs, DefaultFeatures, OptimisationProfile, Recipe, TargetArgs,
-};
-pub use skeleton::*;
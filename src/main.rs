@@ -1,12 +1,19 @@
 use anyhow::{anyhow, Context};
-use chef::{
-    AllFeatures, CommandArg, CookArgs, DefaultFeatures, OptimisationProfile, Recipe, TargetArgs,
+use chef_core::{
+    ignored_toolchain_file, postprocess_recipe, sign_recipe, verify_recipe_signature, AllFeatures,
+    ArtifactStore, BestEffort, ChefProfile, CommandArg, CookArgs, CookMetrics, CookPhase,
+    CriticalPath, DefaultFeatures, DemoStrategy, EnvFingerprint, Linker, NetworkSettings,
+    OptimisationProfile, OtlpExporter, OutputSnapshot, PackageStub, ProfileSetting, Recipe,
+    RecipeFormat, RecipeIntegrity, RecipeReport, ReportFormat, Severity, Skeleton, TargetArgs,
+    TimedUnit, TraceFormat, TraceRecorder, WorkspaceRecipe,
 };
 use clap::crate_version;
 use clap::Parser;
 use fs_err as fs;
-use std::collections::HashSet;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
 
 /// Cache the dependencies of your Rust project.
 #[derive(Parser)]
@@ -25,11 +32,37 @@ pub enum CargoInvocation {
     // All `cargo` subcommands receive their name (e.g. `chef` as the first command).
     // See https://github.com/rust-lang/rustfmt/pull/3569
     Chef {
+        /// Run as if `cargo chef` was started in this directory: every relative path, on the
+        /// command line or not, is resolved against it.
+        #[clap(long, global = true)]
+        cwd: Option<PathBuf>,
+        /// Record how long each phase of the command takes (discovery, parsing, masking,
+        /// writing the skeleton, building, cleaning up) as `tracing` spans, written to this path
+        /// once the command is over.
+        #[clap(long, global = true, value_name = "PATH")]
+        trace_output: Option<PathBuf>,
+        /// The format of `--trace-output`: `chrome` for the Chrome trace event format (open it
+        /// in Perfetto or `chrome://tracing`), `otlp` for OTLP/JSON (e.g. for the `otlpjsonfile`
+        /// receiver of the OpenTelemetry Collector).
+        #[clap(
+            long,
+            global = true,
+            value_enum,
+            default_value = "chrome",
+            requires = "trace_output"
+        )]
+        trace_format: TraceOutputFormat,
         #[clap(subcommand)]
         command: Command,
     },
 }
 
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum TraceOutputFormat {
+    Chrome,
+    Otlp,
+}
+
 #[derive(Parser)]
 #[clap(
     version = crate_version!(),
@@ -41,33 +74,365 @@ pub enum Command {
     ///
     /// `cargo chef prepare` emits a recipe file that can be later used via
     /// `cargo chef cook --recipe <recipe-path>.json`.
-    Prepare(Prepare),
+    Prepare(Box<Prepare>),
     /// Re-hydrate the minimum project skeleton identified by `cargo chef prepare` and build
     /// it to cache dependencies.
-    Cook(Cook),
+    Cook(Box<Cook>),
+    /// List all the files (manifests, lockfile and cargo configuration) that `cargo chef prepare`
+    /// reads, relative to the project root.
+    ///
+    /// Useful to generate a `.dockerignore` or a sparse-checkout list.
+    ListInputs(ListInputs),
+    /// Compare the current environment with the one `cargo chef cook` ran in (as recorded in
+    /// the `.chef-env` file it writes) and list every mismatch that would cause dependencies
+    /// to be rebuilt.
+    ///
+    /// Pass the same flags you are going to build the project with. The command fails if
+    /// any mismatch is found.
+    EnvCheck(EnvCheck),
+    /// Upgrade a recipe written by an older version of `cargo-chef` (or by upstream
+    /// `cargo-chef`) to the current format, in place, re-deriving what older formats do not
+    /// record. Recipes that are already up to date are left untouched.
+    UpgradeRecipe(UpgradeRecipe),
+    /// Compare a recipe with the one of a base revision (e.g. the target branch of a pull
+    /// request): did it change, which dependencies changed, and how long does rebuilding them
+    /// take. Meant to be posted as a pull request comment by CI, with `--format markdown`.
+    Report(Report),
+    /// Scaffold a small workspace, with a Dockerfile demonstrating a way of caching its
+    /// dependencies with `cargo-chef`.
+    Demo(Demo),
+}
+
+#[derive(Parser)]
+pub struct Demo {
+    /// What the Dockerfile demonstrates.
+    #[clap(value_enum)]
+    strategy: Strategy,
+    /// Where to write the demo. None of its files may exist yet.
+    #[clap(long, default_value = "chef-demo")]
+    path: PathBuf,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Strategy {
+    /// A planner, a builder and a runtime stage.
+    Basic,
+    /// One cook for several target triples, one runtime image per target.
+    MultiTarget,
+    /// A recipe with dev-dependencies for the CI image, one without for production.
+    SplitRecipes,
+    /// Cache mounts for the registry and the target directory.
+    CacheMounts,
+}
+
+#[derive(Parser)]
+pub struct EnvCheck {
+    /// The fingerprint written by `cargo chef cook`.
+    #[clap(long, default_value = chef_core::FINGERPRINT_FILE_NAME)]
+    fingerprint_path: PathBuf,
+    /// Build artifacts with the specified profile.
+    #[clap(long)]
+    profile: Option<String>,
+    /// Build in release mode.
+    #[clap(long)]
+    release: bool,
+    /// Build for the target triple. The flag can be passed multiple times.
+    #[clap(long)]
+    target: Option<Vec<String>>,
+    /// Do not activate the `default` feature.
+    #[clap(long)]
+    no_default_features: bool,
+    /// Enable all features.
+    #[clap(long)]
+    all_features: bool,
+    /// Space or comma separated list of features to activate.
+    #[clap(long, value_delimiter = ',')]
+    features: Option<Vec<String>>,
+}
+
+#[derive(Parser)]
+pub struct UpgradeRecipe {
+    /// The recipe to upgrade.
+    #[clap(long, default_value = "recipe.json")]
+    recipe_path: PathBuf,
+    /// Do not write anything, fail if the recipe is not up to date.
+    #[clap(long)]
+    check: bool,
+}
+
+#[derive(Parser)]
+pub struct Report {
+    /// The recipe of the base revision.
+    #[clap(long)]
+    base: PathBuf,
+    /// The recipe to report on.
+    #[clap(long, default_value = "recipe.json")]
+    recipe_path: PathBuf,
+    /// A timing report of a cook of the base recipe (`cook --timings` writes it to
+    /// `target/cargo-timings/cargo-timing.html`), to estimate the extra compile time.
+    #[clap(long)]
+    timings: Option<PathBuf>,
+    #[clap(long, value_enum, default_value = "text")]
+    format: ReportOutput,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum ReportOutput {
+    /// Plain text.
+    Text,
+    /// GitHub-flavoured markdown.
+    Markdown,
+}
+
+#[derive(Parser)]
+pub struct ListInputs {
+    /// How to print the list of files.
+    #[clap(long, value_enum, default_value = "text")]
+    format: ListFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum ListFormat {
+    /// One path per line.
+    Text,
+    /// A JSON array of paths.
+    Json,
 }
 
 #[derive(Parser)]
 pub struct Prepare {
     /// The filepath used to save the computed recipe.
     ///
-    /// It defaults to "recipe.json".
+    /// It defaults to "recipe.json". Use `-` to write the recipe to stdout.
     #[clap(long, default_value = "recipe.json")]
     recipe_path: PathBuf,
 
     /// When --bin is specified, `cargo-chef` will ignore all members of the workspace
-    /// that are not necessary to successfully compile the specific binary.
+    /// that are not necessary to successfully compile the specific binary (i.e. the package
+    /// it belongs to and its path dependencies), as well as their dev-dependencies.
     #[clap(long)]
     bin: Option<String>,
+
+    /// Like --bin, but selects a package (by name) instead of a binary.
+    #[clap(long, short = 'p', conflicts_with = "bin")]
+    package: Option<String>,
+
+    /// Root of an independent workspace, relative to the current directory, to include in the
+    /// recipe. The flag can be passed multiple times to combine the workspaces of a monorepo
+    /// into a single recipe.
+    ///
+    /// It defaults to the current directory.
+    #[clap(long, conflicts_with_all = ["bin", "package"])]
+    path: Vec<PathBuf>,
+
+    /// Leave out of the recipe the workspace members whose package name or path matches this
+    /// glob (e.g. `fuzz` or `examples/*`). The flag can be passed multiple times.
+    ///
+    /// The excluded members are recorded in the `workspace.exclude` of the recipe, so that `cook`
+    /// builds the pruned workspace. Members that other members depend on are kept as path
+    /// dependencies.
+    #[clap(long, conflicts_with_all = ["bin", "package", "path"])]
+    exclude: Vec<String>,
+
+    /// Workspace members matching this glob (e.g. `generated/*`) are allowed to be missing,
+    /// for example because they are generated at build time: `prepare` replaces them with
+    /// placeholder libraries in the recipe.
+    #[clap(long)]
+    allow_missing_members: Option<String>,
+
+    /// Apply the settings of a profile declared in `chef.toml`, as `[profiles.<name>]`: whether
+    /// dev-dependencies and package metadata are kept, and which workspace members are left
+    /// out of the recipe.
+    #[clap(long)]
+    chef_profile: Option<String>,
+
+    /// Keep the real build script of this package in the recipe, instead of a dummy one, so
+    /// that whatever it compiles or generates (e.g. native libraries) is cooked. The flag can
+    /// be passed multiple times.
+    ///
+    /// Packages can opt in on their own with `package.metadata.chef.keep-build-script = true`,
+    /// and declare the files their build script reads with
+    /// `package.metadata.chef.build-script-assets`, a list of globs relative to the package.
+    #[clap(long, conflicts_with = "path")]
+    keep_build_script: Vec<String>,
+
+    /// Also keep the build scripts of packages depending on crates whose build-time inputs are
+    /// well known, along with those inputs: `.proto` files for `prost-build` and `tonic-build`,
+    /// `.sqlx/` offline data and `migrations/` for `sqlx`, `migrations/` and `diesel.toml` for
+    /// `diesel_migrations`.
+    #[clap(long)]
+    detect_assets: bool,
+
+    /// Keep the real sources of this workspace member in the recipe, instead of dummies, so
+    /// that it is cooked for real: e.g. a proc-macro crate used by the other members, whose
+    /// dependents would otherwise be built again. The flag can be passed multiple times.
+    ///
+    /// Packages can opt in on their own with `package.metadata.chef.keep-source = true`. The
+    /// members a kept package depends on must be kept as well.
+    #[clap(long, conflicts_with = "path")]
+    keep_source: Vec<String>,
+
+    /// Include the path dependencies that live outside of the current directory (e.g.
+    /// `{ path = "../../shared/foo" }`) in the recipe, instead of only warning about them.
+    ///
+    /// Their stubs are stored under `chef-external/` in the recipe and the paths pointing to
+    /// them are rewritten, so that `cook` can build their dependencies without access to them.
+    #[clap(long)]
+    allow_external_paths: bool,
+
+    /// Write one recipe per workspace member instead of a single one, each containing only
+    /// the manifests of the member and of its path dependencies.
+    ///
+    /// Recipes are named after the packages, next to `--recipe-path`: e.g. `recipe.api.json`
+    /// for the `api` package. Cook them in separate layers, heaviest first, so that a change
+    /// to a small member does not invalidate the dependencies of the others.
+    #[clap(long, conflicts_with_all = ["bin", "package", "path", "check"])]
+    split_per_member: bool,
+
+    /// Also write one recipe per workspace nested in the current one (e.g. a fuzzing
+    /// workspace listed in `workspace.exclude`), which are otherwise left out.
+    ///
+    /// Recipes are named after the path of the nested workspace, next to `--recipe-path`:
+    /// e.g. `recipe.fuzz.json` for the workspace in `fuzz/`. Cook them from the same directory
+    /// as the main recipe.
+    #[clap(long, conflicts_with_all = ["path", "split_per_member", "check"])]
+    nested_workspaces: bool,
+
+    /// Only look for nested workspaces in this directory, relative to the current one. The
+    /// flag can be passed multiple times.
+    ///
+    /// Useful in hybrid repositories, where Rust code is a small part of a large tree (e.g.
+    /// next to Python or Node projects). The workspace itself is always resolved from the
+    /// current directory, through `cargo metadata`, which does not scan the filesystem.
+    #[clap(long, requires = "nested_workspaces")]
+    scan_root: Vec<PathBuf>,
+
+    /// Pipe the recipe through an external command before saving it, e.g. to apply custom
+    /// normalization or policies.
+    ///
+    /// The command is run through the shell. It receives the recipe, serialized as JSON, on
+    /// stdin and must print the resulting recipe, as JSON, on stdout.
+    #[clap(long)]
+    postprocess: Option<String>,
+
+    /// The format used to serialize the recipe.
+    ///
+    /// `cook` detects the format on its own, there is no need to specify it again.
+    #[clap(long, value_enum, default_value = "json")]
+    format: Format,
+
+    /// Write a detached ed25519 signature of the recipe next to it.
+    ///
+    /// The PEM-encoded private key is read from `--signing-key-path` or, if omitted,
+    /// from the `CHEF_SIGNING_KEY` environment variable.
+    #[clap(long)]
+    sign: bool,
+
+    /// Path to the PEM-encoded ed25519 private key used by `--sign`.
+    #[clap(long, requires = "sign")]
+    signing_key_path: Option<PathBuf>,
+
+    /// Where to write the signature produced by `--sign`.
+    ///
+    /// It defaults to the recipe path with a `.sig` suffix. When several recipes are written,
+    /// e.g. with `--split-per-member`, each one gets its own signature: `recipe.sig` becomes
+    /// `recipe.<member>.sig`.
+    #[clap(long, requires = "sign")]
+    signature_path: Option<PathBuf>,
+
+    /// Do not write the recipe: print the files it would be built from and warn about
+    /// anything that is likely to cause trouble when cooking it.
+    ///
+    /// The command fails if the recipe cannot be computed, which makes it suitable for
+    /// pre-commit hooks.
+    #[clap(long, conflicts_with = "sign")]
+    check: bool,
+
+    /// Fail if `Cargo.lock` does not satisfy the dependencies declared by the manifests, i.e.
+    /// if cooking the recipe with `--locked` would fail.
+    #[clap(long)]
+    locked: bool,
+
+    /// Whether `cook` fetches dependencies over multiplexed HTTP/2 connections, recorded as
+    /// `http.multiplexing` in the cargo configuration of the recipe.
+    ///
+    /// Cargo cannot cap the number of concurrent downloads: `--http-multiplexing false` is the
+    /// way to throttle them, to at most two at a time per host, on small CI runners or behind
+    /// proxies that cannot keep up.
+    #[clap(long)]
+    http_multiplexing: Option<bool>,
+
+    /// Timeout of the HTTP requests of `cook`, in seconds, recorded as `http.timeout` in the
+    /// cargo configuration of the recipe.
+    #[clap(long)]
+    http_timeout: Option<u32>,
+
+    /// How many times `cook` retries failed network requests, recorded as `net.retry` in the
+    /// cargo configuration of the recipe.
+    #[clap(long)]
+    net_retry: Option<u32>,
+
+    /// Discover the workspace members without invoking `cargo`, so that `prepare` runs without
+    /// a Rust toolchain: e.g. in a planner stage built `FROM scratch`, holding nothing but the
+    /// static (musl) chef binary and the manifests.
+    ///
+    /// The recipe is the same as the one computed with `cargo metadata` for the usual layouts:
+    /// `members` globs and `exclude`, path dependencies in the workspace directory, automatic
+    /// targets and versions inherited from `workspace.package`.
+    #[clap(
+        long,
+        conflicts_with_all = ["path", "allow_missing_members", "allow_external_paths", "nested_workspaces"]
+    )]
+    static_binary_friendly: bool,
+
+    /// Do not prepare the current project: check that `--static-binary-friendly` works in this
+    /// environment instead, by computing the recipe of a small built-in workspace with neither
+    /// `cargo` nor `rustc` within reach.
+    #[clap(long, requires = "static_binary_friendly")]
+    self_test: bool,
+
+    /// Also write the warnings (always printed to stderr) to this file, as a JSON array of
+    /// `{"severity", "file", "message"}` objects, e.g. to turn them into CI annotations.
+    ///
+    /// The file is written even if there are no warnings.
+    #[clap(long, conflicts_with = "check")]
+    warnings_path: Option<PathBuf>,
+}
+
+/// A warning emitted by `prepare`, as written to `--warnings-path`.
+#[derive(serde::Serialize)]
+struct PrepareWarning {
+    severity: &'static str,
+    /// The file the warning is about, relative to the project root.
+    file: PathBuf,
+    message: String,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum Phase {
+    BuildDeps,
+    Deps,
+    DevDeps,
 }
 
 #[derive(Parser)]
 pub struct Cook {
     /// The filepath `cook` should be reading the recipe from.
     ///
-    /// It defaults to "recipe.json".
+    /// It defaults to "recipe.json". Use `-` to read the recipe from stdin.
+    ///
+    /// The flag can be passed multiple times to cook several independent recipes at once, e.g.
+    /// `--recipe-path api/recipe.json --recipe-path worker/recipe.json` for an image hosting
+    /// several services: each recipe is cooked in the directory it lives in, and they all
+    /// build into the same target directory (`target`, unless `--target-dir` is passed).
     #[clap(long, default_value = "recipe.json")]
-    recipe_path: PathBuf,
+    recipe_path: Vec<PathBuf>,
     /// Build artifacts with the specified profile.
     #[clap(long)]
     profile: Option<String>,
@@ -75,11 +440,33 @@ pub struct Cook {
     #[clap(long)]
     release: bool,
     /// Run `cargo check` instead of `cargo build`. Primarily useful for speeding up your CI pipeline.
+    ///
+    /// Dependencies are only compiled to metadata (`.rmeta`), which is all `cargo check` needs:
+    /// build scripts and proc-macros, which run at compile time, are still built in full.
     #[clap(long)]
     check: bool,
     /// Run `cargo clippy` instead of `cargo build`. Primarily useful for speeding up your CI pipeline. Requires clippy to be installed.
     #[clap(long)]
     clippy: bool,
+    /// Run `cargo doc` instead of `cargo build`, so that the documentation of the dependencies
+    /// is rendered once, in the cooked layer, rather than by every `cargo doc` of the project.
+    #[clap(long, conflicts_with_all = ["tests", "benches", "all_targets"])]
+    doc: bool,
+    /// Run `cargo test --no-run` instead of `cargo build`: the invocation behind
+    /// `cargo nextest run` and `cargo nextest archive`, which then only compile the workspace
+    /// members. Pass the same `--profile` as the `--cargo-profile` of nextest.
+    #[clap(long, conflicts_with_all = ["check", "clippy", "zigbuild", "doc"])]
+    nextest: bool,
+    /// Instrument dependencies for coverage, with the rustflags and in the target directory of
+    /// `cargo llvm-cov`, which then reuses them. Pair it with `--tests` or `--nextest`, as
+    /// `cargo llvm-cov` builds the tests of the workspace.
+    #[clap(long, conflicts_with_all = ["check", "clippy", "doc"])]
+    coverage: bool,
+    /// Lint flags passed on to clippy, separated by whitespace, e.g.
+    /// `--clippy-args "-D warnings"`. Pass the same ones as in the final `cargo clippy`: lints
+    /// apply to the (empty) dummy sources of the workspace members as well.
+    #[clap(long, requires = "clippy", allow_hyphen_values = true)]
+    clippy_args: Option<String>,
     /// Build for the target triple. The flag can be passed multiple times to cook for multiple targets.
     #[clap(long)]
     target: Option<Vec<String>>,
@@ -118,14 +505,28 @@ pub struct Cook {
     #[clap(long, short = 'p')]
     package: Option<Vec<String>>,
     /// Build all members in the workspace.
+    ///
+    /// Without it, like `cargo build`, only the `default-members` of the workspace are cooked
+    /// (if it declares any): the other members are left out of the skeleton.
     #[clap(long)]
     workspace: bool,
+    /// Exclude packages from the build, together with `--workspace`. Their manifests are left
+    /// out of the skeleton, unless other members depend on them.
+    #[clap(long, requires = "workspace")]
+    exclude: Option<Vec<String>>,
     /// Build offline.
     #[clap(long)]
     offline: bool,
     /// Report build timings.
     #[clap(long)]
     timings: bool,
+    /// Print the critical path of the build: the chain of dependencies, each one waiting on the
+    /// previous one, that bounds its duration. Implies `--timings`, whose HTML report plots
+    /// every unit.
+    ///
+    /// Only the units that were actually built are taken into account, fresh ones are not.
+    #[clap(long)]
+    critical_path: bool,
     /// Cook using `#[no_std]` configuration  (does not affect `proc-macro` crates)
     #[clap(long)]
     no_std: bool,
@@ -134,46 +535,306 @@ pub struct Cook {
     #[clap(long)]
     bin: Option<String>,
     /// Run `cargo zigbuild` instead of `cargo build`. You need to install
-    /// the `cargo-zigbuild` crate and the Zig compiler toolchain separately
+    /// the `cargo-zigbuild` crate and the Zig compiler toolchain separately.
+    /// Targets can carry the version of glibc to link against, e.g.
+    /// `--target x86_64-unknown-linux-gnu.2.17`.
     #[clap(long)]
     zigbuild: bool,
+    /// Run `cross build` (or `cross check`, `cross clippy`) instead of `cargo build`, to cook
+    /// dependencies in the container `cross` builds the target in. You need to install `cross`
+    /// separately. Targets default to `CARGO_BUILD_TARGET` or the configured `build.target`.
+    #[clap(long)]
+    cross: bool,
+    /// Only cook part of the dependencies, to cache them in separate layers.
+    ///
+    /// `build-deps` builds build-dependencies and proc-macros only, `deps` the dependencies of
+    /// libraries and binaries, `dev-deps` the ones of tests, benches and examples on top of
+    /// them (and does nothing if the recipe does not declare any dev-dependency). Production
+    /// images can stop after `deps`.
+    #[clap(long, value_enum, conflicts_with_all = ["benches", "tests", "examples", "all_targets"])]
+    phase: Option<Phase>,
+    /// Build a group of dependencies last, and only warn if some of them fail to compile:
+    /// `dev` for the dev-dependencies, or one of the `--target` triples for the dependencies
+    /// built for it. The flag can be passed multiple times.
+    ///
+    /// Whatever did compile is cached, and the final build compiles the rest: useful while
+    /// migrating a large workspace, until every dependency cooks.
+    #[clap(long, value_parser = |group: &str| group.parse::<BestEffort>())]
+    best_effort: Vec<BestEffort>,
+    /// Shorthand for `--phase build-deps`.
+    #[clap(long, conflicts_with = "phase")]
+    only_build_deps: bool,
+    /// Replace the `cargo` invocation used to build dependencies, e.g. to use `cargo remote`
+    /// or a distributed build wrapper. `cargo-chef` still takes care of the skeleton,
+    /// the cleanup and the environment fingerprint.
+    ///
+    /// Arguments are separated by whitespace: `{cargo}` stands for the path to `cargo` and
+    /// `{args}` for the arguments `cook` would pass to it (appended if missing), e.g.
+    /// `--build-command "cargo remote -- {args}"`.
+    #[clap(long)]
+    build_command: Option<String>,
+    /// Replace `cargo build` with another program and subcommand, separated by whitespace,
+    /// e.g. `--cargo-command "cargo auditable build"`: the arguments `cook` would pass to
+    /// `cargo build` are appended.
+    #[clap(
+        long,
+        conflicts_with_all = ["build_command", "check", "clippy", "zigbuild", "doc", "nextest", "cross"]
+    )]
+    cargo_command: Option<String>,
+    /// Arguments passed on to `cargo` as they are, after the ones of `cook`, e.g.
+    /// `cargo chef cook --release -- --config net.git-fetch-with-cli=true`.
+    #[clap(last = true)]
+    extra_arguments: Vec<String>,
+    /// Cook the recipe even if it was modified since it was prepared, e.g. edited by hand,
+    /// which `cook` otherwise refuses to do.
+    #[clap(long)]
+    allow_modified: bool,
+    /// Refuse to cook the recipe unless it matches its detached ed25519 signature,
+    /// as produced by `cargo chef prepare --sign`.
+    ///
+    /// The PEM-encoded public key is read from `--verifying-key-path` or, if omitted,
+    /// from the `CHEF_VERIFYING_KEY` environment variable.
+    #[clap(long)]
+    verify_signature: bool,
+    /// Path to the PEM-encoded ed25519 public key used by `--verify-signature`.
+    #[clap(long, requires = "verify_signature")]
+    verifying_key_path: Option<PathBuf>,
+    /// Where to read the signature checked by `--verify-signature` from.
+    ///
+    /// It defaults to the recipe path with a `.sig` suffix.
+    #[clap(long, requires = "verify_signature")]
+    signature_path: Option<PathBuf>,
+    /// Build the dependencies that are not part of the workspace faster (more codegen units,
+    /// no incremental compilation), at the expense of their runtime performance, by injecting
+    /// `[profile.<profile>.package."*"]` overrides in the skeleton.
+    ///
+    /// The final build must use the same overrides (in the manifest or with `--config`, as
+    /// printed at the end of the cook), otherwise dependencies are rebuilt.
+    #[clap(long)]
+    fast_deps: bool,
+    /// The debug info of the dependencies that are not part of the workspace, e.g. `0` to keep
+    /// gigabytes of it out of the cooked layer, by injecting `[profile.<profile>.package."*"]`
+    /// overrides in the skeleton. Workspace members follow the settings of the profile.
+    ///
+    /// As with `--fast-deps`, the final build must use the same overrides, otherwise
+    /// dependencies are rebuilt.
+    #[clap(long, value_name = "LEVEL", value_parser = clap::builder::PossibleValuesParser::new([
+        "0", "1", "2", "false", "true", "none", "line-directives-only", "line-tables-only", "limited", "full",
+    ]))]
+    deps_debuginfo: Option<String>,
+    /// Patch a setting of the `[profile]` table of the skeleton before building, as
+    /// `<profile>.<key>=<value>`: e.g. `release.lto=thin`, `release.debug=1` or
+    /// `release.package."*".opt-level=3` for the dependencies only. It can be repeated.
+    ///
+    /// As with `--fast-deps`, the final build must use the same settings, otherwise
+    /// dependencies are rebuilt.
+    #[clap(long = "profile-setting", value_name = "SETTING")]
+    profile_settings: Vec<String>,
+    /// Replace a local crate with a stub, for crates that cannot be compiled where `cook` runs
+    /// (e.g. FFI crates whose native library is only available in the final build): it is
+    /// cooked without its dependencies nor build script, while the rest of the dependency tree
+    /// is cooked as usual. It can be repeated.
+    ///
+    /// `<name>` declares every feature of the crate, emptied. `<name>=<features>` only declares
+    /// the comma-separated features the rest of the workspace enables, and
+    /// `<name>=<path>.toml` uses the mock manifest at `path` instead.
+    #[clap(long, value_name = "NAME[=FEATURES|MANIFEST]")]
+    replace_local: Vec<String>,
+    /// Install the toolchain pinned by the `rust-toolchain.toml` file of the recipe with
+    /// rustup, unless it already is. Dependencies are built with `cargo +<channel>` either way.
+    #[clap(long)]
+    install_toolchain: bool,
+    /// Install the standard library of the `--target`s that are missing with
+    /// `rustup target add`, instead of failing to build them.
+    #[clap(long)]
+    auto_install_target: bool,
+    /// Cook even if the toolchain is older than the `rust-version` of the workspace, which
+    /// `cook` otherwise refuses to do. `--ignore-rust-version` is passed on to `cargo`, the
+    /// final build needs it as well.
+    #[clap(long)]
+    ignore_rust_version: bool,
+    /// Link dependencies with `mold` or `lld`, which must be installed: `-C
+    /// link-arg=-fuse-ld=<linker>` is appended to the `rustflags` of the skeleton
+    /// configuration. The final build must use the same flags, otherwise dependencies are
+    /// rebuilt.
+    #[clap(long, value_parser = |linker: &str| linker.parse::<Linker>())]
+    linker: Option<Linker>,
+    /// Do not disable incremental compilation, which `cook` does by setting
+    /// `CARGO_INCREMENTAL=0` unless it is already set: dependencies are never compiled
+    /// incrementally, and the incremental artifacts of the dummy workspace members only bloat
+    /// the cooked layer.
+    #[clap(long)]
+    incremental: bool,
+    /// Write the list of files `cook` added to the target directories and to `CARGO_HOME`,
+    /// with their SHA-256, to this path as JSON - e.g. for layer squashers or SBOM tools.
+    #[clap(long)]
+    artifacts_manifest: Option<PathBuf>,
+    /// Experimental: keep the cooked files in a content-addressed store at this path, shared
+    /// by the images of several services, and hardlink them into the target directories.
+    ///
+    /// Before cooking, the target directories are rebuilt from the store if it already holds
+    /// the artifacts of the same recipe. Files are copied when the store is on another
+    /// filesystem.
+    #[clap(long, value_name = "DIR")]
+    artifact_store: Option<PathBuf>,
+    /// The recipe the target directory was last cooked from, e.g. kept in a cache mount along
+    /// with it: if only `Cargo.lock` changed since, only the external crates whose locked
+    /// version changed, and the ones depending on them, are rebuilt. The recipe is saved to
+    /// this path after cooking, for the next time.
+    ///
+    /// Everything is cooked if the path does not exist yet, if the manifests changed or if
+    /// features, tests or several targets are selected.
+    #[clap(long, value_name = "PATH")]
+    previous_recipe: Option<PathBuf>,
+    /// Cook each package once per feature, like `cargo hack --each-feature` builds it: without
+    /// default features, with each of its features alone, then with all of them. The
+    /// dependencies of every feature selection end up in the cooked layer.
+    #[clap(long, conflicts_with_all = ["features", "no_default_features", "all_features", "bin"])]
+    each_feature: bool,
+    /// Cook offline from a read-only snapshot of `CARGO_HOME` (e.g. a bind-mounted volume),
+    /// holding the registry index, the downloaded crates and the git checkouts the recipe
+    /// needs. Implies `--offline`.
+    ///
+    /// `cargo` needs to write to `CARGO_HOME`: the snapshot is overlaid, with symlinks, onto
+    /// the writable `--registry-scratch` directory, which the final build must use as
+    /// `CARGO_HOME` too.
+    #[clap(long, value_name = "DIR")]
+    registry_snapshot: Option<PathBuf>,
+    /// The writable directory the registry snapshot is overlaid onto.
+    #[clap(long, value_name = "DIR", requires = "registry_snapshot")]
+    registry_scratch: Option<PathBuf>,
 }
 
 fn _main() -> Result<(), anyhow::Error> {
-    let current_directory = std::env::current_dir().unwrap();
-
     let cli = Cli::parse();
     // "Unwrapping" the actual command.
-    let command = match cli.command {
-        CargoInvocation::Chef { command } => command,
+    let (cwd, trace_output, trace_format, command) = match cli.command {
+        CargoInvocation::Chef {
+            cwd,
+            trace_output,
+            trace_format,
+            command,
+        } => (cwd, trace_output, trace_format, command),
+    };
+    if let Some(cwd) = cwd {
+        std::env::set_current_dir(&cwd)
+            .with_context(|| format!("Failed to change the working directory to {:?}", cwd))?;
+    }
+    let current_directory = std::env::current_dir().unwrap();
+
+    let Some(trace_output) = trace_output else {
+        return run(command, current_directory);
+    };
+    let recorder = TraceRecorder::install()?;
+    let outcome = {
+        let _span = tracing::info_span!("cargo_chef").entered();
+        run(command, current_directory.clone())
+    };
+    // The trace is all the more useful when the command failed.
+    recorder.write(
+        &current_directory.join(trace_output),
+        match trace_format {
+            TraceOutputFormat::Chrome => TraceFormat::Chrome,
+            TraceOutputFormat::Otlp => TraceFormat::Otlp,
+        },
+    )?;
+    outcome
+}
+
+/// The package and the stub of a `--replace-local` value.
+fn package_stub(
+    current_directory: &Path,
+    replacement: &str,
+) -> Result<(String, PackageStub), anyhow::Error> {
+    let Some((name, stub)) = replacement.split_once('=') else {
+        return Ok((replacement.to_string(), PackageStub::Features(None)));
+    };
+    let stub = if stub.ends_with(".toml") {
+        let path = current_directory.join(stub);
+        PackageStub::Manifest(
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read the mock manifest of `{}`.", name))?,
+        )
+    } else {
+        PackageStub::Features(Some(
+            stub.split(',')
+                .map(str::trim)
+                .filter(|feature| !feature.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ))
     };
+    Ok((name.to_string(), stub))
+}
 
+fn run(command: Command, current_directory: PathBuf) -> Result<(), anyhow::Error> {
     match command {
-        Command::Cook(Cook {
-            recipe_path,
-            profile,
-            release,
-            check,
-            clippy,
-            target,
-            no_default_features,
-            all_features,
-            features,
-            unstable_features,
-            target_dir,
-            benches,
-            tests,
-            examples,
-            all_targets,
-            manifest_path,
-            package,
-            workspace,
-            offline,
-            timings,
-            no_std,
-            bin,
-            zigbuild,
-        }) => {
+        Command::Cook(cook) => {
+            let Cook {
+                recipe_path,
+                profile,
+                release,
+                check,
+                clippy,
+                clippy_args,
+                doc,
+                nextest,
+                coverage,
+                target,
+                no_default_features,
+                all_features,
+                features,
+                unstable_features,
+                target_dir,
+                benches,
+                tests,
+                examples,
+                all_targets,
+                manifest_path,
+                package,
+                workspace,
+                exclude,
+                offline,
+                timings,
+                critical_path,
+                no_std,
+                bin,
+                zigbuild,
+                cross,
+                phase,
+                best_effort,
+                only_build_deps,
+                build_command,
+                cargo_command,
+                extra_arguments,
+                allow_modified,
+                verify_signature,
+                verifying_key_path,
+                signature_path,
+                fast_deps,
+                deps_debuginfo,
+                profile_settings,
+                replace_local,
+                install_toolchain,
+                auto_install_target,
+                ignore_rust_version,
+                linker,
+                incremental,
+                artifacts_manifest,
+                artifact_store,
+                previous_recipe: previous_recipe_path,
+                each_feature,
+                registry_snapshot,
+                registry_scratch,
+            } = *cook;
+            let reads_stdin = recipe_path.iter().any(|path| is_stdio(path));
+            if reads_stdin && atty::is(atty::Stream::Stdin) {
+                return Err(anyhow!(
+                    "stdin appears to be a terminal: pipe the recipe into `cook` to read it with \
+                    `--recipe-path -`, e.g. `cargo chef prepare --recipe-path - | cargo chef cook \
+                    --recipe-path -`."
+                ));
+            }
             if atty::is(atty::Stream::Stdout) {
                 eprintln!("WARNING stdout appears to be a terminal.");
                 eprintln!(
@@ -182,25 +843,33 @@ fn _main() -> Result<(), anyhow::Error> {
                 `Cargo.toml` it finds)."
                 );
                 eprintln!();
-                eprint!("To continue anyway, type `yes`: ");
+                // stdin carries the recipe, and cannot answer: piping it in is deliberate enough.
+                if !reads_stdin {
+                    eprint!("To continue anyway, type `yes`: ");
 
-                let mut answer = String::with_capacity(3);
-                std::io::stdin()
-                    .read_line(&mut answer)
-                    .context("Failed to read from stdin")?;
+                    let mut answer = String::with_capacity(3);
+                    std::io::stdin()
+                        .read_line(&mut answer)
+                        .context("Failed to read from stdin")?;
 
-                if "yes" != answer.trim() {
-                    std::process::exit(1);
+                    if "yes" != answer.trim() {
+                        std::process::exit(1);
+                    }
                 }
             }
 
-            let features: Option<HashSet<String>> = features.and_then(|features| {
-                if features.is_empty() {
-                    None
-                } else {
-                    Some(features.into_iter().collect())
-                }
-            });
+            let features = feature_set(features);
+
+            let profile_settings = profile_settings
+                .iter()
+                .map(|setting| setting.parse())
+                .collect::<Result<Vec<ProfileSetting>, _>>()
+                .context("Invalid `--profile-setting`.")?;
+
+            let replace_local = replace_local
+                .iter()
+                .map(|replacement| package_stub(&current_directory, replacement))
+                .collect::<Result<Vec<_>, _>>()?;
 
             let unstable_features: Option<HashSet<String>> =
                 unstable_features.and_then(|unstable_features| {
@@ -211,45 +880,214 @@ fn _main() -> Result<(), anyhow::Error> {
                     }
                 });
 
-            let profile = match (release, profile) {
-                (false, None) =>  OptimisationProfile::Debug,
-                (false, Some(profile)) if profile == "dev" => OptimisationProfile::Debug,
-                (true, None) => OptimisationProfile::Release,
-                (false, Some(profile)) if profile == "release" => OptimisationProfile::Release,
-                (false, Some(custom_profile)) => OptimisationProfile::Other(custom_profile),
-                (true, Some(_)) => Err(anyhow!("You specified both --release and --profile arguments. Please remove one of them, or both"))?
-            };
-            let command = match (check, clippy, zigbuild) {
-                (true, false, false) => CommandArg::Check,
-                (false, true, false) => CommandArg::Clippy,
-                (false, false, true) => CommandArg::Zigbuild,
-                (false, false, false) => CommandArg::Build,
-                _ => Err(anyhow!("Only one (or none) of the  `clippy`, `check` and `zigbuild` arguments are allowed. Please remove some of them, or all"))?,
+            let profile = optimisation_profile(release, profile)?;
+            let command = match (check, clippy, zigbuild, doc) {
+                (false, false, false, false) if nextest => CommandArg::Nextest,
+                (true, false, false, false) => CommandArg::Check,
+                (false, true, false, false) => CommandArg::Clippy,
+                (false, false, true, false) => CommandArg::Zigbuild,
+                (false, false, false, true) => CommandArg::Doc,
+                (false, false, false, false) => CommandArg::Build,
+                _ => Err(anyhow!("Only one (or none) of the  `clippy`, `check`, `zigbuild` and `doc` arguments are allowed. Please remove some of them, or all"))?,
             };
+            if cross && zigbuild {
+                return Err(anyhow!(
+                    "`cross` and `zigbuild` cannot be combined: `cross` builds in its own container."
+                ));
+            }
 
-            let default_features = if no_default_features {
-                DefaultFeatures::Disabled
-            } else {
-                DefaultFeatures::Enabled
-            };
+            let (default_features, all_features) =
+                feature_selection(no_default_features, all_features);
 
-            let all_features = if all_features {
-                AllFeatures::Enabled
-            } else {
-                AllFeatures::Disabled
-            };
+            let offline = offline || registry_snapshot.is_some();
+            if let Some(snapshot) = &registry_snapshot {
+                let scratch = current_directory.join(
+                    registry_scratch
+                        .unwrap_or_else(|| std::env::temp_dir().join("chef-cargo-home")),
+                );
+                chef_core::overlay_registry_snapshot(&current_directory.join(snapshot), &scratch)
+                    .context("Failed to overlay the registry snapshot.")?;
+                // For `cargo` and everything else `cook` runs - and reports, see `CookSummary`.
+                std::env::set_var("CARGO_HOME", &scratch);
+            }
 
-            let serialized = fs::read_to_string(recipe_path)
-                .context("Failed to read recipe from the specified path.")?;
-            let recipe: Recipe =
-                serde_json::from_str(&serialized).context("Failed to deserialize recipe.")?;
+            if recipe_path.len() > 1 && signature_path.is_some() {
+                return Err(anyhow!(
+                    "`--signature-path` cannot be used with several recipes: their signatures \
+                    are read from next to them."
+                ));
+            }
+            let public_key = verify_signature
+                .then(|| read_key(verifying_key_path, "CHEF_VERIFYING_KEY"))
+                .transpose()?;
+            let mut recipes = vec![];
+            for path in &recipe_path {
+                let serialized = read_recipe(path)?;
+                match Recipe::integrity(&serialized) {
+                    Ok(RecipeIntegrity::Modified) if allow_modified => {
+                        eprintln!("warning: {:?} was modified since it was prepared.", path)
+                    }
+                    Ok(RecipeIntegrity::Modified) => {
+                        return Err(anyhow!(
+                            "{:?} was modified since it was prepared: cooking a recipe edited \
+                            by hand tends to fail in puzzling ways. Prepare it again, or pass \
+                            `--allow-modified` to cook it anyway.",
+                            path
+                        ))
+                    }
+                    // Errors are reported when deserializing the recipe.
+                    Ok(RecipeIntegrity::Intact | RecipeIntegrity::Unsealed) | Err(_) => {}
+                }
+                if let Some(public_key) = &public_key {
+                    let signature_path = signature_path_for(path, signature_path.clone())?;
+                    let signature =
+                        fs::read(signature_path).context("Failed to read signature.")?;
+                    verify_recipe_signature(&serialized, &signature, public_key)?;
+                }
+                recipes.push((path, serialized));
+            }
+            let (recipe, serialized) = match recipes.as_slice() {
+                [(_, serialized)] => (
+                    Recipe::deserialize(serialized).context("Failed to deserialize recipe.")?,
+                    serialized.clone(),
+                ),
+                _ => {
+                    let recipes = recipes
+                        .iter()
+                        .map(|(path, serialized)| {
+                            Ok((
+                                recipe_root(&current_directory, path)?,
+                                Recipe::deserialize(serialized)
+                                    .with_context(|| format!("Failed to deserialize {:?}", path))?,
+                            ))
+                        })
+                        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+                    let recipe = Recipe::merge(recipes)?;
+                    let serialized = recipe.serialize(RecipeFormat::Json)?;
+                    (recipe, serialized)
+                }
+            };
+            // Several recipes share the target directory of the current one.
+            let target_dir = match target_dir {
+                None if recipe_path.len() > 1 => Some(PathBuf::from("target")),
+                target_dir => target_dir,
+            };
             let target_args = TargetArgs {
                 benches,
                 tests,
                 examples,
                 all_targets,
             };
-            recipe
+            let previous_recipe = match &previous_recipe_path {
+                Some(path) if path.exists() => Some(
+                    Recipe::deserialize(&fs::read_to_string(path)?)
+                        .with_context(|| format!("Failed to deserialize {:?}", path))?,
+                ),
+                _ => None,
+            };
+            // `cook` warns on its own about the toolchain file of the recipe.
+            let carries_toolchain_file = std::iter::once(&recipe.skeleton)
+                .chain(
+                    recipe
+                        .workspaces
+                        .iter()
+                        .map(|workspace| &workspace.skeleton),
+                )
+                .any(|skeleton| skeleton.toolchain_file.is_some());
+            if !carries_toolchain_file {
+                if let Some(warning) = ignored_toolchain_file(&current_directory) {
+                    eprintln!("warning: {}", warning);
+                }
+            }
+            let (errors, warnings): (Vec<_>, Vec<_>) = recipe
+                .preflight(target.as_deref())
+                .context("Failed to check the recipe for known build pitfalls.")?
+                .into_iter()
+                .partition(|diagnostic| diagnostic.severity == Severity::Error);
+            for warning in warnings {
+                eprintln!("warning: {}", warning);
+            }
+            if !errors.is_empty() {
+                let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+                return Err(anyhow!(
+                    "Dependencies would fail to build:\n  - {}",
+                    errors.join("\n  - ")
+                ));
+            }
+            let mut fingerprint = EnvFingerprint::capture(
+                &profile,
+                target.as_deref(),
+                default_features,
+                all_features,
+                features.as_ref(),
+            )?;
+            fingerprint.each_feature = each_feature;
+            fingerprint.environment.extend(
+                chef_core::cook_environment(incremental)
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value)),
+            );
+            let snapshot = match &artifacts_manifest {
+                Some(_) => {
+                    let mut directories = recipe.target_directories(target_dir.as_deref())?;
+                    directories.extend(chef_core::cargo_home());
+                    Some(
+                        OutputSnapshot::capture(directories)
+                            .context("Failed to scan the output directories.")?,
+                    )
+                }
+                None => None,
+            };
+            let store = match &artifact_store {
+                Some(root) => {
+                    let store = ArtifactStore::new(current_directory.join(root));
+                    let mut directories = vec![];
+                    for directory in recipe.target_directories(target_dir.as_deref())? {
+                        let key = ArtifactStore::index_key(&recipe, &directory)?;
+                        if let Some(stats) = store.restore(&key, &directory).with_context(|| {
+                            format!("Failed to restore {:?} from the artifact store.", directory)
+                        })? {
+                            eprintln!(
+                                "Restored {} files into {:?} from the artifact store.",
+                                stats.files, directory
+                            );
+                        }
+                        directories.push((key, directory));
+                    }
+                    Some((store, directories))
+                }
+                None => None,
+            };
+            let timing_directories = if critical_path {
+                recipe.target_directories(target_dir.as_deref())?
+            } else {
+                vec![]
+            };
+            let exporter = OtlpExporter::from_env();
+            let metrics_directories = match &exporter {
+                Some(_) => recipe.target_directories(target_dir.as_deref())?,
+                None => vec![],
+            };
+            let mut metrics_attributes = vec![
+                ("profile".to_string(), profile.name().to_string()),
+                (
+                    "command".to_string(),
+                    match command {
+                        CommandArg::Build => "build",
+                        CommandArg::Check => "check",
+                        CommandArg::Clippy => "clippy",
+                        CommandArg::Zigbuild => "zigbuild",
+                        CommandArg::Doc => "doc",
+                        CommandArg::Nextest => "nextest",
+                    }
+                    .to_string(),
+                ),
+            ];
+            if let Some(target) = &target {
+                metrics_attributes.push(("target".to_string(), target.join(",")));
+            }
+            let cook_start = std::time::SystemTime::now();
+            let outcome = recipe
                 .cook(CookArgs {
                     profile,
                     command,
@@ -263,24 +1101,981 @@ fn _main() -> Result<(), anyhow::Error> {
                     manifest_path,
                     package,
                     workspace,
+                    exclude,
                     offline,
-                    timings,
+                    timings: timings || critical_path,
                     no_std,
                     bin,
+                    phase: match phase {
+                        None if only_build_deps => CookPhase::BuildDependencies,
+                        None => CookPhase::All,
+                        Some(Phase::BuildDeps) => CookPhase::BuildDependencies,
+                        Some(Phase::Deps) => CookPhase::Dependencies,
+                        Some(Phase::DevDeps) => CookPhase::DevDependencies,
+                    },
+                    build_command: build_command
+                        .map(|template| {
+                            template
+                                .split_whitespace()
+                                .map(str::to_string)
+                                .collect::<Vec<_>>()
+                        })
+                        .filter(|template| !template.is_empty()),
+                    cargo_command: cargo_command
+                        .map(|command| {
+                            command
+                                .split_whitespace()
+                                .map(str::to_string)
+                                .collect::<Vec<_>>()
+                        })
+                        .filter(|command| !command.is_empty()),
+                    extra_arguments,
+                    clippy_arguments: clippy_args
+                        .map(|flags| flags.split_whitespace().map(str::to_string).collect())
+                        .unwrap_or_default(),
+                    fast_dependencies: fast_deps,
+                    dependencies_debuginfo: deps_debuginfo,
+                    incremental,
+                    previous_recipe,
+                    each_feature,
+                    profile_settings,
+                    replace_local,
+                    toolchain: None,
+                    install_toolchain,
+                    auto_install_target,
+                    ignore_rust_version,
+                    linker,
+                    cross,
+                    best_effort,
+                    coverage,
                 })
-                .context("Failed to cook recipe.")?;
+                .context("Failed to cook recipe.");
+            if let Some(exporter) = exporter {
+                // The cook goes on even if the collector cannot be reached.
+                let exported = CookMetrics::capture(
+                    &serialized,
+                    &metrics_directories,
+                    cook_start,
+                    outcome.is_ok(),
+                )
+                .and_then(|mut metrics| {
+                    metrics.attributes = metrics_attributes;
+                    exporter.export(&metrics)
+                });
+                if let Err(error) = exported {
+                    eprintln!("warning: {:#}", error);
+                }
+            }
+            let summaries = outcome?;
+            if let Some(path) = &previous_recipe_path {
+                fs::write(path, &serialized)
+                    .with_context(|| format!("Failed to save the recipe to {:?}", path))?;
+            }
+            for summary in summaries {
+                eprintln!("{}", summary);
+            }
+            for directory in timing_directories {
+                // Reports left over by an earlier build are not about this one.
+                let Some(report) = CriticalPath::report_path(&directory).filter(|report| {
+                    fs::metadata(report)
+                        .and_then(|metadata| metadata.modified())
+                        .is_ok_and(|modified| modified >= cook_start)
+                }) else {
+                    eprintln!(
+                        "warning: cargo did not write a timing report to {:?}.",
+                        directory
+                    );
+                    continue;
+                };
+                let critical_path = CriticalPath::from_timing_report(&fs::read_to_string(&report)?)
+                    .with_context(|| format!("Failed to read the timing report {:?}", report))?;
+                match critical_path {
+                    Some(critical_path) => {
+                        eprint!("{}", critical_path);
+                        eprintln!("Every unit is plotted in {}", report.display());
+                    }
+                    None => eprintln!("Nothing was built, there is no critical path."),
+                }
+            }
+            if let Some((store, directories)) = store {
+                for (key, directory) in directories {
+                    let stats = store.store(&key, &directory).with_context(|| {
+                        format!("Failed to add {:?} to the artifact store.", directory)
+                    })?;
+                    eprintln!(
+                        "Stored {} files of {:?} in the artifact store, {} of them new.",
+                        stats.files, directory, stats.new_objects
+                    );
+                }
+            }
+            fs::write(
+                current_directory.join(chef_core::FINGERPRINT_FILE_NAME),
+                serde_json::to_string_pretty(&fingerprint)?,
+            )
+            .context("Failed to save the environment fingerprint.")?;
+            if let (Some(path), Some(snapshot)) = (artifacts_manifest, snapshot) {
+                let artifacts = snapshot
+                    .artifacts()
+                    .context("Failed to list the files cooked into the output directories.")?;
+                fs::write(&path, serde_json::to_string_pretty(&artifacts)?).with_context(|| {
+                    format!("Failed to save the artifacts manifest to {:?}", path)
+                })?;
+            }
+        }
+        Command::Prepare(prepare) => {
+            let Prepare {
+                recipe_path,
+                bin,
+                package,
+                path,
+                allow_missing_members,
+                chef_profile,
+                exclude,
+                keep_build_script,
+                detect_assets,
+                keep_source,
+                allow_external_paths,
+                split_per_member,
+                nested_workspaces,
+                scan_root,
+                postprocess,
+                format,
+                sign,
+                signing_key_path,
+                signature_path,
+                check,
+                locked,
+                http_multiplexing,
+                http_timeout,
+                net_retry,
+                static_binary_friendly,
+                self_test,
+                warnings_path,
+            } = *prepare;
+            if self_test {
+                return run_self_test();
+            }
+            let recipe = if static_binary_friendly {
+                Recipe::prepare_standalone(current_directory.clone(), bin.or(package))
+            } else if path.is_empty() {
+                Recipe::prepare(
+                    current_directory.clone(),
+                    bin.or(package),
+                    allow_missing_members.as_deref(),
+                )
+            } else {
+                let paths: Vec<_> = path.iter().map(|p| current_directory.join(p)).collect();
+                Recipe::prepare_workspaces(
+                    current_directory.clone(),
+                    &paths,
+                    allow_missing_members.as_deref(),
+                )
+            }
+            .context("Failed to compute recipe")?;
+            let recipe = if exclude.is_empty() {
+                recipe
+            } else {
+                Recipe {
+                    skeleton: recipe
+                        .skeleton
+                        .exclude_members(&exclude)
+                        .context("Failed to exclude workspace members")?,
+                    ..recipe
+                }
+            };
+            let chef_profile = chef_profile
+                .map(|name| ChefProfile::load(&current_directory, &name))
+                .transpose()?;
+            let pipeline = RecipePipeline {
+                base_path: &current_directory,
+                keep_build_script: &keep_build_script,
+                detect_assets,
+                keep_source: &keep_source,
+                allow_external_paths,
+                network_settings: NetworkSettings {
+                    http_multiplexing,
+                    http_timeout,
+                    net_retry,
+                },
+                postprocess: postprocess.as_deref(),
+            };
+            let recipe = pipeline.apply(recipe, chef_profile.as_ref())?;
+            let mut nested_recipes = vec![];
+            if nested_workspaces {
+                if is_stdio(&recipe_path) {
+                    return Err(anyhow!(
+                        "--nested-workspaces writes several recipes, it cannot use stdout."
+                    ));
+                }
+                // Excluded members belong to the main workspace.
+                let chef_profile = chef_profile.as_ref().map(|profile| ChefProfile {
+                    exclude: vec![],
+                    ..profile.clone()
+                });
+                for nested in Skeleton::nested_workspaces(&current_directory, &scan_root)? {
+                    let recipe = Recipe::prepare_workspaces(
+                        current_directory.clone(),
+                        &[current_directory.join(&nested)],
+                        allow_missing_members.as_deref(),
+                    )
+                    .with_context(|| format!("Failed to compute recipe for {:?}", nested))?;
+                    let recipe = pipeline.apply(recipe, chef_profile.as_ref())?;
+                    let name = nested
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("-");
+                    nested_recipes.push((name, recipe));
+                }
+            }
+            pipeline.check_kept_packages(
+                std::iter::once(&recipe).chain(nested_recipes.iter().map(|(_, recipe)| recipe)),
+            )?;
+            if locked {
+                check_lock_files(
+                    std::iter::once(&recipe).chain(nested_recipes.iter().map(|(_, recipe)| recipe)),
+                )?;
+            }
+            if check {
+                return print_check_report(&recipe);
+            }
+            // stdout is reserved to the recipe, if it is written there: warnings go to stderr.
+            let mut warnings = vec![];
+            for recipe in
+                std::iter::once(&recipe).chain(nested_recipes.iter().map(|(_, recipe)| recipe))
+            {
+                for (root, skeleton) in std::iter::once((Path::new(""), &recipe.skeleton)).chain(
+                    recipe
+                        .workspaces
+                        .iter()
+                        .map(|w| (w.relative_path.as_path(), &w.skeleton)),
+                ) {
+                    for warning in skeleton.credential_warnings()? {
+                        eprintln!("warning: {}", warning);
+                        warnings.push(PrepareWarning {
+                            severity: "warning",
+                            file: root.join("Cargo.lock"),
+                            message: warning,
+                        });
+                    }
+                }
+            }
+            if let Some(path) = &warnings_path {
+                fs::write(path, serde_json::to_string_pretty(&warnings)?)
+                    .with_context(|| format!("Failed to save warnings to {:?}", path))?;
+            }
+            let format = match format {
+                Format::Json => RecipeFormat::Json,
+                Format::Toml => RecipeFormat::Toml,
+            };
+            let private_key = if sign {
+                Some(read_key(signing_key_path, "CHEF_SIGNING_KEY")?)
+            } else {
+                None
+            };
+            let mut recipes = if split_per_member {
+                if is_stdio(&recipe_path) {
+                    return Err(anyhow!(
+                        "--split-per-member writes several recipes, it cannot use stdout."
+                    ));
+                }
+                recipe
+                    .skeleton
+                    .split_per_member()
+                    .context("Failed to split recipe")?
+                    .into_iter()
+                    .map(|(name, skeleton)| {
+                        let recipe = Recipe {
+                            skeleton,
+                            workspaces: vec![],
+                            rules: recipe.rules.clone(),
+                        };
+                        (Some(name), recipe)
+                    })
+                    .collect()
+            } else {
+                vec![(None, recipe)]
+            };
+            recipes.extend(
+                nested_recipes
+                    .into_iter()
+                    .map(|(name, recipe)| (Some(name), recipe)),
+            );
+            let names: Vec<String> = recipes
+                .iter()
+                .filter_map(|(name, _)| name.clone())
+                .collect();
+            let mut file_names = collision_free_names(&names).into_iter();
+            // Every path is resolved before anything is written: a missing `--signature-path`
+            // must not leave a recipe behind, or print one to stdout.
+            let recipes = recipes
+                .into_iter()
+                .map(|(name, recipe)| {
+                    let file_name = name.map(|_| file_names.next().unwrap());
+                    let member_path = |path: &Path| match &file_name {
+                        Some(file_name) => member_recipe_path(path, file_name),
+                        None => path.to_path_buf(),
+                    };
+                    let recipe_path = member_path(&recipe_path);
+                    let signature_path = match &private_key {
+                        Some(_) => Some(signature_path_for(
+                            &recipe_path,
+                            signature_path.as_deref().map(member_path),
+                        )?),
+                        None => None,
+                    };
+                    Ok((recipe_path, signature_path, recipe))
+                })
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+            for (recipe_path, signature_path, recipe) in recipes {
+                let serialized = recipe
+                    .serialize(format)
+                    .context("Failed to serialize recipe.")?;
+                write_recipe(&recipe_path, &serialized)?;
+                if let (Some(private_key), Some(signature_path)) = (&private_key, signature_path) {
+                    let signature = sign_recipe(&serialized, private_key)?;
+                    fs::write(signature_path, signature).context("Failed to save signature.")?;
+                }
+            }
+        }
+        Command::Demo(Demo { strategy, path }) => {
+            let strategy = match strategy {
+                Strategy::Basic => DemoStrategy::Basic,
+                Strategy::MultiTarget => DemoStrategy::MultiTarget,
+                Strategy::SplitRecipes => DemoStrategy::SplitRecipes,
+                Strategy::CacheMounts => DemoStrategy::CacheMounts,
+            };
+            let directory = current_directory.join(&path);
+            strategy
+                .write(&directory)
+                .context("Failed to write the demo")?;
+            eprintln!(
+                "Wrote the `{}` demo to {:?}, see its README.md to build it.",
+                strategy.name(),
+                directory
+            );
+        }
+        Command::UpgradeRecipe(UpgradeRecipe { recipe_path, check }) => {
+            let recipe_path = current_directory.join(recipe_path);
+            let serialized = fs::read_to_string(&recipe_path)
+                .context("Failed to read recipe from the specified path.")?;
+            let Some(upgraded) =
+                Recipe::upgrade(&serialized).context("Failed to upgrade the recipe.")?
+            else {
+                eprintln!("{:?} is up to date.", recipe_path);
+                return Ok(());
+            };
+            if check {
+                return Err(anyhow!(
+                    "{:?} was written by an older version of cargo-chef: run `cargo chef \
+                    upgrade-recipe` to upgrade it.",
+                    recipe_path
+                ));
+            }
+            fs::write(&recipe_path, upgraded)
+                .with_context(|| format!("Failed to write the recipe to {:?}", recipe_path))?;
+            eprintln!("Upgraded {:?} to the current format.", recipe_path);
+            let signature_path = signature_path_for(&recipe_path, None)?;
+            if signature_path.exists() {
+                eprintln!(
+                    "warning: {:?} no longer matches the upgraded recipe, which must be signed again.",
+                    signature_path
+                );
+            }
+        }
+        Command::Report(Report {
+            base,
+            recipe_path,
+            timings,
+            format,
+        }) => {
+            let read = |path: &Path| -> Result<Recipe, anyhow::Error> {
+                let path = current_directory.join(path);
+                let serialized = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read the recipe {:?}", path))?;
+                Recipe::deserialize(&serialized)
+                    .with_context(|| format!("Failed to deserialize {:?}", path))
+            };
+            let mut report = RecipeReport::compare(&read(&base)?, &read(&recipe_path)?)?;
+            if let Some(timings) = timings {
+                let timings = fs::read_to_string(current_directory.join(timings))
+                    .context("Failed to read the timing report.")?;
+                report.estimate(&TimedUnit::from_timing_report(&timings)?);
+            }
+            let format = match format {
+                ReportOutput::Text => ReportFormat::Text,
+                ReportOutput::Markdown => ReportFormat::Markdown,
+            };
+            print!("{}", report.render(format));
+        }
+        Command::ListInputs(ListInputs { format }) => {
+            let inputs = Skeleton::inputs(current_directory).context("Failed to list inputs")?;
+            match format {
+                ListFormat::Text => {
+                    for input in inputs {
+                        println!("{}", input.display());
+                    }
+                }
+                ListFormat::Json => println!("{}", serde_json::to_string(&inputs)?),
+            }
+        }
+        Command::EnvCheck(EnvCheck {
+            fingerprint_path,
+            profile,
+            release,
+            target,
+            no_default_features,
+            all_features,
+            features,
+        }) => {
+            let cooked: EnvFingerprint = serde_json::from_str(
+                &fs::read_to_string(&fingerprint_path)
+                    .context("Failed to read the environment fingerprint.")?,
+            )
+            .context("Failed to deserialize the environment fingerprint.")?;
+            let (default_features, all_features) =
+                feature_selection(no_default_features, all_features);
+            let current = EnvFingerprint::capture(
+                &optimisation_profile(release, profile)?,
+                target.as_deref(),
+                default_features,
+                all_features,
+                feature_set(features).as_ref(),
+            )?;
+            let mismatches = cooked.mismatches(&current);
+            if mismatches.is_empty() {
+                println!("The environment matches the one dependencies were cooked in.");
+                return Ok(());
+            }
+            for mismatch in &mismatches {
+                println!("- {}", mismatch);
+            }
+            return Err(anyhow!(
+                "Found {} mismatch(es) with the environment dependencies were cooked in.",
+                mismatches.len()
+            ));
         }
-        Command::Prepare(Prepare { recipe_path, bin }) => {
-            let recipe =
-                Recipe::prepare(current_directory, bin).context("Failed to compute recipe")?;
-            let serialized =
-                serde_json::to_string(&recipe).context("Failed to serialize recipe.")?;
-            fs::write(recipe_path, serialized).context("Failed to save recipe to 'recipe.json'")?;
+    }
+    Ok(())
+}
+
+fn optimisation_profile(
+    release: bool,
+    profile: Option<String>,
+) -> Result<OptimisationProfile, anyhow::Error> {
+    let profile = match (release, profile) {
+        (false, None) =>  OptimisationProfile::Debug,
+        (false, Some(profile)) if profile == "dev" => OptimisationProfile::Debug,
+        (true, None) => OptimisationProfile::Release,
+        (false, Some(profile)) if profile == "release" => OptimisationProfile::Release,
+        (false, Some(custom_profile)) => OptimisationProfile::Other(custom_profile),
+        (true, Some(_)) => Err(anyhow!("You specified both --release and --profile arguments. Please remove one of them, or both"))?
+    };
+    Ok(profile)
+}
+
+fn feature_selection(
+    no_default_features: bool,
+    all_features: bool,
+) -> (DefaultFeatures, AllFeatures) {
+    let default_features = if no_default_features {
+        DefaultFeatures::Disabled
+    } else {
+        DefaultFeatures::Enabled
+    };
+
+    let all_features = if all_features {
+        AllFeatures::Enabled
+    } else {
+        AllFeatures::Disabled
+    };
+    (default_features, all_features)
+}
+
+/// The features passed with `--features`, whether separated by commas or spaces (as in
+/// `--features "foo bar/baz"`), so that the same selection is always recorded the same way.
+fn feature_set(features: Option<Vec<String>>) -> Option<HashSet<String>> {
+    let features: HashSet<String> = features?
+        .iter()
+        .flat_map(|features| features.split_whitespace())
+        .map(str::to_string)
+        .collect();
+    if features.is_empty() {
+        None
+    } else {
+        Some(features)
+    }
+}
+
+/// What `prepare` does to every recipe it writes: the main one, and those of the workspaces
+/// nested in it.
+struct RecipePipeline<'a> {
+    base_path: &'a Path,
+    keep_build_script: &'a [String],
+    detect_assets: bool,
+    keep_source: &'a [String],
+    allow_external_paths: bool,
+    network_settings: NetworkSettings,
+    postprocess: Option<&'a str>,
+}
+
+impl RecipePipeline<'_> {
+    fn apply(
+        &self,
+        recipe: Recipe,
+        chef_profile: Option<&ChefProfile>,
+    ) -> Result<Recipe, anyhow::Error> {
+        let recipe = keep_build_scripts(
+            recipe,
+            self.base_path,
+            self.keep_build_script,
+            self.detect_assets,
+        )?;
+        let recipe = keep_sources(recipe, self.base_path, self.keep_source)?;
+        let recipe = match chef_profile {
+            Some(profile) => recipe.with_profile(profile)?,
+            None => recipe,
+        };
+        let recipe = if self.allow_external_paths {
+            include_external_paths(recipe, self.base_path)?
+        } else {
+            recipe
+        };
+        let recipe = with_network_settings(recipe, &self.network_settings)?;
+        match self.postprocess {
+            Some(command) => postprocess_recipe(&recipe, command),
+            None => Ok(recipe),
+        }
+    }
+
+    /// Fail if a package passed to `--keep-build-script` or `--keep-source` is in none of the
+    /// `recipes`.
+    fn check_kept_packages<'r>(
+        &self,
+        recipes: impl Iterator<Item = &'r Recipe>,
+    ) -> Result<(), anyhow::Error> {
+        let mut names = HashSet::new();
+        for recipe in recipes {
+            for skeleton in std::iter::once(&recipe.skeleton)
+                .chain(recipe.workspaces.iter().map(|w| &w.skeleton))
+            {
+                names.extend(skeleton.package_names()?);
+            }
+        }
+        let unknown = self
+            .keep_build_script
+            .iter()
+            .chain(self.keep_source)
+            .find(|package| !names.contains(*package));
+        match unknown {
+            Some(package) => Err(anyhow!(
+                "There is no package named `{}` in the workspace.",
+                package
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The `packages` that belong to `skeleton`: those named on the command line are kept in
+/// whichever recipe has them, see [`RecipePipeline::check_kept_packages`].
+fn packages_in(skeleton: &Skeleton, packages: &[String]) -> Result<Vec<String>, anyhow::Error> {
+    let names = skeleton.package_names()?;
+    Ok(packages
+        .iter()
+        .filter(|package| names.contains(package))
+        .cloned()
+        .collect())
+}
+
+fn keep_build_scripts(
+    recipe: Recipe,
+    base_path: &Path,
+    packages: &[String],
+    detect_assets: bool,
+) -> Result<Recipe, anyhow::Error> {
+    let context = "Failed to keep build scripts";
+    let skeleton = recipe
+        .skeleton
+        .keep_build_scripts(
+            base_path,
+            &packages_in(&recipe.skeleton, packages)?,
+            detect_assets,
+        )
+        .context(context)?;
+    // `--keep-build-script` cannot be combined with `--path`, but nested workspaces are
+    // prepared as the only workspace of their recipe.
+    let workspaces = recipe
+        .workspaces
+        .into_iter()
+        .map(|workspace| {
+            let skeleton = workspace
+                .skeleton
+                .keep_build_scripts(
+                    base_path.join(&workspace.relative_path),
+                    &packages_in(&workspace.skeleton, packages)?,
+                    detect_assets,
+                )
+                .context(context)?;
+            Ok(WorkspaceRecipe {
+                skeleton,
+                ..workspace
+            })
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+    Ok(Recipe {
+        skeleton,
+        workspaces,
+        ..recipe
+    })
+}
+
+fn keep_sources(
+    recipe: Recipe,
+    base_path: &Path,
+    packages: &[String],
+) -> Result<Recipe, anyhow::Error> {
+    let context = "Failed to keep sources";
+    let skeleton = recipe
+        .skeleton
+        .keep_sources(base_path, &packages_in(&recipe.skeleton, packages)?)
+        .context(context)?;
+    let workspaces = recipe
+        .workspaces
+        .into_iter()
+        .map(|workspace| {
+            let skeleton = workspace
+                .skeleton
+                .keep_sources(
+                    base_path.join(&workspace.relative_path),
+                    &packages_in(&workspace.skeleton, packages)?,
+                )
+                .context(context)?;
+            Ok(WorkspaceRecipe {
+                skeleton,
+                ..workspace
+            })
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+    Ok(Recipe {
+        skeleton,
+        workspaces,
+        ..recipe
+    })
+}
+
+fn with_network_settings(
+    recipe: Recipe,
+    settings: &NetworkSettings,
+) -> Result<Recipe, anyhow::Error> {
+    let context = "Failed to record the network settings";
+    let apply = |skeleton: Skeleton| -> Result<Skeleton, anyhow::Error> {
+        if skeleton.manifests.is_empty() {
+            return Ok(skeleton);
         }
+        skeleton.with_network_settings(settings).context(context)
+    };
+    let workspaces = recipe
+        .workspaces
+        .into_iter()
+        .map(|workspace| {
+            Ok(WorkspaceRecipe {
+                skeleton: apply(workspace.skeleton)?,
+                ..workspace
+            })
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+    Ok(Recipe {
+        skeleton: apply(recipe.skeleton)?,
+        workspaces,
+        ..recipe
+    })
+}
+
+fn include_external_paths(recipe: Recipe, base_path: &Path) -> Result<Recipe, anyhow::Error> {
+    let context = "Failed to include external path dependencies";
+    let skeleton = recipe
+        .skeleton
+        .include_external_paths(base_path)
+        .context(context)?;
+    let workspaces = recipe
+        .workspaces
+        .into_iter()
+        .map(|workspace| {
+            let skeleton = workspace
+                .skeleton
+                .include_external_paths(base_path.join(&workspace.relative_path))
+                .context(context)?;
+            Ok(WorkspaceRecipe {
+                skeleton,
+                ..workspace
+            })
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+    Ok(Recipe {
+        skeleton,
+        workspaces,
+        ..recipe
+    })
+}
+
+/// Compute the recipe of the `basic` demo, written to a temporary directory, in a `prepare`
+/// run with an empty `PATH`: any attempt to invoke `cargo`, `rustc` or `rustup` along the way
+/// fails.
+fn run_self_test() -> Result<(), anyhow::Error> {
+    let directory =
+        std::env::temp_dir().join(format!("cargo-chef-self-test-{}", std::process::id()));
+    DemoStrategy::Basic
+        .write(&directory)
+        .context("Failed to write the self-test workspace")?;
+    let recipe_path = directory.join("recipe.json");
+    let recipe = std::env::current_exe()
+        .context("Failed to locate the cargo-chef executable")
+        .and_then(|executable| {
+            let status = std::process::Command::new(executable)
+                .current_dir(&directory)
+                .args([
+                    "chef",
+                    "prepare",
+                    "--static-binary-friendly",
+                    "--recipe-path",
+                ])
+                .arg(&recipe_path)
+                .env("PATH", "")
+                .env_remove("CARGO")
+                .env_remove("RUSTC")
+                .env_remove("RUSTUP_TOOLCHAIN")
+                .status()
+                .context("Failed to run `prepare`")?;
+            if !status.success() {
+                return Err(anyhow!("`prepare` exited with {}", status));
+            }
+            Recipe::deserialize(&fs::read_to_string(&recipe_path)?)
+        });
+    let _ = fs::remove_dir_all(&directory);
+    let recipe = recipe.context("Self-test failed: the recipe could not be computed")?;
+    let packages = recipe
+        .skeleton
+        .manifests
+        .iter()
+        .filter(|manifest| !manifest.targets.is_empty())
+        .count();
+    if packages != 2 {
+        return Err(anyhow!(
+            "Self-test failed: found {} packages in the built-in workspace instead of 2.",
+            packages
+        ));
     }
+    eprintln!(
+        "Self-test passed: `prepare --static-binary-friendly` works without a Rust toolchain."
+    );
     Ok(())
 }
 
+/// Fail, listing every mismatch, if the lock file of any skeleton in the recipes does not
+/// satisfy its manifests.
+fn check_lock_files<'a>(recipes: impl Iterator<Item = &'a Recipe>) -> Result<(), anyhow::Error> {
+    let skeletons = recipes.flat_map(|recipe| {
+        std::iter::once((Path::new(""), &recipe.skeleton)).chain(
+            recipe
+                .workspaces
+                .iter()
+                .map(|w| (w.relative_path.as_path(), &w.skeleton)),
+        )
+    });
+    let mut mismatches = vec![];
+    for (root, skeleton) in skeletons {
+        if skeleton.manifests.is_empty() {
+            continue;
+        }
+        let prefix = if root.as_os_str().is_empty() {
+            String::new()
+        } else {
+            format!("{}/", root.display())
+        };
+        mismatches.extend(
+            skeleton
+                .lock_file_mismatches()?
+                .into_iter()
+                .map(|mismatch| format!("{}{}", prefix, mismatch)),
+        );
+    }
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Cargo.lock is out of date, cooking with `--locked` would fail:\n  - {}\nRun `cargo update --workspace` (or build the project) to update it.",
+        mismatches.join("\n  - ")
+    ))
+}
+
+fn print_check_report(recipe: &Recipe) -> Result<(), anyhow::Error> {
+    let skeletons = std::iter::once((Path::new(""), &recipe.skeleton)).chain(
+        recipe
+            .workspaces
+            .iter()
+            .map(|w| (w.relative_path.as_path(), &w.skeleton)),
+    );
+    let mut warnings = vec![];
+    for (root, skeleton) in skeletons {
+        if skeleton.manifests.is_empty() {
+            continue;
+        }
+        println!("Manifests:");
+        for manifest in &skeleton.manifests {
+            println!("  {}", root.join(&manifest.relative_path).display());
+        }
+        if skeleton.lock_file.is_some() {
+            println!("Lockfile: {}", root.join("Cargo.lock").display());
+        }
+        if skeleton.config_file.is_some() {
+            println!(
+                "Cargo configuration: {}",
+                root.join(".cargo/config.toml").display()
+            );
+        }
+        for source in &skeleton.vendored_sources {
+            println!(
+                "Vendored sources: {}",
+                root.join(&source.relative_path).display()
+            );
+        }
+        warnings.extend(skeleton.warnings()?);
+    }
+    // Pitfalls depend on the environment `cook` runs in: this one is only an approximation.
+    warnings.extend(recipe.preflight(None)?.iter().map(ToString::to_string));
+    if !warnings.is_empty() {
+        println!("Warnings:");
+        for warning in warnings {
+            println!("  - {}", warning);
+        }
+    }
+    Ok(())
+}
+
+/// The conventional `-` path, standing for stdin (`cook`) or stdout (`prepare`).
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Read a PEM-encoded key from `path` or, if not provided, from the `env_var` environment variable.
+fn read_key(path: Option<PathBuf>, env_var: &str) -> Result<String, anyhow::Error> {
+    match path {
+        Some(path) => fs::read_to_string(path).context("Failed to read key."),
+        None => std::env::var(env_var)
+            .with_context(|| format!("No key was provided and `{}` is not set.", env_var)),
+    }
+}
+
+fn signature_path_for(
+    recipe_path: &Path,
+    signature_path: Option<PathBuf>,
+) -> Result<PathBuf, anyhow::Error> {
+    match signature_path {
+        Some(signature_path) => Ok(signature_path),
+        None if is_stdio(recipe_path) => Err(anyhow!(
+            "--signature-path must be specified when the recipe goes through stdin/stdout."
+        )),
+        None => {
+            let mut signature_path = recipe_path.as_os_str().to_owned();
+            signature_path.push(".sig");
+            Ok(signature_path.into())
+        }
+    }
+}
+
+/// The directory a recipe cooked along with others is cooked in: the one it lives in, relative
+/// to the current directory.
+fn recipe_root(current_directory: &Path, recipe_path: &Path) -> Result<PathBuf, anyhow::Error> {
+    if is_stdio(recipe_path) {
+        return Err(anyhow!(
+            "Only a single recipe can be read from stdin, and not along with others."
+        ));
+    }
+    let directory = recipe_path.parent().unwrap_or(Path::new(""));
+    let root = directory
+        .strip_prefix(current_directory)
+        .unwrap_or(directory);
+    if root.is_absolute() || root.components().any(|c| c == Component::ParentDir) {
+        return Err(anyhow!(
+            "{:?} is not inside the current directory, where recipes cooked together are \
+            cooked from.",
+            recipe_path
+        ));
+    }
+    Ok(root.to_path_buf())
+}
+
+/// `recipe.json` becomes `recipe.<member>.json`.
+fn member_recipe_path(recipe_path: &Path, member: &str) -> PathBuf {
+    let mut file_name = recipe_path.file_stem().unwrap_or_default().to_owned();
+    file_name.push(".");
+    file_name.push(member);
+    if let Some(extension) = recipe_path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    recipe_path.with_file_name(file_name)
+}
+
+/// `names`, in the same order, with a suffix for those that only differ from another one by
+/// case (or not at all, e.g. nested workspaces in `a-b/c` and `a/b-c`): their recipes would
+/// overwrite each other, on case-insensitive filesystems at least. The suffix only depends on
+/// the name, so that adding a member never renames the recipes of the others.
+fn collision_free_names(names: &[String]) -> Vec<String> {
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    for name in names {
+        *occurrences.entry(name.to_lowercase()).or_default() += 1;
+    }
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            if occurrences[&name.to_lowercase()] == 1 {
+                return name.clone();
+            }
+            // Identical names are told apart by their position.
+            let duplicates = names[..index].iter().filter(|other| *other == name).count();
+            let mut hasher = Sha256::new();
+            hasher.update(name.as_bytes());
+            if duplicates > 0 {
+                hasher.update(duplicates.to_le_bytes());
+            }
+            let digest = hasher.finalize();
+            let suffix: String = digest[..4]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            format!("{}-{}", name, suffix)
+        })
+        .collect()
+}
+
+fn read_recipe(recipe_path: &Path) -> Result<String, anyhow::Error> {
+    if is_stdio(recipe_path) {
+        let mut serialized = String::new();
+        std::io::stdin()
+            .read_to_string(&mut serialized)
+            .context("Failed to read recipe from stdin.")?;
+        Ok(serialized)
+    } else {
+        fs::read_to_string(recipe_path).context("Failed to read recipe from the specified path.")
+    }
+}
+
+fn write_recipe(recipe_path: &Path, serialized: &str) -> Result<(), anyhow::Error> {
+    if is_stdio(recipe_path) {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(serialized.as_bytes())
+            .and_then(|_| stdout.flush())
+            .context("Failed to write recipe to stdout.")
+    } else {
+        fs::write(recipe_path, serialized)
+            .with_context(|| format!("Failed to save recipe to {:?}", recipe_path))
+    }
+}
+
 fn main() -> Result<(), anyhow::Error> {
     env_logger::init();
     _main()
@@ -0,0 +1,298 @@
+//! End-to-end scenarios, described in the TOML files of `tests/scenarios`: a synthetic project,
+//! the commands to run on it and what to expect of each of them. Adding a regression case for a
+//! workspace layout does not require writing any Rust:
+//!
+//! ```toml
+//! description = "What the scenario covers."
+//!
+//! # The files of the project, by path.
+//! [project]
+//! "Cargo.toml" = """
+//! [package]
+//! name = "app"
+//! version = "0.1.0"
+//! """
+//! "src/main.rs" = "fn main() {}"
+//!
+//! # The steps run in order, in the project directory or, with `in = "cook"`, in the directory
+//! # dependencies are cooked in, as two stages of a Dockerfile would. `{project}` and `{cook}`
+//! # in arguments are replaced by the path of these directories.
+//! [[step]]
+//! chef = ["prepare", "--recipe-path", "recipe.json"]
+//! # `success` (true by default), `stdout-contains`, `stderr-contains` and `stderr-excludes`
+//! # check the output of the command.
+//! stderr-contains = ["recipe.json"]
+//!
+//! # Recipe fields, by JSON pointer.
+//! [step.recipe]
+//! path = "recipe.json"
+//! fields = { "/skeleton/manifests/0/relative_path" = "Cargo.toml" }
+//!
+//! [[step]]
+//! in = "cook"
+//! # Copy files of the project, e.g. vendored sources or the sources of the final build.
+//! copy = ["src"]
+//!
+//! [[step]]
+//! in = "cook"
+//! chef = ["cook", "--recipe-path", "{project}/recipe.json"]
+//! # Files that must (not) exist once the command has run.
+//! files = ["src/main.rs"]
+//! absent = ["src/lib.rs"]
+//!
+//! [[step]]
+//! in = "cook"
+//! cargo = ["build", "--offline"]
+//! # Packages that must not be compiled again: their cooked artifacts are reused.
+//! reused = ["foo"]
+//! ```
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    description: String,
+    project: BTreeMap<String, String>,
+    step: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Step {
+    #[serde(default, rename = "in")]
+    directory: Directory,
+    chef: Option<Vec<String>>,
+    cargo: Option<Vec<String>>,
+    #[serde(default)]
+    copy: Vec<PathBuf>,
+    #[serde(default = "succeeds")]
+    success: bool,
+    #[serde(default)]
+    stdout_contains: Vec<String>,
+    #[serde(default)]
+    stderr_contains: Vec<String>,
+    #[serde(default)]
+    stderr_excludes: Vec<String>,
+    #[serde(default)]
+    files: Vec<PathBuf>,
+    #[serde(default)]
+    absent: Vec<PathBuf>,
+    #[serde(default)]
+    reused: Vec<String>,
+    recipe: Option<RecipeFields>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum Directory {
+    #[default]
+    Project,
+    Cook,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeFields {
+    path: PathBuf,
+    fields: BTreeMap<String, toml::Value>,
+}
+
+fn succeeds() -> bool {
+    true
+}
+
+struct Directories {
+    project: TempDir,
+    cook: TempDir,
+}
+
+impl Directories {
+    fn get(&self, directory: Directory) -> &Path {
+        match directory {
+            Directory::Project => self.project.path(),
+            Directory::Cook => self.cook.path(),
+        }
+    }
+
+    fn substitute(&self, argument: &str) -> String {
+        argument
+            .replace("{project}", self.project.path().to_str().unwrap())
+            .replace("{cook}", self.cook.path().to_str().unwrap())
+    }
+}
+
+fn run(scenario: &Scenario) -> Result<(), String> {
+    let directories = Directories {
+        project: TempDir::new().unwrap(),
+        cook: TempDir::new().unwrap(),
+    };
+    for (path, contents) in &scenario.project {
+        directories
+            .project
+            .child(path)
+            .write_str(contents)
+            .map_err(|e| format!("failed to write {}: {}", path, e))?;
+    }
+    for (index, step) in scenario.step.iter().enumerate() {
+        run_step(&directories, step).map_err(|e| format!("step {}: {}", index + 1, e))?;
+    }
+    Ok(())
+}
+
+fn run_step(directories: &Directories, step: &Step) -> Result<(), String> {
+    let directory = directories.get(step.directory);
+    for path in &step.copy {
+        copy(
+            &directories.project.path().join(path),
+            &directory.join(path),
+        )
+        .map_err(|e| format!("failed to copy {}: {}", path.display(), e))?;
+    }
+    let (program, arguments) = match (&step.chef, &step.cargo) {
+        (Some(arguments), None) => (
+            assert_cmd::cargo::cargo_bin("cargo-chef"),
+            std::iter::once("chef".to_string())
+                .chain(arguments.iter().cloned())
+                .collect::<Vec<_>>(),
+        ),
+        (None, Some(arguments)) => (PathBuf::from(env!("CARGO")), arguments.clone()),
+        (None, None) => return Ok(()),
+        (Some(_), Some(_)) => return Err("runs both `chef` and `cargo`".to_string()),
+    };
+    let arguments: Vec<String> = arguments
+        .iter()
+        .map(|argument| directories.substitute(argument))
+        .collect();
+    let output = std::process::Command::new(program)
+        .args(&arguments)
+        .current_dir(directory)
+        .output()
+        .map_err(|e| e.to_string())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let context = || {
+        format!(
+            "`{}`\n--- stdout\n{}\n--- stderr\n{}",
+            arguments.join(" "),
+            stdout,
+            stderr
+        )
+    };
+
+    if output.status.success() != step.success {
+        return Err(format!("unexpected {} of {}", output.status, context()));
+    }
+    for expected in &step.stdout_contains {
+        if !stdout.contains(expected.as_str()) {
+            return Err(format!(
+                "stdout does not contain {:?}: {}",
+                expected,
+                context()
+            ));
+        }
+    }
+    for expected in &step.stderr_contains {
+        if !stderr.contains(expected.as_str()) {
+            return Err(format!(
+                "stderr does not contain {:?}: {}",
+                expected,
+                context()
+            ));
+        }
+    }
+    for unexpected in &step.stderr_excludes {
+        if stderr.contains(unexpected.as_str()) {
+            return Err(format!("stderr contains {:?}: {}", unexpected, context()));
+        }
+    }
+    for package in &step.reused {
+        if stderr.contains(&format!("Compiling {} v", package)) {
+            return Err(format!("`{}` was compiled again: {}", package, context()));
+        }
+    }
+    for path in &step.files {
+        if !directory.join(path).exists() {
+            return Err(format!("{} does not exist", path.display()));
+        }
+    }
+    for path in &step.absent {
+        if directory.join(path).exists() {
+            return Err(format!("{} exists", path.display()));
+        }
+    }
+    if let Some(recipe) = &step.recipe {
+        let contents = std::fs::read_to_string(directory.join(&recipe.path))
+            .map_err(|e| format!("failed to read {}: {}", recipe.path.display(), e))?;
+        let contents: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        for (pointer, expected) in &recipe.fields {
+            let expected = serde_json::to_value(expected).unwrap();
+            match contents.pointer(pointer) {
+                Some(actual) if actual == &expected => {}
+                actual => {
+                    return Err(format!(
+                        "`{}` of the recipe is {:?}, not {}",
+                        pointer, actual, expected
+                    ))
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+#[test]
+fn test_scenarios() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scenario in {}", directory.display());
+
+    let mut failures = vec![];
+    for path in &paths {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let scenario: Scenario = match toml::from_str(&std::fs::read_to_string(path).unwrap()) {
+            Ok(scenario) => scenario,
+            Err(e) => {
+                failures.push(format!("{}: invalid scenario: {}", name, e));
+                continue;
+            }
+        };
+        if let Err(e) = run(&scenario) {
+            failures.push(format!("{} ({}): {}", name, scenario.description, e));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} of {} scenarios failed:\n\n{}",
+        failures.len(),
+        paths.len(),
+        failures.join("\n\n")
+    );
+}